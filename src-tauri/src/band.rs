@@ -0,0 +1,379 @@
+// Band helpers: part assignment, shared setlist and latency calibration.
+// The setlist is pushed by the host over the P2P channel (frontend); each member
+// resolves the hashes against its own library and reports the missing ones so the
+// host can send them. The conductor's "next" is broadcast and applied with goto.
+// For calibration, peers exchange timestamped pings over the band connection (frontend) to estimate
+// clock offset, then play a short test pattern so each side can measure how late the
+// other's notes arrive in game. The resulting per-peer offset (ms, positive = delay
+// our part) is saved in config.json and applied by play_midi_band.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter};
+
+const MAX_OFFSET_MS: i64 = 2000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetlistSong {
+    pub hash: String,
+    pub name: String,
+    // Local file for this song, None until found in the library or received
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Setlist {
+    pub songs: Vec<SetlistSong>,
+    pub index: usize,         // Song the band is on
+    pub missing: Vec<String>, // Hashes with no local file yet
+}
+
+lazy_static::lazy_static! {
+    static ref SETLIST: Mutex<Setlist> = Mutex::new(Setlist::default());
+}
+
+fn update_setlist<T>(
+    app: &AppHandle,
+    apply: impl FnOnce(&mut Setlist) -> Result<T, String>,
+) -> Result<T, String> {
+    let (result, setlist) = {
+        let mut setlist = SETLIST.lock().unwrap();
+        let result = apply(&mut setlist)?;
+        setlist.missing = setlist
+            .songs
+            .iter()
+            .filter(|s| s.path.is_none())
+            .map(|s| s.hash.clone())
+            .collect();
+        (result, setlist.clone())
+    };
+    let _ = app.emit("band-setlist-changed", &setlist);
+    Ok(result)
+}
+
+pub fn get_setlist() -> Setlist {
+    SETLIST.lock().unwrap().clone()
+}
+
+/// Replace the setlist (host building it, or a member receiving it) and resolve local files
+pub fn set_setlist(
+    app: &AppHandle,
+    mut songs: Vec<SetlistSong>,
+    index: usize,
+) -> Result<Setlist, String> {
+    let hashes = songs.iter().map(|s| s.hash.clone()).collect();
+    let paths = crate::resolve_paths_by_hash(&hashes);
+    for song in &mut songs {
+        song.path = paths.get(&song.hash).cloned();
+    }
+    update_setlist(app, |setlist| {
+        setlist.index = index.min(songs.len().saturating_sub(1));
+        setlist.songs = songs;
+        Ok(())
+    })?;
+    Ok(get_setlist())
+}
+
+/// A missing song arrived from the host (e.g. saved with save_temp_midi)
+pub fn provide_song(app: &AppHandle, hash: &str, path: &str) -> Result<Setlist, String> {
+    if crate::compute_file_hash(std::path::Path::new(path)).as_deref() != Some(hash) {
+        return Err("File does not match the setlist song".to_string());
+    }
+    update_setlist(app, |setlist| {
+        for song in setlist.songs.iter_mut().filter(|s| s.hash == hash) {
+            song.path = Some(path.to_string());
+        }
+        Ok(())
+    })?;
+    Ok(get_setlist())
+}
+
+/// Move the band to a song. Emits "band-setlist-advanced" with the new index and song.
+pub fn goto(app: &AppHandle, index: usize) -> Result<SetlistSong, String> {
+    let song = update_setlist(app, |setlist| {
+        let song = setlist
+            .songs
+            .get(index)
+            .cloned()
+            .ok_or_else(|| "Setlist index out of range".to_string())?;
+        setlist.index = index;
+        Ok(song)
+    })?;
+    let _ = app.emit(
+        "band-setlist-advanced",
+        serde_json::json!({ "index": index, "song": &song }),
+    );
+    Ok(song)
+}
+
+/// Conductor's "next": the following song, or an error at the end of the setlist
+pub fn next(app: &AppHandle) -> Result<(usize, SetlistSong), String> {
+    let index = SETLIST.lock().unwrap().index + 1;
+    goto(app, index).map(|song| (index, song))
+}
+
+pub fn clear_setlist(app: &AppHandle) {
+    let _ = update_setlist(app, |setlist| {
+        *setlist = Setlist::default();
+        Ok(())
+    });
+}
+
+/// One ping round trip: we sent at `sent`, the peer received at `peer_received`
+/// and replied at `peer_sent` (peer clock), we got the reply at `received`. All unix ms.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct PingSample {
+    pub sent: f64,
+    pub peer_received: f64,
+    pub peer_sent: f64,
+    pub received: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClockEstimate {
+    pub offset_ms: f64, // peer clock minus ours
+    pub rtt_ms: f64,
+    pub samples: usize,
+}
+
+fn median(values: &mut [f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    let mid = values.len() / 2;
+    Some(if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    })
+}
+
+/// NTP-style clock offset, using the faster half of the samples (least queueing delay)
+pub fn estimate_clock(samples: &[PingSample]) -> Result<ClockEstimate, String> {
+    let mut measured: Vec<(f64, f64)> = samples
+        .iter()
+        .map(|s| {
+            let rtt = (s.received - s.sent) - (s.peer_sent - s.peer_received);
+            let offset = ((s.peer_received - s.sent) + (s.peer_sent - s.received)) / 2.0;
+            (rtt, offset)
+        })
+        .filter(|(rtt, _)| *rtt >= 0.0)
+        .collect();
+    if measured.is_empty() {
+        return Err("No valid ping samples".to_string());
+    }
+    measured.sort_by(|a, b| a.0.total_cmp(&b.0));
+    measured.truncate(measured.len().div_ceil(2));
+
+    let mut rtts: Vec<f64> = measured.iter().map(|m| m.0).collect();
+    let mut offsets: Vec<f64> = measured.iter().map(|m| m.1).collect();
+    Ok(ClockEstimate {
+        offset_ms: median(&mut offsets).unwrap_or(0.0),
+        rtt_ms: median(&mut rtts).unwrap_or(0.0),
+        samples: measured.len(),
+    })
+}
+
+/// Median lag between when test pattern notes were sent and when they were heard
+/// (both on the same clock, matched by index)
+pub fn estimate_latency(sent_ms: &[f64], heard_ms: &[f64]) -> Result<f64, String> {
+    let mut lags: Vec<f64> = sent_ms
+        .iter()
+        .zip(heard_ms)
+        .map(|(sent, heard)| heard - sent)
+        .collect();
+    median(&mut lags).ok_or_else(|| "No test pattern notes to compare".to_string())
+}
+
+/// Press one note `count` times, `interval_ms` apart. Returns when each press was sent (unix ms).
+pub fn play_test_pattern(count: u32, interval_ms: u64) -> Vec<f64> {
+    let key = crate::midi::note_to_game_key(
+        60,
+        crate::midi::KeyMode::Keys21,
+        crate::midi::NoteMode::Closest,
+        0,
+        0,
+    );
+    let interval = std::time::Duration::from_millis(interval_ms.clamp(100, 2000));
+    let mut sent = Vec::new();
+    for i in 0..count.min(32) {
+        if i > 0 {
+            std::thread::sleep(interval);
+        }
+        sent.push(now_ms());
+        crate::keyboard::key_down(&key);
+        crate::keyboard::key_up(&key);
+    }
+    sent
+}
+
+fn now_ms() -> f64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs_f64() * 1000.0)
+        .unwrap_or(0.0)
+}
+
+pub fn peer_offsets() -> HashMap<String, i64> {
+    crate::load_config()
+        .get("band_peer_offsets")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default()
+}
+
+pub fn peer_offset(peer_id: &str) -> i64 {
+    peer_offsets().get(peer_id).copied().unwrap_or(0)
+}
+
+/// Save the offset for a peer (0 removes it). Returns the clamped value.
+pub fn set_peer_offset(peer_id: &str, offset_ms: i64) -> i64 {
+    let offset_ms = offset_ms.clamp(-MAX_OFFSET_MS, MAX_OFFSET_MS);
+    let mut offsets = peer_offsets();
+    if offset_ms == 0 {
+        offsets.remove(peer_id);
+    } else {
+        offsets.insert(peer_id.to_string(), offset_ms);
+    }
+    let mut config = crate::load_config();
+    config["band_peer_offsets"] = serde_json::json!(offsets);
+    crate::save_config(&config);
+    offset_ms
+}
+
+/// One player's share of a suggested band arrangement
+#[derive(Debug, Clone, Serialize)]
+pub struct BandPart {
+    pub player: String,
+    pub filter: Option<crate::midi::BandFilter>, // None = play everything
+    pub note_count: u32,
+    pub tracks: Vec<String>, // Track names, for display
+    pub lowest_note: Option<u8>,
+    pub highest_note: Option<u8>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BandPlan {
+    pub strategy: String, // "solo", "tracks" or "range"
+    pub parts: Vec<BandPart>,
+}
+
+/// Propose who plays what: whole tracks when there are enough of them (balanced by
+/// note count), otherwise the pitch range cut into bands with about as many notes each
+pub fn suggest_assignment(path: &str, players: &[String]) -> Result<BandPlan, String> {
+    use crate::midi::{BandFilter, EventType};
+
+    if players.is_empty() {
+        return Err("No players to assign".to_string());
+    }
+    let midi_data = crate::midi::load_midi(path)?;
+    let tracks: Vec<_> = crate::midi::get_midi_tracks(path)?
+        .into_iter()
+        .filter(|t| t.note_count > 0)
+        .collect();
+    let notes: Vec<(usize, u8)> = midi_data
+        .events
+        .iter()
+        .filter(|e| matches!(e.event_type, EventType::NoteOn))
+        .map(|e| (e.track_id, e.note))
+        .collect();
+
+    let part = |player: &str, filter: Option<BandFilter>| {
+        let mine: Vec<u8> = notes
+            .iter()
+            .filter(|(track_id, note)| match &filter {
+                Some(BandFilter::Tracks { track_ids }) => track_ids.contains(track_id),
+                Some(BandFilter::Track { track_id: id }) => id == track_id,
+                Some(BandFilter::Range { low, high }) => note >= low && note <= high,
+                _ => true,
+            })
+            .map(|(_, note)| *note)
+            .collect();
+        let track_names = tracks
+            .iter()
+            .filter(|t| match &filter {
+                Some(BandFilter::Tracks { track_ids }) => track_ids.contains(&t.id),
+                Some(BandFilter::Track { track_id }) => *track_id == t.id,
+                _ => true,
+            })
+            .map(|t| t.name.clone())
+            .collect();
+        BandPart {
+            player: player.to_string(),
+            note_count: mine.len() as u32,
+            tracks: track_names,
+            lowest_note: mine.iter().min().copied(),
+            highest_note: mine.iter().max().copied(),
+            filter,
+        }
+    };
+
+    if players.len() == 1 {
+        return Ok(BandPlan {
+            strategy: "solo".to_string(),
+            parts: vec![part(&players[0], None)],
+        });
+    }
+
+    if tracks.len() >= players.len() {
+        // Biggest tracks first, each to whoever has the fewest notes so far
+        let mut sorted = tracks.clone();
+        sorted.sort_by_key(|t| std::cmp::Reverse(t.note_count));
+        let mut loads = vec![(0u32, Vec::new()); players.len()];
+        for track in &sorted {
+            let (load, ids) = loads.iter_mut().min_by_key(|(load, _)| *load).unwrap();
+            *load += track.note_count;
+            ids.push(track.id);
+        }
+        let parts = players
+            .iter()
+            .zip(loads)
+            .map(|(player, (_, mut track_ids))| {
+                track_ids.sort_unstable();
+                let filter = match track_ids.as_slice() {
+                    [track_id] => BandFilter::Track {
+                        track_id: *track_id,
+                    },
+                    _ => BandFilter::Tracks { track_ids },
+                };
+                part(player, Some(filter))
+            })
+            .collect();
+        return Ok(BandPlan {
+            strategy: "tracks".to_string(),
+            parts,
+        });
+    }
+
+    // Cut the pitch histogram where the running count passes each player's share,
+    // lowest range to the first player
+    let mut histogram = [0u32; 128];
+    for (_, note) in &notes {
+        histogram[*note as usize] += 1;
+    }
+    let share = (notes.len() as f64 / players.len() as f64).max(1.0);
+    let mut parts = Vec::new();
+    let mut low = 0u8;
+    let mut running = 0u32;
+    for note in 0..128u8 {
+        running += histogram[note as usize];
+        let boundary = share * (parts.len() + 1) as f64;
+        let last_player = parts.len() + 1 == players.len();
+        if !last_player && running as f64 >= boundary {
+            let filter = BandFilter::Range { low, high: note };
+            parts.push(part(&players[parts.len()], Some(filter)));
+            low = note.saturating_add(1);
+        }
+    }
+    while parts.len() < players.len() {
+        let filter = BandFilter::Range { low, high: 127 };
+        parts.push(part(&players[parts.len()], Some(filter)));
+        low = 127;
+    }
+    Ok(BandPlan {
+        strategy: "range".to_string(),
+        parts,
+    })
+}
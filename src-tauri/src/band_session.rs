@@ -0,0 +1,212 @@
+// Band session state machine: lobby -> ready check -> countdown -> playing.
+// Peers relay member updates over the P2P channel (frontend) into the backend, so a
+// countdown can only start once every member has loaded the song and picked a part.
+// Every change is emitted as "band-session-changed"; "band-session-start" fires when
+// the countdown ends and the UI starts band playback.
+
+use crate::midi::BandFilter;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter};
+
+const MAX_COUNTDOWN_MS: f64 = 30_000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionPhase {
+    #[default]
+    Lobby,
+    ReadyCheck,
+    Countdown,
+    Playing,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionMember {
+    pub peer_id: String,
+    pub name: String,
+    #[serde(default)]
+    pub loaded: bool, // Has the session's song loaded
+    #[serde(default)]
+    pub part: Option<BandFilter>,
+    #[serde(default)]
+    pub ready: bool,
+}
+
+impl SessionMember {
+    fn is_set(&self) -> bool {
+        self.loaded && self.part.is_some() && self.ready
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BandSession {
+    pub phase: SessionPhase,
+    pub song_hash: Option<String>,
+    pub members: Vec<SessionMember>,
+    pub start_at: Option<f64>, // unix ms the countdown ends (local clock)
+}
+
+lazy_static::lazy_static! {
+    static ref SESSION: Mutex<BandSession> = Mutex::new(BandSession::default());
+}
+
+// Bumped when a countdown is cancelled so its timer thread doesn't fire
+static COUNTDOWN_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+fn now_ms() -> f64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs_f64() * 1000.0)
+        .unwrap_or(0.0)
+}
+
+fn update<T>(
+    app: &AppHandle,
+    apply: impl FnOnce(&mut BandSession) -> Result<T, String>,
+) -> Result<T, String> {
+    let (result, session) = {
+        let mut session = SESSION.lock().unwrap();
+        let result = apply(&mut session)?;
+        (result, session.clone())
+    };
+    let _ = app.emit("band-session-changed", &session);
+    Ok(result)
+}
+
+pub fn get() -> BandSession {
+    SESSION.lock().unwrap().clone()
+}
+
+/// Add or refresh a member (name only; status comes through update_member)
+pub fn join(app: &AppHandle, peer_id: &str, name: &str) -> Result<(), String> {
+    update(app, |session| {
+        match session.members.iter_mut().find(|m| m.peer_id == peer_id) {
+            Some(member) => member.name = name.to_string(),
+            None => session.members.push(SessionMember {
+                peer_id: peer_id.to_string(),
+                name: name.to_string(),
+                loaded: false,
+                part: None,
+                ready: false,
+            }),
+        }
+        Ok(())
+    })
+}
+
+pub fn leave(app: &AppHandle, peer_id: &str) -> Result<(), String> {
+    update(app, |session| {
+        session.members.retain(|m| m.peer_id != peer_id);
+        Ok(())
+    })
+}
+
+/// A member reported its state. Anything not sent is left as it was.
+pub fn update_member(
+    app: &AppHandle,
+    peer_id: &str,
+    loaded: Option<bool>,
+    part: Option<BandFilter>,
+    ready: Option<bool>,
+) -> Result<(), String> {
+    update(app, |session| {
+        let member = session
+            .members
+            .iter_mut()
+            .find(|m| m.peer_id == peer_id)
+            .ok_or_else(|| "Not a session member".to_string())?;
+        if let Some(loaded) = loaded {
+            member.loaded = loaded;
+        }
+        if part.is_some() {
+            member.part = part;
+        }
+        if let Some(ready) = ready {
+            member.ready = ready;
+        }
+        Ok(())
+    })
+}
+
+/// Lobby -> ready check for a song. Clears everyone's loaded/ready flags.
+pub fn start_ready_check(app: &AppHandle, song_hash: &str) -> Result<(), String> {
+    update(app, |session| {
+        if session.phase != SessionPhase::Lobby && session.phase != SessionPhase::ReadyCheck {
+            return Err("A ready check can only start from the lobby".to_string());
+        }
+        session.phase = SessionPhase::ReadyCheck;
+        session.song_hash = Some(song_hash.to_string());
+        session.start_at = None;
+        for member in &mut session.members {
+            member.loaded = false;
+            member.ready = false;
+        }
+        Ok(())
+    })
+}
+
+/// Ready check -> countdown, once every member is set. `start_at` (unix ms, local clock)
+/// lets members follow the host's start time; otherwise it's `countdown_ms` from now.
+pub fn start_countdown(
+    app: &AppHandle,
+    countdown_ms: u64,
+    start_at: Option<f64>,
+) -> Result<f64, String> {
+    let generation = COUNTDOWN_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    let start_at = update(app, |session| {
+        if session.phase != SessionPhase::ReadyCheck {
+            return Err("Countdown needs a ready check first".to_string());
+        }
+        let waiting: Vec<&str> = session
+            .members
+            .iter()
+            .filter(|m| !m.is_set())
+            .map(|m| m.name.as_str())
+            .collect();
+        if !waiting.is_empty() {
+            return Err(format!("Waiting for: {}", waiting.join(", ")));
+        }
+        let now = now_ms();
+        let start_at = start_at
+            .unwrap_or(now + countdown_ms as f64)
+            .clamp(now, now + MAX_COUNTDOWN_MS);
+        session.phase = SessionPhase::Countdown;
+        session.start_at = Some(start_at);
+        Ok(start_at)
+    })?;
+
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let wait_ms = (start_at - now_ms()).max(0.0);
+        std::thread::sleep(std::time::Duration::from_secs_f64(wait_ms / 1000.0));
+        if COUNTDOWN_GENERATION.load(Ordering::SeqCst) != generation {
+            return;
+        }
+        let started = update(&app, |session| {
+            if session.phase != SessionPhase::Countdown {
+                return Ok(false);
+            }
+            session.phase = SessionPhase::Playing;
+            Ok(true)
+        });
+        if started == Ok(true) {
+            let _ = app.emit("band-session-start", get());
+        }
+    });
+    Ok(start_at)
+}
+
+/// Back to the lobby from any phase (cancel the check or countdown, or the song ended)
+pub fn reset(app: &AppHandle) {
+    COUNTDOWN_GENERATION.fetch_add(1, Ordering::SeqCst);
+    let _ = update(app, |session| {
+        session.phase = SessionPhase::Lobby;
+        session.start_at = None;
+        for member in &mut session.members {
+            member.ready = false;
+        }
+        Ok(())
+    });
+}
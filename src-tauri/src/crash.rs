@@ -0,0 +1,109 @@
+// Panic reporting
+// Panics on any thread (playback, hotkey hook, discovery) are appended to
+// wwm-crash.log and the latest one is kept as a pending report so the UI can
+// show it on the next start
+
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::PathBuf;
+use tauri::{AppHandle, Emitter};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashReport {
+    pub time: String,
+    pub version: String,
+    pub thread: String,
+    pub message: String,
+    pub location: Option<String>,
+    pub backtrace: String,
+}
+
+fn crash_log_path() -> PathBuf {
+    crate::logging::log_dir().join("wwm-crash.log")
+}
+
+fn pending_path() -> PathBuf {
+    crate::logging::log_dir().join("wwm-crash-pending.json")
+}
+
+fn panic_message(info: &std::panic::PanicHookInfo) -> String {
+    if let Some(s) = info.payload().downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = info.payload().downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "Unknown panic payload".to_string()
+    }
+}
+
+/// Install the hook. The default hook still runs so panics reach stderr in dev builds.
+pub fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let report = CrashReport {
+            time: crate::chrono_now(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            thread: std::thread::current()
+                .name()
+                .unwrap_or("unnamed")
+                .to_string(),
+            message: panic_message(info),
+            location: info
+                .location()
+                .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column())),
+            backtrace: std::backtrace::Backtrace::force_capture().to_string(),
+        };
+
+        log::error!(
+            "[CRASH] Panic in thread '{}' at {}: {}",
+            report.thread,
+            report.location.as_deref().unwrap_or("unknown"),
+            report.message
+        );
+
+        if let Ok(mut file) = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(crash_log_path())
+        {
+            let _ = writeln!(
+                file,
+                "=== {} (v{}) thread '{}' ===\n{}\nat {}\n{}\n",
+                report.time,
+                report.version,
+                report.thread,
+                report.message,
+                report.location.as_deref().unwrap_or("unknown"),
+                report.backtrace
+            );
+        }
+
+        if let Ok(json) = serde_json::to_string_pretty(&report) {
+            let _ = std::fs::write(pending_path(), json);
+        }
+
+        default_hook(info);
+    }));
+}
+
+/// Report left behind by a panic in a previous (or the current) session
+pub fn get_pending() -> Option<CrashReport> {
+    let content = std::fs::read_to_string(pending_path()).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+pub fn dismiss_pending() {
+    let _ = std::fs::remove_file(pending_path());
+}
+
+/// Tell the UI about a crash from the last session
+pub fn notify_pending(app: &AppHandle) {
+    if let Some(report) = get_pending() {
+        crate::app_log!(
+            "[CRASH] Previous session crashed in thread '{}': {}",
+            report.thread,
+            report.message
+        );
+        let _ = app.emit("crash-report", &report);
+    }
+}
@@ -0,0 +1,90 @@
+// Rotating backups of favorites.json and playlists.json, kept in a backups folder next
+// to them. The previous version is copied there before every save and before an outside
+// change replaces it, so a bad save (a frontend bug wiping the playlists) can be undone
+// with restore_data_backup. Restoring is a save too, so it can be undone the same way.
+
+use serde::Serialize;
+
+const KEEP: usize = 20; // Per file
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DataBackup {
+    pub file: String, // "playlists.json"
+    pub name: String, // "playlists-1760000000000.json"
+    pub created_ms: u64,
+    pub size: u64,
+}
+
+fn folder() -> Result<std::path::PathBuf, String> {
+    crate::get_data_path("backups")
+}
+
+fn stem(file: &str) -> &str {
+    file.trim_end_matches(".json")
+}
+
+/// Store `content` as the newest backup of `file` and drop the oldest beyond KEEP
+pub fn keep(file: &str, content: &str) -> Result<String, String> {
+    let folder = folder()?;
+    std::fs::create_dir_all(&folder)
+        .map_err(|e| format!("Failed to create backups folder: {}", e))?;
+    let created_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    let name = format!("{}-{}.json", stem(file), created_ms);
+    std::fs::write(folder.join(&name), content)
+        .map_err(|e| format!("Failed to write backup: {}", e))?;
+
+    for old in list(file).into_iter().skip(KEEP) {
+        let _ = std::fs::remove_file(folder.join(&old.name));
+    }
+    Ok(name)
+}
+
+/// Backups of `file`, newest first
+pub fn list(file: &str) -> Vec<DataBackup> {
+    let Ok(folder) = folder() else {
+        return Vec::new();
+    };
+    let Ok(entries) = std::fs::read_dir(&folder) else {
+        return Vec::new();
+    };
+    let prefix = format!("{}-", stem(file));
+    let mut backups: Vec<DataBackup> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let created_ms = name
+                .strip_prefix(&prefix)?
+                .strip_suffix(".json")?
+                .parse()
+                .ok()?;
+            Some(DataBackup {
+                file: file.to_string(),
+                size: entry.metadata().map(|m| m.len()).unwrap_or(0),
+                name,
+                created_ms,
+            })
+        })
+        .collect();
+    backups.sort_by_key(|b| std::cmp::Reverse(b.created_ms));
+    backups
+}
+
+/// Put a backup back in place of `file`; the version it replaces is backed up first
+pub fn restore(file: &str, name: &str) -> Result<(), String> {
+    if !crate::data_watch::FILES.contains(&file) {
+        return Err(format!("Not a backed up file: {}", file));
+    }
+    if !list(file).iter().any(|b| b.name == name) {
+        return Err(format!("Backup not found: {}", name));
+    }
+    let content = std::fs::read_to_string(folder()?.join(name))
+        .map_err(|e| format!("Failed to read backup: {}", e))?;
+    serde_json::from_str::<serde_json::Value>(&content)
+        .map_err(|e| format!("Backup is damaged: {}", e))?;
+    crate::data_watch::write(file, content).map_err(|e| format!("Failed to restore: {}", e))?;
+    crate::app_log!("[DATA] Restored {} from {}", file, name);
+    Ok(())
+}
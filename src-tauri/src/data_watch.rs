@@ -0,0 +1,139 @@
+// Notices when favorites.json or playlists.json change on disk behind the app's back
+// (edited by hand, or updated by Syncthing/OneDrive) and tells the frontend to reload.
+// The last write wins: the version the app knew about goes to the data backups before
+// the new one is taken. All app writes to these files go through `write` so the
+// watcher can tell them apart from outside changes.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use tauri::{AppHandle, Emitter};
+
+pub const FILES: [&str; 2] = ["favorites.json", "playlists.json"];
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+struct Known {
+    modified: Option<SystemTime>,
+    len: u64,
+    content: String,
+}
+
+lazy_static::lazy_static! {
+    static ref KNOWN: Mutex<HashMap<String, Known>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DataFileChanged {
+    pub file: String,
+    pub backup: Option<String>, // The replaced version, if there was one
+}
+
+fn signature(path: &std::path::Path) -> Option<(Option<SystemTime>, u64)> {
+    let meta = std::fs::metadata(path).ok()?;
+    Some((meta.modified().ok(), meta.len()))
+}
+
+fn remember(known: &mut HashMap<String, Known>, file: &str, content: String) {
+    let (modified, len) = crate::get_data_path(file)
+        .ok()
+        .and_then(|path| signature(&path))
+        .unwrap_or((None, 0));
+    known.insert(
+        file.to_string(),
+        Known {
+            modified,
+            len,
+            content,
+        },
+    );
+}
+
+/// Write one of the watched files as the app's own change, backing up what it replaces
+pub fn write(file: &str, content: String) -> std::io::Result<()> {
+    let path = crate::get_data_path(file).map_err(std::io::Error::other)?;
+    let mut known = KNOWN.lock().unwrap();
+    let replaced = std::fs::read_to_string(&path).unwrap_or_default();
+    if !replaced.trim().is_empty() && replaced != content {
+        if let Err(e) = crate::data_backups::keep(file, &replaced) {
+            crate::app_error!("[DATA] Failed to back up {}: {}", file, e);
+        }
+    }
+    std::fs::write(&path, &content)?;
+    remember(&mut known, file, content);
+    Ok(())
+}
+
+/// The file changed on disk if it isn't what the app last read or wrote. Returns the
+/// change once the new content is accepted.
+fn check(file: &str) -> Option<DataFileChanged> {
+    let path = crate::get_data_path(file).ok()?;
+    let mut known = KNOWN.lock().unwrap();
+    let current = signature(&path);
+    let previous = known.get(file);
+    if current == previous.map(|k| (k.modified, k.len)) {
+        return None;
+    }
+
+    let content = std::fs::read_to_string(&path).unwrap_or_default();
+    let previous = previous.map(|k| k.content.clone()).unwrap_or_default();
+    if content == previous {
+        remember(&mut known, file, content);
+        return None;
+    }
+    // A sync tool may be halfway through writing; wait for a file that parses
+    if !content.is_empty() && serde_json::from_str::<serde_json::Value>(&content).is_err() {
+        return None;
+    }
+
+    let backup = if previous.trim().is_empty() {
+        None
+    } else {
+        match crate::data_backups::keep(file, &previous) {
+            Ok(name) => Some(name),
+            Err(e) => {
+                crate::app_error!("[DATA] Failed to back up {}: {}", file, e);
+                None
+            }
+        }
+    };
+    remember(&mut known, file, content);
+    Some(DataFileChanged {
+        file: file.to_string(),
+        backup,
+    })
+}
+
+/// Poll the watched files and broadcast outside changes
+pub fn start(app: AppHandle) {
+    {
+        let mut known = KNOWN.lock().unwrap();
+        for file in FILES {
+            let content = crate::get_data_path(file)
+                .ok()
+                .and_then(|path| std::fs::read_to_string(path).ok())
+                .unwrap_or_default();
+            remember(&mut known, file, content);
+        }
+    }
+
+    let spawned = std::thread::Builder::new()
+        .name("data-watch".to_string())
+        .spawn(move || loop {
+            std::thread::sleep(POLL_INTERVAL);
+            for file in FILES {
+                let Some(change) = check(file) else {
+                    continue;
+                };
+                crate::app_log!("[DATA] {} changed on disk, reloading", file);
+                match file {
+                    "favorites.json" => crate::favorites::notify_changed(&app),
+                    _ => crate::playlists::notify_changed(&app),
+                }
+                let _ = app.emit("data-file-changed", change);
+            }
+        });
+    if let Err(e) = spawned {
+        crate::app_error!("[DATA] Failed to start data file watcher: {}", e);
+    }
+}
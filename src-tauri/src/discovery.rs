@@ -1,9 +1,15 @@
 // Discovery server for P2P song library
 // Can run as server (on VPS) or connect as client
+// Operators can check /stats (JSON) or /status (HTML) with the admin token,
+// sent as "Authorization: Bearer <token>" or ?token=<token>
 
+use crate::error::AppError;
 use axum::{
-    extract::State,
-    http::StatusCode,
+    body::HttpBody,
+    extract::{Query, Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Response},
     routing::{delete, get, post},
     Json, Router,
 };
@@ -22,6 +28,18 @@ pub struct SharedSong {
     pub duration: f64,
     pub bpm: u16,
     pub size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>, // Hex SHA-256 of the file, checked by the receiver
+    // Playability info so remote songs can be filtered before downloading
+    // (defaults keep registrations from older clients valid)
+    #[serde(default)]
+    pub note_density: f32, // notes per second
+    #[serde(default)]
+    pub track_count: u32, // tracks with notes
+    #[serde(default)]
+    pub difficulty: u8, // 0-100
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -54,6 +72,32 @@ pub struct PeerListResponse {
 #[derive(Default)]
 pub struct DiscoveryState {
     peers: HashMap<String, PeerInfo>,
+    admin_token: Option<String>, // None = /stats and /status disabled
+    started_at: Option<Instant>,
+    requests: u64,
+    bytes_in: u64,
+    bytes_out: u64,
+}
+
+impl DiscoveryState {
+    fn new(admin_token: Option<String>) -> Self {
+        DiscoveryState {
+            admin_token: admin_token.filter(|t| !t.is_empty()),
+            started_at: Some(Instant::now()),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerStats {
+    pub peers_online: usize,
+    pub songs_indexed: usize, // Across all peers, duplicates included
+    pub unique_songs: usize,
+    pub uptime_secs: u64,
+    pub requests: u64,
+    pub bytes_in: u64, // API traffic (the server doesn't relay files)
+    pub bytes_out: u64,
 }
 
 type SharedState = Arc<RwLock<DiscoveryState>>;
@@ -135,6 +179,142 @@ async fn health() -> &'static str {
     "OK"
 }
 
+// Count requests and API bytes for /stats
+async fn count_traffic(State(state): State<SharedState>, request: Request, next: Next) -> Response {
+    let bytes_in = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(0);
+    let response = next.run(request).await;
+    let bytes_out = response.body().size_hint().exact().unwrap_or(0);
+
+    let mut state = state.write().unwrap();
+    state.requests += 1;
+    state.bytes_in += bytes_in;
+    state.bytes_out += bytes_out;
+    response
+}
+
+fn is_admin(
+    state: &DiscoveryState,
+    headers: &axum::http::HeaderMap,
+    query: &HashMap<String, String>,
+) -> bool {
+    let Some(expected) = state.admin_token.as_deref() else {
+        return false;
+    };
+    token_matches(headers, query, expected)
+}
+
+/// Check a Bearer token (or ?token=) against `expected`
+pub fn token_matches(
+    headers: &axum::http::HeaderMap,
+    query: &HashMap<String, String>,
+    expected: &str,
+) -> bool {
+    let given = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .or_else(|| query.get("token").map(|t| t.as_str()));
+    // Compare every byte so timing doesn't reveal how much of the token matched
+    given.is_some_and(|given| {
+        given.len() == expected.len()
+            && given
+                .bytes()
+                .zip(expected.bytes())
+                .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    })
+}
+
+fn collect_stats(state: &DiscoveryState) -> ServerStats {
+    let unique: std::collections::HashSet<&str> = state
+        .peers
+        .values()
+        .flat_map(|p| p.songs.iter().map(|s| s.hash.as_str()))
+        .collect();
+    ServerStats {
+        peers_online: state.peers.len(),
+        songs_indexed: state.peers.values().map(|p| p.songs.len()).sum(),
+        unique_songs: unique.len(),
+        uptime_secs: state.started_at.map(|t| t.elapsed().as_secs()).unwrap_or(0),
+        requests: state.requests,
+        bytes_in: state.bytes_in,
+        bytes_out: state.bytes_out,
+    }
+}
+
+async fn stats(
+    State(state): State<SharedState>,
+    headers: axum::http::HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    let state = state.read().unwrap();
+    if !is_admin(&state, &headers, &query) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    Json(collect_stats(&state)).into_response()
+}
+
+async fn status_page(
+    State(state): State<SharedState>,
+    headers: axum::http::HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    let state = state.read().unwrap();
+    if !is_admin(&state, &headers, &query) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    let stats = collect_stats(&state);
+    let uptime = format!(
+        "{}h {}m",
+        stats.uptime_secs / 3600,
+        (stats.uptime_secs % 3600) / 60
+    );
+    let rows = [
+        ("Peers online", stats.peers_online.to_string()),
+        ("Songs indexed", stats.songs_indexed.to_string()),
+        ("Unique songs", stats.unique_songs.to_string()),
+        ("Uptime", uptime),
+        ("Requests", stats.requests.to_string()),
+        (
+            "Received",
+            format!("{:.1} MB", stats.bytes_in as f64 / 1_048_576.0),
+        ),
+        (
+            "Sent",
+            format!("{:.1} MB", stats.bytes_out as f64 / 1_048_576.0),
+        ),
+    ]
+    .iter()
+    .map(|(label, value)| format!("<tr><td>{}</td><td>{}</td></tr>", label, value))
+    .collect::<String>();
+    Html(format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><meta http-equiv=\"refresh\" content=\"10\">\
+         <title>Discovery server</title></head><body style=\"font-family:sans-serif\">\
+         <h1>Discovery server</h1><table>{}</table></body></html>",
+        rows
+    ))
+    .into_response()
+}
+
+fn router(state: SharedState) -> Router {
+    Router::new()
+        .route("/health", get(health))
+        .route("/register", post(register_peer))
+        .route("/unregister", delete(unregister_peer))
+        .route("/peers", get(get_peers))
+        .route("/heartbeat", post(heartbeat))
+        .route("/stats", get(stats))
+        .route("/status", get(status_page))
+        .layer(middleware::from_fn_with_state(state.clone(), count_traffic))
+        .layer(CorsLayer::permissive())
+        .with_state(state)
+}
+
 // Cleanup task to remove stale peers
 async fn cleanup_stale_peers(state: SharedState) {
     loop {
@@ -164,21 +344,14 @@ async fn cleanup_stale_peers(state: SharedState) {
 
 // Start the discovery server
 #[allow(dead_code)]
-pub async fn start_server(port: u16) -> Result<(), String> {
-    let state: SharedState = Arc::new(RwLock::new(DiscoveryState::default()));
+pub async fn start_server(port: u16, admin_token: Option<String>) -> Result<(), String> {
+    let state: SharedState = Arc::new(RwLock::new(DiscoveryState::new(admin_token)));
 
     // Start cleanup task
     let cleanup_state = state.clone();
     tokio::spawn(cleanup_stale_peers(cleanup_state));
 
-    let app = Router::new()
-        .route("/health", get(health))
-        .route("/register", post(register_peer))
-        .route("/unregister", delete(unregister_peer))
-        .route("/peers", get(get_peers))
-        .route("/heartbeat", post(heartbeat))
-        .layer(CorsLayer::permissive())
-        .with_state(state);
+    let app = router(state);
 
     let addr = format!("0.0.0.0:{}", port);
     println!("[DISCOVERY] Starting server on {}", addr);
@@ -205,7 +378,7 @@ pub fn is_server_running() -> bool {
     SERVER_RUNNING.load(std::sync::atomic::Ordering::SeqCst)
 }
 
-pub async fn start_discovery_server(port: u16) -> Result<(), String> {
+pub async fn start_discovery_server(port: u16, admin_token: Option<String>) -> Result<(), String> {
     if is_server_running() {
         return Err("Server already running".to_string());
     }
@@ -219,7 +392,7 @@ pub async fn start_discovery_server(port: u16) -> Result<(), String> {
 
     SERVER_RUNNING.store(true, std::sync::atomic::Ordering::SeqCst);
 
-    let result = start_server_with_shutdown(port, tx).await;
+    let result = start_server_with_shutdown(port, admin_token, tx).await;
 
     SERVER_RUNNING.store(false, std::sync::atomic::Ordering::SeqCst);
 
@@ -250,9 +423,10 @@ pub fn stop_discovery_server() -> Result<(), String> {
 // Start the discovery server with shutdown support
 async fn start_server_with_shutdown(
     port: u16,
+    admin_token: Option<String>,
     shutdown_tx: broadcast::Sender<()>,
 ) -> Result<(), String> {
-    let state: SharedState = Arc::new(RwLock::new(DiscoveryState::default()));
+    let state: SharedState = Arc::new(RwLock::new(DiscoveryState::new(admin_token)));
 
     // Start cleanup task
     let cleanup_state = state.clone();
@@ -266,14 +440,7 @@ async fn start_server_with_shutdown(
         }
     });
 
-    let app = Router::new()
-        .route("/health", get(health))
-        .route("/register", post(register_peer))
-        .route("/unregister", delete(unregister_peer))
-        .route("/peers", get(get_peers))
-        .route("/heartbeat", post(heartbeat))
-        .layer(CorsLayer::permissive())
-        .with_state(state);
+    let app = router(state);
 
     let addr = format!("0.0.0.0:{}", port);
     println!("[DISCOVERY] Starting server on {}", addr);
@@ -295,3 +462,398 @@ async fn start_server_with_shutdown(
     println!("[DISCOVERY] Server stopped");
     Ok(())
 }
+
+// ============ Client ============
+
+/// Peers the user never wants to see, matched by peer id or (case-insensitive) name
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PeerBlocklist {
+    #[serde(default)]
+    pub peer_ids: Vec<String>,
+    #[serde(default)]
+    pub names: Vec<String>,
+}
+
+impl PeerBlocklist {
+    pub fn is_blocked(&self, peer: &PeerInfo) -> bool {
+        self.peer_ids.contains(&peer.peer_id)
+            || self
+                .names
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(peer.name.trim()))
+    }
+}
+
+pub fn load_blocklist() -> PeerBlocklist {
+    crate::load_config()
+        .get("peer_blocklist")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default()
+}
+
+fn save_blocklist(blocklist: &PeerBlocklist) {
+    let mut config = crate::load_config();
+    config["peer_blocklist"] = serde_json::json!(blocklist);
+    crate::save_config(&config);
+}
+
+pub fn block_peer(peer_id: Option<&str>, name: Option<&str>) -> PeerBlocklist {
+    let mut blocklist = load_blocklist();
+    if let Some(id) = peer_id.filter(|id| !id.is_empty()) {
+        if !blocklist.peer_ids.iter().any(|b| b == id) {
+            blocklist.peer_ids.push(id.to_string());
+        }
+    }
+    if let Some(name) = name.map(str::trim).filter(|n| !n.is_empty()) {
+        if !blocklist.names.iter().any(|b| b.eq_ignore_ascii_case(name)) {
+            blocklist.names.push(name.to_string());
+        }
+    }
+    save_blocklist(&blocklist);
+    blocklist
+}
+
+pub fn unblock_peer(peer_id: Option<&str>, name: Option<&str>) -> PeerBlocklist {
+    let mut blocklist = load_blocklist();
+    if let Some(id) = peer_id {
+        blocklist.peer_ids.retain(|b| b != id);
+    }
+    if let Some(name) = name {
+        blocklist
+            .names
+            .retain(|b| !b.eq_ignore_ascii_case(name.trim()));
+    }
+    save_blocklist(&blocklist);
+    blocklist
+}
+
+/// Which songs get published to the discovery network
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShareScope {
+    #[default]
+    All,
+    Favorites,
+    Playlists, // Only songs in `playlist_ids`
+    None,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SharingPolicy {
+    pub scope: ShareScope,
+    pub playlist_ids: Vec<String>,
+    pub max_size_mb: Option<u32>, // Skip files larger than this
+}
+
+pub fn load_sharing_policy() -> SharingPolicy {
+    crate::load_config()
+        .get("sharing_policy")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_sharing_policy(policy: &SharingPolicy) {
+    let mut config = crate::load_config();
+    config["sharing_policy"] = serde_json::json!(policy);
+    crate::save_config(&config);
+}
+
+/// Keep only the songs the policy allows. `allowed` is the favorites or playlist
+/// hashes for those scopes (ignored for All).
+pub fn apply_sharing_policy(
+    songs: Vec<SharedSong>,
+    policy: &SharingPolicy,
+    allowed: &std::collections::HashSet<String>,
+) -> Vec<SharedSong> {
+    let max_bytes = policy.max_size_mb.map(|mb| mb as u64 * 1024 * 1024);
+    songs
+        .into_iter()
+        .filter(|song| match policy.scope {
+            ShareScope::All => true,
+            ShareScope::Favorites | ShareScope::Playlists => allowed.contains(&song.hash),
+            ShareScope::None => false,
+        })
+        .filter(|song| max_bytes.is_none_or(|max| song.size <= max))
+        .collect()
+}
+
+// A rejected token is Unauthorized; anything else means we couldn't talk to the server
+fn unreachable_server(e: ureq::Error) -> AppError {
+    match e {
+        ureq::Error::Status(401, _) => {
+            AppError::Unauthorized("Discovery server rejected the token".to_string())
+        }
+        e => AppError::Network(format!("Failed to reach discovery server: {}", e)),
+    }
+}
+
+fn post_json(url: &str, body: &impl Serialize) -> Result<(), AppError> {
+    let body = serde_json::to_string(body).map_err(|e| format!("Failed to serialize: {}", e))?;
+    ureq::post(url)
+        .timeout(Duration::from_secs(5))
+        .set("Content-Type", "application/json")
+        .send_string(&body)
+        .map_err(unreachable_server)?;
+    Ok(())
+}
+
+/// Register (or re-register) with a discovery server
+pub fn send_register(server_url: &str, request: &RegisterRequest) -> Result<(), AppError> {
+    post_json(
+        &format!("{}/register", server_url.trim_end_matches('/')),
+        request,
+    )
+}
+
+/// Keep our registration alive and refresh the song list
+pub fn send_heartbeat(server_url: &str, request: &RegisterRequest) -> Result<(), AppError> {
+    post_json(
+        &format!("{}/heartbeat", server_url.trim_end_matches('/')),
+        request,
+    )
+}
+
+pub fn send_unregister(server_url: &str, peer_id: &str) -> Result<(), AppError> {
+    let body = serde_json::to_string(peer_id).map_err(|e| e.to_string())?;
+    ureq::delete(&format!("{}/unregister", server_url.trim_end_matches('/')))
+        .timeout(Duration::from_secs(5))
+        .set("Content-Type", "application/json")
+        .send_string(&body)
+        .map_err(unreachable_server)?;
+    Ok(())
+}
+
+/// Fetch the peer list from a discovery server with blocked peers removed
+pub fn fetch_peers(server_url: &str) -> Result<PeerListResponse, AppError> {
+    let url = format!("{}/peers", server_url.trim_end_matches('/'));
+    let body = ureq::get(&url)
+        .timeout(Duration::from_secs(5))
+        .call()
+        .map_err(unreachable_server)?
+        .into_string()
+        .map_err(|e| AppError::Network(format!("Failed to read peer list: {}", e)))?;
+    let mut list: PeerListResponse = serde_json::from_str(&body)
+        .map_err(|e| AppError::Network(format!("Invalid peer list: {}", e)))?;
+
+    let blocklist = load_blocklist();
+    list.peers.retain(|peer| !blocklist.is_blocked(peer));
+    list.total_songs = list.peers.iter().map(|p| p.songs.len()).sum();
+    Ok(list)
+}
+
+// ============ Connection keeper ============
+// Keeps us registered with a discovery server and the browse list fresh. When the server
+// drops out we retry with exponential backoff and keep serving the last catalog from disk,
+// so a VPS hiccup or home-server failover doesn't empty the list or need a manual re-join.
+
+const HEARTBEAT_SECS: u64 = 15; // Well inside the server's PEER_TIMEOUT_SECS
+const RECONNECT_BASE_SECS: u64 = 2;
+const RECONNECT_MAX_SECS: u64 = 60;
+const CATALOG_CACHE_FILE: &str = "discovery_cache.json";
+
+static CONNECTION_GENERATION: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+/// Last peer list seen from a server
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct CachedCatalog {
+    server_url: String,
+    fetched_at: u64, // unix seconds
+    peers: Vec<PeerInfo>,
+}
+
+/// Peer list for the browse view, live or from the on-disk cache
+#[derive(Debug, Clone, Serialize)]
+pub struct Catalog {
+    pub peers: Vec<PeerInfo>,
+    pub total_songs: usize,
+    pub cached: bool,    // Server unreachable, this is the last list we saw
+    pub fetched_at: u64, // unix seconds
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionState {
+    #[default]
+    Disconnected,
+    Connected,
+    Reconnecting,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ConnectionStatus {
+    pub state: ConnectionState,
+    pub server_url: Option<String>,
+    pub attempt: u32,       // Failed attempts since the last success
+    pub retry_in_secs: u64, // Backoff before the next attempt
+    pub last_error: Option<String>,
+}
+
+static CONNECTION: Mutex<Option<ConnectionStatus>> = Mutex::new(None);
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn save_catalog_cache(server_url: &str, peers: &[PeerInfo]) -> u64 {
+    let cache = CachedCatalog {
+        server_url: server_url.trim_end_matches('/').to_string(),
+        fetched_at: now_secs(),
+        peers: peers.to_vec(),
+    };
+    if let Ok(path) = crate::get_data_path(CATALOG_CACHE_FILE) {
+        if let Ok(content) = serde_json::to_string(&cache) {
+            let _ = std::fs::write(path, content);
+        }
+    }
+    cache.fetched_at
+}
+
+/// The cached catalog for a server, with the current blocklist applied
+pub fn cached_catalog(server_url: &str) -> Option<Catalog> {
+    let cache: CachedCatalog = crate::get_data_path(CATALOG_CACHE_FILE)
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())?;
+    if cache.server_url != server_url.trim_end_matches('/') {
+        return None;
+    }
+    let blocklist = load_blocklist();
+    let peers: Vec<PeerInfo> = cache
+        .peers
+        .into_iter()
+        .filter(|peer| !blocklist.is_blocked(peer))
+        .collect();
+    Some(Catalog {
+        total_songs: peers.iter().map(|p| p.songs.len()).sum(),
+        peers,
+        cached: true,
+        fetched_at: cache.fetched_at,
+    })
+}
+
+/// Fetch the live peer list and cache it; fall back to the cache if the server is down
+pub fn load_catalog(server_url: &str) -> Result<Catalog, AppError> {
+    match fetch_peers(server_url) {
+        Ok(list) => {
+            let fetched_at = save_catalog_cache(server_url, &list.peers);
+            Ok(Catalog {
+                peers: list.peers,
+                total_songs: list.total_songs,
+                cached: false,
+                fetched_at,
+            })
+        }
+        Err(e) => cached_catalog(server_url).ok_or(e),
+    }
+}
+
+pub fn connection_status() -> ConnectionStatus {
+    CONNECTION.lock().unwrap().clone().unwrap_or_default()
+}
+
+fn set_status(app: &tauri::AppHandle, status: ConnectionStatus) {
+    use tauri::Emitter;
+    *CONNECTION.lock().unwrap() = Some(status.clone());
+    let _ = app.emit("discovery-connection", status);
+}
+
+/// Register with `server_url` and keep the registration alive until `disconnect` (or
+/// another `connect`). `build_request` is called on every (re)registration so the
+/// published song list is current after an outage.
+pub fn connect(
+    app: tauri::AppHandle,
+    server_url: String,
+    build_request: impl Fn() -> Result<RegisterRequest, String> + Send + 'static,
+) {
+    use std::sync::atomic::Ordering;
+    use tauri::Emitter;
+
+    let generation = CONNECTION_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    let current = move || CONNECTION_GENERATION.load(Ordering::SeqCst) == generation;
+
+    std::thread::spawn(move || {
+        let mut registered: Option<RegisterRequest> = None;
+        let mut attempt: u32 = 0;
+
+        while current() {
+            let result = if let Some(request) = &registered {
+                send_heartbeat(&server_url, request)
+            } else {
+                build_request()
+                    .map_err(AppError::from)
+                    .and_then(|request| send_register(&server_url, &request).map(|_| request))
+                    .map(|request| registered = Some(request))
+            };
+
+            let wait_secs = match result {
+                Ok(()) => {
+                    if connection_status().state != ConnectionState::Connected {
+                        println!("[DISCOVERY] Connected to {}", server_url);
+                    }
+                    attempt = 0;
+                    set_status(
+                        &app,
+                        ConnectionStatus {
+                            state: ConnectionState::Connected,
+                            server_url: Some(server_url.clone()),
+                            ..Default::default()
+                        },
+                    );
+                    if let Ok(catalog) = load_catalog(&server_url) {
+                        let _ = app.emit("discovery-peers", catalog);
+                    }
+                    HEARTBEAT_SECS
+                }
+                Err(e) => {
+                    // The server may have restarted without us, so register afresh next time
+                    registered = None;
+                    attempt += 1;
+                    let backoff =
+                        (RECONNECT_BASE_SECS << (attempt - 1).min(5)).min(RECONNECT_MAX_SECS);
+                    println!(
+                        "[DISCOVERY] Connection lost ({}), retry {} in {}s",
+                        e, attempt, backoff
+                    );
+                    set_status(
+                        &app,
+                        ConnectionStatus {
+                            state: ConnectionState::Reconnecting,
+                            server_url: Some(server_url.clone()),
+                            attempt,
+                            retry_in_secs: backoff,
+                            last_error: Some(e.to_string()),
+                        },
+                    );
+                    if attempt == 1 {
+                        if let Some(catalog) = cached_catalog(&server_url) {
+                            let _ = app.emit("discovery-peers", catalog);
+                        }
+                    }
+                    backoff
+                }
+            };
+
+            // Sleep in slices so disconnect takes effect promptly
+            for _ in 0..wait_secs * 4 {
+                if !current() {
+                    return;
+                }
+                std::thread::sleep(Duration::from_millis(250));
+            }
+        }
+    });
+}
+
+/// Stop the connection keeper and leave the server (best effort)
+pub fn disconnect(app: &tauri::AppHandle, peer_id: Option<&str>) {
+    CONNECTION_GENERATION.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    let server_url = connection_status().server_url;
+    if let (Some(server_url), Some(peer_id)) = (server_url, peer_id) {
+        let _ = send_unregister(&server_url, peer_id);
+    }
+    set_status(app, ConnectionStatus::default());
+}
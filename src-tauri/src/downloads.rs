@@ -0,0 +1,329 @@
+// Download queue for songs fetched from other players.
+// Jobs run at most `concurrency` at a time with retry and exponential backoff.
+// Peer transfers go over WebRTC in the webview: the worker emits
+// "p2p-download-request" and the UI answers with complete/fail. HTTP sources
+// (e.g. a LAN library server) are fetched here directly. Songs already in the
+// library or already queued are skipped. Totals are emitted as "p2p-download-progress".
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+const MAX_ATTEMPTS: u32 = 4;
+const RETRY_BASE_MS: u64 = 2000; // 2s, 4s, 8s
+const PEER_TIMEOUT: Duration = Duration::from_secs(60); // No answer from the UI = failed attempt
+const MAX_DOWNLOAD_BYTES: u64 = 10 * 1024 * 1024;
+pub const MAX_CONCURRENCY: usize = 8;
+
+static CONCURRENCY: AtomicUsize = AtomicUsize::new(3);
+static WORKER_RUNNING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum DownloadSource {
+    Peer {
+        peer_id: String,
+    }, // WebRTC, fetched by the UI
+    Http {
+        url: String,
+        #[serde(default, skip_serializing)]
+        token: Option<String>, // Bearer token, e.g. for a LAN library server
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadRequest {
+    pub hash: String,
+    pub name: String,
+    #[serde(default)]
+    pub sha256: Option<String>,
+    pub source: DownloadSource,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Queued,
+    Active,
+    Done,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadJob {
+    pub id: u64,
+    pub request: DownloadRequest,
+    pub state: JobState,
+    pub attempts: u32,
+    pub error: Option<String>,
+    pub saved_path: Option<String>,
+    #[serde(skip)]
+    not_before: Option<Instant>, // Backoff before the next attempt
+    #[serde(skip)]
+    started: Option<Instant>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DownloadProgress {
+    pub total: usize,
+    pub queued: usize,
+    pub active: usize,
+    pub completed: usize,
+    pub failed: usize,
+    pub skipped: usize, // Duplicates dropped when queueing (this batch)
+}
+
+struct Queue {
+    jobs: Vec<DownloadJob>,
+    next_id: u64,
+}
+
+lazy_static::lazy_static! {
+    static ref QUEUE: Mutex<Queue> = Mutex::new(Queue { jobs: Vec::new(), next_id: 1 });
+}
+
+pub fn set_concurrency(limit: usize) {
+    CONCURRENCY.store(limit.clamp(1, MAX_CONCURRENCY), Ordering::SeqCst);
+}
+
+pub fn get_concurrency() -> usize {
+    CONCURRENCY.load(Ordering::SeqCst)
+}
+
+pub fn jobs() -> Vec<DownloadJob> {
+    QUEUE.lock().unwrap().jobs.clone()
+}
+
+fn progress(skipped: usize) -> DownloadProgress {
+    let queue = QUEUE.lock().unwrap();
+    let count = |state| queue.jobs.iter().filter(|j| j.state == state).count();
+    DownloadProgress {
+        total: queue.jobs.len(),
+        queued: count(JobState::Queued),
+        active: count(JobState::Active),
+        completed: count(JobState::Done),
+        failed: count(JobState::Failed),
+        skipped,
+    }
+}
+
+fn emit_progress(app: &AppHandle) {
+    let _ = app.emit("p2p-download-progress", progress(0));
+}
+
+/// Queue songs for download, skipping ones already in the library or in the queue
+pub fn enqueue(app: &AppHandle, requests: Vec<DownloadRequest>) -> DownloadProgress {
+    let hashes = requests.iter().map(|r| r.hash.clone()).collect();
+    let in_library = crate::resolve_paths_by_hash(&hashes);
+
+    let mut skipped = 0;
+    {
+        let mut queue = QUEUE.lock().unwrap();
+        for request in requests {
+            let duplicate = in_library.contains_key(&request.hash)
+                || queue
+                    .jobs
+                    .iter()
+                    .any(|j| j.request.hash == request.hash && j.state != JobState::Failed);
+            if duplicate {
+                skipped += 1;
+                continue;
+            }
+            let id = queue.next_id;
+            queue.next_id += 1;
+            queue.jobs.push(DownloadJob {
+                id,
+                request,
+                state: JobState::Queued,
+                attempts: 0,
+                error: None,
+                saved_path: None,
+                not_before: None,
+                started: None,
+            });
+        }
+    }
+    ensure_worker(app);
+    let progress = progress(skipped);
+    let _ = app.emit("p2p-download-progress", &progress);
+    progress
+}
+
+/// Data for an active job arrived (from the UI for peer jobs)
+pub fn complete(app: &AppHandle, id: u64, data: &[u8]) -> Result<String, String> {
+    let request = {
+        let queue = QUEUE.lock().unwrap();
+        let job = queue
+            .jobs
+            .iter()
+            .find(|j| j.id == id && j.state == JobState::Active)
+            .ok_or_else(|| "Download job not active".to_string())?;
+        job.request.clone()
+    };
+    match crate::save_midi_to_album(&request.name, data, request.sha256.as_deref()) {
+        Ok(path) => {
+            if let Some(job) = QUEUE.lock().unwrap().jobs.iter_mut().find(|j| j.id == id) {
+                job.state = JobState::Done;
+                job.error = None;
+                job.saved_path = Some(path.clone());
+            }
+            emit_progress(app);
+            Ok(path)
+        }
+        Err(e) => {
+            fail(app, id, &e);
+            Err(e)
+        }
+    }
+}
+
+/// An attempt failed: retry later with backoff, or give up after MAX_ATTEMPTS
+pub fn fail(app: &AppHandle, id: u64, error: &str) {
+    if let Some(job) = QUEUE.lock().unwrap().jobs.iter_mut().find(|j| j.id == id) {
+        if job.state != JobState::Active {
+            return;
+        }
+        job.attempts += 1;
+        job.error = Some(error.to_string());
+        if job.attempts >= MAX_ATTEMPTS {
+            job.state = JobState::Failed;
+            crate::app_error!(
+                "[DOWNLOAD] Giving up on {} after {} attempts: {}",
+                job.request.name,
+                job.attempts,
+                error
+            );
+        } else {
+            job.state = JobState::Queued;
+            let backoff = RETRY_BASE_MS << (job.attempts - 1);
+            job.not_before = Some(Instant::now() + Duration::from_millis(backoff));
+        }
+    }
+    emit_progress(app);
+}
+
+/// Drop queued jobs and forget finished ones (active transfers finish on their own)
+pub fn cancel(app: &AppHandle) {
+    QUEUE
+        .lock()
+        .unwrap()
+        .jobs
+        .retain(|j| j.state == JobState::Active);
+    emit_progress(app);
+}
+
+fn fetch_http(url: &str, token: Option<&str>) -> Result<Vec<u8>, String> {
+    use std::io::Read;
+    let mut request = ureq::get(url).timeout(Duration::from_secs(30));
+    if let Some(token) = token {
+        request = request.set("Authorization", &format!("Bearer {}", token));
+    }
+    let response = request
+        .call()
+        .map_err(|e| format!("Failed to download: {}", e))?;
+    let mut bytes = Vec::new();
+    response
+        .into_reader()
+        .take(MAX_DOWNLOAD_BYTES)
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("Failed to read response: {}", e))?;
+    Ok(bytes)
+}
+
+fn ensure_worker(app: &AppHandle) {
+    if WORKER_RUNNING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let app = app.clone();
+    std::thread::Builder::new()
+        .name("downloads".to_string())
+        .spawn(move || {
+            loop {
+                // Time out peer transfers the UI never answered
+                let timed_out: Vec<u64> = QUEUE
+                    .lock()
+                    .unwrap()
+                    .jobs
+                    .iter()
+                    .filter(|j| {
+                        j.state == JobState::Active
+                            && matches!(j.request.source, DownloadSource::Peer { .. })
+                            && j.started.is_some_and(|t| t.elapsed() > PEER_TIMEOUT)
+                    })
+                    .map(|j| j.id)
+                    .collect();
+                for id in timed_out {
+                    fail(&app, id, "Peer did not send the song in time");
+                }
+
+                // Start whatever fits under the concurrency cap
+                let starting: Vec<DownloadJob> = {
+                    let mut queue = QUEUE.lock().unwrap();
+                    let active = queue
+                        .jobs
+                        .iter()
+                        .filter(|j| j.state == JobState::Active)
+                        .count();
+                    let free = get_concurrency().saturating_sub(active);
+                    let now = Instant::now();
+                    queue
+                        .jobs
+                        .iter_mut()
+                        .filter(|j| {
+                            j.state == JobState::Queued && j.not_before.is_none_or(|t| t <= now)
+                        })
+                        .take(free)
+                        .map(|j| {
+                            j.state = JobState::Active;
+                            j.started = Some(now);
+                            j.clone()
+                        })
+                        .collect()
+                };
+                if !starting.is_empty() {
+                    emit_progress(&app);
+                }
+                for job in starting {
+                    match &job.request.source {
+                        DownloadSource::Peer { peer_id } => {
+                            let _ = app.emit(
+                                "p2p-download-request",
+                                serde_json::json!({
+                                    "job_id": job.id,
+                                    "hash": job.request.hash,
+                                    "name": job.request.name,
+                                    "peer_id": peer_id,
+                                }),
+                            );
+                        }
+                        DownloadSource::Http { url, token } => {
+                            let app = app.clone();
+                            let url = url.clone();
+                            let token = token.clone();
+                            std::thread::spawn(move || match fetch_http(&url, token.as_deref()) {
+                                Ok(bytes) => {
+                                    let _ = complete(&app, job.id, &bytes);
+                                }
+                                Err(e) => fail(&app, job.id, &e),
+                            });
+                        }
+                    }
+                }
+
+                let pending = QUEUE
+                    .lock()
+                    .unwrap()
+                    .jobs
+                    .iter()
+                    .any(|j| matches!(j.state, JobState::Queued | JobState::Active));
+                if !pending {
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(200));
+            }
+            WORKER_RUNNING.store(false, Ordering::SeqCst);
+        })
+        .ok();
+}
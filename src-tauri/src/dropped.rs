@@ -0,0 +1,119 @@
+// Files and folders dropped on the window, sorted by what they are so one command can
+// route each to its importer: MIDI files are copied in, zips go through the archive
+// import, Internet shortcuts (.url) are downloaded, folders are searched for the above.
+
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+// Deep enough for "Music/Artist/Album/disc 1", shallow enough not to wander a whole drive
+const MAX_FOLDER_DEPTH: usize = 6;
+
+#[derive(Debug, Clone)]
+pub enum DroppedItem {
+    Midi(PathBuf),
+    Zip(PathBuf),
+    Shortcut { path: PathBuf, url: String },
+}
+
+impl DroppedItem {
+    pub fn path(&self) -> &Path {
+        match self {
+            DroppedItem::Midi(path) | DroppedItem::Zip(path) => path,
+            DroppedItem::Shortcut { path, .. } => path,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DropFailure {
+    pub path: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DropSummary {
+    pub imported: Vec<String>,   // Album paths of new songs
+    pub duplicates: Vec<String>, // Album paths of songs that were already there
+    pub failed: Vec<DropFailure>,
+    pub unsupported: Vec<String>, // Dropped paths that aren't anything we import
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DropProgress {
+    pub done: usize,
+    pub total: usize,
+    pub current: String,
+}
+
+fn has_extension(path: &Path, ext: &str) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case(ext))
+}
+
+/// The URL of a Windows Internet shortcut, if it points to the web
+fn shortcut_url(path: &Path) -> Option<String> {
+    let content = std::fs::read_to_string(crate::paths::long(path)).ok()?;
+    content
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("URL="))
+        .map(|url| url.trim().to_string())
+        .find(|url| url.starts_with("http://") || url.starts_with("https://"))
+}
+
+fn classify_file(path: &Path) -> Option<DroppedItem> {
+    if has_extension(path, "mid") {
+        Some(DroppedItem::Midi(path.to_path_buf()))
+    } else if has_extension(path, "zip") {
+        Some(DroppedItem::Zip(path.to_path_buf()))
+    } else if has_extension(path, "url") {
+        shortcut_url(path).map(|url| DroppedItem::Shortcut {
+            path: path.to_path_buf(),
+            url,
+        })
+    } else {
+        None
+    }
+}
+
+fn walk_folder(dir: &Path, depth: usize, items: &mut Vec<DroppedItem>) {
+    let Ok(entries) = std::fs::read_dir(crate::paths::long(dir)) else {
+        return;
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| PathBuf::from(crate::paths::short(&entry.path())))
+        .collect();
+    paths.sort();
+    for path in paths {
+        if path.is_dir() {
+            if depth < MAX_FOLDER_DEPTH {
+                walk_folder(&path, depth + 1, items);
+            }
+        } else if let Some(item) = classify_file(&path) {
+            items.push(item);
+        }
+    }
+}
+
+/// Sort dropped paths into things to import; the rest come back as unsupported.
+/// Folders are replaced by what they contain.
+pub fn classify(paths: &[String]) -> (Vec<DroppedItem>, Vec<String>) {
+    let mut items = Vec::new();
+    let mut unsupported = Vec::new();
+    for raw in paths {
+        let path = Path::new(raw);
+        if path.is_dir() {
+            let before = items.len();
+            walk_folder(path, 0, &mut items);
+            if items.len() == before {
+                unsupported.push(raw.clone());
+            }
+        } else {
+            match classify_file(path) {
+                Some(item) => items.push(item),
+                None => unsupported.push(raw.clone()),
+            }
+        }
+    }
+    (items, unsupported)
+}
@@ -0,0 +1,85 @@
+// Errors returned by commands. The frontend gets { code, message }: the code says
+// what went wrong so the UI can react (e.g. offer to focus the game), the message
+// is for people. Helpers that still return `Result<_, String>` convert with `?`.
+
+use serde::Serialize;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "code", content = "message", rename_all = "snake_case")]
+pub enum AppError {
+    FileNotFound(String),
+    PermissionDenied(String),
+    GameWindowNotFound(String),
+    InvalidMidi(String),
+    NoSongLoaded(String),
+    InvalidInput(String),
+    Network(String),
+    Unauthorized(String),
+    Io(String),
+    #[serde(rename = "error")]
+    Other(String),
+}
+
+impl AppError {
+    pub fn message(&self) -> &str {
+        match self {
+            AppError::FileNotFound(m)
+            | AppError::PermissionDenied(m)
+            | AppError::GameWindowNotFound(m)
+            | AppError::InvalidMidi(m)
+            | AppError::NoSongLoaded(m)
+            | AppError::InvalidInput(m)
+            | AppError::Network(m)
+            | AppError::Unauthorized(m)
+            | AppError::Io(m)
+            | AppError::Other(m) => m,
+        }
+    }
+
+    /// An I/O failure while doing `action` ("read file", ...), coded by its kind
+    pub fn io(action: &str, e: std::io::Error) -> Self {
+        let message = format!("Failed to {}: {}", action, e);
+        match e.kind() {
+            std::io::ErrorKind::NotFound => AppError::FileNotFound(message),
+            std::io::ErrorKind::PermissionDenied => AppError::PermissionDenied(message),
+            _ => AppError::Io(message),
+        }
+    }
+
+    pub fn no_song_loaded() -> Self {
+        AppError::NoSongLoaded("No MIDI file loaded".to_string())
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<String> for AppError {
+    fn from(message: String) -> Self {
+        AppError::Other(message)
+    }
+}
+
+impl From<&str> for AppError {
+    fn from(message: &str) -> Self {
+        AppError::Other(message.to_string())
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(e: std::io::Error) -> Self {
+        AppError::io("access file", e)
+    }
+}
+
+impl From<AppError> for String {
+    fn from(e: AppError) -> Self {
+        e.to_string()
+    }
+}
@@ -0,0 +1,117 @@
+// Favorites, keyed by content hash and stored in favorites.json next to the exe.
+// The backend owns the list so every window sees the same favorites; changes
+// are broadcast as "favorites-changed"
+
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Favorite {
+    pub hash: String,
+    pub name: String,
+    // Resolved from the hash when listing; never written to disk
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    // Library fields the UI shows (duration, bpm, ...), kept as sent
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+lazy_static::lazy_static! {
+    // Serializes read-modify-write of favorites.json
+    static ref FAVORITES_LOCK: Mutex<()> = Mutex::new(());
+}
+
+fn load() -> Vec<Favorite> {
+    crate::get_data_path("favorites.json")
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save(favorites: &[Favorite]) -> Result<(), String> {
+    let stripped: Vec<Favorite> = favorites
+        .iter()
+        .cloned()
+        .map(|mut f| {
+            f.path = None;
+            f
+        })
+        .collect();
+    let content = serde_json::to_string_pretty(&stripped)
+        .map_err(|e| format!("Failed to serialize favorites: {}", e))?;
+    crate::data_watch::write("favorites.json", content)
+        .map_err(|e| format!("Failed to write favorites: {}", e))
+}
+
+/// All favorites with paths resolved against the current library (None if the file is gone)
+pub fn list() -> Vec<Favorite> {
+    let mut favorites = load();
+    let hashes = favorites.iter().map(|f| f.hash.clone()).collect();
+    let paths = crate::resolve_paths_by_hash(&hashes);
+    for favorite in &mut favorites {
+        favorite.path = paths.get(&favorite.hash).cloned();
+    }
+    favorites
+}
+
+pub fn is_favorite(hash: &str) -> bool {
+    load().iter().any(|f| f.hash == hash)
+}
+
+pub fn notify_changed(app: &AppHandle) {
+    let _ = app.emit("favorites-changed", list());
+}
+
+fn modify(app: &AppHandle, apply: impl FnOnce(&mut Vec<Favorite>) -> bool) -> Result<bool, String> {
+    let changed = {
+        let _guard = FAVORITES_LOCK.lock().unwrap();
+        let mut favorites = load();
+        let changed = apply(&mut favorites);
+        if changed {
+            save(&favorites)?;
+        }
+        changed
+    };
+    if changed {
+        notify_changed(app);
+    }
+    Ok(changed)
+}
+
+/// Returns false if it was already a favorite
+pub fn add(app: &AppHandle, favorite: Favorite) -> Result<bool, String> {
+    modify(app, |favorites| {
+        if favorites.iter().any(|f| f.hash == favorite.hash) {
+            return false;
+        }
+        favorites.push(favorite);
+        true
+    })
+}
+
+/// Returns false if it wasn't a favorite
+pub fn remove(app: &AppHandle, hash: &str) -> Result<bool, String> {
+    modify(app, |favorites| {
+        let before = favorites.len();
+        favorites.retain(|f| f.hash != hash);
+        favorites.len() != before
+    })
+}
+
+/// Returns whether the song is a favorite afterwards
+pub fn toggle(app: &AppHandle, favorite: Favorite) -> Result<bool, String> {
+    let mut now_favorite = false;
+    modify(app, |favorites| {
+        let before = favorites.len();
+        favorites.retain(|f| f.hash != favorite.hash);
+        if favorites.len() == before {
+            favorites.push(favorite);
+            now_favorite = true;
+        }
+        true
+    })?;
+    Ok(now_favorite)
+}
@@ -0,0 +1,105 @@
+// Optional "Open with" entry for .mid files. Registered per user under
+// HKCU\Software\Classes, so no admin rights are needed, and only as an extra handler:
+// Windows keeps the player's chosen default app until they pick this one themselves.
+
+#[cfg(target_os = "windows")]
+const PROG_ID: &str = "WWMMidiPlayer.mid";
+
+#[cfg(target_os = "windows")]
+fn reg(args: &[&str]) -> Result<bool, String> {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    let status = std::process::Command::new("reg")
+        .args(args)
+        .creation_flags(CREATE_NO_WINDOW)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .map_err(|e| format!("Failed to run reg: {}", e))?;
+    Ok(status.success())
+}
+
+#[cfg(target_os = "windows")]
+pub fn is_registered() -> bool {
+    let key = format!(r"HKCU\Software\Classes\{}\shell\open\command", PROG_ID);
+    reg(&["query", &key]).unwrap_or(false)
+}
+
+#[cfg(target_os = "windows")]
+pub fn register() -> Result<(), String> {
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    let exe = exe.to_string_lossy();
+    let classes = r"HKCU\Software\Classes";
+    let command = format!("\"{}\" \"%1\"", exe);
+    let icon = format!("\"{}\",0", exe);
+
+    let steps: [Vec<String>; 4] = [
+        vec![
+            format!(r"{}\{}", classes, PROG_ID),
+            "/ve".into(),
+            "/d".into(),
+            "MIDI song".into(),
+        ],
+        vec![
+            format!(r"{}\{}\DefaultIcon", classes, PROG_ID),
+            "/ve".into(),
+            "/d".into(),
+            icon,
+        ],
+        vec![
+            format!(r"{}\{}\shell\open\command", classes, PROG_ID),
+            "/ve".into(),
+            "/d".into(),
+            command,
+        ],
+        vec![
+            format!(r"{}\.mid\OpenWithProgids", classes),
+            "/v".into(),
+            PROG_ID.into(),
+            "/t".into(),
+            "REG_NONE".into(),
+        ],
+    ];
+    for step in steps {
+        let mut args = vec!["add"];
+        args.extend(step.iter().map(String::as_str));
+        args.push("/f");
+        if !reg(&args)? {
+            return Err(format!("Failed to write registry key {}", step[0]));
+        }
+    }
+    crate::app_log!("[ASSOC] Registered as a handler for .mid files");
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+pub fn unregister() -> Result<(), String> {
+    let classes = r"HKCU\Software\Classes";
+    // Either may already be gone
+    let _ = reg(&[
+        "delete",
+        &format!(r"{}\.mid\OpenWithProgids", classes),
+        "/v",
+        PROG_ID,
+        "/f",
+    ]);
+    let _ = reg(&["delete", &format!(r"{}\{}", classes, PROG_ID), "/f"]);
+    crate::app_log!("[ASSOC] Removed the .mid handler");
+    Ok(())
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn is_registered() -> bool {
+    false
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn register() -> Result<(), String> {
+    Err("File associations are only supported on Windows".to_string())
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn unregister() -> Result<(), String> {
+    Ok(())
+}
@@ -0,0 +1,284 @@
+// Fuzz-style property tests for the paths that take files from other players: MIDI
+// validation, MIDI parsing and zip import. Inputs are mutated from valid seeds with a
+// seeded generator so any failure reproduces; set WWM_FUZZ_CASES to run longer.
+
+use crate::midi;
+use std::io::{Cursor, Write};
+
+const DEFAULT_CASES: usize = 2000;
+
+fn cases() -> usize {
+    std::env::var("WWM_FUZZ_CASES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_CASES)
+}
+
+/// xorshift64*: tiny, deterministic, good enough to drive mutations
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Rng(seed.max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n.max(1) as u64) as usize
+    }
+
+    fn byte(&mut self) -> u8 {
+        self.next() as u8
+    }
+
+    fn bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.byte()).collect()
+    }
+
+    /// 1..=max random bytes
+    fn chunk(&mut self, max: usize) -> Vec<u8> {
+        let len = 1 + self.below(max);
+        self.bytes(len)
+    }
+}
+
+/// Format 1, two tracks (tempo map + a few notes with running status), 480 ticks per beat
+fn seed_midi() -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(b"MThd");
+    data.extend_from_slice(&[0, 0, 0, 6, 0, 1, 0, 2, 0x01, 0xE0]);
+
+    let tempo_track: &[u8] = &[
+        0x00, 0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20, // 120 BPM
+        0x00, 0xFF, 0x58, 0x04, 0x04, 0x02, 0x18, 0x08, // 4/4
+        0x00, 0xFF, 0x2F, 0x00,
+    ];
+    let note_track: &[u8] = &[
+        0x00, 0xC0, 0x00, // Program change
+        0x00, 0x90, 0x3C, 0x64, // C4 on
+        0x83, 0x60, 0x3C, 0x00, // Running status: C4 off (velocity 0)
+        0x00, 0x3E, 0x64, // D4 on
+        0x83, 0x60, 0x3E, 0x00, // D4 off
+        0x00, 0xFF, 0x2F, 0x00,
+    ];
+    for track in [tempo_track, note_track] {
+        data.extend_from_slice(b"MTrk");
+        data.extend_from_slice(&(track.len() as u32).to_be_bytes());
+        data.extend_from_slice(track);
+    }
+    data
+}
+
+fn seed_zip() -> Vec<u8> {
+    use zip::write::SimpleFileOptions;
+
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let stored = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    let deflated =
+        SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    let entries: [(&str, SimpleFileOptions, Vec<u8>); 4] = [
+        ("song.mid", deflated, seed_midi()),
+        ("folder/other.mid", stored, seed_midi()),
+        ("../escape.mid", stored, seed_midi()),
+        (
+            "metadata.json",
+            deflated,
+            br#"{"export_type":"playlist","name":"Seed"}"#.to_vec(),
+        ),
+    ];
+    for (name, options, contents) in entries {
+        zip.start_file(name, options).unwrap();
+        zip.write_all(&contents).unwrap();
+    }
+    zip.finish().unwrap().into_inner()
+}
+
+/// Damage `data` the way a bad transfer or a hostile peer might
+fn mutate(rng: &mut Rng, data: &[u8]) -> Vec<u8> {
+    let mut out = data.to_vec();
+    for _ in 0..=rng.below(4) {
+        if out.is_empty() {
+            out = rng.chunk(32);
+            continue;
+        }
+        let at = rng.below(out.len());
+        match rng.below(7) {
+            0 => out[at] ^= 1 << rng.below(8),
+            1 => out[at] = [0x00, 0x7F, 0x80, 0xFF][rng.below(4)],
+            2 => out.truncate(at),
+            3 => {
+                let chunk = rng.chunk(16);
+                out.splice(at..at, chunk);
+            }
+            4 => {
+                // Duplicate a slice (repeated chunks, bogus track counts)
+                let end = (at + 1 + rng.below(32)).min(out.len());
+                let chunk = out[at..end].to_vec();
+                out.splice(at..at, chunk);
+            }
+            5 => {
+                // Huge or zero length fields
+                let value: u32 = [0, 6, 0x7FFF_FFFF, 0xFFFF_FFFF][rng.below(4)];
+                for (i, b) in value.to_be_bytes().iter().enumerate() {
+                    if let Some(slot) = out.get_mut(at + i) {
+                        *slot = *b;
+                    }
+                }
+            }
+            _ => {
+                let end = (at + 1 + rng.below(16)).min(out.len());
+                out.drain(at..end);
+            }
+        }
+    }
+    out
+}
+
+#[test]
+fn seeds_are_valid() {
+    assert_eq!(crate::check_midi_bytes(&seed_midi()), Ok(true));
+    let midi_data = midi::midi_data_from_bytes(&seed_midi(), None).unwrap();
+    assert_eq!(midi_data.events.len(), 4);
+    assert!(crate::read_zip_checked(Cursor::new(seed_zip())).is_ok());
+}
+
+#[test]
+fn smpte_division_overflow_is_rejected() {
+    // Found by mutated_midi_never_panics_the_parser: midly overflows on -128 fps
+    let mut data = seed_midi();
+    data[12] = 0x80;
+    assert!(midi::parse_smf(&data).is_err());
+    assert_eq!(crate::check_midi_bytes(&data), Ok(false));
+}
+
+#[test]
+fn mutated_midi_never_panics_the_parser() {
+    let mut rng = Rng::new(0x4D54_6864);
+    let seed = seed_midi();
+    for _ in 0..cases() {
+        let data = mutate(&mut rng, &seed);
+        let _ = midi::parse_smf(&data);
+        if let Ok(midi_data) = midi::midi_data_from_bytes(&data, None) {
+            assert!(midi_data.duration.is_finite() && midi_data.duration >= 0.0);
+            assert!(midi_data
+                .events
+                .windows(2)
+                .all(|w| w[0].time_ms <= w[1].time_ms));
+            assert!(midi_data.events.iter().all(|e| e.note < 128));
+            assert!((-12..=12).contains(&midi_data.transpose));
+        }
+    }
+}
+
+#[test]
+fn random_bytes_never_panic_validation() {
+    let mut rng = Rng::new(0xBAD_F00D);
+    for _ in 0..cases() {
+        let len = rng.below(300);
+        let mut data = rng.bytes(len);
+        // Often start like a MIDI file so the deeper checks run too
+        if rng.below(2) == 0 && data.len() >= 4 {
+            data[..4].copy_from_slice(b"MThd");
+        }
+        let _ = crate::is_executable_data(&data);
+        let _ = crate::check_midi_bytes(&data);
+        let _ = midi::midi_data_from_bytes(&data, None);
+    }
+}
+
+#[test]
+fn accepted_midi_is_really_midi() {
+    let mut rng = Rng::new(0x5EED);
+    let seed = seed_midi();
+    for _ in 0..cases() {
+        let data = mutate(&mut rng, &seed);
+        if crate::check_midi_bytes(&data) == Ok(true) {
+            assert_eq!(&data[..4], b"MThd");
+            assert!(crate::is_executable_data(&data).is_none());
+            assert!(midly::Smf::parse(&data).is_ok());
+        }
+    }
+}
+
+#[test]
+fn executables_are_never_accepted() {
+    const SIGNATURES: [&[u8]; 5] = [
+        b"MZ\x90\x00",
+        b"\x7FELF",
+        b"#!/bin/sh\n",
+        b"\xCF\xFA\xED\xFE",
+        b"\xCA\xFE\xBA\xBE",
+    ];
+    let mut rng = Rng::new(0xE8E);
+    let seed = seed_midi();
+    for _ in 0..cases() {
+        let mut data = SIGNATURES[rng.below(SIGNATURES.len())].to_vec();
+        data.extend(mutate(&mut rng, &seed));
+        assert!(crate::is_executable_data(&data).is_some());
+        assert!(crate::check_midi_bytes(&data).is_err());
+
+        // A PE header hidden inside an otherwise valid MIDI file
+        let mut smuggled = seed.clone();
+        let at = 4 + rng.below(smuggled.len() - 3);
+        smuggled.splice(at..at, b"PE\x00\x00".iter().copied());
+        assert!(crate::check_midi_bytes(&smuggled).is_err());
+    }
+}
+
+#[test]
+fn mutated_zips_never_panic_or_escape() {
+    let mut rng = Rng::new(0x504B_0304);
+    let seed = seed_zip();
+    for _ in 0..cases() {
+        let data = mutate(&mut rng, &seed);
+        let Ok(mut archive) = crate::read_zip_checked(Cursor::new(data)) else {
+            continue;
+        };
+        let mut total = 0u64;
+        for i in 0..archive.len() {
+            let Ok(file) = archive.by_index(i) else {
+                continue;
+            };
+            if let Some(name) = crate::safe_entry_name(file.enclosed_name().as_deref()) {
+                assert!(!name.contains('/') && !name.contains('\\') && !name.starts_with('.'));
+            }
+            if let Ok(contents) =
+                crate::read_entry_limited(file, crate::ZIP_MAX_FILE_BYTES, &mut total)
+            {
+                assert!(contents.len() as u64 <= crate::ZIP_MAX_FILE_BYTES);
+                let _ = midi::midi_data_from_bytes(&contents, None);
+            }
+        }
+    }
+}
+
+#[test]
+fn oversized_entries_count_toward_the_archive_total() {
+    // An entry that inflates past the per-file limit is skipped, but what was read still counts
+    let mut total = 0u64;
+    let result = crate::read_entry_limited(std::io::repeat(0), 1024, &mut total);
+    assert!(result.is_err());
+    assert_eq!(total, 1025);
+}
+
+#[test]
+fn entry_names_stay_in_the_folder() {
+    const PARTS: [&str; 8] = ["..", ".", "/", "\\", "song", ".mid", "C:", "\u{0}"];
+    let mut rng = Rng::new(0xD07);
+    for _ in 0..cases() {
+        let raw: String = (0..1 + rng.below(8))
+            .map(|_| PARTS[rng.below(PARTS.len())])
+            .collect();
+        let path = std::path::Path::new(&raw);
+        if let Some(name) = crate::safe_entry_name(Some(path)) {
+            assert!(!name.contains('/') && !name.contains('\\'), "{:?}", raw);
+            assert!(!name.starts_with('.') && !name.contains(':'), "{:?}", raw);
+        }
+    }
+}
@@ -0,0 +1,251 @@
+// Startup self-check: the things that make the player quietly not work (no admin rights
+// while the game has them, hotkeys taken by another app, no keyboard hook, no game
+// window, an album folder that can't be written, a broken config.json) are checked once
+// at launch and reported as "health-report", so the UI can say why up front.
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter};
+
+// Set by the hotkey thread once SetWindowsHookExW succeeds
+pub static HOOK_INSTALLED: AtomicBool = AtomicBool::new(false);
+
+// Hotkeys are registered on their own thread; give it time before checking
+const STARTUP_DELAY_MS: u64 = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Ok,
+    Warning, // Works, but something may not (e.g. the game isn't open yet)
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthCheck {
+    pub id: &'static str,
+    pub status: CheckStatus,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    pub checked_at: u64,     // unix seconds
+    pub status: CheckStatus, // The worst of the checks
+    pub checks: Vec<HealthCheck>,
+}
+
+static LAST_REPORT: Mutex<Option<HealthReport>> = Mutex::new(None);
+
+fn check(id: &'static str, status: CheckStatus, message: impl Into<String>) -> HealthCheck {
+    HealthCheck {
+        id,
+        status,
+        message: message.into(),
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn is_elevated() -> bool {
+    use windows::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows::Win32::Security::{
+        GetTokenInformation, TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY,
+    };
+    use windows::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+    unsafe {
+        let mut token = HANDLE::default();
+        if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token).is_err() {
+            return false;
+        }
+        let mut elevation = TOKEN_ELEVATION::default();
+        let mut size = 0u32;
+        let ok = GetTokenInformation(
+            token,
+            TokenElevation,
+            Some(&mut elevation as *mut _ as *mut _),
+            std::mem::size_of::<TOKEN_ELEVATION>() as u32,
+            &mut size,
+        )
+        .is_ok();
+        let _ = CloseHandle(token);
+        ok && elevation.TokenIsElevated != 0
+    }
+}
+
+fn check_admin() -> HealthCheck {
+    #[cfg(target_os = "windows")]
+    {
+        if is_elevated() {
+            check("admin", CheckStatus::Ok, "Running as administrator")
+        } else {
+            check(
+                "admin",
+                CheckStatus::Warning,
+                "Not running as administrator - if the game runs as administrator, keys won't reach it",
+            )
+        }
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        check("admin", CheckStatus::Ok, "Not needed on this platform")
+    }
+}
+
+fn check_hotkeys() -> HealthCheck {
+    let status = crate::HOTKEY_STATUS.lock().unwrap().clone();
+    let failed: Vec<String> = status
+        .iter()
+        .filter(|(_, ok)| !ok)
+        .map(|(name, _)| name.clone())
+        .collect();
+    if status.is_empty() {
+        check(
+            "hotkeys",
+            CheckStatus::Warning,
+            "No global hotkeys registered",
+        )
+    } else if failed.is_empty() {
+        check(
+            "hotkeys",
+            CheckStatus::Ok,
+            format!("{} global hotkeys registered", status.len()),
+        )
+    } else {
+        check(
+            "hotkeys",
+            CheckStatus::Warning,
+            format!(
+                "In use by another app: {} (the keyboard hook still catches them)",
+                failed.join(", ")
+            ),
+        )
+    }
+}
+
+fn check_hook() -> HealthCheck {
+    if HOOK_INSTALLED.load(Ordering::SeqCst) {
+        check("hook", CheckStatus::Ok, "Keyboard hook installed")
+    } else {
+        check(
+            "hook",
+            CheckStatus::Failed,
+            "Keyboard hook not installed - hotkeys only work when registered",
+        )
+    }
+}
+
+fn check_game_window() -> HealthCheck {
+    if crate::keyboard::is_game_window_found() {
+        check("game_window", CheckStatus::Ok, "Game window found")
+    } else {
+        check(
+            "game_window",
+            CheckStatus::Warning,
+            "Game window not found - start the game or add a window keyword",
+        )
+    }
+}
+
+fn check_album() -> HealthCheck {
+    let album = match crate::get_album_folder() {
+        Ok(album) => album,
+        Err(e) => return check("album", CheckStatus::Failed, e),
+    };
+    if let Err(e) = std::fs::create_dir_all(crate::paths::long(&album)) {
+        return check(
+            "album",
+            CheckStatus::Failed,
+            format!("Failed to create album folder: {}", e),
+        );
+    }
+    let probe = crate::paths::long(album.join(".write_test"));
+    match std::fs::write(&probe, b"") {
+        Ok(()) => {
+            let _ = std::fs::remove_file(&probe);
+            check(
+                "album",
+                CheckStatus::Ok,
+                format!("Album folder is writable: {}", album.display()),
+            )
+        }
+        Err(e) => check(
+            "album",
+            CheckStatus::Failed,
+            format!("Album folder is not writable: {}", e),
+        ),
+    }
+}
+
+fn check_config() -> HealthCheck {
+    let path = match crate::get_config_path() {
+        Ok(path) => path,
+        Err(e) => return check("config", CheckStatus::Failed, e),
+    };
+    if !path.exists() {
+        return check(
+            "config",
+            CheckStatus::Ok,
+            "No config.json yet, using defaults",
+        );
+    }
+    match std::fs::read_to_string(&path).map(|c| serde_json::from_str::<serde_json::Value>(&c)) {
+        Ok(Ok(_)) => check("config", CheckStatus::Ok, "config.json loaded"),
+        Ok(Err(e)) => check(
+            "config",
+            CheckStatus::Failed,
+            format!("config.json is damaged, settings were reset: {}", e),
+        ),
+        Err(e) => check(
+            "config",
+            CheckStatus::Failed,
+            format!("Failed to read config.json: {}", e),
+        ),
+    }
+}
+
+pub fn run() -> HealthReport {
+    let checks = vec![
+        check_admin(),
+        check_hotkeys(),
+        check_hook(),
+        check_game_window(),
+        check_album(),
+        check_config(),
+    ];
+    let status = if checks.iter().any(|c| c.status == CheckStatus::Failed) {
+        CheckStatus::Failed
+    } else if checks.iter().any(|c| c.status == CheckStatus::Warning) {
+        CheckStatus::Warning
+    } else {
+        CheckStatus::Ok
+    };
+    let report = HealthReport {
+        checked_at: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        status,
+        checks,
+    };
+    *LAST_REPORT.lock().unwrap() = Some(report.clone());
+    report
+}
+
+/// The startup report, or a fresh one if it hasn't run yet
+pub fn last() -> HealthReport {
+    let last = LAST_REPORT.lock().unwrap().clone();
+    last.unwrap_or_else(run)
+}
+
+pub fn start(app: AppHandle) {
+    std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(STARTUP_DELAY_MS));
+        let report = run();
+        for failed in report.checks.iter().filter(|c| c.status != CheckStatus::Ok) {
+            crate::app_log!("[HEALTH] {}: {}", failed.id, failed.message);
+        }
+        let _ = app.emit("health-report", report);
+    });
+}
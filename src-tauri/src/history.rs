@@ -0,0 +1,212 @@
+// Play history
+// One entry per song, most recent first, stored in history.json next to the exe.
+// Backs the "recently played" / "continue listening" lists on the home screen.
+// Entries are matched by content hash, with the path kept alongside it, so a song
+// renamed or moved inside the album keeps its history.
+
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+const MAX_HISTORY: usize = 200;
+// A song stopped within this many seconds of either end isn't worth resuming
+const RESUME_MARGIN_SECS: f64 = 10.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub path: String,
+    pub name: String,
+    #[serde(default)]
+    pub hash: String,
+    pub played_at: u64, // unix seconds
+    pub play_count: u32,
+    pub last_position: f64, // seconds reached when playback stopped
+    pub duration: f64,
+    pub completed: bool, // played through to the end
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecentFile {
+    pub path: String,
+    pub name: String,
+    pub added_at: u64, // file modification time, unix seconds
+}
+
+lazy_static::lazy_static! {
+    // Serializes read-modify-write of history.json between playback threads
+    static ref HISTORY_LOCK: Mutex<()> = Mutex::new(());
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn load() -> Vec<HistoryEntry> {
+    crate::get_data_path("history.json")
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save(entries: &[HistoryEntry]) {
+    if let Ok(path) = crate::get_data_path("history.json") {
+        if let Ok(content) = serde_json::to_string_pretty(entries) {
+            let _ = std::fs::write(path, content);
+        }
+    }
+}
+
+fn file_name(path: &str) -> String {
+    std::path::Path::new(path)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("Unknown")
+        .to_string()
+}
+
+/// The song's entry: same content if the hash is known, otherwise same path
+fn find_entry(entries: &[HistoryEntry], path: &str, hash: &str) -> Option<usize> {
+    entries
+        .iter()
+        .position(|e| !hash.is_empty() && e.hash == hash)
+        .or_else(|| entries.iter().position(|e| e.path == path))
+}
+
+/// Song started playing. Restarting the current song (seek) doesn't count as a new play.
+pub fn record_start(path: &str, duration: f64) {
+    let _guard = HISTORY_LOCK.lock().unwrap();
+    let mut entries = load();
+    let hash = crate::compute_file_hash(std::path::Path::new(path)).unwrap_or_default();
+
+    let mut entry = match find_entry(&entries, path, &hash) {
+        // Already the current song and not finished: a seek or resume, not a new play
+        Some(0) if !entries[0].completed => return,
+        Some(index) => entries.remove(index),
+        None => HistoryEntry {
+            path: path.to_string(),
+            name: file_name(path),
+            hash: hash.clone(),
+            played_at: 0,
+            play_count: 0,
+            last_position: 0.0,
+            duration,
+            completed: false,
+        },
+    };
+    // Follow renames and moves
+    entry.path = path.to_string();
+    entry.name = file_name(path);
+    if !hash.is_empty() {
+        entry.hash = hash;
+    }
+    entry.played_at = now_secs();
+    entry.play_count += 1;
+    entry.last_position = 0.0;
+    entry.duration = duration;
+    entry.completed = false;
+
+    entries.insert(0, entry);
+    entries.truncate(MAX_HISTORY);
+    save(&entries);
+}
+
+/// Song stopped (completed = played to the end)
+pub fn record_stop(path: &str, position: f64, completed: bool) {
+    let _guard = HISTORY_LOCK.lock().unwrap();
+    let mut entries = load();
+    let hash = crate::compute_file_hash(std::path::Path::new(path)).unwrap_or_default();
+    if let Some(entry) = find_entry(&entries, path, &hash).map(|i| &mut entries[i]) {
+        entry.last_position = if completed { entry.duration } else { position };
+        entry.completed = completed;
+        save(&entries);
+    }
+}
+
+/// Entries whose file is still in the library, pointed at where it is now
+fn with_current_paths(entries: Vec<HistoryEntry>) -> Vec<HistoryEntry> {
+    let moved: std::collections::HashSet<String> = entries
+        .iter()
+        .filter(|e| !e.hash.is_empty() && !std::path::Path::new(&e.path).exists())
+        .map(|e| e.hash.clone())
+        .collect();
+    let found = if moved.is_empty() {
+        std::collections::HashMap::new()
+    } else {
+        crate::resolve_paths_by_hash(&moved)
+    };
+    entries
+        .into_iter()
+        .filter_map(|mut e| {
+            if !std::path::Path::new(&e.path).exists() {
+                e.path = found.get(&e.hash)?.clone();
+                e.name = file_name(&e.path);
+            }
+            Some(e)
+        })
+        .collect()
+}
+
+pub fn recently_played(limit: usize) -> Vec<HistoryEntry> {
+    with_current_paths(load()).into_iter().take(limit).collect()
+}
+
+/// Songs stopped partway through, most recent first
+pub fn continue_listening(limit: usize) -> Vec<HistoryEntry> {
+    let partway: Vec<HistoryEntry> = load()
+        .into_iter()
+        .filter(|e| {
+            !e.completed
+                && e.last_position > RESUME_MARGIN_SECS
+                && e.last_position < e.duration - RESUME_MARGIN_SECS
+        })
+        .collect();
+    with_current_paths(partway)
+        .into_iter()
+        .take(limit)
+        .collect()
+}
+
+/// Play count per content hash (for catalog export and sorting)
+pub fn play_counts() -> std::collections::HashMap<String, u32> {
+    // Older histories can hold a renamed song twice, once per path
+    let mut counts = std::collections::HashMap::new();
+    for e in load().into_iter().filter(|e| !e.hash.is_empty()) {
+        *counts.entry(e.hash).or_insert(0) += e.play_count;
+    }
+    counts
+}
+
+/// Newest files in the album folder by modification time
+pub fn recently_added(limit: usize) -> Result<Vec<RecentFile>, String> {
+    fn collect(dir: &std::path::Path, files: &mut Vec<RecentFile>) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                collect(&path, files);
+            } else if path.extension().and_then(|s| s.to_str()) == Some("mid") {
+                files.push(RecentFile {
+                    name: path
+                        .file_stem()
+                        .and_then(|s| s.to_str())
+                        .unwrap_or("Unknown")
+                        .to_string(),
+                    added_at: crate::get_file_mtime(&path),
+                    path: path.to_string_lossy().to_string(),
+                });
+            }
+        }
+    }
+
+    let album = crate::get_album_folder()?;
+    let mut files = Vec::new();
+    collect(&album, &mut files);
+    files.sort_by_key(|f| std::cmp::Reverse(f.added_at));
+    files.truncate(limit);
+    Ok(files)
+}
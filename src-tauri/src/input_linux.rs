@@ -0,0 +1,572 @@
+// Linux key injection
+// X11: XTest fake key events (libX11/libXtst are loaded at runtime, so Wayland-only
+// systems still start)
+// Wayland: a virtual keyboard created through /dev/uinput (needs write access,
+// e.g. membership in the "input" group or a udev rule)
+// Window detection uses wmctrl, which sees X11 and XWayland windows (Proton, browsers)
+
+use std::ffi::CString;
+use std::os::raw::{c_int, c_uchar, c_ulong};
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use x11_dl::{xlib, xtest};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Modifier {
+    None,
+    Shift,
+    Ctrl,
+}
+
+/// Split "shift+a" / "ctrl+a" / "a" and resolve custom note bindings
+fn parse_key(key: &str) -> (String, Modifier) {
+    let key = crate::keyboard::resolve_note_key(key);
+    let (base, modifier) = if let Some(base) = key.strip_prefix("shift+") {
+        (base, Modifier::Shift)
+    } else if let Some(base) = key.strip_prefix("ctrl+") {
+        (base, Modifier::Ctrl)
+    } else {
+        (key.as_str(), Modifier::None)
+    };
+    (base.to_string(), modifier)
+}
+
+// ============ X11 (XTest) ============
+
+/// X keysym name for a key string
+fn keysym_name(key: &str) -> Option<&str> {
+    match key {
+        ";" | "semicolon" => Some("semicolon"),
+        "," | "comma" => Some("comma"),
+        "." | "period" => Some("period"),
+        "/" | "slash" => Some("slash"),
+        "-" | "minus" => Some("minus"),
+        "=" | "equal" => Some("equal"),
+        "[" => Some("bracketleft"),
+        "]" => Some("bracketright"),
+        "'" => Some("apostrophe"),
+        "`" => Some("grave"),
+        "\\" => Some("backslash"),
+        " " | "space" => Some("space"),
+        k if k.len() == 1 && k.chars().all(|c| c.is_ascii_alphanumeric()) => Some(k),
+        _ => None,
+    }
+}
+
+struct X11Backend {
+    xlib: xlib::Xlib,
+    xtest: xtest::Xf86vmode,
+    display: *mut xlib::Display,
+}
+
+// The display is only ever used while holding the BACKEND lock
+unsafe impl Send for X11Backend {}
+
+impl X11Backend {
+    fn open() -> Option<Self> {
+        let xlib = xlib::Xlib::open().ok()?;
+        let xtest = xtest::Xf86vmode::open().ok()?;
+        let display = unsafe { (xlib.XOpenDisplay)(std::ptr::null()) };
+        if display.is_null() {
+            return None;
+        }
+        Some(X11Backend {
+            xlib,
+            xtest,
+            display,
+        })
+    }
+
+    fn keycode(&self, name: &str) -> Option<u32> {
+        let name = CString::new(name).ok()?;
+        unsafe {
+            let keysym = (self.xlib.XStringToKeysym)(name.as_ptr());
+            if keysym == 0 {
+                return None;
+            }
+            match (self.xlib.XKeysymToKeycode)(self.display, keysym) {
+                0 => None,
+                code => Some(code as u32),
+            }
+        }
+    }
+
+    fn modifier_keycode(&self, modifier: Modifier) -> Option<u32> {
+        match modifier {
+            Modifier::Shift => self.keycode("Shift_L"),
+            Modifier::Ctrl => self.keycode("Control_L"),
+            Modifier::None => None,
+        }
+    }
+
+    fn send(&self, key: &str, modifier: Modifier, press: bool) -> bool {
+        let Some(code) = keysym_name(key).and_then(|name| self.keycode(name)) else {
+            return false;
+        };
+        let modifier_code = self.modifier_keycode(modifier);
+        unsafe {
+            // Modifier goes down first and comes up last
+            if press {
+                if let Some(m) = modifier_code {
+                    (self.xtest.XTestFakeKeyEvent)(self.display, m, 1, 0);
+                }
+                (self.xtest.XTestFakeKeyEvent)(self.display, code, 1, 0);
+            } else {
+                (self.xtest.XTestFakeKeyEvent)(self.display, code, 0, 0);
+                if let Some(m) = modifier_code {
+                    (self.xtest.XTestFakeKeyEvent)(self.display, m, 0, 0);
+                }
+            }
+            (self.xlib.XFlush)(self.display);
+        }
+        true
+    }
+
+    fn atom(&self, name: &str) -> c_ulong {
+        let name = CString::new(name).unwrap_or_default();
+        unsafe { (self.xlib.XInternAtom)(self.display, name.as_ptr(), 0) }
+    }
+
+    /// Raw bytes of a window property (any type)
+    fn property(&self, window: c_ulong, name: &str) -> Option<Vec<u8>> {
+        let mut actual_type: c_ulong = 0;
+        let mut actual_format: c_int = 0;
+        let mut item_count: c_ulong = 0;
+        let mut bytes_after: c_ulong = 0;
+        let mut data: *mut c_uchar = std::ptr::null_mut();
+        unsafe {
+            let status = (self.xlib.XGetWindowProperty)(
+                self.display,
+                window,
+                self.atom(name),
+                0,
+                1024,
+                0,
+                0, // AnyPropertyType
+                &mut actual_type,
+                &mut actual_format,
+                &mut item_count,
+                &mut bytes_after,
+                &mut data,
+            );
+            if status != 0 || data.is_null() {
+                return None;
+            }
+            // 32-bit format items are stored as C longs
+            let item_size = match actual_format {
+                8 => 1,
+                16 => 2,
+                32 => std::mem::size_of::<c_ulong>(),
+                _ => 0,
+            };
+            let bytes = std::slice::from_raw_parts(data, item_count as usize * item_size).to_vec();
+            (self.xlib.XFree)(data as *mut _);
+            Some(bytes)
+        }
+    }
+
+    fn active_window_title(&self) -> Option<String> {
+        let root = unsafe { (self.xlib.XDefaultRootWindow)(self.display) };
+        let active = self.property(root, "_NET_ACTIVE_WINDOW")?;
+        let window = c_ulong::from_ne_bytes(
+            active
+                .get(..std::mem::size_of::<c_ulong>())?
+                .try_into()
+                .ok()?,
+        );
+        if window == 0 {
+            return None;
+        }
+        let title = self
+            .property(window, "_NET_WM_NAME")
+            .or_else(|| self.property(window, "WM_NAME"))?;
+        Some(String::from_utf8_lossy(&title).to_string())
+    }
+}
+
+// ============ Wayland (uinput) ============
+
+const UI_SET_EVBIT: c_ulong = 0x4004_5564;
+const UI_SET_KEYBIT: c_ulong = 0x4004_5565;
+const UI_DEV_CREATE: c_ulong = 0x5501;
+const UI_DEV_DESTROY: c_ulong = 0x5502;
+const EV_SYN: u16 = 0x00;
+const EV_KEY: u16 = 0x01;
+const SYN_REPORT: u16 = 0;
+const KEY_LEFTCTRL: u16 = 29;
+const KEY_LEFTSHIFT: u16 = 42;
+
+#[repr(C)]
+struct InputId {
+    bustype: u16,
+    vendor: u16,
+    product: u16,
+    version: u16,
+}
+
+#[repr(C)]
+struct UinputUserDev {
+    name: [u8; 80],
+    id: InputId,
+    ff_effects_max: u32,
+    absmax: [i32; 64],
+    absmin: [i32; 64],
+    absfuzz: [i32; 64],
+    absflat: [i32; 64],
+}
+
+#[repr(C)]
+struct InputEvent {
+    time: libc::timeval,
+    kind: u16,
+    code: u16,
+    value: i32,
+}
+
+/// Linux evdev key code (US layout positions)
+fn evdev_code(key: &str) -> Option<u16> {
+    let code = match key {
+        "1" => 2,
+        "2" => 3,
+        "3" => 4,
+        "4" => 5,
+        "5" => 6,
+        "6" => 7,
+        "7" => 8,
+        "8" => 9,
+        "9" => 10,
+        "0" => 11,
+        "-" | "minus" => 12,
+        "=" | "equal" => 13,
+        "q" => 16,
+        "w" => 17,
+        "e" => 18,
+        "r" => 19,
+        "t" => 20,
+        "y" => 21,
+        "u" => 22,
+        "i" => 23,
+        "o" => 24,
+        "p" => 25,
+        "[" => 26,
+        "]" => 27,
+        "a" => 30,
+        "s" => 31,
+        "d" => 32,
+        "f" => 33,
+        "g" => 34,
+        "h" => 35,
+        "j" => 36,
+        "k" => 37,
+        "l" => 38,
+        ";" | "semicolon" => 39,
+        "'" => 40,
+        "`" => 41,
+        "\\" => 43,
+        "z" => 44,
+        "x" => 45,
+        "c" => 46,
+        "v" => 47,
+        "b" => 48,
+        "n" => 49,
+        "m" => 50,
+        "," | "comma" => 51,
+        "." | "period" => 52,
+        "/" | "slash" => 53,
+        " " | "space" => 57,
+        _ => return None,
+    };
+    Some(code)
+}
+
+struct UinputDevice {
+    fd: c_int,
+}
+
+impl UinputDevice {
+    fn open() -> Option<Self> {
+        let path = CString::new("/dev/uinput").ok()?;
+        let fd = unsafe { libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_NONBLOCK) };
+        if fd < 0 {
+            return None;
+        }
+        let device = UinputDevice { fd };
+
+        unsafe {
+            if libc::ioctl(fd, UI_SET_EVBIT as _, EV_KEY as c_int) < 0 {
+                return None;
+            }
+            // Every key we can ever send
+            for code in 1..=57 {
+                libc::ioctl(fd, UI_SET_KEYBIT as _, code as c_int);
+            }
+
+            let mut setup: UinputUserDev = std::mem::zeroed();
+            let name = b"WWM MIDI Player Virtual Keyboard";
+            setup.name[..name.len()].copy_from_slice(name);
+            setup.id = InputId {
+                bustype: 0x03, // BUS_USB
+                vendor: 0x1234,
+                product: 0x5678,
+                version: 1,
+            };
+            let size = std::mem::size_of::<UinputUserDev>();
+            if libc::write(fd, &setup as *const _ as *const _, size) != size as isize {
+                return None;
+            }
+            if libc::ioctl(fd, UI_DEV_CREATE as _) < 0 {
+                return None;
+            }
+        }
+
+        // Give the compositor a moment to pick up the new device
+        std::thread::sleep(Duration::from_millis(200));
+        Some(device)
+    }
+
+    fn emit(&self, kind: u16, code: u16, value: i32) {
+        let event = InputEvent {
+            time: libc::timeval {
+                tv_sec: 0,
+                tv_usec: 0,
+            },
+            kind,
+            code,
+            value,
+        };
+        unsafe {
+            libc::write(
+                self.fd,
+                &event as *const _ as *const _,
+                std::mem::size_of::<InputEvent>(),
+            );
+        }
+    }
+
+    fn send(&self, key: &str, modifier: Modifier, press: bool) -> bool {
+        let Some(code) = evdev_code(key) else {
+            return false;
+        };
+        let modifier_code = match modifier {
+            Modifier::Shift => Some(KEY_LEFTSHIFT),
+            Modifier::Ctrl => Some(KEY_LEFTCTRL),
+            Modifier::None => None,
+        };
+        let value = if press { 1 } else { 0 };
+        if press {
+            if let Some(m) = modifier_code {
+                self.emit(EV_KEY, m, value);
+            }
+            self.emit(EV_KEY, code, value);
+        } else {
+            self.emit(EV_KEY, code, value);
+            if let Some(m) = modifier_code {
+                self.emit(EV_KEY, m, value);
+            }
+        }
+        self.emit(EV_SYN, SYN_REPORT, 0);
+        true
+    }
+}
+
+impl Drop for UinputDevice {
+    fn drop(&mut self) {
+        unsafe {
+            libc::ioctl(self.fd, UI_DEV_DESTROY as _);
+            libc::close(self.fd);
+        }
+    }
+}
+
+// ============ Backend selection ============
+
+enum Backend {
+    X11(Box<X11Backend>),
+    Uinput(UinputDevice),
+    Unavailable,
+}
+
+lazy_static::lazy_static! {
+    static ref BACKEND: Mutex<Option<Backend>> = Mutex::new(None);
+    // X11 connection for focus queries - kept even when keys go through uinput
+    static ref X11_QUERY: Mutex<Option<X11Backend>> = Mutex::new(X11Backend::open());
+    static ref FOCUS_CACHE: Mutex<Option<(Instant, bool)>> = Mutex::new(None);
+    static ref WINDOW_CACHE: Mutex<Option<(Instant, Option<GameWindow>)>> = Mutex::new(None);
+}
+
+const FOCUS_CACHE_DURATION: Duration = Duration::from_millis(250);
+const WINDOW_CACHE_DURATION: Duration = Duration::from_secs(5);
+
+fn is_wayland_session() -> bool {
+    std::env::var_os("WAYLAND_DISPLAY").is_some()
+        || std::env::var("XDG_SESSION_TYPE")
+            .map(|t| t == "wayland")
+            .unwrap_or(false)
+}
+
+/// Native Wayland apps ignore XTest, so prefer uinput there and XTest on X11
+fn open_backend() -> Backend {
+    let backend = if is_wayland_session() {
+        UinputDevice::open()
+            .map(Backend::Uinput)
+            .or_else(|| X11Backend::open().map(|x11| Backend::X11(Box::new(x11))))
+    } else {
+        X11Backend::open()
+            .map(|x11| Backend::X11(Box::new(x11)))
+            .or_else(|| UinputDevice::open().map(Backend::Uinput))
+    };
+    let backend = backend.unwrap_or(Backend::Unavailable);
+    match backend {
+        Backend::X11(_) => crate::app_log!("[KEYBOARD] Linux input backend: X11 (XTest)"),
+        Backend::Uinput(_) => crate::app_log!("[KEYBOARD] Linux input backend: uinput"),
+        Backend::Unavailable => crate::app_error!(
+            "[KEYBOARD] No Linux input backend available (need X11 with XTest or write access to /dev/uinput)"
+        ),
+    }
+    backend
+}
+
+/// Returns the backend that sent the key
+fn send(key: &str, press: bool) -> Result<&'static str, String> {
+    // Injection is global, so only send while the game has focus
+    if !is_game_focused() {
+        return Err("Game is not focused".to_string());
+    }
+    let (key, modifier) = parse_key(key);
+    let mut guard = BACKEND.lock().unwrap();
+    let (backend, sent) = match guard.get_or_insert_with(open_backend) {
+        Backend::X11(x11) => ("x11", x11.send(&key, modifier, press)),
+        Backend::Uinput(device) => ("uinput", device.send(&key, modifier, press)),
+        Backend::Unavailable => return Err("No input backend available".to_string()),
+    };
+    if sent {
+        Ok(backend)
+    } else {
+        Err(format!("Key can't be sent: {}", key))
+    }
+}
+
+pub fn key_down(key: &str) -> Result<&'static str, String> {
+    send(key, true)
+}
+
+pub fn key_up(key: &str) {
+    let _ = send(key, false);
+}
+
+/// Whether the focused window is the game. Pure Wayland gives no way to ask, so assume yes.
+pub fn is_game_focused() -> bool {
+    let mut cache = FOCUS_CACHE.lock().unwrap();
+    if let Some((checked, focused)) = *cache {
+        if checked.elapsed() < FOCUS_CACHE_DURATION {
+            return focused;
+        }
+    }
+    let focused = match X11_QUERY.lock().unwrap().as_ref() {
+        Some(x11) => x11
+            .active_window_title()
+            .map(|title| crate::keyboard::match_target_title(&title).is_some())
+            .unwrap_or(true),
+        None => true,
+    };
+    *cache = Some((Instant::now(), focused));
+    focused
+}
+
+// ============ Window detection (wmctrl) ============
+
+#[derive(Debug, Clone)]
+pub struct GameWindow {
+    pub id: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    pub title: String,
+}
+
+/// Parse `wmctrl -lG`: "<id> <desktop> <x> <y> <w> <h> <host> <title...>"
+fn list_windows() -> Option<Vec<GameWindow>> {
+    let output = Command::new("wmctrl").arg("-lG").output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    Some(
+        text.lines()
+            .filter_map(|line| {
+                let mut parts = line.split_whitespace();
+                let id = parts.next()?.to_string();
+                let _desktop = parts.next()?;
+                let x = parts.next()?.parse().ok()?;
+                let y = parts.next()?.parse().ok()?;
+                let width = parts.next()?.parse().ok()?;
+                let height = parts.next()?.parse().ok()?;
+                let _host = parts.next()?;
+                let title = parts.collect::<Vec<_>>().join(" ");
+                Some(GameWindow {
+                    id,
+                    x,
+                    y,
+                    width,
+                    height,
+                    title,
+                })
+            })
+            .collect(),
+    )
+}
+
+/// Returns None for the outer Option when wmctrl isn't usable
+fn find_game_window() -> Option<Option<GameWindow>> {
+    let mut cache = WINDOW_CACHE.lock().unwrap();
+    if let Some((checked, window)) = cache.as_ref() {
+        if checked.elapsed() < WINDOW_CACHE_DURATION {
+            return Some(window.clone());
+        }
+    }
+    let window = list_windows()?
+        .into_iter()
+        .find(|w| crate::keyboard::match_target_title(&w.title).is_some());
+    if let Some(w) = &window {
+        println!("[WINDOW] Found matching window: '{}' id={}", w.title, w.id);
+    }
+    *cache = Some((Instant::now(), window.clone()));
+    Some(window)
+}
+
+pub fn clear_window_cache() {
+    *WINDOW_CACHE.lock().unwrap() = None;
+    *FOCUS_CACHE.lock().unwrap() = None;
+}
+
+/// Without wmctrl we can't tell, so report found like before
+pub fn is_game_window_found() -> bool {
+    find_game_window().map(|w| w.is_some()).unwrap_or(true)
+}
+
+pub fn game_window_title() -> Option<String> {
+    Some(find_game_window()??.title)
+}
+
+pub fn get_game_window_rect() -> Option<(i32, i32, i32, i32)> {
+    let window = find_game_window()??;
+    Some((window.x, window.y, window.width, window.height))
+}
+
+pub fn focus_game_window() -> Result<(), String> {
+    let window = find_game_window()
+        .ok_or("wmctrl is required to focus the game window")?
+        .ok_or("Game window not found (WWM or GeForce Now)")?;
+    let status = Command::new("wmctrl")
+        .args(["-i", "-a", &window.id])
+        .status()
+        .map_err(|e| format!("Failed to run wmctrl: {}", e))?;
+    if !status.success() {
+        return Err("wmctrl failed to activate the game window".to_string());
+    }
+    *FOCUS_CACHE.lock().unwrap() = None;
+    std::thread::sleep(Duration::from_millis(100));
+    Ok(())
+}
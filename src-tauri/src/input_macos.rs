@@ -0,0 +1,376 @@
+// macOS key injection (GeForce Now / browser streaming)
+// Keys are posted as CGEvents at the HID level; this and the Accessibility queries
+// below need the app to be allowed under Privacy & Security > Accessibility
+// Window detection uses CGWindowList (owner app name is always available, window
+// titles only with Screen Recording permission) and the Accessibility API for focus
+
+use core_foundation::base::{CFType, CFTypeRef, TCFType};
+use core_foundation::boolean::CFBoolean;
+use core_foundation::dictionary::{CFDictionary, CFDictionaryRef};
+use core_foundation::number::CFNumber;
+use core_foundation::string::{CFString, CFStringRef};
+use core_graphics::event::{CGEvent, CGEventFlags, CGEventTapLocation, CGKeyCode};
+use core_graphics::event_source::{CGEventSource, CGEventSourceStateID};
+use core_graphics::window::{
+    copy_window_info, kCGNullWindowID, kCGWindowBounds, kCGWindowLayer,
+    kCGWindowListExcludeDesktopElements, kCGWindowListOptionOnScreenOnly, kCGWindowName,
+    kCGWindowOwnerName, kCGWindowOwnerPID,
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+type AXError = i32;
+const AX_ERROR_SUCCESS: AXError = 0;
+
+#[link(name = "ApplicationServices", kind = "framework")]
+extern "C" {
+    fn AXIsProcessTrusted() -> bool;
+    fn AXUIElementCreateSystemWide() -> CFTypeRef;
+    fn AXUIElementCreateApplication(pid: i32) -> CFTypeRef;
+    fn AXUIElementCopyAttributeValue(
+        element: CFTypeRef,
+        attribute: CFStringRef,
+        value: *mut CFTypeRef,
+    ) -> AXError;
+    fn AXUIElementSetAttributeValue(
+        element: CFTypeRef,
+        attribute: CFStringRef,
+        value: CFTypeRef,
+    ) -> AXError;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Modifier {
+    None,
+    Shift,
+    Ctrl,
+}
+
+/// Split "shift+a" / "ctrl+a" / "a" and resolve custom note bindings
+fn parse_key(key: &str) -> (String, Modifier) {
+    let key = crate::keyboard::resolve_note_key(key);
+    let (base, modifier) = if let Some(base) = key.strip_prefix("shift+") {
+        (base, Modifier::Shift)
+    } else if let Some(base) = key.strip_prefix("ctrl+") {
+        (base, Modifier::Ctrl)
+    } else {
+        (key.as_str(), Modifier::None)
+    };
+    (base.to_string(), modifier)
+}
+
+/// macOS virtual key code (ANSI layout positions)
+fn mac_keycode(key: &str) -> Option<CGKeyCode> {
+    let code = match key {
+        "a" => 0,
+        "s" => 1,
+        "d" => 2,
+        "f" => 3,
+        "h" => 4,
+        "g" => 5,
+        "z" => 6,
+        "x" => 7,
+        "c" => 8,
+        "v" => 9,
+        "b" => 11,
+        "q" => 12,
+        "w" => 13,
+        "e" => 14,
+        "r" => 15,
+        "y" => 16,
+        "t" => 17,
+        "1" => 18,
+        "2" => 19,
+        "3" => 20,
+        "4" => 21,
+        "6" => 22,
+        "5" => 23,
+        "=" | "equal" => 24,
+        "9" => 25,
+        "7" => 26,
+        "-" | "minus" => 27,
+        "8" => 28,
+        "0" => 29,
+        "]" => 30,
+        "o" => 31,
+        "u" => 32,
+        "[" => 33,
+        "i" => 34,
+        "p" => 35,
+        "l" => 37,
+        "j" => 38,
+        "'" => 39,
+        "k" => 40,
+        ";" | "semicolon" => 41,
+        "\\" => 42,
+        "," | "comma" => 43,
+        "/" | "slash" => 44,
+        "n" => 45,
+        "m" => 46,
+        "." | "period" => 47,
+        " " | "space" => 49,
+        "`" => 50,
+        _ => return None,
+    };
+    Some(code)
+}
+
+const KEY_SHIFT: CGKeyCode = 56;
+const KEY_CONTROL: CGKeyCode = 59;
+
+static TRUST_WARNED: AtomicBool = AtomicBool::new(false);
+
+fn post_key(code: CGKeyCode, down: bool, flags: CGEventFlags) -> bool {
+    let Ok(source) = CGEventSource::new(CGEventSourceStateID::HIDSystemState) else {
+        return false;
+    };
+    let Ok(event) = CGEvent::new_keyboard_event(source, code, down) else {
+        return false;
+    };
+    event.set_flags(flags);
+    event.post(CGEventTapLocation::HID);
+    true
+}
+
+fn send(key: &str, press: bool) -> Result<(), String> {
+    if !unsafe { AXIsProcessTrusted() } {
+        if !TRUST_WARNED.swap(true, Ordering::SeqCst) {
+            crate::app_error!(
+                "[KEYBOARD] Accessibility permission missing - allow the player under Privacy & Security > Accessibility"
+            );
+        }
+        return Err("Accessibility permission missing".to_string());
+    }
+    // Events go to whatever has focus, so only send while the game does
+    if !is_game_focused() {
+        return Err("Game is not focused".to_string());
+    }
+
+    let (key, modifier) = parse_key(key);
+    let Some(code) = mac_keycode(&key) else {
+        return Err(format!("Key can't be sent: {}", key));
+    };
+    let (modifier_code, flags) = match modifier {
+        Modifier::Shift => (Some(KEY_SHIFT), CGEventFlags::CGEventFlagShift),
+        Modifier::Ctrl => (Some(KEY_CONTROL), CGEventFlags::CGEventFlagControl),
+        Modifier::None => (None, CGEventFlags::CGEventFlagNull),
+    };
+
+    // Modifier goes down first and comes up last
+    let posted = if press {
+        let modifier_posted = modifier_code.is_none_or(|m| post_key(m, true, flags));
+        post_key(code, true, flags) && modifier_posted
+    } else {
+        let key_posted = post_key(code, false, flags);
+        let modifier_posted =
+            modifier_code.is_none_or(|m| post_key(m, false, CGEventFlags::CGEventFlagNull));
+        key_posted && modifier_posted
+    };
+    if posted {
+        Ok(())
+    } else {
+        Err("Failed to post key event".to_string())
+    }
+}
+
+/// Returns the backend that sent the key
+pub fn key_down(key: &str) -> Result<&'static str, String> {
+    send(key, true).map(|_| "cgevent")
+}
+
+pub fn key_up(key: &str) {
+    let _ = send(key, false);
+}
+
+// ============ Accessibility ============
+
+fn ax_attribute(element: &CFType, name: &str) -> Option<CFType> {
+    let attribute = CFString::new(name);
+    let mut value: CFTypeRef = std::ptr::null();
+    let err = unsafe {
+        AXUIElementCopyAttributeValue(
+            element.as_CFTypeRef(),
+            attribute.as_concrete_TypeRef(),
+            &mut value,
+        )
+    };
+    if err != AX_ERROR_SUCCESS || value.is_null() {
+        return None;
+    }
+    Some(unsafe { CFType::wrap_under_create_rule(value) })
+}
+
+fn ax_title(element: &CFType) -> Option<String> {
+    ax_attribute(element, "AXTitle")?
+        .downcast::<CFString>()
+        .map(|s| s.to_string())
+}
+
+lazy_static::lazy_static! {
+    static ref FOCUS_CACHE: Mutex<Option<(Instant, bool)>> = Mutex::new(None);
+    static ref WINDOW_CACHE: Mutex<Option<(Instant, Option<GameWindow>)>> = Mutex::new(None);
+}
+
+const FOCUS_CACHE_DURATION: Duration = Duration::from_millis(250);
+const WINDOW_CACHE_DURATION: Duration = Duration::from_secs(5);
+
+/// Focused window title, falling back to the focused app's name (e.g. "GeForce NOW")
+pub fn is_game_focused() -> bool {
+    let mut cache = FOCUS_CACHE.lock().unwrap();
+    if let Some((checked, focused)) = *cache {
+        if checked.elapsed() < FOCUS_CACHE_DURATION {
+            return focused;
+        }
+    }
+
+    let system = unsafe { CFType::wrap_under_create_rule(AXUIElementCreateSystemWide()) };
+    let focused = match ax_attribute(&system, "AXFocusedApplication") {
+        Some(app) => {
+            let window_title = ax_attribute(&app, "AXFocusedWindow").and_then(|w| ax_title(&w));
+            let app_title = ax_title(&app);
+            [window_title, app_title]
+                .iter()
+                .flatten()
+                .any(|title| crate::keyboard::match_target_title(title).is_some())
+        }
+        // No Accessibility permission - can't tell, don't block
+        None => true,
+    };
+
+    *cache = Some((Instant::now(), focused));
+    focused
+}
+
+// ============ Window detection (CGWindowList) ============
+
+#[derive(Debug, Clone)]
+pub struct GameWindow {
+    pub pid: i32,
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    pub title: String,
+}
+
+fn dict_string(dict: &CFDictionary<CFString, CFType>, key: CFStringRef) -> Option<String> {
+    let key = unsafe { CFString::wrap_under_get_rule(key) };
+    dict.find(&key)?
+        .downcast::<CFString>()
+        .map(|s| s.to_string())
+}
+
+fn dict_number(dict: &CFDictionary<CFString, CFType>, key: CFStringRef) -> Option<f64> {
+    let key = unsafe { CFString::wrap_under_get_rule(key) };
+    dict.find(&key)?.downcast::<CFNumber>()?.to_f64()
+}
+
+fn list_windows() -> Vec<GameWindow> {
+    let Some(windows) = copy_window_info(
+        kCGWindowListOptionOnScreenOnly | kCGWindowListExcludeDesktopElements,
+        kCGNullWindowID,
+    ) else {
+        return Vec::new();
+    };
+
+    windows
+        .iter()
+        .filter_map(|item| {
+            let dict: CFDictionary<CFString, CFType> =
+                unsafe { CFDictionary::wrap_under_get_rule(*item as CFDictionaryRef) };
+
+            // Layer 0 = normal app windows (skip menu bar, dock, overlays)
+            if dict_number(&dict, unsafe { kCGWindowLayer })? as i32 != 0 {
+                return None;
+            }
+            let owner = dict_string(&dict, unsafe { kCGWindowOwnerName }).unwrap_or_default();
+            let name = dict_string(&dict, unsafe { kCGWindowName }).unwrap_or_default();
+            let pid = dict_number(&dict, unsafe { kCGWindowOwnerPID })? as i32;
+
+            let bounds_key = unsafe { CFString::wrap_under_get_rule(kCGWindowBounds) };
+            let bounds = dict.find(&bounds_key)?;
+            let bounds: CFDictionary<CFString, CFType> = unsafe {
+                CFDictionary::wrap_under_get_rule(bounds.as_CFTypeRef() as CFDictionaryRef)
+            };
+            let field = |name: &str| -> Option<i32> {
+                let key = CFString::new(name);
+                Some(bounds.find(&key)?.downcast::<CFNumber>()?.to_f64()? as i32)
+            };
+
+            Some(GameWindow {
+                pid,
+                x: field("X")?,
+                y: field("Y")?,
+                width: field("Width")?,
+                height: field("Height")?,
+                title: if name.is_empty() {
+                    owner
+                } else {
+                    format!("{} - {}", name, owner)
+                },
+            })
+        })
+        .collect()
+}
+
+fn find_game_window() -> Option<GameWindow> {
+    let mut cache = WINDOW_CACHE.lock().unwrap();
+    if let Some((checked, window)) = cache.as_ref() {
+        if checked.elapsed() < WINDOW_CACHE_DURATION {
+            return window.clone();
+        }
+    }
+    let window = list_windows()
+        .into_iter()
+        .find(|w| crate::keyboard::match_target_title(&w.title).is_some());
+    if let Some(w) = &window {
+        println!(
+            "[WINDOW] Found matching window: '{}' pid={}",
+            w.title, w.pid
+        );
+    }
+    *cache = Some((Instant::now(), window.clone()));
+    window
+}
+
+pub fn clear_window_cache() {
+    *WINDOW_CACHE.lock().unwrap() = None;
+    *FOCUS_CACHE.lock().unwrap() = None;
+}
+
+pub fn is_game_window_found() -> bool {
+    find_game_window().is_some()
+}
+
+pub fn game_window_title() -> Option<String> {
+    Some(find_game_window()?.title)
+}
+
+pub fn get_game_window_rect() -> Option<(i32, i32, i32, i32)> {
+    let window = find_game_window()?;
+    Some((window.x, window.y, window.width, window.height))
+}
+
+/// Bring the game's app to the front through Accessibility
+pub fn focus_game_window() -> Result<(), String> {
+    let window = find_game_window().ok_or("Game window not found (WWM or GeForce Now)")?;
+    let app = unsafe { CFType::wrap_under_create_rule(AXUIElementCreateApplication(window.pid)) };
+    let attribute = CFString::new("AXFrontmost");
+    let err = unsafe {
+        AXUIElementSetAttributeValue(
+            app.as_CFTypeRef(),
+            attribute.as_concrete_TypeRef(),
+            CFBoolean::true_value().as_CFTypeRef(),
+        )
+    };
+    if err != AX_ERROR_SUCCESS {
+        return Err(format!(
+            "Failed to focus game window (Accessibility error {})",
+            err
+        ));
+    }
+    *FOCUS_CACHE.lock().unwrap() = None;
+    std::thread::sleep(Duration::from_millis(100));
+    Ok(())
+}
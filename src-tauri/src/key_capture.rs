@@ -0,0 +1,187 @@
+// Guided note key capture: the player presses the key they use for each note in turn
+// ("press the key you use for low do") and the low-level hook feeds those presses in
+// here, instead of key names being typed into text boxes. Keys the input backends
+// can't send and keys already taken are rejected on the spot.
+// The hook is Windows-only, so elsewhere a capture can be started but never fed.
+#![cfg_attr(not(target_os = "windows"), allow(dead_code))]
+
+use crate::keyboard::{self, NoteKeySet};
+use crate::midi::KeyMode;
+use serde::Serialize;
+use std::sync::Mutex;
+
+const ROWS: [&str; 3] = ["low", "mid", "high"];
+const NATURALS: [&str; 7] = ["do", "re", "mi", "fa", "sol", "la", "ti"];
+const ACCIDENTALS: [&str; 5] = ["do#", "mib", "fa#", "sol#", "sib"];
+
+#[derive(Debug, Clone, Copy)]
+enum Slot {
+    Natural(usize, usize), // Row, index
+    Accidental(usize, usize),
+}
+
+impl Slot {
+    fn label(&self) -> String {
+        match *self {
+            Slot::Natural(row, i) => format!("{} {}", ROWS[row], NATURALS[i]),
+            Slot::Accidental(row, i) => format!("{} {}", ROWS[row], ACCIDENTALS[i]),
+        }
+    }
+
+    fn assign(&self, set: &mut NoteKeySet, key: String) {
+        match *self {
+            Slot::Natural(row, i) => [&mut set.low, &mut set.mid, &mut set.high][row][i] = key,
+            Slot::Accidental(row, i) => {
+                let keys = match row {
+                    0 => &mut set.accidentals.low,
+                    1 => &mut set.accidentals.mid,
+                    _ => &mut set.accidentals.high,
+                };
+                keys.resize(ACCIDENTALS.len(), String::new());
+                keys[i] = key;
+            }
+        }
+    }
+}
+
+struct Capture {
+    mode: KeyMode,
+    slots: Vec<Slot>,
+    step: usize,
+    set: NoteKeySet,
+    taken: Vec<String>, // Keys captured so far
+}
+
+static CAPTURE: Mutex<Option<Capture>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureStatus {
+    Waiting,   // For the key of `note`
+    Rejected,  // The key just pressed can't be used; still waiting for `note`
+    Finished,  // All notes captured and saved to `keys`
+    Cancelled, // Nothing was changed
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptureUpdate {
+    pub status: CaptureStatus,
+    pub mode: KeyMode,
+    pub step: usize,
+    pub total: usize,
+    pub note: String,
+    pub key: Option<String>, // Key just pressed
+    pub message: String,
+    pub keys: NoteKeySet, // Bindings so far
+}
+
+impl Capture {
+    fn update(&self, status: CaptureStatus, key: Option<String>, message: String) -> CaptureUpdate {
+        CaptureUpdate {
+            status,
+            mode: self.mode,
+            step: self.step,
+            total: self.slots.len(),
+            note: self
+                .slots
+                .get(self.step)
+                .map(|s| s.label())
+                .unwrap_or_default(),
+            key,
+            message,
+            keys: self.set.clone(),
+        }
+    }
+
+    fn waiting(&self, key: Option<String>) -> CaptureUpdate {
+        let message = format!(
+            "Press the key you use for {}",
+            self.slots[self.step].label()
+        );
+        self.update(CaptureStatus::Waiting, key, message)
+    }
+
+    /// Move on; the last step applies the new bindings
+    fn advance(capture: &mut Option<Capture>, key: Option<String>) -> CaptureUpdate {
+        let current = capture.as_mut().unwrap();
+        current.step += 1;
+        if current.step < current.slots.len() {
+            return current.waiting(key);
+        }
+        let done = capture.take().unwrap();
+        match keyboard::set_note_key_set(done.mode, done.set.clone()) {
+            Ok(()) => done.update(CaptureStatus::Finished, key, "Note keys saved".to_string()),
+            Err(e) => done.update(CaptureStatus::Cancelled, key, e),
+        }
+    }
+}
+
+pub fn is_active() -> bool {
+    CAPTURE.lock().unwrap().is_some()
+}
+
+/// Start capturing every note of `mode`; 36-key mode can include the accidentals
+pub fn start(mode: KeyMode, accidentals: bool) -> CaptureUpdate {
+    let mut slots: Vec<Slot> = (0..ROWS.len())
+        .flat_map(|row| (0..NATURALS.len()).map(move |i| Slot::Natural(row, i)))
+        .collect();
+    if accidentals && mode == KeyMode::Keys36 {
+        slots.extend(
+            (0..ROWS.len())
+                .flat_map(|row| (0..ACCIDENTALS.len()).map(move |i| Slot::Accidental(row, i))),
+        );
+    }
+    let capture = Capture {
+        mode,
+        slots,
+        step: 0,
+        set: keyboard::get_note_key_set(mode),
+        taken: Vec::new(),
+    };
+    let update = capture.waiting(None);
+    *CAPTURE.lock().unwrap() = Some(capture);
+    println!("[CAPTURE] Capturing {} note keys", update.total);
+    update
+}
+
+/// A key pressed during capture ("z", "shift+1"); None when it can't be a note key.
+/// Returns None if no capture is running.
+pub fn on_key(key: Option<String>) -> Option<CaptureUpdate> {
+    let mut capture = CAPTURE.lock().unwrap();
+    let current = capture.as_mut()?;
+    let slot = current.slots[current.step];
+
+    let Some(key) = key else {
+        let message = "That key can't be sent to the game, use a letter, digit or ; , . /";
+        return Some(current.update(CaptureStatus::Rejected, None, message.to_string()));
+    };
+    if matches!(slot, Slot::Natural(..)) && key.contains('+') {
+        let message = "Natural notes can't use modifiers".to_string();
+        return Some(current.update(CaptureStatus::Rejected, Some(key), message));
+    }
+    if current.taken.contains(&key) {
+        let message = format!("{} is already used for another note", key);
+        return Some(current.update(CaptureStatus::Rejected, Some(key), message));
+    }
+
+    slot.assign(&mut current.set, key.clone());
+    current.taken.push(key.clone());
+    Some(Capture::advance(&mut capture, Some(key)))
+}
+
+/// Keep the current binding for this note and move on
+pub fn skip() -> Option<CaptureUpdate> {
+    let mut capture = CAPTURE.lock().unwrap();
+    capture.as_ref()?;
+    Some(Capture::advance(&mut capture, None))
+}
+
+pub fn cancel() -> Option<CaptureUpdate> {
+    let capture = CAPTURE.lock().unwrap().take()?;
+    println!("[CAPTURE] Cancelled");
+    Some(capture.update(
+        CaptureStatus::Cancelled,
+        None,
+        "Note keys unchanged".to_string(),
+    ))
+}
@@ -0,0 +1,144 @@
+// Note key presets: the note key bindings of both key modes plus extra window
+// keywords, saved as a small JSON file players can pass around. A few keyboard
+// layouts ship built in; they put the notes back on the keys the game expects.
+
+use crate::error::AppError;
+use crate::keyboard::{self, NoteKeySet, NoteKeySets};
+use serde::{Deserialize, Serialize};
+
+const EXPORT_TYPE: &str = "note_key_preset";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyPreset {
+    #[serde(default = "export_type")]
+    pub export_type: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub keys: NoteKeySets,
+    #[serde(default)]
+    pub window_keywords: Vec<String>,
+    #[serde(default)]
+    pub built_in: bool,
+}
+
+fn export_type() -> String {
+    EXPORT_TYPE.to_string()
+}
+
+/// Rows as typed on the layout, in the default QWERTY key positions
+fn layout(name: &str, description: &str, low: &str, mid: &str, high: &str) -> KeyPreset {
+    let row = |keys: &str| keys.split(' ').map(String::from).collect();
+    let set = NoteKeySet {
+        low: row(low),
+        mid: row(mid),
+        high: row(high),
+        ..Default::default()
+    };
+    KeyPreset {
+        export_type: export_type(),
+        name: name.to_string(),
+        description: description.to_string(),
+        keys: NoteKeySets {
+            keys21: set.clone(),
+            keys36: set,
+        },
+        window_keywords: Vec::new(),
+        built_in: true,
+    }
+}
+
+pub fn built_in() -> Vec<KeyPreset> {
+    vec![
+        layout(
+            "QWERTY",
+            "Default bindings",
+            "z x c v b n m",
+            "a s d f g h j",
+            "q w e r t y u",
+        ),
+        layout(
+            "QWERTZ",
+            "German/Central European keyboards (Y and Z swapped)",
+            "y x c v b n m",
+            "a s d f g h j",
+            "q w e r t z u",
+        ),
+        layout(
+            "AZERTY",
+            "French/Belgian keyboards",
+            "w x c v b n ,",
+            "q s d f g h j",
+            "a z e r t y u",
+        ),
+        layout(
+            "Colemak",
+            "Colemak layout",
+            "z x c v b k m",
+            "a r s t d h n",
+            "q w f p g j l",
+        ),
+    ]
+}
+
+pub fn find_built_in(name: &str) -> Option<KeyPreset> {
+    built_in()
+        .into_iter()
+        .find(|p| p.name.eq_ignore_ascii_case(name))
+}
+
+/// The bindings and window keywords in use, under `name`
+pub fn current(name: String) -> KeyPreset {
+    KeyPreset {
+        export_type: export_type(),
+        name,
+        description: String::new(),
+        keys: keyboard::get_note_key_sets(),
+        window_keywords: keyboard::get_custom_window_keywords(),
+        built_in: false,
+    }
+}
+
+/// Use the preset's bindings for both key modes. Its window keywords are added to
+/// the player's own rather than replacing them.
+pub fn apply(preset: &KeyPreset) -> Result<(), AppError> {
+    // Check both sets before touching either
+    let keys21 = preset.keys.keys21.clone().normalized();
+    let keys36 = preset.keys.keys36.clone().normalized();
+    keyboard::load_note_key_sets(NoteKeySets {
+        keys21: keys21.map_err(AppError::InvalidInput)?,
+        keys36: keys36.map_err(AppError::InvalidInput)?,
+    });
+
+    let mut keywords = keyboard::get_custom_window_keywords();
+    for keyword in &preset.window_keywords {
+        let keyword = keyword.trim();
+        if !keyword.is_empty() && !keywords.iter().any(|k| k.eq_ignore_ascii_case(keyword)) {
+            keywords.push(keyword.to_string());
+        }
+    }
+    keyboard::set_custom_window_keywords(keywords);
+    println!("[KEYBOARD] Applied note key preset \"{}\"", preset.name);
+    Ok(())
+}
+
+pub fn read(path: &str) -> Result<KeyPreset, AppError> {
+    let content = std::fs::read_to_string(path).map_err(|e| AppError::io("read preset file", e))?;
+    let mut preset: KeyPreset = serde_json::from_str(&content)
+        .map_err(|e| AppError::InvalidInput(format!("Invalid note key preset: {}", e)))?;
+    if preset.export_type != EXPORT_TYPE {
+        return Err(AppError::InvalidInput(format!(
+            "Not a note key preset: {}",
+            preset.export_type
+        )));
+    }
+    // Shared files can't claim to be shipped with the app
+    preset.built_in = false;
+    Ok(preset)
+}
+
+pub fn write(path: &str, preset: &KeyPreset) -> Result<(), AppError> {
+    let content = serde_json::to_string_pretty(preset)
+        .map_err(|e| format!("Failed to serialize preset: {}", e))?;
+    std::fs::write(path, content).map_err(|e| AppError::io("write preset file", e))
+}
@@ -1,6 +1,7 @@
 // Virtual keyboard input using PostMessage to game window
 // Sends WM_KEYDOWN/WM_KEYUP directly - doesn't affect other apps!
 
+use crate::error::AppError;
 use std::sync::atomic::{AtomicBool, AtomicIsize, AtomicU64, Ordering};
 use std::sync::Mutex;
 use std::time::{Duration, Instant};
@@ -8,23 +9,14 @@ use std::time::{Duration, Instant};
 // Configurable modifier delay in milliseconds (default 0ms = instant)
 static MODIFIER_DELAY_MS: AtomicU64 = AtomicU64::new(0);
 
+// How long played notes are held (0 = release right away); cloud streams can miss
+// instant taps
+static KEY_DURATION_MS: AtomicU64 = AtomicU64::new(0);
+pub const MAX_KEY_DURATION_MS: u64 = 200;
+
 // Input mode: false = PostMessage (default), true = SendInput (for cloud gaming)
 static USE_SEND_INPUT: AtomicBool = AtomicBool::new(false);
 
-use std::collections::HashMap;
-use std::sync::RwLock as StdRwLock;
-
-// Custom key bindings for each note position
-// Maps logical key (e.g., "low_0") to physical key (e.g., "z")
-lazy_static::lazy_static! {
-    static ref CUSTOM_KEY_BINDINGS: StdRwLock<HashMap<String, String>> = StdRwLock::new(HashMap::new());
-}
-
-// Default key bindings (QWERTY layout)
-pub const DEFAULT_LOW_KEYS: [&str; 7] = ["z", "x", "c", "v", "b", "n", "m"];
-pub const DEFAULT_MID_KEYS: [&str; 7] = ["a", "s", "d", "f", "g", "h", "j"];
-pub const DEFAULT_HIGH_KEYS: [&str; 7] = ["q", "w", "e", "r", "t", "y", "u"];
-
 /// Set input mode: true = SendInput (cloud gaming), false = PostMessage (local)
 pub fn set_send_input_mode(enabled: bool) {
     USE_SEND_INPUT.store(enabled, Ordering::SeqCst);
@@ -43,120 +35,338 @@ pub fn get_send_input_mode() -> bool {
     USE_SEND_INPUT.load(Ordering::SeqCst)
 }
 
-/// Set custom key bindings for notes
-/// keys format: { "low": ["z","x",...], "mid": ["a","s",...], "high": ["q","w",...] }
-pub fn set_note_key_bindings(low: Vec<String>, mid: Vec<String>, high: Vec<String>) {
-    if let Ok(mut bindings) = CUSTOM_KEY_BINDINGS.write() {
-        bindings.clear();
-        for (i, key) in low.iter().enumerate() {
-            if i < 7 {
-                bindings.insert(format!("low_{}", i), key.to_lowercase());
-            }
+/// Swap between PostMessage and SendInput mid-song; returns whether SendInput is now on.
+/// Keys pressed through the old path are released through it first.
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))] // Bound to a global hotkey
+pub fn toggle_send_input_mode() -> bool {
+    release_held_keys();
+    let enabled = !get_send_input_mode();
+    set_send_input_mode(enabled);
+    enabled
+}
+
+use crate::midi::KeyMode;
+use std::collections::HashMap;
+use std::sync::RwLock as StdRwLock;
+
+// Default key bindings (QWERTY layout)
+pub const DEFAULT_LOW_KEYS: [&str; 7] = ["z", "x", "c", "v", "b", "n", "m"];
+pub const DEFAULT_MID_KEYS: [&str; 7] = ["a", "s", "d", "f", "g", "h", "j"];
+pub const DEFAULT_HIGH_KEYS: [&str; 7] = ["q", "w", "e", "r", "t", "y", "u"];
+
+const NOTE_MODIFIERS: [&str; 2] = ["shift", "ctrl"];
+
+// The 36-key accidentals of each row (C#, Eb, F#, G#, Bb) as the player sends them:
+// the logical modifier and the natural key it's held with
+const ACCIDENTALS: [(&str, usize); 5] = [
+    ("shift", 0),
+    ("ctrl", 2),
+    ("shift", 3),
+    ("shift", 4),
+    ("ctrl", 6),
+];
+
+/// Custom keys for the 36-key accidentals, five per row in the order C#, Eb, F#, G#,
+/// Bb ("shift+z", "ctrl+1", "p", ...). An empty entry follows the row keys and the
+/// sharp/flat modifiers.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct AccidentalKeys {
+    pub low: Vec<String>,
+    pub mid: Vec<String>,
+    pub high: Vec<String>,
+}
+
+/// Physical keys for one key mode. Rows follow the default layout (low = z..m,
+/// mid = a..j, high = q..u); the modifiers are what 36-key mode holds for sharps
+/// ("shift+" notes) and flats ("ctrl+" notes).
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct NoteKeySet {
+    pub low: Vec<String>,
+    pub mid: Vec<String>,
+    pub high: Vec<String>,
+    #[serde(default = "default_sharp_modifier")]
+    pub sharp_modifier: String,
+    #[serde(default = "default_flat_modifier")]
+    pub flat_modifier: String,
+    #[serde(default)]
+    pub accidentals: AccidentalKeys,
+}
+
+fn default_sharp_modifier() -> String {
+    "shift".to_string()
+}
+
+fn default_flat_modifier() -> String {
+    "ctrl".to_string()
+}
+
+fn default_row(row: &[&str; 7]) -> Vec<String> {
+    row.iter().map(|k| k.to_string()).collect()
+}
+
+impl Default for NoteKeySet {
+    fn default() -> Self {
+        NoteKeySet {
+            low: default_row(&DEFAULT_LOW_KEYS),
+            mid: default_row(&DEFAULT_MID_KEYS),
+            high: default_row(&DEFAULT_HIGH_KEYS),
+            sharp_modifier: default_sharp_modifier(),
+            flat_modifier: default_flat_modifier(),
+            accidentals: AccidentalKeys::default(),
         }
-        for (i, key) in mid.iter().enumerate() {
-            if i < 7 {
-                bindings.insert(format!("mid_{}", i), key.to_lowercase());
+    }
+}
+
+impl NoteKeySet {
+    /// Lowercased, seven keys per row (gaps take the default key), known modifiers only
+    pub fn normalized(self) -> Result<Self, String> {
+        let row = |keys: Vec<String>, defaults: &[&str; 7]| -> Result<Vec<String>, String> {
+            (0..7)
+                .map(|i| match keys.get(i).map(|k| k.trim().to_lowercase()) {
+                    Some(k) if k.is_empty() => Ok(defaults[i].to_string()),
+                    Some(k) if !is_note_key(&k) => Err(format!("Key can't be sent: {}", k)),
+                    Some(k) => Ok(k),
+                    None => Ok(defaults[i].to_string()),
+                })
+                .collect()
+        };
+        let sharp_modifier = self.sharp_modifier.to_lowercase();
+        let flat_modifier = self.flat_modifier.to_lowercase();
+        for modifier in [&sharp_modifier, &flat_modifier] {
+            if !NOTE_MODIFIERS.contains(&modifier.as_str()) {
+                return Err(format!(
+                    "Unknown modifier: {} (use shift or ctrl)",
+                    modifier
+                ));
             }
         }
-        for (i, key) in high.iter().enumerate() {
-            if i < 7 {
-                bindings.insert(format!("high_{}", i), key.to_lowercase());
-            }
+        if sharp_modifier == flat_modifier {
+            return Err("Sharps and flats need different modifiers".to_string());
         }
-        println!(
-            "[KEYBOARD] Custom key bindings set: low={:?}, mid={:?}, high={:?}",
-            low, mid, high
-        );
+        let accidental_row = |keys: Vec<String>| -> Result<Vec<String>, String> {
+            (0..ACCIDENTALS.len())
+                .map(|i| {
+                    let key = keys
+                        .get(i)
+                        .map(|k| k.trim().to_lowercase())
+                        .unwrap_or_default();
+                    let base = NOTE_MODIFIERS
+                        .iter()
+                        .find_map(|m| key.strip_prefix(&format!("{}+", m)))
+                        .unwrap_or(&key);
+                    if !key.is_empty() && !is_note_key(base) {
+                        return Err(format!("Invalid accidental key: {}", key));
+                    }
+                    Ok(key)
+                })
+                .collect()
+        };
+        let accidentals = AccidentalKeys {
+            low: accidental_row(self.accidentals.low)?,
+            mid: accidental_row(self.accidentals.mid)?,
+            high: accidental_row(self.accidentals.high)?,
+        };
+        Ok(NoteKeySet {
+            low: row(self.low, &DEFAULT_LOW_KEYS)?,
+            mid: row(self.mid, &DEFAULT_MID_KEYS)?,
+            high: row(self.high, &DEFAULT_HIGH_KEYS)?,
+            sharp_modifier,
+            flat_modifier,
+            accidentals,
+        })
+    }
+
+    fn row(&self, row: usize) -> &[String] {
+        [&self.low, &self.mid, &self.high][row]
+    }
+
+    /// Physical key for a default-layout key name; anything else is returned as-is
+    fn bound(&self, logical_key: &str) -> String {
+        match natural_position(logical_key) {
+            Some((row, index)) => self.row(row)[index].clone(),
+            None => logical_key.to_string(),
+        }
+    }
+
+    /// Custom key for a logical accidental ("shift+z"), if one is set
+    fn bound_accidental(&self, modifier: &str, logical_key: &str) -> Option<String> {
+        let (row, index) = natural_position(logical_key)?;
+        let slot = ACCIDENTALS.iter().position(|a| *a == (modifier, index))?;
+        let keys = [
+            &self.accidentals.low,
+            &self.accidentals.mid,
+            &self.accidentals.high,
+        ][row];
+        keys.get(slot).filter(|k| !k.is_empty()).cloned()
+    }
+
+    /// The keys each accidental is actually played with, custom or not
+    pub fn accidental_keys(&self) -> AccidentalKeys {
+        let row = |row: usize| -> Vec<String> {
+            let naturals = [&DEFAULT_LOW_KEYS, &DEFAULT_MID_KEYS, &DEFAULT_HIGH_KEYS][row];
+            ACCIDENTALS
+                .iter()
+                .map(|(modifier, index)| self.resolve(modifier, naturals[*index]))
+                .collect()
+        };
+        AccidentalKeys {
+            low: row(0),
+            mid: row(1),
+            high: row(2),
+        }
+    }
+
+    /// Physical key for a logical "modifier+key" note
+    fn resolve(&self, modifier: &str, logical_key: &str) -> String {
+        if let Some(key) = self.bound_accidental(modifier, logical_key) {
+            return key;
+        }
+        let modifier = match modifier {
+            "shift" => &self.sharp_modifier,
+            _ => &self.flat_modifier,
+        };
+        format!("{}+{}", modifier, self.bound(logical_key))
     }
 }
 
-/// Get current note key bindings
-pub fn get_note_key_bindings() -> (Vec<String>, Vec<String>, Vec<String>) {
-    let bindings = CUSTOM_KEY_BINDINGS.read().ok();
-
-    let low: Vec<String> = (0..7)
-        .map(|i| {
-            bindings
-                .as_ref()
-                .and_then(|b| b.get(&format!("low_{}", i)))
-                .map(|s| s.clone())
-                .unwrap_or_else(|| DEFAULT_LOW_KEYS[i].to_string())
-        })
-        .collect();
-
-    let mid: Vec<String> = (0..7)
-        .map(|i| {
-            bindings
-                .as_ref()
-                .and_then(|b| b.get(&format!("mid_{}", i)))
-                .map(|s| s.clone())
-                .unwrap_or_else(|| DEFAULT_MID_KEYS[i].to_string())
-        })
-        .collect();
-
-    let high: Vec<String> = (0..7)
-        .map(|i| {
-            bindings
-                .as_ref()
-                .and_then(|b| b.get(&format!("high_{}", i)))
-                .map(|s| s.clone())
-                .unwrap_or_else(|| DEFAULT_HIGH_KEYS[i].to_string())
-        })
-        .collect();
-
-    (low, mid, high)
-}
-
-/// Get the physical key for a logical note key
-fn get_bound_key(logical_key: &str) -> String {
-    // Map the default key names to their positions
-    let (octave, index) = match logical_key {
-        // Low octave
-        "z" => ("low", 0),
-        "x" => ("low", 1),
-        "c" => ("low", 2),
-        "v" => ("low", 3),
-        "b" => ("low", 4),
-        "n" => ("low", 5),
-        "m" => ("low", 6),
-        // Mid octave
-        "a" => ("mid", 0),
-        "s" => ("mid", 1),
-        "d" => ("mid", 2),
-        "f" => ("mid", 3),
-        "g" => ("mid", 4),
-        "h" => ("mid", 5),
-        "j" => ("mid", 6),
-        // High octave
-        "q" => ("high", 0),
-        "w" => ("high", 1),
-        "e" => ("high", 2),
-        "r" => ("high", 3),
-        "t" => ("high", 4),
-        "y" => ("high", 5),
-        "u" => ("high", 6),
-        // Unknown - return as-is
-        _ => return logical_key.to_string(),
-    };
+/// Keys every input backend can send as a note: letters, digits and ; , . /
+pub fn is_note_key(key: &str) -> bool {
+    match key {
+        "semicolon" | "comma" | "period" | "slash" => true,
+        _ => {
+            let mut chars = key.chars();
+            matches!((chars.next(), chars.next()), (Some(c), None)
+                if c.is_ascii_alphanumeric() || ";,./".contains(c))
+        }
+    }
+}
+
+/// Note key name for a virtual key code, None for keys that can't be a note key
+#[cfg(target_os = "windows")]
+pub fn vk_to_note_key(vk: u32) -> Option<String> {
+    match vk {
+        0x41..=0x5A => Some(((b'a' + (vk - 0x41) as u8) as char).to_string()),
+        0x30..=0x39 => Some(((b'0' + (vk - 0x30) as u8) as char).to_string()),
+        0xBA => Some(";".to_string()),
+        0xBC => Some(",".to_string()),
+        0xBE => Some(".".to_string()),
+        0xBF => Some("/".to_string()),
+        _ => None,
+    }
+}
+
+/// Row and index of a default-layout note key
+fn natural_position(logical_key: &str) -> Option<(usize, usize)> {
+    [&DEFAULT_LOW_KEYS, &DEFAULT_MID_KEYS, &DEFAULT_HIGH_KEYS]
+        .iter()
+        .enumerate()
+        .find_map(|(row, keys)| Some((row, keys.iter().position(|k| *k == logical_key)?)))
+}
 
-    let binding_key = format!("{}_{}", octave, index);
+/// One binding set per key mode, as saved in config ("note_key_sets")
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct NoteKeySets {
+    #[serde(default)]
+    pub keys21: NoteKeySet,
+    #[serde(default)]
+    pub keys36: NoteKeySet,
+}
+
+impl NoteKeySets {
+    pub fn get(&self, mode: KeyMode) -> &NoteKeySet {
+        match mode {
+            KeyMode::Keys21 => &self.keys21,
+            KeyMode::Keys36 => &self.keys36,
+        }
+    }
 
-    if let Ok(bindings) = CUSTOM_KEY_BINDINGS.read() {
-        if let Some(bound) = bindings.get(&binding_key) {
-            return bound.clone();
+    fn get_mut(&mut self, mode: KeyMode) -> &mut NoteKeySet {
+        match mode {
+            KeyMode::Keys21 => &mut self.keys21,
+            KeyMode::Keys36 => &mut self.keys36,
         }
     }
+}
+
+struct NoteBindings {
+    sets: NoteKeySets,
+    mode: KeyMode, // Whose set resolves keys, follows the player's key mode
+}
+
+lazy_static::lazy_static! {
+    static ref NOTE_BINDINGS: StdRwLock<NoteBindings> = StdRwLock::new(NoteBindings {
+        sets: NoteKeySets::default(),
+        mode: KeyMode::Keys21,
+    });
+}
+
+/// Switch to the binding set of `mode` (called from set_key_mode)
+pub fn set_binding_key_mode(mode: KeyMode) {
+    let mut bindings = NOTE_BINDINGS.write().unwrap();
+    if bindings.mode != mode {
+        bindings.mode = mode;
+        println!("[KEYBOARD] Using the {:?} note key bindings", mode);
+    }
+}
+
+pub fn get_binding_key_mode() -> KeyMode {
+    NOTE_BINDINGS.read().unwrap().mode
+}
+
+/// Replace the binding set of one key mode
+pub fn set_note_key_set(mode: KeyMode, set: NoteKeySet) -> Result<(), String> {
+    let set = set.normalized()?;
+    println!(
+        "[KEYBOARD] {:?} key bindings set: low={:?}, mid={:?}, high={:?}, sharp={}, flat={}",
+        mode, set.low, set.mid, set.high, set.sharp_modifier, set.flat_modifier
+    );
+    *NOTE_BINDINGS.write().unwrap().sets.get_mut(mode) = set;
+    Ok(())
+}
 
-    // Return default
-    logical_key.to_string()
+pub fn get_note_key_set(mode: KeyMode) -> NoteKeySet {
+    NOTE_BINDINGS.read().unwrap().sets.get(mode).clone()
 }
 
-/// Reset key bindings to defaults
-pub fn reset_note_key_bindings() {
-    if let Ok(mut bindings) = CUSTOM_KEY_BINDINGS.write() {
-        bindings.clear();
+pub fn get_note_key_sets() -> NoteKeySets {
+    NOTE_BINDINGS.read().unwrap().sets.clone()
+}
+
+/// Saved sets from config; a broken one falls back to the defaults
+pub fn load_note_key_sets(sets: NoteKeySets) {
+    let mut bindings = NOTE_BINDINGS.write().unwrap();
+    bindings.sets = NoteKeySets {
+        keys21: sets.keys21.normalized().unwrap_or_default(),
+        keys36: sets.keys36.normalized().unwrap_or_default(),
+    };
+}
+
+/// Reset one key mode's bindings to defaults
+pub fn reset_note_key_set(mode: KeyMode) {
+    *NOTE_BINDINGS.write().unwrap().sets.get_mut(mode) = NoteKeySet::default();
+    println!("[KEYBOARD] {:?} key bindings reset to defaults", mode);
+}
+
+/// Get the note rows of the active key mode's bindings
+pub fn get_note_key_bindings() -> (Vec<String>, Vec<String>, Vec<String>) {
+    let set = get_note_key_set(get_binding_key_mode());
+    (set.low, set.mid, set.high)
+}
+
+/// Physical key for a logical note key ("z", "shift+a", ...) under the active set:
+/// the letter follows the rows, and the logical shift/ctrl accidentals use their
+/// custom key or else the set's sharp/flat modifiers
+pub fn resolve_note_key(key: &str) -> String {
+    let key = key.to_lowercase();
+    let bindings = NOTE_BINDINGS.read().unwrap();
+    let set = bindings.sets.get(bindings.mode);
+    match NOTE_MODIFIERS
+        .iter()
+        .find_map(|m| Some((*m, key.strip_prefix(&format!("{}+", m))?)))
+    {
+        Some((modifier, base)) => set.resolve(modifier, base),
+        None => set.bound(&key),
     }
-    println!("[KEYBOARD] Key bindings reset to defaults");
 }
 
 // Cached window handle and last check time
@@ -164,6 +374,7 @@ static CACHED_HWND: AtomicIsize = AtomicIsize::new(0);
 lazy_static::lazy_static! {
     static ref LAST_WINDOW_CHECK: Mutex<Option<Instant>> = Mutex::new(None);
 }
+#[cfg(target_os = "windows")]
 const WINDOW_CACHE_DURATION: Duration = Duration::from_secs(5);
 
 /// Set the delay between modifier key and main key press
@@ -176,6 +387,23 @@ pub fn get_modifier_delay() -> u64 {
     MODIFIER_DELAY_MS.load(Ordering::SeqCst)
 }
 
+pub fn set_key_duration(duration_ms: u64) {
+    KEY_DURATION_MS.store(duration_ms.min(MAX_KEY_DURATION_MS), Ordering::SeqCst);
+}
+
+pub fn get_key_duration() -> u64 {
+    KEY_DURATION_MS.load(Ordering::SeqCst)
+}
+
+/// Press and release `key`, holding it for the key duration
+pub fn tap_key(key: &str) {
+    key_down(key);
+    match get_key_duration() {
+        0 => key_up(key),
+        ms => schedule_release(key, Duration::from_millis(ms)),
+    }
+}
+
 #[cfg(target_os = "windows")]
 use windows::Win32::Foundation::{BOOL, HWND, LPARAM, RECT, WPARAM};
 #[cfg(target_os = "windows")]
@@ -185,11 +413,13 @@ use windows::Win32::UI::Input::KeyboardAndMouse::{
 };
 #[cfg(target_os = "windows")]
 use windows::Win32::UI::WindowsAndMessaging::{
-    EnumWindows, GetForegroundWindow, GetWindowRect, GetWindowTextW, PostMessageW,
+    EnumWindows, GetForegroundWindow, GetWindowRect, GetWindowTextW, IsWindow, PostMessageW,
     SetForegroundWindow, ShowWindow, SW_RESTORE, WM_KEYDOWN, WM_KEYUP,
 };
 
-#[cfg(target_os = "windows")]
+// Built-in keywords that belong to cloud streaming clients rather than the game
+const CLOUD_WINDOW_KEYWORDS: [&str; 3] = ["geforce now", "geforcenow", "nvidia geforce"];
+
 const TARGET_WINDOW_KEYWORDS: [&str; 8] = [
     "where winds meet",
     "wwm",
@@ -223,14 +453,10 @@ struct EnumData {
     target: Option<HWND>,
 }
 
-#[cfg(target_os = "windows")]
-fn matches_target_window(hwnd: HWND, log: bool) -> bool {
-    let mut title = [0u16; 256];
-    let len = unsafe { GetWindowTextW(hwnd, &mut title) };
-    if len <= 0 {
-        return false;
-    }
-    let title_string = String::from_utf16_lossy(&title[..len as usize]).to_lowercase();
+/// Check a window title against the built-in and custom keywords.
+/// Returns the matched keyword and whether it was a custom one.
+pub fn match_target_title(title: &str) -> Option<(String, bool)> {
+    let title_string = title.to_lowercase();
 
     // Skip our own window and common apps that should never receive keys
     // Also skip browsers (they may have game-related titles from YouTube/Twitch/etc)
@@ -254,39 +480,86 @@ fn matches_target_window(hwnd: HWND, log: bool) -> bool {
         || title_string.contains("youtube")
         || title_string.contains("twitch")
     {
-        return false;
+        return None;
     }
 
     // Check built-in keywords
-    let matched_keyword = TARGET_WINDOW_KEYWORDS
+    if let Some(keyword) = TARGET_WINDOW_KEYWORDS
         .iter()
-        .find(|keyword| title_string.contains(*keyword));
-    if let Some(keyword) = matched_keyword {
-        if log {
-            println!(
-                "[WINDOW] Found matching window: '{}' (matched: '{}') hwnd={:?}",
-                title_string, keyword, hwnd.0
-            );
-        }
-        return true;
+        .find(|keyword| title_string.contains(*keyword))
+    {
+        return Some((keyword.to_string(), false));
     }
 
     // Check custom keywords
     if let Ok(custom) = CUSTOM_WINDOW_KEYWORDS.read() {
         for keyword in custom.iter() {
             if !keyword.is_empty() && title_string.contains(&keyword.to_lowercase()) {
-                if log {
-                    println!(
-                        "[WINDOW] Found matching window: '{}' (custom: '{}') hwnd={:?}",
-                        title_string, keyword, hwnd.0
-                    );
-                }
-                return true;
+                return Some((keyword.clone(), true));
             }
         }
     }
 
-    false
+    None
+}
+
+pub fn is_cloud_title(title: &str) -> bool {
+    let title = title.to_lowercase();
+    CLOUD_WINDOW_KEYWORDS
+        .iter()
+        .any(|keyword| title.contains(keyword))
+}
+
+#[cfg(target_os = "windows")]
+fn window_title(hwnd: HWND) -> Option<String> {
+    let mut title = [0u16; 256];
+    let len = unsafe { GetWindowTextW(hwnd, &mut title) };
+    if len <= 0 {
+        return None;
+    }
+    Some(String::from_utf16_lossy(&title[..len as usize]))
+}
+
+/// Whether the game runs in a cloud streaming client (None: no game window).
+/// In a browser the tab title ("GeForce NOW - ...") is part of the window title.
+#[cfg(target_os = "windows")]
+pub fn game_window_is_cloud() -> Option<bool> {
+    let title = window_title(find_game_window()?)?;
+    Some(is_cloud_title(&title))
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn game_window_is_cloud() -> Option<bool> {
+    #[cfg(target_os = "linux")]
+    return crate::input_linux::game_window_title().map(|title| is_cloud_title(&title));
+    #[cfg(target_os = "macos")]
+    return crate::input_macos::game_window_title().map(|title| is_cloud_title(&title));
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    None
+}
+
+#[cfg(target_os = "windows")]
+fn matches_target_window(hwnd: HWND, log: bool) -> bool {
+    let Some(title) = window_title(hwnd) else {
+        return false;
+    };
+    let title_string = title.to_lowercase();
+
+    match match_target_title(&title_string) {
+        Some((keyword, custom)) => {
+            if log {
+                println!(
+                    "[WINDOW] Found matching window: '{}' ({}: '{}') hwnd={:?}",
+                    title_string,
+                    if custom { "custom" } else { "matched" },
+                    keyword,
+                    hwnd.0
+                );
+            }
+            true
+        }
+        None => false,
+    }
 }
 
 #[cfg(target_os = "windows")]
@@ -319,7 +592,11 @@ fn find_game_window() -> Option<HWND> {
     };
 
     if !should_refresh && cached != 0 {
-        return Some(HWND(cached as *mut std::ffi::c_void));
+        // The game may have restarted since; never post to a dead handle
+        if window_alive(cached) {
+            return Some(HWND(cached as *mut std::ffi::c_void));
+        }
+        println!("[WINDOW] Cached game window is gone, searching again");
     }
 
     // Search for window
@@ -345,6 +622,29 @@ fn find_game_window() -> Option<HWND> {
     }
 }
 
+#[cfg(target_os = "windows")]
+fn window_alive(hwnd: isize) -> bool {
+    unsafe { IsWindow(HWND(hwnd as *mut std::ffi::c_void)).as_bool() }
+}
+
+/// Validate the cached handle (re-searching if it died or expired) so the playback
+/// path rarely has to enumerate windows. Returns whether a game window is present.
+#[cfg(target_os = "windows")]
+pub fn refresh_game_window() -> bool {
+    let cached = CACHED_HWND.load(Ordering::SeqCst);
+    if cached != 0 && window_alive(cached) {
+        *LAST_WINDOW_CHECK.lock().unwrap() = Some(Instant::now());
+        return true;
+    }
+    clear_window_cache();
+    find_game_window().is_some()
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn refresh_game_window() -> bool {
+    get_game_window_rect().is_some()
+}
+
 /// Clear the cached window handle (call when starting new playback)
 #[allow(dead_code)]
 pub fn clear_window_cache() {
@@ -352,6 +652,10 @@ pub fn clear_window_cache() {
     if let Ok(mut last_check) = LAST_WINDOW_CHECK.lock() {
         *last_check = None;
     }
+    #[cfg(target_os = "linux")]
+    crate::input_linux::clear_window_cache();
+    #[cfg(target_os = "macos")]
+    crate::input_macos::clear_window_cache();
 }
 
 /// Get current game window rectangle in screen coordinates
@@ -372,6 +676,11 @@ pub fn get_game_window_rect() -> Option<(i32, i32, i32, i32)> {
 
 #[cfg(not(target_os = "windows"))]
 pub fn get_game_window_rect() -> Option<(i32, i32, i32, i32)> {
+    #[cfg(target_os = "linux")]
+    return crate::input_linux::get_game_window_rect();
+    #[cfg(target_os = "macos")]
+    return crate::input_macos::get_game_window_rect();
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
     None
 }
 
@@ -394,24 +703,18 @@ pub enum Modifier {
 /// Format: "key" for normal, "shift+key" for shift, "ctrl+key" for ctrl
 #[cfg(target_os = "windows")]
 fn parse_key(key: &str) -> Option<(u32, Modifier)> {
-    let key_lower = key.to_lowercase();
+    // Resolve custom bindings first, then convert to VK
+    let key = resolve_note_key(key);
 
     // Check for modifier prefix
-    if key_lower.starts_with("shift+") {
-        let base_key = &key_lower[6..];
-        // First resolve custom binding, then convert to VK
-        let bound_key = get_bound_key(base_key);
-        return char_to_vk(&bound_key).map(|vk| (vk, Modifier::Shift));
+    if let Some(base_key) = key.strip_prefix("shift+") {
+        return char_to_vk(base_key).map(|vk| (vk, Modifier::Shift));
     }
-    if key_lower.starts_with("ctrl+") {
-        let base_key = &key_lower[5..];
-        let bound_key = get_bound_key(base_key);
-        return char_to_vk(&bound_key).map(|vk| (vk, Modifier::Ctrl));
+    if let Some(base_key) = key.strip_prefix("ctrl+") {
+        return char_to_vk(base_key).map(|vk| (vk, Modifier::Ctrl));
     }
 
-    // First resolve custom binding, then convert to VK
-    let bound_key = get_bound_key(&key_lower);
-    char_to_vk(&bound_key).map(|vk| (vk, Modifier::None))
+    char_to_vk(&key).map(|vk| (vk, Modifier::None))
 }
 
 /// Convert a single character key to virtual key code
@@ -498,15 +801,15 @@ fn modifier_to_vk(modifier: Modifier) -> Option<u32> {
     }
 }
 
-/// Reset modifier counts (no-op now, kept for compatibility)
+/// Release anything still held down (stop, crash, end of song)
 pub fn reset_modifier_counts() {
-    // No longer using reference counting
+    release_held_keys();
 }
 
 // ============ SendInput-based functions (for cloud gaming) ============
 
 #[cfg(target_os = "windows")]
-fn send_input_key_down(vk: u32) {
+fn send_input_key_down(vk: u32) -> bool {
     unsafe {
         let scan_code = MapVirtualKeyW(vk, MAPVK_VK_TO_VSC) as u16;
         let input = INPUT {
@@ -521,7 +824,7 @@ fn send_input_key_down(vk: u32) {
                 },
             },
         };
-        SendInput(&[input], std::mem::size_of::<INPUT>() as i32);
+        SendInput(&[input], std::mem::size_of::<INPUT>() as i32) == 1
     }
 }
 
@@ -547,7 +850,7 @@ fn send_input_key_up(vk: u32) {
 
 /// Send modifier + key down in a single atomic SendInput call (instant, no delay)
 #[cfg(target_os = "windows")]
-fn send_input_combo_down(mod_vk: u32, key_vk: u32) {
+fn send_input_combo_down(mod_vk: u32, key_vk: u32) -> bool {
     unsafe {
         let mod_scan = MapVirtualKeyW(mod_vk, MAPVK_VK_TO_VSC) as u16;
         let key_scan = MapVirtualKeyW(key_vk, MAPVK_VK_TO_VSC) as u16;
@@ -582,7 +885,7 @@ fn send_input_combo_down(mod_vk: u32, key_vk: u32) {
                 },
             },
         ];
-        SendInput(&inputs, std::mem::size_of::<INPUT>() as i32);
+        SendInput(&inputs, std::mem::size_of::<INPUT>() as i32) == inputs.len() as u32
     }
 }
 
@@ -628,96 +931,420 @@ fn send_input_combo_up(key_vk: u32, mod_vk: u32) {
 }
 
 // ============ Key down/up with mode switching ============
+// Every press that goes out is remembered with the path it took until its release.
+// The release goes back the same way even if focus, the game window or the input mode
+// changed in between, so nothing stays stuck down (a SendInput release is sent even
+// when the game lost focus; it's harmless wherever focus went).
 
 #[cfg(target_os = "windows")]
-pub fn key_down(key: &str) {
-    if let Some((vk, modifier)) = parse_key(key) {
-        if USE_SEND_INPUT.load(Ordering::SeqCst) {
-            // SendInput mode - global keyboard simulation
-            // Only send if a game window is currently focused (prevent typing in Discord etc)
-            if !is_wwm_focused().unwrap_or(false) {
-                return;
-            }
-            // Use atomic combo for modifier keys (instant, no delay)
-            if let Some(mod_vk) = modifier_to_vk(modifier) {
-                send_input_combo_down(mod_vk, vk);
-            } else {
-                send_input_key_down(vk);
-            }
-        } else {
-            // PostMessage mode - targeted to game window
-            if let Some(hwnd) = find_game_window() {
-                unsafe {
-                    // Send modifier + key instantly (back-to-back, no delay)
-                    if let Some(mod_vk) = modifier_to_vk(modifier) {
-                        let mod_lparam = make_keydown_lparam(mod_vk);
-                        let key_lparam = make_keydown_lparam(vk);
-                        let _ = PostMessageW(hwnd, WM_KEYDOWN, WPARAM(mod_vk as usize), mod_lparam);
-                        let _ = PostMessageW(hwnd, WM_KEYDOWN, WPARAM(vk as usize), key_lparam);
-                    } else {
-                        let lparam = make_keydown_lparam(vk);
-                        let _ = PostMessageW(hwnd, WM_KEYDOWN, WPARAM(vk as usize), lparam);
-                    }
-                }
-            }
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum HeldVia {
+    PostMessage(isize), // Window the press was posted to
+    SendInput,
+}
+
+#[cfg(target_os = "windows")]
+lazy_static::lazy_static! {
+    static ref HELD_KEYS: Mutex<HashMap<String, HeldVia>> = Mutex::new(HashMap::new());
+}
+
+#[cfg(target_os = "windows")]
+fn platform_key_down(key: &str) -> KeySent {
+    let Some((vk, modifier)) = parse_key(key) else {
+        return KeySent::new(key, "none", 0, Err(format!("Key can't be sent: {}", key)));
+    };
+    let mod_vk = modifier_to_vk(modifier);
+    if USE_SEND_INPUT.load(Ordering::SeqCst) {
+        // SendInput mode - global keyboard simulation
+        // Only send if a game window is currently focused (prevent typing in Discord etc)
+        if !is_wwm_focused().unwrap_or(false) {
+            // Focus moved away mid-song: let go of whatever is still down
+            release_held_keys();
+            return KeySent::new(key, "send_input", 0, Err("Game is not focused".to_string()));
         }
+        let sent = send_input_press(vk, mod_vk);
+        HELD_KEYS
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), HeldVia::SendInput);
+        let result = if sent {
+            Ok(())
+        } else {
+            Err("SendInput was blocked".to_string())
+        };
+        KeySent::new(key, "send_input", 0, result)
+    } else if let Some(hwnd) = find_game_window() {
+        // PostMessage mode - targeted to game window
+        let sent = post_press(hwnd, vk, mod_vk);
+        HELD_KEYS
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), HeldVia::PostMessage(hwnd.0 as isize));
+        let result = if sent {
+            Ok(())
+        } else {
+            Err("PostMessage failed".to_string())
+        };
+        KeySent::new(key, "post_message", hwnd.0 as isize, result)
+    } else {
+        KeySent::new(
+            key,
+            "post_message",
+            0,
+            Err("Game window not found".to_string()),
+        )
     }
 }
 
 #[cfg(target_os = "windows")]
-pub fn key_up(key: &str) {
-    if let Some((vk, modifier)) = parse_key(key) {
-        if USE_SEND_INPUT.load(Ordering::SeqCst) {
-            // SendInput mode - global keyboard simulation
-            // Only send if a game window is currently focused (prevent typing in Discord etc)
-            if !is_wwm_focused().unwrap_or(false) {
-                return;
-            }
-            // Use atomic combo for modifier keys (instant, no delay)
-            if let Some(mod_vk) = modifier_to_vk(modifier) {
-                send_input_combo_up(vk, mod_vk);
-            } else {
-                send_input_key_up(vk);
-            }
-        } else {
-            // PostMessage mode - targeted to game window
-            if let Some(hwnd) = find_game_window() {
-                unsafe {
-                    // Release key + modifier instantly (back-to-back, no delay)
-                    if let Some(mod_vk) = modifier_to_vk(modifier) {
-                        let key_lparam = make_keyup_lparam(vk);
-                        let mod_lparam = make_keyup_lparam(mod_vk);
-                        let _ = PostMessageW(hwnd, WM_KEYUP, WPARAM(vk as usize), key_lparam);
-                        let _ = PostMessageW(hwnd, WM_KEYUP, WPARAM(mod_vk as usize), mod_lparam);
-                    } else {
-                        let lparam = make_keyup_lparam(vk);
-                        let _ = PostMessageW(hwnd, WM_KEYUP, WPARAM(vk as usize), lparam);
-                    }
-                }
-            }
+fn platform_key_up(key: &str) {
+    let held = HELD_KEYS.lock().unwrap().remove(key);
+    if let Some(via) = held {
+        release_via(key, via);
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn release_via(key: &str, via: HeldVia) {
+    let Some((vk, modifier)) = parse_key(key) else {
+        return;
+    };
+    let mod_vk = modifier_to_vk(modifier);
+    match via {
+        HeldVia::SendInput => send_input_release(vk, mod_vk),
+        // A window that's gone has nothing left to release
+        HeldVia::PostMessage(hwnd) if window_alive(hwnd) => {
+            post_release(HWND(hwnd as *mut std::ffi::c_void), vk, mod_vk)
         }
+        HeldVia::PostMessage(_) => {}
     }
 }
 
-#[cfg(not(target_os = "windows"))]
-pub fn key_down(_key: &str) {
-    // Non-Windows: no-op for now
+/// Release every key still down, each through the path that pressed it
+#[cfg(target_os = "windows")]
+pub fn release_held_keys() {
+    let held: Vec<(String, HeldVia)> = HELD_KEYS.lock().unwrap().drain().collect();
+    for (key, via) in held {
+        release_via(&key, via);
+    }
 }
 
 #[cfg(not(target_os = "windows"))]
-pub fn key_up(_key: &str) {
-    // Non-Windows: no-op for now
+pub fn release_held_keys() {}
+
+#[cfg(target_os = "windows")]
+fn send_input_press(vk: u32, mod_vk: Option<u32>) -> bool {
+    // Use atomic combo for modifier keys, unless a modifier delay is set
+    let delay = get_modifier_delay();
+    match mod_vk {
+        Some(mod_vk) if delay > 0 => {
+            let modifier_sent = send_input_key_down(mod_vk);
+            std::thread::sleep(Duration::from_millis(delay));
+            send_input_key_down(vk) && modifier_sent
+        }
+        Some(mod_vk) => send_input_combo_down(mod_vk, vk),
+        None => send_input_key_down(vk),
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn send_input_release(vk: u32, mod_vk: Option<u32>) {
+    let delay = get_modifier_delay();
+    match mod_vk {
+        Some(mod_vk) if delay > 0 => {
+            send_input_key_up(vk);
+            std::thread::sleep(Duration::from_millis(delay));
+            send_input_key_up(mod_vk);
+        }
+        Some(mod_vk) => send_input_combo_up(vk, mod_vk),
+        None => send_input_key_up(vk),
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn post_press(hwnd: HWND, vk: u32, mod_vk: Option<u32>) -> bool {
+    unsafe {
+        // Send modifier + key instantly (back-to-back, no delay)
+        let mut posted = true;
+        if let Some(mod_vk) = mod_vk {
+            let mod_lparam = make_keydown_lparam(mod_vk);
+            posted &= PostMessageW(hwnd, WM_KEYDOWN, WPARAM(mod_vk as usize), mod_lparam).is_ok();
+        }
+        let lparam = make_keydown_lparam(vk);
+        posted & PostMessageW(hwnd, WM_KEYDOWN, WPARAM(vk as usize), lparam).is_ok()
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn post_release(hwnd: HWND, vk: u32, mod_vk: Option<u32>) {
+    unsafe {
+        // Release key + modifier instantly (back-to-back, no delay)
+        let lparam = make_keyup_lparam(vk);
+        let _ = PostMessageW(hwnd, WM_KEYUP, WPARAM(vk as usize), lparam);
+        if let Some(mod_vk) = mod_vk {
+            let mod_lparam = make_keyup_lparam(mod_vk);
+            let _ = PostMessageW(hwnd, WM_KEYUP, WPARAM(mod_vk as usize), mod_lparam);
+        }
+    }
 }
 
 #[cfg(not(target_os = "windows"))]
-pub fn reset_modifier_counts() {
-    // Non-Windows: no-op
+fn platform_key_down(key: &str) -> KeySent {
+    #[cfg(target_os = "linux")]
+    let (platform, result) = ("linux", crate::input_linux::key_down(key));
+    #[cfg(target_os = "macos")]
+    let (platform, result) = ("macos", crate::input_macos::key_down(key));
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    let (platform, result): (&str, Result<&'static str, String>) =
+        ("none", Err("Key input isn't supported here".to_string()));
+    match result {
+        Ok(backend) => KeySent::new(key, backend, 0, Ok(())),
+        Err(e) => KeySent::new(key, platform, 0, Err(e)),
+    }
 }
 
 #[cfg(not(target_os = "windows"))]
-#[allow(dead_code)]
-pub fn clear_window_cache() {
-    // Non-Windows: no-op
+fn platform_key_up(key: &str) {
+    #[cfg(target_os = "linux")]
+    crate::input_linux::key_up(key);
+    #[cfg(target_os = "macos")]
+    crate::input_macos::key_up(key);
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    let _ = key;
+}
+
+// ============ Key sink ============
+// Every press and release goes through the installed sink. The platform sink sends
+// real input (PostMessage/SendInput, or the Linux/macOS backends); tests swap in a
+// recording sink to check what playback pressed and when.
+
+pub trait KeySink: Send + Sync {
+    fn key_down(&self, key: &str);
+    fn key_up(&self, key: &str);
+}
+
+/// Outcome of one real key press, reported as the "key-sent" event
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct KeySent {
+    pub key: String,
+    pub backend: &'static str, // post_message, send_input, x11, uinput, cgevent
+    pub hwnd: isize,           // Window the press was posted to (PostMessage only)
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+impl KeySent {
+    fn new(key: &str, backend: &'static str, hwnd: isize, result: Result<(), String>) -> Self {
+        KeySent {
+            key: key.to_string(),
+            backend,
+            hwnd,
+            success: result.is_ok(),
+            error: result.err(),
+        }
+    }
+}
+
+type KeySentListener = Box<dyn Fn(&KeySent) + Send + Sync>;
+
+static KEY_SENT_LISTENER: StdRwLock<Option<KeySentListener>> = StdRwLock::new(None);
+
+/// Called after every real key press with how it went
+pub fn set_key_sent_listener(listener: impl Fn(&KeySent) + Send + Sync + 'static) {
+    *KEY_SENT_LISTENER.write().unwrap() = Some(Box::new(listener));
+}
+
+struct PlatformKeySink;
+
+impl KeySink for PlatformKeySink {
+    fn key_down(&self, key: &str) {
+        let sent = platform_key_down(key);
+        if let Some(listener) = KEY_SENT_LISTENER.read().unwrap().as_ref() {
+            listener(&sent);
+        }
+    }
+
+    fn key_up(&self, key: &str) {
+        platform_key_up(key);
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref KEY_SINK: StdRwLock<std::sync::Arc<dyn KeySink>> =
+        StdRwLock::new(std::sync::Arc::new(PlatformKeySink));
+}
+
+/// Send presses and releases to `sink` from now on; returns the sink it replaces
+#[cfg(test)]
+pub fn set_key_sink(sink: std::sync::Arc<dyn KeySink>) -> std::sync::Arc<dyn KeySink> {
+    std::mem::replace(&mut *KEY_SINK.write().unwrap(), sink)
+}
+
+fn key_sink() -> std::sync::Arc<dyn KeySink> {
+    KEY_SINK.read().unwrap().clone()
+}
+
+pub fn key_down(key: &str) {
+    key_sink().key_down(key);
+}
+
+pub fn key_up(key: &str) {
+    key_sink().key_up(key);
+}
+
+/// A key event seen by `RecordingKeySink`
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SentKey {
+    pub key: String,
+    pub down: bool,
+    pub at: Duration, // Since the sink was created
+}
+
+/// Records every press and release with a timestamp instead of sending input
+#[cfg(test)]
+pub struct RecordingKeySink {
+    started: Instant,
+    sent: Mutex<Vec<SentKey>>,
+}
+
+#[cfg(test)]
+impl RecordingKeySink {
+    pub fn new() -> Self {
+        RecordingKeySink {
+            started: Instant::now(),
+            sent: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn sent(&self) -> Vec<SentKey> {
+        self.sent.lock().unwrap().clone()
+    }
+
+    /// Pressed keys in order, with when they were pressed
+    pub fn presses(&self) -> Vec<(String, Duration)> {
+        self.sent()
+            .into_iter()
+            .filter(|k| k.down)
+            .map(|k| (k.key, k.at))
+            .collect()
+    }
+
+    fn record(&self, key: &str, down: bool) {
+        self.sent.lock().unwrap().push(SentKey {
+            key: key.to_string(),
+            down,
+            at: self.started.elapsed(),
+        });
+    }
+}
+
+#[cfg(test)]
+impl KeySink for RecordingKeySink {
+    fn key_down(&self, key: &str) {
+        self.record(key, true);
+    }
+
+    fn key_up(&self, key: &str) {
+        self.record(key, false);
+    }
+}
+
+// ============ Scheduled key releases ============
+// One worker thread owns every pending release (ordered by deadline) instead of a
+// sleeping thread per tapped note. Re-pressing a key before its release replaces the
+// pending one, so a fast repeat isn't cut short by the earlier tap's release.
+
+struct ReleaseQueue {
+    pending: std::collections::BinaryHeap<std::cmp::Reverse<(Instant, u64)>>,
+    keys: HashMap<u64, String>,   // Release id -> key
+    latest: HashMap<String, u64>, // Key -> its current release id
+    next_id: u64,
+}
+
+lazy_static::lazy_static! {
+    static ref RELEASES: (Mutex<ReleaseQueue>, std::sync::Condvar) = {
+        std::thread::Builder::new()
+            .name("key-release".to_string())
+            .spawn(release_worker)
+            .expect("Failed to start key release worker");
+        (
+            Mutex::new(ReleaseQueue {
+                pending: std::collections::BinaryHeap::new(),
+                keys: HashMap::new(),
+                latest: HashMap::new(),
+                next_id: 0,
+            }),
+            std::sync::Condvar::new(),
+        )
+    };
+}
+
+/// Release `key` after `delay` (tap notes, live input, hold-mode playback)
+pub fn schedule_release(key: &str, delay: Duration) {
+    let (queue, wake) = &*RELEASES;
+    let mut queue = queue.lock().unwrap();
+    let id = queue.next_id;
+    queue.next_id += 1;
+    if let Some(previous) = queue.latest.insert(key.to_string(), id) {
+        queue.keys.remove(&previous); // Superseded; its heap entry is skipped
+    }
+    queue.keys.insert(id, key.to_string());
+    queue
+        .pending
+        .push(std::cmp::Reverse((Instant::now() + delay, id)));
+    wake.notify_one();
+}
+
+/// Release every key with a pending release right away (stop, focus loss)
+pub fn flush_releases() {
+    let due: Vec<String> = {
+        let mut queue = RELEASES.0.lock().unwrap();
+        queue.pending.clear();
+        queue.latest.clear();
+        queue.keys.drain().map(|(_, key)| key).collect()
+    };
+    for key in &due {
+        key_up(key);
+    }
+}
+
+fn release_worker() {
+    let (queue, wake) = &*RELEASES;
+    let mut queue = queue.lock().unwrap();
+    loop {
+        let now = Instant::now();
+        let mut due = Vec::new();
+        while let Some(&std::cmp::Reverse((deadline, id))) = queue.pending.peek() {
+            if deadline > now {
+                break;
+            }
+            queue.pending.pop();
+            if let Some(key) = queue.keys.remove(&id) {
+                queue.latest.remove(&key);
+                due.push(key);
+            }
+        }
+
+        if !due.is_empty() {
+            // Don't hold the lock while sending input
+            drop(queue);
+            for key in &due {
+                key_up(key);
+            }
+            queue = RELEASES.0.lock().unwrap();
+            continue;
+        }
+
+        queue = match queue.pending.peek() {
+            Some(&std::cmp::Reverse((deadline, _))) => {
+                wake.wait_timeout(queue, deadline.saturating_duration_since(now))
+                    .unwrap()
+                    .0
+            }
+            None => wake.wait(queue).unwrap(),
+        };
+    }
 }
 
 // ============ Old Enigo-based method (commented out) ============
@@ -780,11 +1407,16 @@ pub fn is_game_window_found() -> bool {
 
 #[cfg(not(target_os = "windows"))]
 pub fn is_game_window_found() -> bool {
+    #[cfg(target_os = "linux")]
+    return crate::input_linux::is_game_window_found();
+    #[cfg(target_os = "macos")]
+    return crate::input_macos::is_game_window_found();
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
     true
 }
 
 #[cfg(target_os = "windows")]
-pub fn is_wwm_focused() -> Result<bool, String> {
+pub fn is_wwm_focused() -> Result<bool, AppError> {
     unsafe {
         let hwnd = GetForegroundWindow();
         if hwnd.0.is_null() {
@@ -795,20 +1427,25 @@ pub fn is_wwm_focused() -> Result<bool, String> {
 }
 
 #[cfg(not(target_os = "windows"))]
-pub fn is_wwm_focused() -> Result<bool, String> {
-    // For non-Windows platforms, always return true for now
+pub fn is_wwm_focused() -> Result<bool, AppError> {
+    #[cfg(target_os = "linux")]
+    return Ok(crate::input_linux::is_game_focused());
+    #[cfg(target_os = "macos")]
+    return Ok(crate::input_macos::is_game_focused());
+    // Other platforms: always return true for now
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
     Ok(true)
 }
 
 #[cfg(target_os = "windows")]
-pub fn focus_black_desert_window() -> Result<(), String> {
+pub fn focus_black_desert_window() -> Result<(), AppError> {
     unsafe {
         let mut data = EnumData { target: None };
         EnumWindows(
             Some(enum_windows_proc),
             LPARAM(&mut data as *mut _ as isize),
         )
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| AppError::Other(e.to_string()))?;
 
         if let Some(hwnd) = data.target {
             let _ = ShowWindow(hwnd, SW_RESTORE);
@@ -817,12 +1454,161 @@ pub fn focus_black_desert_window() -> Result<(), String> {
             std::thread::sleep(std::time::Duration::from_millis(100));
             Ok(())
         } else {
-            Err("Game window not found (WWM or GeForce Now)".into())
+            Err(AppError::GameWindowNotFound(
+                "Game window not found (WWM or GeForce Now)".to_string(),
+            ))
         }
     }
 }
 
 #[cfg(not(target_os = "windows"))]
-pub fn focus_black_desert_window() -> Result<(), String> {
+pub fn focus_black_desert_window() -> Result<(), AppError> {
+    #[cfg(target_os = "linux")]
+    return crate::input_linux::focus_game_window().map_err(AppError::GameWindowNotFound);
+    #[cfg(target_os = "macos")]
+    return crate::input_macos::focus_game_window().map_err(AppError::GameWindowNotFound);
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
     Ok(())
 }
+
+// ============ Auto-focus before playback ============
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoFocus {
+    Never,
+    IfUnfocused,
+    Always,
+}
+
+static AUTO_FOCUS: std::sync::atomic::AtomicU8 =
+    std::sync::atomic::AtomicU8::new(AutoFocus::Always as u8);
+// Lets the click that started playback finish before focus moves
+const AUTO_FOCUS_DELAY: Duration = Duration::from_millis(100);
+
+pub fn set_auto_focus(mode: AutoFocus) {
+    AUTO_FOCUS.store(mode as u8, Ordering::SeqCst);
+}
+
+pub fn get_auto_focus() -> AutoFocus {
+    match AUTO_FOCUS.load(Ordering::SeqCst) {
+        0 => AutoFocus::Never,
+        1 => AutoFocus::IfUnfocused,
+        _ => AutoFocus::Always,
+    }
+}
+
+/// Bring the game forward as the auto-focus setting says. Blocks while the window
+/// manager catches up, so commands run it on a thread of its own.
+pub fn auto_focus_game() {
+    match get_auto_focus() {
+        AutoFocus::Never => return,
+        AutoFocus::IfUnfocused if is_wwm_focused().unwrap_or(false) => return,
+        _ => {}
+    }
+    std::thread::sleep(AUTO_FOCUS_DELAY);
+    if let Err(e) = focus_black_desert_window() {
+        println!("[WINDOW] Auto-focus skipped: {}", e);
+    }
+}
+
+/// `auto_focus_game` without holding up the caller
+pub fn auto_focus_game_async() {
+    std::thread::spawn(auto_focus_game);
+}
+
+// ============ Input path probe ============
+// Sends F24 (on no real keyboard, bound by no game) to check that input can reach the
+// game: PostMessage must be accepted by the window, SendInput must show up in our
+// low-level keyboard hook (it doesn't when the game runs elevated and we don't).
+
+#[cfg(target_os = "windows")]
+const PROBE_VK: u32 = 0x87; // VK_F24
+#[cfg(target_os = "windows")]
+const PROBE_WAIT: Duration = Duration::from_millis(300);
+#[cfg(target_os = "windows")]
+static PROBE_SEEN: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct InputProbe {
+    pub game_window_found: bool,
+    pub game_focused: bool,
+    pub post_message: Option<bool>, // Accepted by the game window (None: not checked)
+    pub send_input: Option<bool>,   // Injected and seen by the keyboard hook (None: not checked)
+}
+
+/// Called by the low-level keyboard hook for every key press it sees
+#[cfg(target_os = "windows")]
+pub fn note_hooked_key(vk: u32, injected: bool) {
+    if vk == PROBE_VK && injected {
+        PROBE_SEEN.store(true, Ordering::SeqCst);
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn send_probe_key() -> bool {
+    let key = |flags| INPUT {
+        r#type: INPUT_KEYBOARD,
+        Anonymous: windows::Win32::UI::Input::KeyboardAndMouse::INPUT_0 {
+            ki: KEYBDINPUT {
+                wVk: windows::Win32::UI::Input::KeyboardAndMouse::VIRTUAL_KEY(PROBE_VK as u16),
+                wScan: 0,
+                dwFlags: flags,
+                time: 0,
+                dwExtraInfo: 0,
+            },
+        },
+    };
+    let inputs = [
+        key(windows::Win32::UI::Input::KeyboardAndMouse::KEYBD_EVENT_FLAGS(0)),
+        key(KEYEVENTF_KEYUP),
+    ];
+    unsafe { SendInput(&inputs, std::mem::size_of::<INPUT>() as i32) == inputs.len() as u32 }
+}
+
+/// Send the probe key down both input paths (blocks for up to PROBE_WAIT)
+#[cfg(target_os = "windows")]
+pub fn probe_input_path() -> InputProbe {
+    let hwnd = find_game_window();
+    let post_message = hwnd.map(|hwnd| unsafe {
+        PostMessageW(
+            hwnd,
+            WM_KEYDOWN,
+            WPARAM(PROBE_VK as usize),
+            make_keydown_lparam(PROBE_VK),
+        )
+        .is_ok()
+            && PostMessageW(
+                hwnd,
+                WM_KEYUP,
+                WPARAM(PROBE_VK as usize),
+                make_keyup_lparam(PROBE_VK),
+            )
+            .is_ok()
+    });
+
+    PROBE_SEEN.store(false, Ordering::SeqCst);
+    let injected = send_probe_key();
+    let started = Instant::now();
+    while injected && !PROBE_SEEN.load(Ordering::SeqCst) && started.elapsed() < PROBE_WAIT {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    InputProbe {
+        game_window_found: hwnd.is_some(),
+        game_focused: is_wwm_focused().unwrap_or(false),
+        post_message,
+        send_input: Some(injected && PROBE_SEEN.load(Ordering::SeqCst)),
+    }
+}
+
+/// Linux and macOS have a single input path; only the window checks apply
+#[cfg(not(target_os = "windows"))]
+pub fn probe_input_path() -> InputProbe {
+    InputProbe {
+        game_window_found: is_game_window_found(),
+        game_focused: is_wwm_focused().unwrap_or(false),
+        post_message: None,
+        send_input: None,
+    }
+}
@@ -0,0 +1,170 @@
+// Shared library server: serve our own album to another instance on the LAN, no
+// discovery server needed. Everything needs the library token (Bearer or ?token=).
+//   GET /library        songs allowed by the sharing policy (same shape as discovery)
+//   GET /library/:hash  the MIDI file
+// The other side browses with `fetch_library` and pulls through the download queue.
+
+use crate::discovery::SharedSong;
+use crate::error::AppError;
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+pub const DEFAULT_PORT: u16 = 3031;
+// Building the song list hashes the whole album, so reuse it for a while
+const CATALOG_TTL: Duration = Duration::from_secs(60);
+
+struct LibraryState {
+    token: String,
+    catalog: tokio::sync::Mutex<Option<(Instant, Vec<SharedSong>)>>,
+}
+
+type SharedState = Arc<LibraryState>;
+
+static SHUTDOWN_SENDER: Mutex<Option<oneshot::Sender<()>>> = Mutex::new(None);
+
+pub fn is_running() -> bool {
+    SHUTDOWN_SENDER.lock().unwrap().is_some()
+}
+
+async fn catalog(state: &LibraryState) -> Result<Vec<SharedSong>, String> {
+    let mut cached = state.catalog.lock().await;
+    if let Some((built, songs)) = cached.as_ref() {
+        if built.elapsed() < CATALOG_TTL {
+            return Ok(songs.clone());
+        }
+    }
+    let songs = crate::shared_songs().await?;
+    *cached = Some((Instant::now(), songs.clone()));
+    Ok(songs)
+}
+
+async fn list_songs(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    if !crate::discovery::token_matches(&headers, &query, &state.token) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    match catalog(&state).await {
+        Ok(songs) => Json(songs).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
+
+async fn download_song(
+    State(state): State<SharedState>,
+    Path(hash): Path<String>,
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    if !crate::discovery::token_matches(&headers, &query, &state.token) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    // Only songs the sharing policy publishes can be pulled
+    let shared = match catalog(&state).await {
+        Ok(songs) => songs.iter().any(|s| s.hash == hash),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    };
+    if !shared {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let hashes = std::collections::HashSet::from([hash.clone()]);
+    let Some(path) = crate::resolve_paths_by_hash(&hashes).remove(&hash) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    match tokio::fs::read(&path).await {
+        Ok(bytes) => ([(header::CONTENT_TYPE, "audio/midi")], bytes).into_response(),
+        Err(_) => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+async fn health() -> &'static str {
+    "OK"
+}
+
+/// Serve the album on 0.0.0.0:`port` until `stop` is called
+pub async fn start(port: u16, token: String) -> Result<(), String> {
+    if is_running() {
+        return Err("Library server already running".to_string());
+    }
+    let state: SharedState = Arc::new(LibraryState {
+        token,
+        catalog: tokio::sync::Mutex::new(None),
+    });
+    let app = Router::new()
+        .route("/health", get(health))
+        .route("/library", get(list_songs))
+        .route("/library/:hash", get(download_song))
+        .with_state(state);
+
+    let addr = format!("0.0.0.0:{}", port);
+    let listener = tokio::net::TcpListener::bind(&addr)
+        .await
+        .map_err(|e| format!("Failed to bind: {}", e))?;
+    println!("[LIBRARY] Serving album on {}", addr);
+
+    let (tx, rx) = oneshot::channel();
+    *SHUTDOWN_SENDER.lock().unwrap() = Some(tx);
+    tokio::spawn(async move {
+        let result = axum::serve(listener, app)
+            .with_graceful_shutdown(async move {
+                let _ = rx.await;
+            })
+            .await;
+        if let Err(e) = result {
+            crate::app_error!("[LIBRARY] Server error: {}", e);
+        }
+        // Unless a newer server has taken the slot since
+        let mut sender = SHUTDOWN_SENDER.lock().unwrap();
+        if sender.as_ref().is_some_and(|tx| tx.is_closed()) {
+            *sender = None;
+        }
+        println!("[LIBRARY] Server stopped");
+    });
+    Ok(())
+}
+
+pub fn stop() -> Result<(), String> {
+    let sender = SHUTDOWN_SENDER.lock().unwrap().take();
+    match sender {
+        Some(tx) => {
+            let _ = tx.send(());
+            Ok(())
+        }
+        None => Err("Library server is not running".to_string()),
+    }
+}
+
+// ============ Client ============
+
+/// Songs another instance's library server shares
+pub fn fetch_library(server_url: &str, token: &str) -> Result<Vec<SharedSong>, AppError> {
+    let url = format!("{}/library", server_url.trim_end_matches('/'));
+    let body = ureq::get(&url)
+        .timeout(Duration::from_secs(30))
+        .set("Authorization", &format!("Bearer {}", token))
+        .call()
+        .map_err(|e| match e {
+            ureq::Error::Status(401, _) => {
+                AppError::Unauthorized("Library server rejected the token".to_string())
+            }
+            e => AppError::Network(format!("Failed to reach library server: {}", e)),
+        })?
+        .into_string()
+        .map_err(|e| AppError::Network(format!("Failed to read song list: {}", e)))?;
+    serde_json::from_str(&body).map_err(|e| AppError::Network(format!("Invalid song list: {}", e)))
+}
+
+pub fn song_url(server_url: &str, hash: &str) -> String {
+    format!("{}/library/{}", server_url.trim_end_matches('/'), hash)
+}
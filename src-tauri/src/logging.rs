@@ -0,0 +1,185 @@
+// File logging with size-based rotation and a runtime-adjustable level
+// wwm-overlay.log is the current file; older ones are wwm-overlay.1.log (newest) .. .N.log
+
+use serde::Serialize;
+use simplelog::{ConfigBuilder, LevelFilter, WriteLogger};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const MAX_LOG_BYTES: u64 = 5 * 1024 * 1024; // Rotate after 5MB
+const DEFAULT_KEEP_FILES: usize = 5;
+
+pub fn log_dir() -> PathBuf {
+    std::env::current_exe()
+        .ok()
+        .and_then(|p| p.parent().map(|d| d.to_path_buf()))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+pub fn log_path() -> PathBuf {
+    log_dir().join("wwm-overlay.log")
+}
+
+fn rotated_path(index: usize) -> PathBuf {
+    log_dir().join(format!("wwm-overlay.{}.log", index))
+}
+
+/// Current log file first, then rotated files newest to oldest
+pub fn log_files() -> Vec<PathBuf> {
+    std::iter::once(log_path())
+        .chain((1..=keep_files()).map(rotated_path))
+        .filter(|p| p.exists())
+        .collect()
+}
+
+fn keep_files() -> usize {
+    crate::load_config()
+        .get("log_keep_files")
+        .and_then(|v| v.as_u64())
+        .map(|n| n.clamp(1, 50) as usize)
+        .unwrap_or(DEFAULT_KEEP_FILES)
+}
+
+/// Shift wwm-overlay.log -> .1.log -> .2.log ..., dropping the oldest
+fn rotate(path: &Path, keep: usize) {
+    let _ = std::fs::remove_file(rotated_path(keep));
+    for i in (1..keep).rev() {
+        let _ = std::fs::rename(rotated_path(i), rotated_path(i + 1));
+    }
+    let _ = std::fs::rename(path, rotated_path(1));
+}
+
+/// File writer that rotates once the file grows past MAX_LOG_BYTES
+struct RotatingWriter {
+    path: PathBuf,
+    file: Option<File>,
+    written: u64,
+    keep: usize,
+}
+
+impl Write for RotatingWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.written + buf.len() as u64 > MAX_LOG_BYTES {
+            self.file = None; // Close before renaming (required on Windows)
+            rotate(&self.path, self.keep);
+            self.file = File::create(&self.path).ok();
+            self.written = 0;
+        }
+        match self.file.as_mut() {
+            Some(file) => {
+                let n = file.write(buf)?;
+                self.written += n as u64;
+                Ok(n)
+            }
+            None => Ok(buf.len()),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self.file.as_mut() {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+pub fn parse_level(level: &str) -> Option<LevelFilter> {
+    match level.to_lowercase().as_str() {
+        "off" => Some(LevelFilter::Off),
+        "error" => Some(LevelFilter::Error),
+        "warn" | "warning" => Some(LevelFilter::Warn),
+        "info" => Some(LevelFilter::Info),
+        "debug" => Some(LevelFilter::Debug),
+        "trace" => Some(LevelFilter::Trace),
+        _ => None,
+    }
+}
+
+fn saved_level() -> LevelFilter {
+    crate::load_config()
+        .get("log_level")
+        .and_then(|v| v.as_str())
+        .and_then(parse_level)
+        .unwrap_or(LevelFilter::Info)
+}
+
+lazy_static::lazy_static! {
+    static ref CURRENT_LEVEL: Mutex<LevelFilter> = Mutex::new(LevelFilter::Info);
+}
+
+/// Start logging. The previous session's log is rotated instead of overwritten.
+pub fn init() {
+    let path = log_path();
+    let keep = keep_files();
+    if path.exists() {
+        rotate(&path, keep);
+    }
+
+    let config = ConfigBuilder::new().set_time_format_rfc3339().build();
+
+    if let Ok(file) = File::create(&path) {
+        let writer = RotatingWriter {
+            path,
+            file: Some(file),
+            written: 0,
+            keep,
+        };
+        // Logger accepts everything; log::set_max_level does the runtime filtering
+        if WriteLogger::init(LevelFilter::Trace, config, writer).is_ok() {
+            set_level(saved_level());
+            log::info!("=== WWM Overlay Started ===");
+        }
+    }
+}
+
+pub fn set_level(level: LevelFilter) {
+    log::set_max_level(level);
+    *CURRENT_LEVEL.lock().unwrap() = level;
+}
+
+pub fn get_level() -> LevelFilter {
+    *CURRENT_LEVEL.lock().unwrap()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LogLine {
+    pub level: String, // "ERROR", "WARN", "INFO", "DEBUG", "TRACE"
+    pub text: String,
+}
+
+/// Level tag simplelog writes as "<time> [LEVEL] message"
+fn line_level(line: &str) -> Option<log::Level> {
+    let start = line.find('[')?;
+    let end = start + line[start..].find(']')?;
+    line[start + 1..end].trim().parse().ok()
+}
+
+/// Last `count` entries at or above `min_level`, oldest first.
+/// Lines without a level tag (multi-line messages) inherit the previous entry's level.
+pub fn tail(count: usize, min_level: LevelFilter) -> Result<Vec<LogLine>, String> {
+    let mut files: Vec<PathBuf> = log_files().into_iter().take(2).collect();
+    files.reverse(); // Previous rotation first so a fresh file still has history
+
+    let mut lines = Vec::new();
+    for path in files {
+        let content =
+            std::fs::read(&path).map_err(|e| format!("Failed to read log file: {}", e))?;
+        let mut level = log::Level::Info;
+        for text in String::from_utf8_lossy(&content).lines() {
+            if let Some(parsed) = line_level(text) {
+                level = parsed;
+            }
+            if level <= min_level {
+                lines.push(LogLine {
+                    level: level.to_string(),
+                    text: text.to_string(),
+                });
+            }
+        }
+    }
+
+    let skip = lines.len().saturating_sub(count);
+    Ok(lines.split_off(skip))
+}
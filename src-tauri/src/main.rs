@@ -1,9 +1,6 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use log::info;
 use rayon::prelude::*;
-use simplelog::{ConfigBuilder, LevelFilter, WriteLogger};
-use std::fs::File;
 use std::sync::{Arc, Mutex};
 
 /// Log macro that prints to console AND logs to file
@@ -23,31 +20,23 @@ macro_rules! app_error {
     }};
 }
 
-fn init_logger() {
-    let log_path = std::env::current_exe()
-        .ok()
-        .and_then(|p| p.parent().map(|d| d.join("wwm-overlay.log")))
-        .unwrap_or_else(|| std::path::PathBuf::from("wwm-overlay.log"));
-
-    let config = ConfigBuilder::new().set_time_format_rfc3339().build();
-
-    if let Ok(file) = File::create(&log_path) {
-        let _ = WriteLogger::init(LevelFilter::Info, config, file);
-        info!("=== WWM Overlay Started ===");
-    }
-}
 use serde::{Deserialize, Serialize};
 use std::thread;
-use tauri::{AppHandle, Emitter, State, Window};
+use tauri::{AppHandle, Emitter, Manager, State, Window};
+#[cfg(target_os = "windows")]
 use windows::Win32::Foundation::LPARAM;
+#[cfg(target_os = "windows")]
 use windows::Win32::System::Threading::{GetCurrentProcess, SetPriorityClass, HIGH_PRIORITY_CLASS};
+#[cfg(target_os = "windows")]
 use windows::Win32::UI::Input::KeyboardAndMouse::{RegisterHotKey, MOD_NOREPEAT, VK_END};
+#[cfg(target_os = "windows")]
 use windows::Win32::UI::WindowsAndMessaging::{
     CallNextHookEx, DispatchMessageW, GetMessageW, SetWindowsHookExW, TranslateMessage, HHOOK,
-    KBDLLHOOKSTRUCT, MSG, WH_KEYBOARD_LL, WM_HOTKEY, WM_KEYDOWN, WM_SYSKEYDOWN,
+    KBDLLHOOKSTRUCT, LLKHF_INJECTED, MSG, WH_KEYBOARD_LL, WM_HOTKEY, WM_KEYDOWN, WM_SYSKEYDOWN,
 };
 
 // Global app handle for low-level hook callback
+#[cfg(target_os = "windows")]
 static mut GLOBAL_APP_HANDLE: Option<AppHandle> = None;
 
 // Global album path (None = default to exe_dir/album)
@@ -64,6 +53,14 @@ pub struct KeyBindings {
     pub mode_prev: String,    // Default: "["
     pub mode_next: String,    // Default: "]"
     pub toggle_mini: String,  // Default: "Insert"
+    #[serde(default)]
+    pub toggle_input_mode: String, // PostMessage <-> SendInput; unbound by default
+    #[serde(default)]
+    pub opacity_up: String, // Overlay opacity +10%; unbound by default
+    #[serde(default)]
+    pub opacity_down: String, // Overlay opacity -10%; unbound by default
+    #[serde(default)]
+    pub cycle_layout: String, // Full -> compact -> bar only; unbound by default
 }
 
 impl Default for KeyBindings {
@@ -76,6 +73,10 @@ impl Default for KeyBindings {
             mode_prev: "[".to_string(),
             mode_next: "]".to_string(),
             toggle_mini: "Insert".to_string(),
+            toggle_input_mode: String::new(),
+            opacity_up: String::new(),
+            opacity_down: String::new(),
+            cycle_layout: String::new(),
         }
     }
 }
@@ -264,88 +265,161 @@ fn save_album_path(path: Option<&str>) {
     save_config(&config);
 }
 
+fn load_saved_max_polyphony() {
+    let config = load_config();
+    if let Some(max) = config.get("max_polyphony").and_then(|v| v.as_u64()) {
+        midi::set_max_polyphony(max.min(u8::MAX as u64) as u8);
+    }
+}
+
+fn load_saved_velocity_emphasis() {
+    let config = load_config();
+    if let Some(emphasis) = config
+        .get("velocity_emphasis")
+        .and_then(|v| serde_json::from_value::<midi::VelocityEmphasis>(v.clone()).ok())
+    {
+        midi::set_velocity_emphasis(emphasis);
+    }
+}
+
+fn load_saved_metronome() {
+    let config = load_config();
+    if let Some(settings) = config
+        .get("metronome")
+        .and_then(|v| serde_json::from_value::<metronome::MetronomeSettings>(v.clone()).ok())
+    {
+        metronome::set_settings(settings);
+    }
+}
+
+fn load_saved_note_event_batch() {
+    let config = load_config();
+    if let Some(ms) = config.get("note_event_batch_ms").and_then(|v| v.as_u64()) {
+        player::set_note_event_batch_ms(ms);
+    }
+}
+
+fn load_saved_auto_octave_shift() {
+    let config = load_config();
+    if let Some(enabled) = config.get("auto_octave_shift").and_then(|v| v.as_bool()) {
+        midi::set_auto_octave_shift(enabled);
+    }
+}
+
+fn load_saved_silent_rehearsal() {
+    let config = load_config();
+    if let Some(enabled) = config.get("silent_rehearsal").and_then(|v| v.as_bool()) {
+        player::set_silent_rehearsal(enabled);
+    }
+}
+
+fn load_saved_require_game_window() {
+    let config = load_config();
+    if let Some(enabled) = config.get("require_game_window").and_then(|v| v.as_bool()) {
+        player::set_require_game_window(enabled);
+    }
+}
+
+fn load_saved_key_duration() {
+    let config = load_config();
+    if let Some(ms) = config.get("key_duration_ms").and_then(|v| v.as_u64()) {
+        keyboard::set_key_duration(ms);
+    }
+}
+
+fn load_saved_game_profiles() {
+    let config = load_config();
+    let profiles = &config["game_profiles"];
+    let auto = profiles["auto"].as_bool().unwrap_or(true);
+    let cloud = serde_json::from_value::<profiles::InputSettings>(profiles["cloud"].clone()).ok();
+    profiles::load(auto, cloud);
+}
+
+fn save_game_profiles() {
+    let status = profiles::status();
+    let mut config = load_config();
+    config["game_profiles"] = serde_json::json!({ "auto": status.auto, "cloud": status.cloud });
+    save_config(&config);
+}
+
+fn load_saved_auto_focus() {
+    let config = load_config();
+    if let Some(mode) = config
+        .get("auto_focus")
+        .and_then(|v| serde_json::from_value::<keyboard::AutoFocus>(v.clone()).ok())
+    {
+        keyboard::set_auto_focus(mode);
+    }
+}
+
+fn load_saved_octave_splits() {
+    let config = load_config();
+    if let Some(splits) = config
+        .get("octave_splits")
+        .and_then(|v| serde_json::from_value::<midi::OctaveSplits>(v.clone()).ok())
+    {
+        midi::set_octave_splits(splits);
+    }
+}
+
 fn load_saved_note_keys() {
     let config = load_config();
-    if let Some(keys) = config.get("note_keys") {
-        let low: Vec<String> = keys["low"]
-            .as_array()
-            .map(|a| {
-                a.iter()
-                    .filter_map(|v| v.as_str().map(String::from))
-                    .collect()
-            })
-            .unwrap_or_default();
-        let mid: Vec<String> = keys["mid"]
-            .as_array()
-            .map(|a| {
-                a.iter()
-                    .filter_map(|v| v.as_str().map(String::from))
-                    .collect()
-            })
-            .unwrap_or_default();
-        let high: Vec<String> = keys["high"]
+    if let Some(sets) = config
+        .get("note_key_sets")
+        .and_then(|v| serde_json::from_value::<keyboard::NoteKeySets>(v.clone()).ok())
+    {
+        keyboard::load_note_key_sets(sets);
+        app_log!("Loaded note key bindings");
+        return;
+    }
+
+    // Migration: one set of rows ("note_keys", or the older qwertz_mode flag) was
+    // shared by both key modes, so both sets start from it
+    let rows = |name: &str| -> Vec<String> {
+        config["note_keys"][name]
             .as_array()
             .map(|a| {
                 a.iter()
                     .filter_map(|v| v.as_str().map(String::from))
                     .collect()
             })
-            .unwrap_or_default();
-
-        if !low.is_empty() && !mid.is_empty() && !high.is_empty() {
-            keyboard::set_note_key_bindings(low.clone(), mid.clone(), high.clone());
-            app_log!("Loaded note key bindings");
-        }
-    }
-    // Migration: if old qwertz_mode exists, migrate to new format
-    else if let Some(enabled) = config["qwertz_mode"].as_bool() {
-        if enabled {
-            // QWERTZ: swap Y and Z
-            let low = vec![
-                "y".to_string(),
-                "x".to_string(),
-                "c".to_string(),
-                "v".to_string(),
-                "b".to_string(),
-                "n".to_string(),
-                "m".to_string(),
-            ];
-            let mid = vec![
-                "a".to_string(),
-                "s".to_string(),
-                "d".to_string(),
-                "f".to_string(),
-                "g".to_string(),
-                "h".to_string(),
-                "j".to_string(),
-            ];
-            let high = vec![
-                "q".to_string(),
-                "w".to_string(),
-                "e".to_string(),
-                "r".to_string(),
-                "t".to_string(),
-                "z".to_string(),
-                "u".to_string(),
-            ];
-            keyboard::set_note_key_bindings(low.clone(), mid.clone(), high.clone());
-            save_note_keys(&low, &mid, &high);
-            app_log!("Migrated from qwertz_mode to note_keys");
-        }
-    }
-}
-
-fn save_note_keys(low: &[String], mid: &[String], high: &[String]) {
-    let mut config = load_config();
-    config["note_keys"] = serde_json::json!({
-        "low": low,
-        "mid": mid,
-        "high": high
+            .unwrap_or_default()
+    };
+    let set = if config.get("note_keys").is_some() {
+        let (low, mid, high) = (rows("low"), rows("mid"), rows("high"));
+        if low.is_empty() || mid.is_empty() || high.is_empty() {
+            return;
+        }
+        keyboard::NoteKeySet {
+            low,
+            mid,
+            high,
+            ..Default::default()
+        }
+    } else if config["qwertz_mode"].as_bool() == Some(true) {
+        // QWERTZ: swap Y and Z
+        let mut set = keyboard::NoteKeySet::default();
+        set.low[0] = "y".to_string();
+        set.high[5] = "z".to_string();
+        set
+    } else {
+        return;
+    };
+    keyboard::load_note_key_sets(keyboard::NoteKeySets {
+        keys21: set.clone(),
+        keys36: set,
     });
-    // Remove old format if present
-    if config.get("qwertz_mode").is_some() {
-        config.as_object_mut().unwrap().remove("qwertz_mode");
-    }
+    save_note_key_sets();
+    app_log!("Migrated note key bindings to per key mode sets");
+}
+
+fn save_note_key_sets() {
+    let mut config = load_config();
+    config["note_key_sets"] = serde_json::json!(keyboard::get_note_key_sets());
+    // Remove old formats if present
+    let object = config.as_object_mut().unwrap();
+    object.remove("note_keys");
+    object.remove("qwertz_mode");
     save_config(&config);
 }
 
@@ -400,12 +474,47 @@ fn get_album_folder() -> Result<std::path::PathBuf, String> {
     Ok(exe_dir.join("album"))
 }
 
+mod band;
+mod band_session;
+mod crash;
+mod data_backups;
+mod data_watch;
 mod discovery;
+mod downloads;
+mod dropped;
+mod error;
+mod favorites;
+mod file_association;
+#[cfg(test)]
+mod fuzz;
+mod health;
+mod history;
+#[cfg(target_os = "linux")]
+mod input_linux;
+#[cfg(target_os = "macos")]
+mod input_macos;
+mod key_capture;
+mod key_presets;
 mod keyboard;
+mod library_server;
+mod logging;
+mod metronome;
 mod midi;
+mod midi_edit;
 mod midi_input;
+mod overlay;
+mod paths;
+mod performance;
+mod player;
+mod playlists;
+mod profiles;
+mod queue;
+mod single_instance;
+mod song_meta;
+mod song_requests;
 mod state;
 
+use error::AppError;
 use state::{AppState, PlaybackState, VisualizerNote};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -415,10 +524,19 @@ struct MidiFile {
     duration: f64,
     bpm: u16,
     note_density: f32,
+    difficulty: u8,
+    tempo: midi::TempoStats,
+    repaired: bool,
+    note_count: u32,
+    track_count: u16,    // Tracks with notes; more than one means "pick a part"
+    multi_channel: bool, // Notes on more than one channel
     hash: String,
     size: u64,
 }
 
+// Bump when cached fields change so stale entries are re-parsed
+const METADATA_CACHE_VERSION: u8 = 5;
+
 #[derive(Debug, Serialize, Deserialize)]
 struct MetadataCache {
     version: u8,
@@ -432,9 +550,23 @@ struct CachedMetadata {
     bpm: u16,
     note_density: f32,
     #[serde(default)]
+    difficulty: u8,
+    #[serde(default)]
+    tempo: midi::TempoStats,
+    #[serde(default)]
+    repaired: bool,
+    #[serde(default)]
+    note_count: u32,
+    #[serde(default)]
+    track_count: u16,
+    #[serde(default)]
+    multi_channel: bool,
+    #[serde(default)]
     hash: String,
     #[serde(default)]
     size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tracks: Option<Vec<midi::MidiTrackInfo>>, // Filled lazily by get_midi_tracks
 }
 
 fn get_metadata_cache_path() -> Result<std::path::PathBuf, String> {
@@ -447,7 +579,7 @@ fn load_metadata_cache() -> MetadataCache {
         if cache_path.exists() {
             if let Ok(content) = std::fs::read_to_string(&cache_path) {
                 if let Ok(cache) = serde_json::from_str::<MetadataCache>(&content) {
-                    if cache.version == 1 {
+                    if cache.version == METADATA_CACHE_VERSION {
                         return cache;
                     }
                 }
@@ -455,7 +587,7 @@ fn load_metadata_cache() -> MetadataCache {
         }
     }
     MetadataCache {
-        version: 1,
+        version: METADATA_CACHE_VERSION,
         files: std::collections::HashMap::new(),
     }
 }
@@ -469,7 +601,7 @@ fn save_metadata_cache(cache: &MetadataCache) {
 }
 
 fn get_file_mtime(path: &std::path::Path) -> u64 {
-    path.metadata()
+    std::fs::metadata(paths::long(path))
         .and_then(|m| m.modified())
         .ok()
         .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
@@ -480,7 +612,7 @@ fn get_file_mtime(path: &std::path::Path) -> u64 {
 // Compute a simple hash of file content for identification
 fn compute_file_hash(path: &std::path::Path) -> Option<String> {
     use std::io::Read;
-    let mut file = std::fs::File::open(path).ok()?;
+    let mut file = std::fs::File::open(paths::long(path)).ok()?;
 
     // Read first 8KB + file size for quick but reliable hash
     let mut buffer = [0u8; 8192];
@@ -498,16 +630,24 @@ fn compute_file_hash(path: &std::path::Path) -> Option<String> {
 }
 
 // Hotkey IDs
+#[cfg(target_os = "windows")]
 const HOTKEY_PAUSE_RESUME: i32 = 1;
+#[cfg(target_os = "windows")]
 const HOTKEY_STOP_END: i32 = 2;
+#[cfg(target_os = "windows")]
 const HOTKEY_STOP_F12: i32 = 3;
+#[cfg(target_os = "windows")]
 const HOTKEY_PREV_F10: i32 = 4;
+#[cfg(target_os = "windows")]
 const HOTKEY_NEXT_F11: i32 = 5;
 
+// Registration results from startup, kept for diagnostics
+static HOTKEY_STATUS: Mutex<Vec<(String, bool)>> = Mutex::new(Vec::new());
+
 // Load MIDI files from album folder with metadata caching
 // Note: For large libraries (1000+ files), use load_midi_files_streaming instead
 #[tauri::command]
-async fn load_midi_files() -> Result<Vec<MidiFile>, String> {
+async fn load_midi_files() -> Result<Vec<MidiFile>, AppError> {
     let album_path = get_album_folder()?;
     let mut files = Vec::new();
 
@@ -519,73 +659,80 @@ async fn load_midi_files() -> Result<Vec<MidiFile>, String> {
     let mut cache = load_metadata_cache();
     let mut cache_modified = false;
 
-    let entries = std::fs::read_dir(&album_path).map_err(|e| e.to_string())?;
+    let entries = paths::list_midi(&album_path).map_err(|e| e.to_string())?;
 
-    for entry in entries {
-        if let Ok(entry) = entry {
-            let path = entry.path();
-            if path.extension().and_then(|s| s.to_str()) != Some("mid") {
-                continue;
-            }
+    for path in entries {
+        let path_str = path.to_string_lossy().to_string();
+        let name = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("Unknown")
+            .to_string();
+        let mtime = get_file_mtime(&path);
 
-            let path_str = path.to_string_lossy().to_string();
-            let name = path
-                .file_stem()
-                .and_then(|s| s.to_str())
-                .unwrap_or("Unknown")
-                .to_string();
-            let mtime = get_file_mtime(&path);
-
-            // Check cache - now includes hash and size
-            if let Some(cached) = cache.files.get(&path_str) {
-                if cached.mtime == mtime && !cached.hash.is_empty() {
-                    // Full cache hit
-                    files.push(MidiFile {
-                        name,
-                        path: path_str,
-                        duration: cached.duration,
-                        bpm: cached.bpm,
-                        note_density: cached.note_density,
-                        hash: cached.hash.clone(),
-                        size: cached.size,
-                    });
-                    continue;
-                }
+        // Check cache - now includes hash and size
+        if let Some(cached) = cache.files.get(&path_str) {
+            if cached.mtime == mtime && !cached.hash.is_empty() {
+                // Full cache hit
+                files.push(MidiFile {
+                    name,
+                    path: path_str,
+                    duration: cached.duration,
+                    bpm: cached.bpm,
+                    note_density: cached.note_density,
+                    difficulty: cached.difficulty,
+                    tempo: cached.tempo,
+                    repaired: cached.repaired,
+                    note_count: cached.note_count,
+                    track_count: cached.track_count,
+                    multi_channel: cached.multi_channel,
+                    hash: cached.hash.clone(),
+                    size: cached.size,
+                });
+                continue;
             }
+        }
 
-            // Cache miss or stale - parse and compute
-            let meta = midi::get_midi_metadata(&path_str).unwrap_or(midi::MidiMetadata {
-                duration: 0.0,
-                bpm: 120,
-                note_count: 0,
-                note_density: 0.0,
-            });
-            let file_size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
-            let file_hash = compute_file_hash(&path).unwrap_or_else(|| format!("{:x}", file_size));
-
-            cache.files.insert(
-                path_str.clone(),
-                CachedMetadata {
-                    mtime,
-                    duration: meta.duration,
-                    bpm: meta.bpm,
-                    note_density: meta.note_density,
-                    hash: file_hash.clone(),
-                    size: file_size,
-                },
-            );
-            cache_modified = true;
+        // Cache miss or stale - parse and compute
+        let meta = midi::get_midi_metadata(&path_str).unwrap_or_default();
+        let file_size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        let file_hash = compute_file_hash(&path).unwrap_or_else(|| format!("{:x}", file_size));
 
-            files.push(MidiFile {
-                name,
-                path: path_str,
+        cache.files.insert(
+            path_str.clone(),
+            CachedMetadata {
+                mtime,
                 duration: meta.duration,
                 bpm: meta.bpm,
                 note_density: meta.note_density,
-                hash: file_hash,
+                difficulty: meta.difficulty,
+                tempo: meta.tempo,
+                repaired: meta.repaired,
+                note_count: meta.note_count,
+                track_count: meta.track_count,
+                multi_channel: meta.multi_channel,
+                hash: file_hash.clone(),
                 size: file_size,
-            });
-        }
+                tracks: None,
+            },
+        );
+        cache_modified = true;
+
+        files.push(MidiFile {
+            name,
+            path: path_str,
+            duration: meta.duration,
+            bpm: meta.bpm,
+            note_density: meta.note_density,
+            difficulty: meta.difficulty,
+            tempo: meta.tempo,
+            repaired: meta.repaired,
+            note_count: meta.note_count,
+            track_count: meta.track_count,
+            multi_channel: meta.multi_channel,
+            hash: file_hash,
+            size: file_size,
+        });
     }
 
     // Save cache if modified
@@ -615,7 +762,7 @@ struct LibraryInfo {
 
 // Quick count of MIDI files and check cache status
 #[tauri::command]
-async fn get_library_info() -> Result<LibraryInfo, String> {
+async fn get_library_info() -> Result<LibraryInfo, AppError> {
     let album_path = get_album_folder()?;
     if !album_path.exists() {
         return Ok(LibraryInfo {
@@ -626,12 +773,7 @@ async fn get_library_info() -> Result<LibraryInfo, String> {
     }
 
     // Get all midi files
-    let files: Vec<_> = std::fs::read_dir(&album_path)
-        .map_err(|e| e.to_string())?
-        .filter_map(|e| e.ok())
-        .map(|e| e.path())
-        .filter(|p| p.extension().and_then(|s| s.to_str()) == Some("mid"))
-        .collect();
+    let files = paths::list_midi(&album_path).map_err(|e| e.to_string())?;
 
     let total_files = files.len();
 
@@ -662,21 +804,346 @@ async fn get_library_info() -> Result<LibraryInfo, String> {
 
 // Quick count of MIDI files without loading metadata (legacy)
 #[tauri::command]
-async fn count_midi_files() -> Result<usize, String> {
+async fn count_midi_files() -> Result<usize, AppError> {
     let album_path = get_album_folder()?;
     if !album_path.exists() {
         return Ok(0);
     }
 
-    let count = std::fs::read_dir(&album_path)
+    let count = paths::list_midi(&album_path)
         .map_err(|e| e.to_string())?
-        .filter_map(|e| e.ok())
-        .filter(|e| e.path().extension().and_then(|s| s.to_str()) == Some("mid"))
-        .count();
+        .len();
 
     Ok(count)
 }
 
+// Score how well a song name matches a search query (None = no match)
+// Exact > prefix > substring > all words present; shorter names win ties
+fn search_score(name: &str, query: &str) -> Option<u32> {
+    let normalize = |s: &str| -> String {
+        s.to_lowercase()
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+            .collect::<String>()
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let name = normalize(name);
+    let query = normalize(query);
+    if query.is_empty() {
+        return None;
+    }
+
+    let base = if name == query {
+        1000
+    } else if name.starts_with(&query) {
+        800
+    } else if name.contains(&query) {
+        600
+    } else if query.split(' ').all(|word| name.contains(word)) {
+        400
+    } else {
+        return None;
+    };
+
+    let extra_len = name.chars().count().saturating_sub(query.chars().count()) as u32;
+    Some(base - extra_len.min(199))
+}
+
+// Search the album by name, best matches first (uses metadata cache when fresh)
+fn search_album(query: &str, limit: usize) -> Result<Vec<MidiFile>, String> {
+    let album_path = get_album_folder()?;
+    if !album_path.exists() {
+        return Ok(vec![]);
+    }
+
+    let cache = load_metadata_cache();
+    let mut matches: Vec<(u32, MidiFile)> = Vec::new();
+
+    for path in paths::list_midi(&album_path).map_err(|e| e.to_string())? {
+        let name = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("Unknown")
+            .to_string();
+        let score = match search_score(&name, query) {
+            Some(score) => score,
+            None => continue,
+        };
+
+        let path_str = path.to_string_lossy().to_string();
+        let file = match cache.files.get(&path_str) {
+            Some(cached) if cached.mtime == get_file_mtime(&path) && !cached.hash.is_empty() => {
+                MidiFile {
+                    name,
+                    path: path_str,
+                    duration: cached.duration,
+                    bpm: cached.bpm,
+                    note_density: cached.note_density,
+                    difficulty: cached.difficulty,
+                    tempo: cached.tempo,
+                    repaired: cached.repaired,
+                    note_count: cached.note_count,
+                    track_count: cached.track_count,
+                    multi_channel: cached.multi_channel,
+                    hash: cached.hash.clone(),
+                    size: cached.size,
+                }
+            }
+            _ => midi_file_info(&path),
+        };
+        matches.push((score, file));
+    }
+
+    matches.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.name.cmp(&b.1.name)));
+    Ok(matches.into_iter().take(limit).map(|(_, f)| f).collect())
+}
+
+#[tauri::command]
+async fn search_library(query: String, limit: Option<usize>) -> Result<Vec<MidiFile>, AppError> {
+    Ok(search_album(&query, limit.unwrap_or(50))?)
+}
+
+// Filters for query_library; unset fields don't filter
+#[derive(Debug, Clone, Default, Deserialize)]
+struct LibraryFilters {
+    query: Option<String>, // Case-insensitive substring of the name
+    min_duration: Option<f64>,
+    max_duration: Option<f64>,
+    min_bpm: Option<u16>,
+    max_bpm: Option<u16>,
+    max_difficulty: Option<u8>,
+    min_rating: Option<u8>,
+    tag: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+struct LibraryPage {
+    total: usize, // Matches before paging
+    offset: usize,
+    files: Vec<MidiFile>,
+}
+
+// Sort and filter the library in Rust and return one page
+// sort_by: "name", "duration", "bpm", "density", "difficulty", "date_added", "rating"
+#[tauri::command]
+async fn query_library(
+    sort_by: Option<String>,
+    descending: Option<bool>,
+    filters: Option<LibraryFilters>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> Result<LibraryPage, AppError> {
+    let filters = filters.unwrap_or_default();
+    let offset = offset.unwrap_or(0);
+    let limit = limit.unwrap_or(100);
+    let meta = song_meta::load_all();
+    let query = filters.query.as_ref().map(|q| q.to_lowercase());
+    let tag = filters.tag.as_ref().map(|t| t.to_lowercase());
+    let rating_of = |file: &MidiFile| meta.get(&file.hash).and_then(|m| m.rating);
+
+    let mut files: Vec<MidiFile> = load_midi_files()
+        .await?
+        .into_iter()
+        .filter(|f| {
+            query
+                .as_ref()
+                .is_none_or(|q| f.name.to_lowercase().contains(q))
+                && filters.min_duration.is_none_or(|d| f.duration >= d)
+                && filters.max_duration.is_none_or(|d| f.duration <= d)
+                && filters.min_bpm.is_none_or(|b| f.bpm >= b)
+                && filters.max_bpm.is_none_or(|b| f.bpm <= b)
+                && filters.max_difficulty.is_none_or(|d| f.difficulty <= d)
+                && filters
+                    .min_rating
+                    .is_none_or(|r| rating_of(f).is_some_and(|rating| rating >= r))
+                && tag.as_ref().is_none_or(|t| {
+                    meta.get(&f.hash)
+                        .is_some_and(|m| m.tags.iter().any(|tag| tag.to_lowercase() == *t))
+                })
+        })
+        .collect();
+
+    match sort_by.as_deref().unwrap_or("name") {
+        "duration" => files.sort_by(|a, b| a.duration.total_cmp(&b.duration)),
+        "bpm" => files.sort_by_key(|f| f.bpm),
+        "density" => files.sort_by(|a, b| a.note_density.total_cmp(&b.note_density)),
+        "difficulty" => files.sort_by_key(|f| f.difficulty),
+        "date_added" => files.sort_by_cached_key(|f| get_file_mtime(std::path::Path::new(&f.path))),
+        "rating" => files.sort_by_key(|f| rating_of(f).unwrap_or(0)),
+        "name" => files.sort_by_cached_key(|f| f.name.to_lowercase()),
+        other => {
+            return Err(AppError::InvalidInput(format!(
+                "Unknown sort field: {}",
+                other
+            )))
+        }
+    }
+    if descending.unwrap_or(false) {
+        files.reverse();
+    }
+
+    let total = files.len();
+    let files = files.into_iter().skip(offset).take(limit).collect();
+    Ok(LibraryPage {
+        total,
+        offset,
+        files,
+    })
+}
+
+#[tauri::command]
+async fn get_song_meta(hash: String) -> Result<song_meta::SongMeta, AppError> {
+    Ok(song_meta::get(&hash))
+}
+
+#[tauri::command]
+async fn set_song_rating(
+    hash: String,
+    rating: Option<u8>,
+) -> Result<song_meta::SongMeta, AppError> {
+    Ok(song_meta::set_rating(&hash, rating)?)
+}
+
+// Per-song playback options; applied immediately if it's the loaded song
+#[tauri::command]
+async fn set_song_settings(
+    hash: String,
+    settings: midi::SongSettings,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<song_meta::SongMeta, AppError> {
+    let meta = song_meta::set_settings(&hash, settings)?;
+    state
+        .lock()
+        .unwrap()
+        .update_song_settings_live(&hash, &meta.settings);
+    Ok(meta)
+}
+
+// Per-track semitone offset for the loaded song (e.g. bass up an octave), applied live
+#[tauri::command]
+async fn set_track_transpose(
+    track_id: usize,
+    semitones: i32,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<(), AppError> {
+    state
+        .lock()
+        .unwrap()
+        .set_track_transpose(track_id, semitones)?;
+    println!("Track {} transpose set to: {}", track_id, semitones);
+    Ok(())
+}
+
+// Tracks of the loaded song to leave out of solo playback (e.g. percussion), applied live
+#[tauri::command]
+async fn set_muted_tracks(
+    track_ids: Vec<usize>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<(), AppError> {
+    state.lock().unwrap().set_muted_tracks(track_ids.clone())?;
+    println!("Muted tracks set to: {:?}", track_ids);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_song_note(hash: String) -> Result<String, AppError> {
+    Ok(song_meta::get(&hash).note)
+}
+
+#[tauri::command]
+async fn set_song_note(hash: String, note: String) -> Result<song_meta::SongMeta, AppError> {
+    Ok(song_meta::set_note(&hash, &note)?)
+}
+
+#[tauri::command]
+async fn set_song_tags(hash: String, tags: Vec<String>) -> Result<song_meta::SongMeta, AppError> {
+    Ok(song_meta::set_tags(&hash, tags)?)
+}
+
+/// One row of the exported library catalog
+#[derive(Debug, Clone, Serialize)]
+struct CatalogEntry {
+    name: String,
+    duration: f64,
+    bpm: u16,
+    note_density: f32,
+    tags: Vec<String>,
+    rating: Option<u8>,
+    note: String,
+    play_count: u32,
+    hash: String,
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// Dump the library (without the MIDI files) as "csv" or "json"
+#[tauri::command]
+async fn export_catalog(path: String, format: String) -> Result<usize, AppError> {
+    let files = load_midi_files().await?;
+    let meta = song_meta::load_all();
+    let play_counts = history::play_counts();
+
+    let entries: Vec<CatalogEntry> = files
+        .into_iter()
+        .map(|file| {
+            let song = meta.get(&file.hash).cloned().unwrap_or_default();
+            CatalogEntry {
+                play_count: play_counts.get(&file.hash).copied().unwrap_or(0),
+                name: file.name,
+                duration: file.duration,
+                bpm: file.bpm,
+                note_density: file.note_density,
+                tags: song.tags,
+                rating: song.rating,
+                note: song.note,
+                hash: file.hash,
+            }
+        })
+        .collect();
+
+    let content = match format.to_lowercase().as_str() {
+        "json" => serde_json::to_string_pretty(&entries)
+            .map_err(|e| format!("Failed to serialize catalog: {}", e))?,
+        "csv" => {
+            let mut csv =
+                String::from("name,duration,bpm,note_density,tags,rating,note,play_count,hash\n");
+            for entry in &entries {
+                csv.push_str(&format!(
+                    "{},{:.1},{},{:.2},{},{},{},{},{}\n",
+                    csv_field(&entry.name),
+                    entry.duration,
+                    entry.bpm,
+                    entry.note_density,
+                    csv_field(&entry.tags.join("; ")),
+                    entry.rating.map(|r| r.to_string()).unwrap_or_default(),
+                    csv_field(&entry.note),
+                    entry.play_count,
+                    entry.hash
+                ));
+            }
+            csv
+        }
+        other => {
+            return Err(AppError::InvalidInput(format!(
+                "Unsupported catalog format: {}",
+                other
+            )))
+        }
+    };
+
+    std::fs::write(&path, content).map_err(|e| AppError::io("write catalog", e))?;
+    app_log!("[LIBRARY] Exported {} songs to {}", entries.len(), path);
+    Ok(entries.len())
+}
+
 // Load MIDI files with streaming progress events (for large libraries)
 // offset: skip first N files (for pagination)
 // limit: max files to load (0 = all)
@@ -685,7 +1152,7 @@ async fn load_midi_files_streaming(
     window: Window,
     offset: Option<usize>,
     limit: Option<usize>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     let album_path = get_album_folder()?;
     let offset = offset.unwrap_or(0);
     let limit = limit.unwrap_or(0); // 0 means no limit
@@ -707,12 +1174,8 @@ async fn load_midi_files_streaming(
     let window_clone = window.clone();
     std::thread::spawn(move || {
         // First pass: quickly collect all .mid file paths
-        let all_entries: Vec<_> = match std::fs::read_dir(&album_path) {
-            Ok(dir) => dir
-                .filter_map(|e| e.ok())
-                .map(|e| e.path())
-                .filter(|p| p.extension().and_then(|s| s.to_str()) == Some("mid"))
-                .collect(),
+        let all_entries = match paths::list_midi(&album_path) {
+            Ok(files) => files,
             Err(_) => {
                 let _ = window_clone.emit(
                     "midi-load-progress",
@@ -796,6 +1259,12 @@ async fn load_midi_files_streaming(
                             duration: cached.duration,
                             bpm: cached.bpm,
                             note_density: cached.note_density,
+                            difficulty: cached.difficulty,
+                            tempo: cached.tempo,
+                            repaired: cached.repaired,
+                            note_count: cached.note_count,
+                            track_count: cached.track_count,
+                            multi_channel: cached.multi_channel,
                             hash: cached.hash.clone(),
                             size: cached.size,
                         });
@@ -807,15 +1276,10 @@ async fn load_midi_files_streaming(
             }
 
             // Step 2: Parse uncached files in parallel (no locking needed)
-            let parsed_files: Vec<(MidiFile, String, u64, f64, u16, f32, String, u64)> = uncached
+            let parsed_files: Vec<(MidiFile, String, CachedMetadata)> = uncached
                 .par_iter()
                 .filter_map(|(path, path_str, name, mtime)| {
-                    let meta = midi::get_midi_metadata(path_str).unwrap_or(midi::MidiMetadata {
-                        duration: 0.0,
-                        bpm: 120,
-                        note_count: 0,
-                        note_density: 0.0,
-                    });
+                    let meta = midi::get_midi_metadata(path_str).unwrap_or_default();
                     let file_size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
                     let file_hash =
                         compute_file_hash(path).unwrap_or_else(|| format!("{:x}", file_size));
@@ -827,33 +1291,38 @@ async fn load_midi_files_streaming(
                             duration: meta.duration,
                             bpm: meta.bpm,
                             note_density: meta.note_density,
+                            difficulty: meta.difficulty,
+                            tempo: meta.tempo,
+                            repaired: meta.repaired,
+                            note_count: meta.note_count,
+                            track_count: meta.track_count,
+                            multi_channel: meta.multi_channel,
                             hash: file_hash.clone(),
                             size: file_size,
                         },
                         path_str.clone(),
-                        *mtime,
-                        meta.duration,
-                        meta.bpm,
-                        meta.note_density,
-                        file_hash,
-                        file_size,
+                        CachedMetadata {
+                            mtime: *mtime,
+                            duration: meta.duration,
+                            bpm: meta.bpm,
+                            note_density: meta.note_density,
+                            difficulty: meta.difficulty,
+                            tempo: meta.tempo,
+                            repaired: meta.repaired,
+                            note_count: meta.note_count,
+                            track_count: meta.track_count,
+                            multi_channel: meta.multi_channel,
+                            hash: file_hash,
+                            size: file_size,
+                            tracks: None,
+                        },
                     ))
                 })
                 .collect();
 
             // Step 3: Update cache with newly parsed files (single-threaded)
-            for (file, path_str, mtime, duration, bpm, note_density, hash, size) in parsed_files {
-                cache.files.insert(
-                    path_str,
-                    CachedMetadata {
-                        mtime,
-                        duration,
-                        bpm,
-                        note_density,
-                        hash,
-                        size,
-                    },
-                );
+            for (file, path_str, cached) in parsed_files {
+                cache.files.insert(path_str, cached);
                 cache_modified = true;
                 cached_files.push(file);
             }
@@ -880,73 +1349,609 @@ async fn load_midi_files_streaming(
     Ok(())
 }
 
-#[tauri::command]
-async fn get_midi_tracks(path: String) -> Result<Vec<midi::MidiTrackInfo>, String> {
-    midi::get_midi_tracks(&path)
+// Lightweight library entry for huge libraries: the UI lists these and asks for
+// details (get_midi_details) only for the rows it shows
+#[derive(Debug, Clone, Serialize)]
+struct MidiFileId {
+    path: String,
+    name: String,
 }
 
 #[tauri::command]
-async fn play_midi(
-    path: String,
-    state: State<'_, Arc<Mutex<AppState>>>,
-    window: Window,
-) -> Result<(), String> {
+async fn list_midi_ids() -> Result<Vec<MidiFileId>, AppError> {
+    let album_path = get_album_folder()?;
+    if !album_path.exists() {
+        return Ok(Vec::new());
+    }
+    let entries = paths::list_midi(&album_path).map_err(|e| e.to_string())?;
+    Ok(entries
+        .into_iter()
+        .map(|p| MidiFileId {
+            name: p
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("Unknown")
+                .to_string(),
+            path: p.to_string_lossy().to_string(),
+        })
+        .collect())
+}
+
+// Parse a file for the metadata cache
+fn parse_cached_metadata(path_str: &str, mtime: u64) -> CachedMetadata {
+    let path = std::path::Path::new(path_str);
+    let meta = midi::get_midi_metadata(path_str).unwrap_or_default();
+    let file_size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    let file_hash = compute_file_hash(path).unwrap_or_else(|| format!("{:x}", file_size));
+    CachedMetadata {
+        mtime,
+        duration: meta.duration,
+        bpm: meta.bpm,
+        note_density: meta.note_density,
+        difficulty: meta.difficulty,
+        tempo: meta.tempo,
+        repaired: meta.repaired,
+        note_count: meta.note_count,
+        track_count: meta.track_count,
+        multi_channel: meta.multi_channel,
+        hash: file_hash,
+        size: file_size,
+        tracks: None,
+    }
+}
+
+fn midi_file_from_cache(path_str: &str, cached: &CachedMetadata) -> MidiFile {
+    let name = std::path::Path::new(path_str)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("Unknown")
+        .to_string();
+    MidiFile {
+        name,
+        path: path_str.to_string(),
+        duration: cached.duration,
+        bpm: cached.bpm,
+        note_density: cached.note_density,
+        difficulty: cached.difficulty,
+        tempo: cached.tempo,
+        repaired: cached.repaired,
+        note_count: cached.note_count,
+        track_count: cached.track_count,
+        multi_channel: cached.multi_channel,
+        hash: cached.hash.clone(),
+        size: cached.size,
+    }
+}
+
+// Bumped by every list_file_names call so an older hydration pass stops early
+static HYDRATION_GENERATION: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+#[derive(Clone, Serialize)]
+struct MetadataHydration {
+    files: Vec<MidiFile>,
+    hydrated: usize,
+    total: usize,
+    done: bool,
+}
+
+// Names and paths straight from the folder listing, so the list and search work right
+// away. Metadata follows through "midi-metadata-hydrated" events: cached entries in one
+// go, then the rest as they are parsed.
+#[tauri::command]
+async fn list_file_names(window: Window) -> Result<Vec<MidiFileId>, AppError> {
+    use std::sync::atomic::Ordering;
+
+    let ids = list_midi_ids().await?;
+    let generation = HYDRATION_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    let paths: Vec<String> = ids.iter().map(|id| id.path.clone()).collect();
+
+    std::thread::spawn(move || {
+        // Parsed files per event; small so the first ones show up quickly
+        const BATCH_SIZE: usize = 200;
+        let total = paths.len();
+        let mut cache = load_metadata_cache();
+        let mut cached_files = Vec::new();
+        let mut stale = Vec::new();
+        for path_str in paths {
+            let mtime = get_file_mtime(std::path::Path::new(&path_str));
+            match cache.files.get(&path_str) {
+                Some(cached) if cached.mtime == mtime && !cached.hash.is_empty() => {
+                    cached_files.push(midi_file_from_cache(&path_str, cached));
+                }
+                _ => stale.push((path_str, mtime)),
+            }
+        }
+
+        let mut hydrated = cached_files.len();
+        let _ = window.emit(
+            "midi-metadata-hydrated",
+            MetadataHydration {
+                files: cached_files,
+                hydrated,
+                total,
+                done: stale.is_empty(),
+            },
+        );
+
+        let mut cache_modified = false;
+        for batch in stale.chunks(BATCH_SIZE) {
+            if HYDRATION_GENERATION.load(Ordering::SeqCst) != generation {
+                println!("[LIBRARY] Metadata hydration superseded");
+                break;
+            }
+            let parsed: Vec<(String, CachedMetadata)> = batch
+                .par_iter()
+                .map(|(path_str, mtime)| {
+                    (path_str.clone(), parse_cached_metadata(path_str, *mtime))
+                })
+                .collect();
+            let files: Vec<MidiFile> = parsed
+                .iter()
+                .map(|(path_str, cached)| midi_file_from_cache(path_str, cached))
+                .collect();
+            for (path_str, cached) in parsed {
+                cache.files.insert(path_str, cached);
+            }
+            cache_modified = true;
+
+            hydrated += files.len();
+            let _ = window.emit(
+                "midi-metadata-hydrated",
+                MetadataHydration {
+                    files,
+                    hydrated,
+                    total,
+                    done: hydrated >= total,
+                },
+            );
+        }
+
+        if cache_modified {
+            save_metadata_cache(&cache);
+        }
+    });
+
+    Ok(ids)
+}
+
+// Full entries for some paths, from the metadata cache or parsed in parallel
+#[tauri::command]
+async fn get_midi_details(paths: Vec<String>) -> Result<Vec<MidiFile>, AppError> {
+    Ok(tokio::task::spawn_blocking(move || {
+        let mut cache = load_metadata_cache();
+        let lookups: Vec<(String, u64, bool)> = paths
+            .into_iter()
+            .map(|path_str| {
+                let mtime = get_file_mtime(std::path::Path::new(&path_str));
+                let fresh = cache
+                    .files
+                    .get(&path_str)
+                    .is_some_and(|c| c.mtime == mtime && !c.hash.is_empty());
+                (path_str, mtime, fresh)
+            })
+            .collect();
+
+        let parsed: Vec<(String, CachedMetadata)> = lookups
+            .par_iter()
+            .filter(|(path_str, _, fresh)| !fresh && std::path::Path::new(path_str).exists())
+            .map(|(path_str, mtime, _)| (path_str.clone(), parse_cached_metadata(path_str, *mtime)))
+            .collect();
+        let cache_modified = !parsed.is_empty();
+        for (path_str, cached) in parsed {
+            cache.files.insert(path_str, cached);
+        }
+
+        // Keep the requested order; missing files are left out
+        let files = lookups
+            .iter()
+            .filter_map(|(path_str, mtime, _)| {
+                let cached = cache.files.get(path_str).filter(|c| c.mtime == *mtime)?;
+                Some(midi_file_from_cache(path_str, cached))
+            })
+            .collect();
+        if cache_modified {
+            save_metadata_cache(&cache);
+        }
+        files
+    })
+    .await
+    .map_err(|e| format!("Failed to load song details: {}", e))?)
+}
+
+#[tauri::command]
+async fn get_midi_tracks(path: String) -> Result<Vec<midi::MidiTrackInfo>, AppError> {
+    // Reuse track info from the metadata cache while the file is unchanged
+    let mtime = get_file_mtime(std::path::Path::new(&path));
+    let mut cache = load_metadata_cache();
+    if let Some(cached) = cache.files.get(&path) {
+        if cached.mtime == mtime {
+            if let Some(tracks) = &cached.tracks {
+                return Ok(tracks.clone());
+            }
+        }
+    }
+
+    let tracks = midi::get_midi_tracks(&path)?;
+
+    // Only album files have a cache entry; stale entries get replaced on the next library load
+    if let Some(cached) = cache.files.get_mut(&path) {
+        if cached.mtime == mtime {
+            cached.tracks = Some(tracks.clone());
+            save_metadata_cache(&cache);
+        }
+    }
+    Ok(tracks)
+}
+
+// Tempo changes over time (for the seek bar)
+#[tauri::command]
+async fn get_tempo_map(path: String) -> Result<Vec<midi::TempoPoint>, AppError> {
+    midi::get_tempo_map(&path)
+}
+
+// Notes per second over time (for the seek bar heatmap)
+#[tauri::command]
+async fn get_density_profile(
+    path: String,
+    buckets: Option<usize>,
+) -> Result<midi::DensityProfile, AppError> {
+    midi::get_density_profile(&path, buckets.unwrap_or(200))
+}
+
+// Dry run: the keys that would be pressed in a time range (for "what's next" and sheets)
+#[tauri::command]
+async fn preview_keys(
+    path: String,
+    from_sec: f64,
+    to_sec: f64,
+    mode: midi::NoteMode,
+    key_mode: midi::KeyMode,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<Vec<midi::KeyPreview>, AppError> {
+    let hash = compute_file_hash(std::path::Path::new(&path));
+    let midi_data = midi::load_midi_for(&path, hash.as_deref())?;
+    let mut settings = hash
+        .map(|h| song_meta::get(&h).settings)
+        .unwrap_or_default();
+    if mode == midi::NoteMode::Smart && settings.smart_mode.is_none() {
+        settings.smart_mode = Some(midi::pick_smart_modes(&midi_data));
+    }
+    let octave_shift = state.lock().unwrap().get_octave_shift();
+    Ok(midi::preview_keys(
+        &midi_data,
+        from_sec,
+        to_sec,
+        mode,
+        key_mode,
+        octave_shift,
+        &settings,
+    ))
+}
+
+// Parse a song on a blocking thread before taking the state lock, so the overlay
+// keeps updating. Emits "midi-loading" and "midi-loaded" around it.
+async fn prepare_midi_async(path: &str, window: &Window) -> Result<state::PreparedMidi, String> {
+    let _ = window.emit("midi-loading", path);
+    let owned = path.to_string();
+    let result = tokio::task::spawn_blocking(move || state::prepare_midi(&owned))
+        .await
+        .map_err(|e| format!("Failed to load MIDI: {}", e))
+        .and_then(|r| r);
+    let _ = window.emit(
+        "midi-loaded",
+        serde_json::json!({ "path": path, "ok": result.is_ok() }),
+    );
+    if let Ok(prepared) = &result {
+        // install_midi applies it when auto_applied is set
+        let suggestion = prepared.octave_suggestion;
+        let _ = window.emit(
+            "octave-suggestion",
+            serde_json::json!({
+                "path": path,
+                "shift": suggestion.shift,
+                "in_range": suggestion.in_range,
+                "unshifted_in_range": suggestion.unshifted_in_range,
+                "auto_applied": midi::get_auto_octave_shift(),
+            }),
+        );
+    }
+    result
+}
+
+#[tauri::command]
+async fn play_midi(
+    path: String,
+    state: State<'_, Arc<Mutex<AppState>>>,
+    window: Window,
+) -> Result<(), AppError> {
+    let prepared = prepare_midi_async(&path, &window).await?;
     let mut app_state = state.lock().unwrap();
-    app_state.stop_playback();
-    app_state.load_midi(&path)?;
+    app_state.stop_playback()?;
+    app_state.install_midi(prepared);
     app_state.start_playback(window)?;
     drop(app_state);
 
-    std::thread::sleep(std::time::Duration::from_millis(100));
-    let _ = keyboard::focus_black_desert_window();
+    keyboard::auto_focus_game_async();
+
+    Ok(())
+}
+
+// Songs played from outside the album (dropped on the window, opened from Explorer),
+// listed until the app closes so they can be auditioned without copying them in
+const MAX_EXTERNAL_FILES: usize = 50;
+static EXTERNAL_FILES: Mutex<Vec<MidiFile>> = Mutex::new(Vec::new());
+
+fn add_external_file(path: &str) -> Result<(), AppError> {
+    let file_path = std::path::Path::new(path);
+    if !file_path.is_file() {
+        return Err(AppError::FileNotFound(path.to_string()));
+    }
+    if !file_path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("mid"))
+    {
+        return Err(AppError::InvalidInput(
+            "File must be a .mid file".to_string(),
+        ));
+    }
+    if get_album_folder().is_ok_and(|album| paths::is_inside(file_path, &album)) {
+        return Ok(()); // Already in the library
+    }
+
+    let cached = parse_cached_metadata(path, get_file_mtime(file_path));
+    let file = midi_file_from_cache(path, &cached);
+    let mut external = EXTERNAL_FILES.lock().unwrap();
+    external.retain(|f| f.path != path);
+    external.insert(0, file);
+    external.truncate(MAX_EXTERNAL_FILES);
+    Ok(())
+}
+
+// Play a file where it is; it joins the external list instead of the album
+#[tauri::command]
+async fn play_external_file(
+    path: String,
+    state: State<'_, Arc<Mutex<AppState>>>,
+    window: Window,
+) -> Result<(), AppError> {
+    add_external_file(&path)?;
+    play_midi(path, state, window).await
+}
+
+#[tauri::command]
+async fn list_external_files() -> Result<Vec<MidiFile>, AppError> {
+    Ok(EXTERNAL_FILES.lock().unwrap().clone())
+}
+
+#[tauri::command]
+async fn remove_external_file(path: String) -> Result<(), AppError> {
+    EXTERNAL_FILES.lock().unwrap().retain(|f| f.path != path);
+    Ok(())
+}
 
+#[tauri::command]
+async fn clear_external_files() -> Result<(), AppError> {
+    EXTERNAL_FILES.lock().unwrap().clear();
     Ok(())
 }
 
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 async fn play_midi_band(
     path: String,
     mode: String,
     slot: usize,
     total_players: usize,
     track_id: Option<usize>,
+    peer_id: Option<String>,
+    part: Option<midi::BandFilter>,
     state: State<'_, Arc<Mutex<AppState>>>,
     window: Window,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
+    let prepared = prepare_midi_async(&path, &window).await?;
     let mut app_state = state.lock().unwrap();
-    app_state.stop_playback();
-    app_state.load_midi(&path)?;
+    app_state.stop_playback()?;
+    app_state.install_midi(prepared);
+
+    // Line our part up with the peer we calibrated against
+    if let Some(peer_id) = peer_id.as_deref() {
+        let offset_ms = band::peer_offset(peer_id);
+        if offset_ms != 0 {
+            app_log!(
+                "[BAND] Applying {}ms offset for peer {}",
+                offset_ms,
+                peer_id
+            );
+            app_state.apply_band_offset(offset_ms);
+        }
+    }
 
-    // Set band mode filter before starting playback
-    app_state.set_band_filter(mode, slot, total_players, track_id);
+    // Set band mode filter before starting playback (a part from a band plan wins)
+    match part {
+        Some(filter) => app_state.update_band_filter_live(Some(filter))?,
+        None => app_state.set_band_filter(mode, slot, total_players, track_id),
+    }
 
     app_state.start_playback(window)?;
     drop(app_state);
 
-    std::thread::sleep(std::time::Duration::from_millis(100));
-    let _ = keyboard::focus_black_desert_window();
+    keyboard::auto_focus_game_async();
+
+    Ok(())
+}
+
+// Who plays which tracks or pitch range; each part's filter can be passed to play_midi_band
+#[tauri::command]
+async fn suggest_band_assignment(
+    path: String,
+    players: Vec<String>,
+) -> Result<band::BandPlan, AppError> {
+    Ok(band::suggest_assignment(&path, &players)?)
+}
+
+// Shared band setlist: the host pushes it over P2P, members resolve songs by hash,
+// and the conductor's next is applied everywhere with band_setlist_goto
+#[tauri::command]
+async fn band_set_setlist(
+    songs: Vec<band::SetlistSong>,
+    index: Option<usize>,
+    app_handle: AppHandle,
+) -> Result<band::Setlist, AppError> {
+    Ok(band::set_setlist(&app_handle, songs, index.unwrap_or(0))?)
+}
+
+#[tauri::command]
+async fn band_get_setlist() -> Result<band::Setlist, AppError> {
+    Ok(band::get_setlist())
+}
+
+#[tauri::command]
+async fn band_setlist_provide(
+    hash: String,
+    path: String,
+    app_handle: AppHandle,
+) -> Result<band::Setlist, AppError> {
+    Ok(band::provide_song(&app_handle, &hash, &path)?)
+}
+
+#[tauri::command]
+async fn band_setlist_goto(
+    index: usize,
+    app_handle: AppHandle,
+) -> Result<band::SetlistSong, AppError> {
+    Ok(band::goto(&app_handle, index)?)
+}
+
+#[tauri::command]
+async fn band_setlist_next(app_handle: AppHandle) -> Result<band::SetlistSong, AppError> {
+    let (index, song) = band::next(&app_handle)?;
+    println!("[BAND] Setlist advanced to #{}: {}", index + 1, song.name);
+    Ok(song)
+}
+
+#[tauri::command]
+async fn band_clear_setlist(app_handle: AppHandle) -> Result<(), AppError> {
+    band::clear_setlist(&app_handle);
+    Ok(())
+}
+
+// Band session state (lobby -> ready check -> countdown -> playing). Member updates
+// arrive over P2P and are fed in here; "band-session-start" fires after the countdown
+#[tauri::command]
+async fn band_session_get() -> Result<band_session::BandSession, AppError> {
+    Ok(band_session::get())
+}
+
+#[tauri::command]
+async fn band_session_join(
+    peer_id: String,
+    name: String,
+    app_handle: AppHandle,
+) -> Result<(), AppError> {
+    Ok(band_session::join(&app_handle, &peer_id, &name)?)
+}
+
+#[tauri::command]
+async fn band_session_leave(peer_id: String, app_handle: AppHandle) -> Result<(), AppError> {
+    Ok(band_session::leave(&app_handle, &peer_id)?)
+}
+
+#[tauri::command]
+async fn band_session_update_member(
+    peer_id: String,
+    loaded: Option<bool>,
+    part: Option<midi::BandFilter>,
+    ready: Option<bool>,
+    app_handle: AppHandle,
+) -> Result<(), AppError> {
+    Ok(band_session::update_member(
+        &app_handle,
+        &peer_id,
+        loaded,
+        part,
+        ready,
+    )?)
+}
+
+#[tauri::command]
+async fn band_session_ready_check(
+    song_hash: String,
+    app_handle: AppHandle,
+) -> Result<(), AppError> {
+    Ok(band_session::start_ready_check(&app_handle, &song_hash)?)
+}
+
+#[tauri::command]
+async fn band_session_countdown(
+    countdown_ms: Option<u64>,
+    start_at: Option<f64>,
+    app_handle: AppHandle,
+) -> Result<f64, AppError> {
+    Ok(band_session::start_countdown(
+        &app_handle,
+        countdown_ms.unwrap_or(3000),
+        start_at,
+    )?)
+}
 
+#[tauri::command]
+async fn band_session_reset(app_handle: AppHandle) -> Result<(), AppError> {
+    band_session::reset(&app_handle);
     Ok(())
 }
 
+// Band latency calibration: ping timing, test pattern and stored per-peer offsets
+#[tauri::command]
+async fn band_estimate_clock(
+    samples: Vec<band::PingSample>,
+) -> Result<band::ClockEstimate, AppError> {
+    Ok(band::estimate_clock(&samples)?)
+}
+
+#[tauri::command]
+async fn band_play_test_pattern(count: u32, interval_ms: u64) -> Result<Vec<f64>, AppError> {
+    Ok(tokio::task::spawn_blocking(move || {
+        keyboard::auto_focus_game();
+        band::play_test_pattern(count, interval_ms)
+    })
+    .await
+    .map_err(|e| format!("Failed to play test pattern: {}", e))?)
+}
+
+#[tauri::command]
+async fn band_estimate_latency(sent_ms: Vec<f64>, heard_ms: Vec<f64>) -> Result<f64, AppError> {
+    Ok(band::estimate_latency(&sent_ms, &heard_ms)?)
+}
+
+#[tauri::command]
+async fn set_band_peer_offset(peer_id: String, offset_ms: i64) -> Result<i64, AppError> {
+    let offset_ms = band::set_peer_offset(&peer_id, offset_ms);
+    println!("[BAND] Offset for peer {} set to {}ms", peer_id, offset_ms);
+    Ok(offset_ms)
+}
+
+#[tauri::command]
+async fn get_band_peer_offsets() -> Result<std::collections::HashMap<String, i64>, AppError> {
+    Ok(band::peer_offsets())
+}
+
 #[tauri::command]
-async fn pause_resume(state: State<'_, Arc<Mutex<AppState>>>) -> Result<PlaybackState, String> {
+async fn pause_resume(state: State<'_, Arc<Mutex<AppState>>>) -> Result<PlaybackState, AppError> {
     let mut app_state = state.lock().unwrap();
     app_state.toggle_pause();
     Ok(app_state.get_playback_state())
 }
 
 #[tauri::command]
-async fn stop_playback(state: State<'_, Arc<Mutex<AppState>>>) -> Result<(), String> {
+async fn stop_playback(state: State<'_, Arc<Mutex<AppState>>>) -> Result<(), AppError> {
     let mut app_state = state.lock().unwrap();
-    app_state.stop_playback();
-    Ok(())
+    Ok(app_state.stop_playback()?)
 }
 
 #[tauri::command]
 async fn get_playback_status(
     state: State<'_, Arc<Mutex<AppState>>>,
-) -> Result<PlaybackState, String> {
+) -> Result<PlaybackState, AppError> {
     let app_state = state.lock().unwrap();
     Ok(app_state.get_playback_state())
 }
@@ -955,17 +1960,28 @@ async fn get_playback_status(
 async fn set_loop_mode(
     enabled: bool,
     state: State<'_, Arc<Mutex<AppState>>>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     let mut app_state = state.lock().unwrap();
     app_state.set_loop_mode(enabled);
     Ok(())
 }
 
+#[tauri::command]
+async fn set_loop_count(
+    count: u16,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<(), AppError> {
+    let mut app_state = state.lock().unwrap();
+    app_state.set_loop_count(count);
+    println!("Loop count set to: {}", count);
+    Ok(())
+}
+
 #[tauri::command]
 async fn set_note_mode(
     mode: midi::NoteMode,
     state: State<'_, Arc<Mutex<AppState>>>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     let mut app_state = state.lock().unwrap();
     app_state.set_note_mode(mode);
     println!("Note mode set to: {:?}", mode);
@@ -973,24 +1989,67 @@ async fn set_note_mode(
 }
 
 #[tauri::command]
-async fn get_note_mode(state: State<'_, Arc<Mutex<AppState>>>) -> Result<midi::NoteMode, String> {
+async fn get_note_mode(state: State<'_, Arc<Mutex<AppState>>>) -> Result<midi::NoteMode, AppError> {
     let app_state = state.lock().unwrap();
     Ok(app_state.get_note_mode())
 }
 
+// Lost-note statistics per candidate mode, best first (what Smart mode picks from)
+#[tauri::command]
+async fn analyze_note_modes(
+    path: String,
+    key_mode: midi::KeyMode,
+) -> Result<Vec<midi::MappingStats>, AppError> {
+    let midi_data = midi::load_midi(&path)?;
+    Ok(midi::analyze_modes(&midi_data, key_mode))
+}
+
 #[tauri::command]
 async fn set_track_filter(
     track_id: Option<usize>,
     state: State<'_, Arc<Mutex<AppState>>>,
-) -> Result<(), String> {
-    let app_state = state.lock().unwrap();
-    app_state.update_band_filter_live(track_id);
+) -> Result<(), AppError> {
+    let mut app_state = state.lock().unwrap();
+    app_state
+        .update_band_filter_live(track_id.map(|id| midi::BandFilter::Track { track_id: id }))?;
     println!("Track filter set to: {:?}", track_id);
     Ok(())
 }
 
+// Mute tracks mid-song to hear which is which; cleared when another song loads
+#[tauri::command]
+async fn set_track_mutes(
+    track_ids: Vec<usize>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<(), AppError> {
+    let mut app_state = state.lock().unwrap();
+    app_state.set_track_mutes(track_ids.clone());
+    println!("Track mutes set to: {:?}", track_ids);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_track_mutes(state: State<'_, Arc<Mutex<AppState>>>) -> Result<Vec<usize>, AppError> {
+    Ok(state.lock().unwrap().get_track_mutes())
+}
+
+// Change the band part mid-song (split slot/players, track, or switch modes) without restarting
+#[tauri::command]
+async fn set_band_filter_live(
+    filter: Option<midi::BandFilter>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<(), AppError> {
+    let mut app_state = state.lock().unwrap();
+    app_state.update_band_filter_live(filter.clone())?;
+    println!("[BAND] Filter changed live to: {:?}", filter);
+    Ok(())
+}
+
 #[tauri::command]
-async fn set_octave_shift(shift: i8, state: State<'_, Arc<Mutex<AppState>>>) -> Result<(), String> {
+async fn set_octave_shift(
+    shift: i8,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<(), AppError> {
     let mut app_state = state.lock().unwrap();
     app_state.set_octave_shift(shift);
     println!("Octave shift set to: {}", shift);
@@ -998,13 +2057,13 @@ async fn set_octave_shift(shift: i8, state: State<'_, Arc<Mutex<AppState>>>) ->
 }
 
 #[tauri::command]
-async fn get_octave_shift(state: State<'_, Arc<Mutex<AppState>>>) -> Result<i8, String> {
+async fn get_octave_shift(state: State<'_, Arc<Mutex<AppState>>>) -> Result<i8, AppError> {
     let app_state = state.lock().unwrap();
     Ok(app_state.get_octave_shift())
 }
 
 #[tauri::command]
-async fn set_speed(speed: f64, state: State<'_, Arc<Mutex<AppState>>>) -> Result<(), String> {
+async fn set_speed(speed: f64, state: State<'_, Arc<Mutex<AppState>>>) -> Result<(), AppError> {
     let mut app_state = state.lock().unwrap();
     app_state.set_speed(speed);
     println!("Speed set to: {}x", speed);
@@ -1012,7 +2071,7 @@ async fn set_speed(speed: f64, state: State<'_, Arc<Mutex<AppState>>>) -> Result
 }
 
 #[tauri::command]
-async fn get_speed(state: State<'_, Arc<Mutex<AppState>>>) -> Result<f64, String> {
+async fn get_speed(state: State<'_, Arc<Mutex<AppState>>>) -> Result<f64, AppError> {
     let app_state = state.lock().unwrap();
     Ok(app_state.get_speed())
 }
@@ -1021,7 +2080,7 @@ async fn get_speed(state: State<'_, Arc<Mutex<AppState>>>) -> Result<f64, String
 async fn set_key_mode(
     mode: midi::KeyMode,
     state: State<'_, Arc<Mutex<AppState>>>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     let mut app_state = state.lock().unwrap();
     app_state.set_key_mode(mode);
     println!("Key mode set to: {:?}", mode);
@@ -1029,109 +2088,516 @@ async fn set_key_mode(
 }
 
 #[tauri::command]
-async fn get_key_mode(state: State<'_, Arc<Mutex<AppState>>>) -> Result<midi::KeyMode, String> {
+async fn get_key_mode(state: State<'_, Arc<Mutex<AppState>>>) -> Result<midi::KeyMode, AppError> {
     let app_state = state.lock().unwrap();
     Ok(app_state.get_key_mode())
 }
 
 #[tauri::command]
-async fn is_game_focused() -> Result<bool, String> {
-    keyboard::is_wwm_focused().map_err(|e| e.to_string())
+async fn is_game_focused() -> Result<bool, AppError> {
+    keyboard::is_wwm_focused()
 }
 
 #[tauri::command]
-async fn is_game_window_found() -> Result<bool, String> {
+async fn is_game_window_found() -> Result<bool, AppError> {
     Ok(keyboard::is_game_window_found())
 }
 
 #[tauri::command]
-async fn set_modifier_delay(delay_ms: u64) -> Result<(), String> {
+async fn set_modifier_delay(delay_ms: u64) -> Result<(), AppError> {
     keyboard::set_modifier_delay(delay_ms);
     println!("Modifier delay set to: {}ms", delay_ms);
     Ok(())
 }
 
 #[tauri::command]
-async fn get_modifier_delay() -> Result<u64, String> {
+async fn get_modifier_delay() -> Result<u64, AppError> {
     Ok(keyboard::get_modifier_delay())
 }
 
+// How long each played note is held (0 = instant tap)
 #[tauri::command]
-async fn set_cloud_mode(enabled: bool) -> Result<(), String> {
-    keyboard::set_send_input_mode(enabled);
+async fn set_key_duration(duration_ms: u64) -> Result<u64, AppError> {
+    keyboard::set_key_duration(duration_ms);
+    let ms = keyboard::get_key_duration();
+    let mut config = load_config();
+    config["key_duration_ms"] = serde_json::json!(ms);
+    save_config(&config);
+    Ok(ms)
+}
+
+#[tauri::command]
+async fn get_key_duration() -> Result<u64, AppError> {
+    Ok(keyboard::get_key_duration())
+}
+
+#[tauri::command]
+async fn get_game_profile() -> Result<profiles::ProfileStatus, AppError> {
+    Ok(profiles::status())
+}
+
+// Follow the game window with the local/cloud profile, or keep your own settings
+#[tauri::command]
+async fn set_game_profile_auto(enabled: bool) -> Result<profiles::ProfileStatus, AppError> {
+    profiles::set_auto(enabled);
+    save_game_profiles();
+    Ok(profiles::status())
+}
+
+#[tauri::command]
+async fn set_cloud_profile(
+    settings: profiles::InputSettings,
+) -> Result<profiles::ProfileStatus, AppError> {
+    profiles::set_cloud_settings(settings);
+    save_game_profiles();
+    Ok(profiles::status())
+}
+
+#[tauri::command]
+async fn reset_cloud_profile() -> Result<profiles::ProfileStatus, AppError> {
+    profiles::set_cloud_settings(profiles::InputSettings::CLOUD_RECOMMENDED);
+    save_game_profiles();
+    Ok(profiles::status())
+}
+
+#[tauri::command]
+async fn set_max_polyphony(max: u8) -> Result<(), AppError> {
+    midi::set_max_polyphony(max);
+    let mut config = load_config();
+    config["max_polyphony"] = serde_json::json!(max);
+    save_config(&config);
+    println!("Max polyphony set to: {}", max);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_max_polyphony() -> Result<u8, AppError> {
+    Ok(midi::get_max_polyphony())
+}
+
+// Skip ghost notes and let accents through the polyphony cap, based on note velocity
+#[tauri::command]
+async fn set_velocity_emphasis(emphasis: midi::VelocityEmphasis) -> Result<(), AppError> {
+    midi::set_velocity_emphasis(emphasis);
+    let mut config = load_config();
+    config["velocity_emphasis"] = serde_json::json!(emphasis);
+    save_config(&config);
+    println!("Velocity emphasis set to: {:?}", emphasis);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_velocity_emphasis() -> Result<midi::VelocityEmphasis, AppError> {
+    Ok(midi::get_velocity_emphasis())
+}
+
+// Metronome: UI click or a tapped spare key on each beat, with optional count-in
+#[tauri::command]
+async fn set_metronome(settings: metronome::MetronomeSettings) -> Result<(), AppError> {
+    if settings.output == metronome::MetronomeOutput::Key {
+        let key = settings.key.to_lowercase();
+        let (low, mid, high) = keyboard::get_note_key_bindings();
+        if key.is_empty() || low.iter().chain(&mid).chain(&high).any(|k| *k == key) {
+            return Err(AppError::InvalidInput(
+                "Metronome key must be a spare key, not a note key".to_string(),
+            ));
+        }
+    }
+    metronome::set_settings(settings.clone());
+    let mut config = load_config();
+    config["metronome"] = serde_json::json!(settings);
+    save_config(&config);
+    println!("Metronome set to: {:?}", settings);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_metronome() -> Result<metronome::MetronomeSettings, AppError> {
+    Ok(metronome::get_settings())
+}
+
+// Apply each song's suggested octave shift ("octave-suggestion") when it loads
+#[tauri::command]
+async fn set_auto_octave_shift(enabled: bool) -> Result<(), AppError> {
+    midi::set_auto_octave_shift(enabled);
+    let mut config = load_config();
+    config["auto_octave_shift"] = serde_json::json!(enabled);
+    save_config(&config);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_auto_octave_shift() -> Result<bool, AppError> {
+    Ok(midi::get_auto_octave_shift())
+}
+
+// How often played keys are batched to the visualizer (0 = every note immediately)
+#[tauri::command]
+async fn set_note_event_batch(ms: u64) -> Result<u64, AppError> {
+    player::set_note_event_batch_ms(ms);
+    let ms = player::get_note_event_batch_ms();
+    let mut config = load_config();
+    config["note_event_batch_ms"] = serde_json::json!(ms);
+    save_config(&config);
+    Ok(ms)
+}
+
+#[tauri::command]
+async fn get_note_event_batch() -> Result<u64, AppError> {
+    Ok(player::get_note_event_batch_ms())
+}
+
+// Play without a game window as a silent rehearsal (events only, no keys)
+#[tauri::command]
+async fn set_silent_rehearsal(enabled: bool) -> Result<(), AppError> {
+    player::set_silent_rehearsal(enabled);
+    let mut config = load_config();
+    config["silent_rehearsal"] = serde_json::json!(enabled);
+    save_config(&config);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_silent_rehearsal() -> Result<bool, AppError> {
+    Ok(player::get_silent_rehearsal())
+}
+
+// Refuse to play without a game window, and pause while it's gone (or unfocused in
+// SendInput mode); "playback-blocked" / "playback-unblocked" tell the UI why
+#[tauri::command]
+async fn set_require_game_window(enabled: bool) -> Result<(), AppError> {
+    player::set_require_game_window(enabled);
+    let mut config = load_config();
+    config["require_game_window"] = serde_json::json!(enabled);
+    save_config(&config);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_require_game_window() -> Result<bool, AppError> {
+    Ok(player::get_require_game_window())
+}
+
+#[tauri::command]
+async fn set_octave_splits(splits: midi::OctaveSplits) -> Result<(), AppError> {
+    if splits.wide_low >= splits.wide_high || splits.keys36_low >= splits.keys36_high {
+        return Err(AppError::InvalidInput(
+            "Low split must be below the high split".to_string(),
+        ));
+    }
+    midi::set_octave_splits(splits);
+    let mut config = load_config();
+    config["octave_splits"] = serde_json::json!(splits);
+    save_config(&config);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_octave_splits() -> Result<midi::OctaveSplits, AppError> {
+    Ok(midi::get_octave_splits())
+}
+
+#[tauri::command]
+async fn set_cloud_mode(enabled: bool) -> Result<(), AppError> {
+    keyboard::set_send_input_mode(enabled);
     Ok(())
 }
 
 #[tauri::command]
-async fn get_cloud_mode() -> Result<bool, String> {
+async fn get_cloud_mode() -> Result<bool, AppError> {
     Ok(keyboard::get_send_input_mode())
 }
 
+#[derive(Debug, Clone, Serialize)]
+struct InputPathReport {
+    #[serde(flatten)]
+    probe: keyboard::InputProbe,
+    cloud_mode: bool,
+    needs_confirmation: bool, // Delivered, but only the player can tell if the game reacted
+    suggestion: Option<String>, // "send_input" / "post_message"
+    switched: bool,
+    message: String,
+}
+
+// Health check for the input backend. A window accepting PostMessage doesn't mean the
+// game reads it, so that path needs the player's word: call again with `works` once they
+// have tried a note. With `auto_switch`, a PostMessage path that fails moves to SendInput.
+#[tauri::command]
+async fn test_input_path(
+    works: Option<bool>,
+    auto_switch: Option<bool>,
+    app: AppHandle,
+) -> Result<InputPathReport, AppError> {
+    let probe = tauri::async_runtime::spawn_blocking(keyboard::probe_input_path)
+        .await
+        .map_err(|e| format!("Input probe failed: {}", e))?;
+    let cloud_mode = keyboard::get_send_input_mode();
+    let mut report = InputPathReport {
+        probe,
+        cloud_mode,
+        needs_confirmation: false,
+        suggestion: None,
+        switched: false,
+        message: String::new(),
+    };
+
+    if !report.probe.game_window_found {
+        report.message = "Game window not found - start the game first".to_string();
+        return Ok(report);
+    }
+
+    let send_input_ok = report.probe.send_input != Some(false);
+    if cloud_mode {
+        report.message = if !send_input_ok {
+            if report.probe.post_message == Some(true) {
+                report.suggestion = Some("post_message".to_string());
+            }
+            "SendInput is being blocked - if the game runs as administrator, run this app as administrator too".to_string()
+        } else if works == Some(false) && !report.probe.game_focused {
+            "SendInput only reaches the focused window - keep the game focused".to_string()
+        } else if works == Some(false) {
+            "Keys are sent but the game ignores them - check the key bindings".to_string()
+        } else {
+            "SendInput is working".to_string()
+        };
+        return Ok(report);
+    }
+
+    let post_failed = report.probe.post_message == Some(false) || works == Some(false);
+    if !post_failed {
+        report.needs_confirmation = works.is_none();
+        report.message = if works == Some(true) {
+            "PostMessage is working".to_string()
+        } else {
+            "Keys reach the game window - did a note play in game?".to_string()
+        };
+        return Ok(report);
+    }
+
+    if !send_input_ok {
+        report.message =
+            "Neither input path gets through - try running this app as administrator".to_string();
+        return Ok(report);
+    }
+    report.suggestion = Some("send_input".to_string());
+    if auto_switch.unwrap_or(false) {
+        keyboard::set_send_input_mode(true);
+        report.switched = true;
+        report.cloud_mode = true;
+        let _ = app.emit(
+            "input-mode-changed",
+            serde_json::json!({ "cloud_mode": true }),
+        );
+        report.message = "The game ignores PostMessage - switched to SendInput".to_string();
+    } else {
+        report.message = "The game ignores PostMessage - try SendInput (cloud mode)".to_string();
+    }
+    Ok(report)
+}
+
+// Note key bindings: one set per key mode. `mode` defaults to the current key mode,
+// whose set is the one used for playback.
 #[tauri::command]
 async fn set_note_keys(
     low: Vec<String>,
     mid: Vec<String>,
     high: Vec<String>,
-) -> Result<(), String> {
-    keyboard::set_note_key_bindings(low.clone(), mid.clone(), high.clone());
-    save_note_keys(&low, &mid, &high);
+    mode: Option<midi::KeyMode>,
+) -> Result<keyboard::NoteKeySet, AppError> {
+    let mode = mode.unwrap_or_else(keyboard::get_binding_key_mode);
+    let set = keyboard::NoteKeySet {
+        low,
+        mid,
+        high,
+        ..keyboard::get_note_key_set(mode)
+    };
+    keyboard::set_note_key_set(mode, set).map_err(AppError::InvalidInput)?;
+    save_note_key_sets();
+    Ok(keyboard::get_note_key_set(mode))
+}
+
+/// Which modifiers play sharps and flats in 36-key mode ("shift" or "ctrl")
+#[tauri::command]
+async fn set_note_key_modifiers(
+    sharp_modifier: String,
+    flat_modifier: String,
+    mode: Option<midi::KeyMode>,
+) -> Result<keyboard::NoteKeySet, AppError> {
+    let mode = mode.unwrap_or(midi::KeyMode::Keys36);
+    let set = keyboard::NoteKeySet {
+        sharp_modifier,
+        flat_modifier,
+        ..keyboard::get_note_key_set(mode)
+    };
+    keyboard::set_note_key_set(mode, set).map_err(AppError::InvalidInput)?;
+    save_note_key_sets();
+    Ok(keyboard::get_note_key_set(mode))
+}
+
+/// Custom keys for the 36-key accidentals (C#, Eb, F#, G#, Bb per row); an empty
+/// entry keeps the default modifier + row key
+#[tauri::command]
+async fn set_accidental_keys(
+    keys: keyboard::AccidentalKeys,
+    mode: Option<midi::KeyMode>,
+) -> Result<keyboard::AccidentalKeys, AppError> {
+    let mode = mode.unwrap_or(midi::KeyMode::Keys36);
+    let set = keyboard::NoteKeySet {
+        accidentals: keys,
+        ..keyboard::get_note_key_set(mode)
+    };
+    keyboard::set_note_key_set(mode, set).map_err(AppError::InvalidInput)?;
+    save_note_key_sets();
+    Ok(keyboard::get_note_key_set(mode).accidental_keys())
+}
+
+/// The keys the accidentals are played with right now, custom or default
+#[tauri::command]
+async fn get_accidental_keys(
+    mode: Option<midi::KeyMode>,
+) -> Result<keyboard::AccidentalKeys, AppError> {
+    let mode = mode.unwrap_or(midi::KeyMode::Keys36);
+    Ok(keyboard::get_note_key_set(mode).accidental_keys())
+}
+
+#[tauri::command]
+async fn reset_accidental_keys(
+    mode: Option<midi::KeyMode>,
+) -> Result<keyboard::AccidentalKeys, AppError> {
+    let mode = mode.unwrap_or(midi::KeyMode::Keys36);
+    let set = keyboard::NoteKeySet {
+        accidentals: keyboard::AccidentalKeys::default(),
+        ..keyboard::get_note_key_set(mode)
+    };
+    keyboard::set_note_key_set(mode, set).map_err(AppError::InvalidInput)?;
+    save_note_key_sets();
+    Ok(keyboard::get_note_key_set(mode).accidental_keys())
+}
+
+#[tauri::command]
+async fn get_note_keys(mode: Option<midi::KeyMode>) -> Result<keyboard::NoteKeySet, AppError> {
+    let mode = mode.unwrap_or_else(keyboard::get_binding_key_mode);
+    Ok(keyboard::get_note_key_set(mode))
+}
+
+#[derive(serde::Serialize)]
+struct NoteKeySetsStatus {
+    active: midi::KeyMode,
+    #[serde(flatten)]
+    sets: keyboard::NoteKeySets,
+}
+
+#[tauri::command]
+async fn get_note_key_sets() -> Result<NoteKeySetsStatus, AppError> {
+    Ok(NoteKeySetsStatus {
+        active: keyboard::get_binding_key_mode(),
+        sets: keyboard::get_note_key_sets(),
+    })
+}
+
+#[tauri::command]
+async fn reset_note_keys(mode: Option<midi::KeyMode>) -> Result<keyboard::NoteKeySet, AppError> {
+    let mode = mode.unwrap_or_else(keyboard::get_binding_key_mode);
+    keyboard::reset_note_key_set(mode);
+    save_note_key_sets();
+    // Return defaults
+    Ok(keyboard::get_note_key_set(mode))
+}
+
+// Guided note key capture, driven by the keyboard hook; progress comes as
+// "note-key-capture" events. Escape cancels.
+#[tauri::command]
+async fn start_note_key_capture(
+    mode: Option<midi::KeyMode>,
+    accidentals: Option<bool>,
+) -> Result<key_capture::CaptureUpdate, AppError> {
+    let mode = mode.unwrap_or_else(keyboard::get_binding_key_mode);
+    Ok(key_capture::start(mode, accidentals.unwrap_or(false)))
+}
+
+#[tauri::command]
+async fn skip_note_key_capture(app: AppHandle) -> Result<key_capture::CaptureUpdate, AppError> {
+    let update = key_capture::skip()
+        .ok_or_else(|| AppError::InvalidInput("No key capture running".to_string()))?;
+    if update.status == key_capture::CaptureStatus::Finished {
+        save_note_key_sets();
+    }
+    let _ = app.emit("note-key-capture", update.clone());
+    Ok(update)
+}
+
+#[tauri::command]
+async fn cancel_note_key_capture(app: AppHandle) -> Result<(), AppError> {
+    if let Some(update) = key_capture::cancel() {
+        let _ = app.emit("note-key-capture", update);
+    }
     Ok(())
 }
 
+// Note key presets: built-in layouts plus preset files shared between players
 #[tauri::command]
-async fn get_note_keys() -> Result<serde_json::Value, String> {
-    let (low, mid, high) = keyboard::get_note_key_bindings();
-    Ok(serde_json::json!({
-        "low": low,
-        "mid": mid,
-        "high": high
-    }))
+async fn list_key_presets() -> Result<Vec<key_presets::KeyPreset>, AppError> {
+    Ok(key_presets::built_in())
+}
+
+fn apply_key_preset_and_save(preset: &key_presets::KeyPreset) -> Result<(), AppError> {
+    key_presets::apply(preset)?;
+    save_note_key_sets();
+    save_custom_window_keywords(&keyboard::get_custom_window_keywords());
+    Ok(())
 }
 
 #[tauri::command]
-async fn reset_note_keys() -> Result<serde_json::Value, String> {
-    keyboard::reset_note_key_bindings();
-    // Clear from config
-    let mut config = load_config();
-    if config.get("note_keys").is_some() {
-        config.as_object_mut().unwrap().remove("note_keys");
-        save_config(&config);
+async fn apply_key_preset(name: String) -> Result<key_presets::KeyPreset, AppError> {
+    let preset = key_presets::find_built_in(&name)
+        .ok_or_else(|| AppError::InvalidInput(format!("Unknown preset: {}", name)))?;
+    apply_key_preset_and_save(&preset)?;
+    Ok(preset)
+}
+
+#[tauri::command]
+async fn export_key_preset(path: String, name: String) -> Result<(), AppError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::InvalidInput("Preset name is empty".to_string()));
     }
-    // Return defaults
-    let (low, mid, high) = keyboard::get_note_key_bindings();
-    Ok(serde_json::json!({
-        "low": low,
-        "mid": mid,
-        "high": high
-    }))
+    key_presets::write(&path, &key_presets::current(name.to_string()))
+}
+
+#[tauri::command]
+async fn import_key_preset(path: String) -> Result<key_presets::KeyPreset, AppError> {
+    let preset = key_presets::read(&path)?;
+    apply_key_preset_and_save(&preset)?;
+    app_log!("Imported note key preset \"{}\"", preset.name);
+    Ok(preset)
 }
 
 #[tauri::command]
-async fn set_custom_window_keywords(keywords: Vec<String>) -> Result<(), String> {
+async fn set_custom_window_keywords(keywords: Vec<String>) -> Result<(), AppError> {
     keyboard::set_custom_window_keywords(keywords.clone());
     save_custom_window_keywords(&keywords);
     Ok(())
 }
 
 #[tauri::command]
-async fn get_custom_window_keywords() -> Result<Vec<String>, String> {
+async fn get_custom_window_keywords() -> Result<Vec<String>, AppError> {
     Ok(keyboard::get_custom_window_keywords())
 }
 
 #[tauri::command]
-async fn cmd_get_keybindings() -> Result<KeyBindings, String> {
+async fn cmd_get_keybindings() -> Result<KeyBindings, AppError> {
     Ok(get_keybindings())
 }
 
 #[tauri::command]
-async fn cmd_set_keybindings(keybindings: KeyBindings) -> Result<(), String> {
+async fn cmd_set_keybindings(keybindings: KeyBindings) -> Result<(), AppError> {
     save_keybindings(&keybindings);
     cache_keybinding_vks(); // Hot reload
     Ok(())
 }
 
 #[tauri::command]
-async fn cmd_reset_keybindings() -> Result<KeyBindings, String> {
+async fn cmd_reset_keybindings() -> Result<KeyBindings, AppError> {
     let default_kb = KeyBindings::default();
     save_keybindings(&default_kb);
     cache_keybinding_vks(); // Hot reload
@@ -1139,7 +2605,7 @@ async fn cmd_reset_keybindings() -> Result<KeyBindings, String> {
 }
 
 #[tauri::command]
-async fn cmd_set_keybindings_enabled(enabled: bool) -> Result<(), String> {
+async fn cmd_set_keybindings_enabled(enabled: bool) -> Result<(), AppError> {
     unsafe {
         KEYBINDINGS_DISABLED = !enabled;
         RECORDING_MODE = !enabled;
@@ -1148,23 +2614,26 @@ async fn cmd_set_keybindings_enabled(enabled: bool) -> Result<(), String> {
 }
 
 #[tauri::command]
-async fn cmd_unfocus_window() -> Result<(), String> {
-    use windows::Win32::UI::WindowsAndMessaging::{GetDesktopWindow, SetForegroundWindow};
-    unsafe {
-        let desktop = GetDesktopWindow();
-        let _ = SetForegroundWindow(desktop);
+async fn cmd_unfocus_window() -> Result<(), AppError> {
+    #[cfg(target_os = "windows")]
+    {
+        use windows::Win32::UI::WindowsAndMessaging::{GetDesktopWindow, SetForegroundWindow};
+        unsafe {
+            let desktop = GetDesktopWindow();
+            let _ = SetForegroundWindow(desktop);
+        }
     }
     Ok(())
 }
 
 #[tauri::command]
-async fn cmd_exit_app(app: tauri::AppHandle) -> Result<(), String> {
+async fn cmd_exit_app(app: tauri::AppHandle) -> Result<(), AppError> {
     app.exit(0);
     Ok(())
 }
 
 #[tauri::command]
-async fn press_key(key: String) -> Result<(), String> {
+async fn press_key(key: String) -> Result<(), AppError> {
     keyboard::key_down(&key);
     keyboard::key_up(&key);
     Ok(())
@@ -1173,14 +2642,14 @@ async fn press_key(key: String) -> Result<(), String> {
 /// Tap a key directly - same as test_all_keys_36
 /// Supports modifier keys: "shift+z", "ctrl+c", etc.
 #[tauri::command]
-async fn tap_key(key: String) -> Result<(), String> {
+async fn tap_key(key: String) -> Result<(), AppError> {
     keyboard::key_down(&key);
     keyboard::key_up(&key);
     Ok(())
 }
 
 #[tauri::command]
-async fn test_all_keys() -> Result<(), String> {
+async fn test_all_keys() -> Result<(), AppError> {
     // Test all 21 keys: Low (Z-M), Mid (A-J), High (Q-U)
     let keys = [
         "z", "x", "c", "v", "b", "n", "m", "a", "s", "d", "f", "g", "h", "j", "q", "w", "e", "r",
@@ -1197,7 +2666,7 @@ async fn test_all_keys() -> Result<(), String> {
 }
 
 #[tauri::command]
-async fn test_all_keys_36() -> Result<(), String> {
+async fn test_all_keys_36() -> Result<(), AppError> {
     // Test all 36 keys including modifiers
     // 21 normal keys + 9 shift keys (sharps) + 6 ctrl keys (flats)
 
@@ -1248,7 +2717,7 @@ async fn test_all_keys_36() -> Result<(), String> {
 /// Spam test - rapidly press keys to test PostMessage reliability
 /// delay_ms: delay between each key press (0 = max speed)
 #[tauri::command]
-fn spam_test(key: String, count: u32, delay_ms: u64) -> Result<(), String> {
+fn spam_test(key: String, count: u32, delay_ms: u64) -> Result<(), AppError> {
     println!(
         "[SPAM] Starting: key='{}' count={} delay={}ms",
         key, count, delay_ms
@@ -1270,7 +2739,7 @@ fn spam_test(key: String, count: u32, delay_ms: u64) -> Result<(), String> {
 
 /// Multi-key spam test - rapidly press multiple different keys
 #[tauri::command]
-fn spam_test_multi(count: u32, delay_ms: u64) -> Result<(), String> {
+fn spam_test_multi(count: u32, delay_ms: u64) -> Result<(), AppError> {
     let keys = [
         "z", "x", "c", "v", "b", "n", "m", // Low
         "a", "s", "d", "f", "g", "h", "j", // Mid
@@ -1298,7 +2767,7 @@ fn spam_test_multi(count: u32, delay_ms: u64) -> Result<(), String> {
 
 /// Chord test - press multiple keys at the SAME time
 #[tauri::command]
-fn spam_test_chord(chord_size: u32, count: u32, delay_ms: u64) -> Result<(), String> {
+fn spam_test_chord(chord_size: u32, count: u32, delay_ms: u64) -> Result<(), AppError> {
     let keys = [
         "z", "x", "c", "v", "b", "n", "m", // Low
         "a", "s", "d", "f", "g", "h", "j", // Mid
@@ -1336,7 +2805,7 @@ fn spam_test_chord(chord_size: u32, count: u32, delay_ms: u64) -> Result<(), Str
 }
 
 #[tauri::command]
-async fn set_interaction_mode(window: Window, interactive: bool) -> Result<(), String> {
+async fn set_interaction_mode(window: Window, interactive: bool) -> Result<(), AppError> {
     window
         .set_ignore_cursor_events(!interactive)
         .map_err(|e| e.to_string())?;
@@ -1344,17 +2813,36 @@ async fn set_interaction_mode(window: Window, interactive: bool) -> Result<(), S
 }
 
 #[tauri::command]
-async fn focus_game_window() -> Result<(), String> {
-    keyboard::focus_black_desert_window().map_err(|e| e.to_string())
+async fn focus_game_window() -> Result<(), AppError> {
+    tokio::task::spawn_blocking(keyboard::focus_black_desert_window)
+        .await
+        .map_err(|e| format!("Failed to focus game window: {}", e))?
+}
+
+// Whether starting playback brings the game forward: never, only if unfocused, always
+#[tauri::command]
+async fn set_auto_focus(mode: keyboard::AutoFocus) -> Result<(), AppError> {
+    keyboard::set_auto_focus(mode);
+    let mut config = load_config();
+    config["auto_focus"] = serde_json::json!(mode);
+    save_config(&config);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_auto_focus() -> Result<keyboard::AutoFocus, AppError> {
+    Ok(keyboard::get_auto_focus())
 }
 
 #[tauri::command]
-async fn import_midi_file(source_path: String) -> Result<MidiFile, String> {
+async fn import_midi_file(source_path: String) -> Result<MidiFile, AppError> {
     let source = std::path::Path::new(&source_path);
 
     // Verify it's a .mid file
     if source.extension().and_then(|s| s.to_str()) != Some("mid") {
-        return Err("File must be a .mid file".to_string());
+        return Err(AppError::InvalidInput(
+            "File must be a .mid file".to_string(),
+        ));
     }
 
     // Get album folder path
@@ -1371,14 +2859,14 @@ async fn import_midi_file(source_path: String) -> Result<MidiFile, String> {
 
     // Check if file already exists
     if dest_path.exists() {
-        return Err(format!(
+        return Err(AppError::InvalidInput(format!(
             "File '{}' already exists in album",
             filename.to_string_lossy()
-        ));
+        )));
     }
 
     // Copy file to album folder
-    std::fs::copy(&source, &dest_path).map_err(|e| format!("Failed to copy file: {}", e))?;
+    std::fs::copy(&source, &dest_path).map_err(|e| AppError::io("copy file", e))?;
 
     // Get metadata and return file info
     let name = source
@@ -1387,13 +2875,7 @@ async fn import_midi_file(source_path: String) -> Result<MidiFile, String> {
         .unwrap_or("Unknown")
         .to_string();
 
-    let meta =
-        midi::get_midi_metadata(&dest_path.to_string_lossy()).unwrap_or(midi::MidiMetadata {
-            duration: 0.0,
-            bpm: 120,
-            note_count: 0,
-            note_density: 0.0,
-        });
+    let meta = midi::get_midi_metadata(&dest_path.to_string_lossy()).unwrap_or_default();
 
     let file_size = std::fs::metadata(&dest_path).map(|m| m.len()).unwrap_or(0);
     let file_hash = compute_file_hash(&dest_path).unwrap_or_else(|| format!("{:x}", file_size));
@@ -1404,56 +2886,124 @@ async fn import_midi_file(source_path: String) -> Result<MidiFile, String> {
         duration: meta.duration,
         bpm: meta.bpm,
         note_density: meta.note_density,
+        difficulty: meta.difficulty,
+        tempo: meta.tempo,
+        repaired: meta.repaired,
+        note_count: meta.note_count,
+        track_count: meta.track_count,
+        multi_channel: meta.multi_channel,
         hash: file_hash,
         size: file_size,
     })
 }
 
-// Import all .mid files from a zip archive
-#[tauri::command]
-async fn import_from_zip(zip_path: String) -> Result<Vec<MidiFile>, String> {
+// Archive import limits (archives may come from strangers on the P2P network)
+const ZIP_MAX_ENTRIES: usize = 10_000;
+const ZIP_MAX_FILE_BYTES: u64 = 16 * 1024 * 1024; // Way beyond any real MIDI file
+const ZIP_MAX_TOTAL_BYTES: u64 = 512 * 1024 * 1024;
+const ZIP_MAX_METADATA_BYTES: u64 = 1024 * 1024;
+
+fn open_zip_checked(zip_path: &str) -> Result<zip::ZipArchive<std::fs::File>, String> {
+    let file =
+        std::fs::File::open(zip_path).map_err(|e| format!("Failed to open zip file: {}", e))?;
+    read_zip_checked(file)
+}
+
+fn read_zip_checked<R: std::io::Read + std::io::Seek>(
+    reader: R,
+) -> Result<zip::ZipArchive<R>, String> {
+    let archive =
+        zip::ZipArchive::new(reader).map_err(|e| format!("Failed to read zip archive: {}", e))?;
+    if archive.len() > ZIP_MAX_ENTRIES {
+        return Err(format!(
+            "Zip archive has too many entries ({}, max {})",
+            archive.len(),
+            ZIP_MAX_ENTRIES
+        ));
+    }
+    Ok(archive)
+}
+
+/// File name of a zip entry, only if it can't escape the extraction folder.
+/// Folders inside the archive are flattened. Backslashes count as separators on every
+/// platform, and drive or stream names (`:`) are refused.
+fn safe_entry_name(enclosed: Option<&std::path::Path>) -> Option<String> {
+    let name = enclosed?.file_name()?.to_str()?.rsplit('\\').next()?;
+    if name.is_empty() || name.starts_with('.') || name.contains(':') || name.contains('\0') {
+        return None;
+    }
+    Some(name.to_string())
+}
+
+/// Read an entry without trusting its declared size, counting it against the archive total
+fn read_entry_limited(
+    entry: impl std::io::Read,
+    limit: u64,
+    total: &mut u64,
+) -> Result<Vec<u8>, String> {
     use std::io::Read;
 
-    let zip_file =
-        std::fs::File::open(&zip_path).map_err(|e| format!("Failed to open zip: {}", e))?;
-    let mut archive =
-        zip::ZipArchive::new(zip_file).map_err(|e| format!("Invalid zip file: {}", e))?;
+    let mut contents = Vec::new();
+    entry
+        .take(limit + 1)
+        .read_to_end(&mut contents)
+        .map_err(|e| format!("Failed to read entry: {}", e))?;
+    // Count what was inflated even if the entry is then skipped, or a stack of
+    // oversized entries would never add up to the archive limit
+    *total += contents.len() as u64;
+    if *total > ZIP_MAX_TOTAL_BYTES {
+        return Err("Zip archive exceeds total size limit".to_string());
+    }
+    if contents.len() as u64 > limit {
+        return Err(format!("Entry exceeds {} bytes", limit));
+    }
+    Ok(contents)
+}
+
+// Import all .mid files from a zip archive
+#[tauri::command]
+async fn import_from_zip(zip_path: String) -> Result<Vec<MidiFile>, AppError> {
+    let mut archive = open_zip_checked(&zip_path)?;
 
     let album_path = get_album_folder()?;
     std::fs::create_dir_all(&album_path).ok();
 
     let mut imported = Vec::new();
+    let mut total_bytes = 0u64;
 
     for i in 0..archive.len() {
-        let mut file = match archive.by_index(i) {
+        let file = match archive.by_index(i) {
             Ok(f) => f,
             Err(_) => continue,
         };
 
-        let path = match file.enclosed_name() {
-            Some(p) => p.to_owned(),
+        let filename = match safe_entry_name(file.enclosed_name().as_deref()) {
+            Some(n) => n,
             None => continue,
         };
 
         // Only .mid files
-        if path.extension().and_then(|s| s.to_str()) != Some("mid") {
+        if !filename.to_lowercase().ends_with(".mid") {
             continue;
         }
 
-        let filename = match path.file_name() {
-            Some(n) => n.to_owned(),
-            None => continue,
-        };
-
         let dest = album_path.join(&filename);
         if dest.exists() {
             continue;
         }
 
-        let mut contents = Vec::new();
-        if file.read_to_end(&mut contents).is_err() {
+        if file.size() > ZIP_MAX_FILE_BYTES {
+            app_log!("[IMPORT] Skipping oversized entry: {}", filename);
             continue;
         }
+        let contents = match read_entry_limited(file, ZIP_MAX_FILE_BYTES, &mut total_bytes) {
+            Ok(c) => c,
+            Err(e) if total_bytes > ZIP_MAX_TOTAL_BYTES => return Err(e.into()),
+            Err(e) => {
+                app_log!("[IMPORT] Skipping {}: {}", filename, e);
+                continue;
+            }
+        };
         if std::fs::write(&dest, &contents).is_err() {
             continue;
         }
@@ -1463,12 +3013,7 @@ async fn import_from_zip(zip_path: String) -> Result<Vec<MidiFile>, String> {
             .and_then(|s| s.to_str())
             .unwrap_or("Unknown")
             .to_string();
-        let meta = midi::get_midi_metadata(&dest.to_string_lossy()).unwrap_or(midi::MidiMetadata {
-            duration: 0.0,
-            bpm: 120,
-            note_count: 0,
-            note_density: 0.0,
-        });
+        let meta = midi::get_midi_metadata(&dest.to_string_lossy()).unwrap_or_default();
         let hash = compute_file_hash(&dest).unwrap_or_default();
 
         imported.push(MidiFile {
@@ -1477,6 +3022,12 @@ async fn import_from_zip(zip_path: String) -> Result<Vec<MidiFile>, String> {
             duration: meta.duration,
             bpm: meta.bpm,
             note_density: meta.note_density,
+            difficulty: meta.difficulty,
+            tempo: meta.tempo,
+            repaired: meta.repaired,
+            note_count: meta.note_count,
+            track_count: meta.track_count,
+            multi_channel: meta.multi_channel,
             hash,
             size: contents.len() as u64,
         });
@@ -1487,7 +3038,7 @@ async fn import_from_zip(zip_path: String) -> Result<Vec<MidiFile>, String> {
 
 // List all .mid files in a folder (recursive)
 #[tauri::command]
-async fn list_midi_in_folder(folder_path: String) -> Result<Vec<String>, String> {
+async fn list_midi_in_folder(folder_path: String) -> Result<Vec<String>, AppError> {
     fn find_midi(dir: &std::path::Path, files: &mut Vec<String>) {
         if let Ok(entries) = std::fs::read_dir(dir) {
             for entry in entries.flatten() {
@@ -1507,34 +3058,38 @@ async fn list_midi_in_folder(folder_path: String) -> Result<Vec<String>, String>
 }
 
 #[tauri::command]
-async fn get_album_path() -> Result<String, String> {
+async fn get_album_path() -> Result<String, AppError> {
     let path = get_album_folder()?;
     Ok(path.to_string_lossy().to_string())
 }
 
 #[tauri::command]
-async fn set_album_path(path: String) -> Result<(), String> {
+async fn set_album_path(path: String) -> Result<(), AppError> {
     let path_buf = std::path::PathBuf::from(&path);
     if !path_buf.exists() {
-        return Err("Path does not exist".to_string());
+        return Err(AppError::FileNotFound("Path does not exist".to_string()));
     }
     if !path_buf.is_dir() {
-        return Err("Path is not a directory".to_string());
+        return Err(AppError::InvalidInput(
+            "Path is not a directory".to_string(),
+        ));
     }
 
     if let Ok(mut guard) = ALBUM_PATH.write() {
         *guard = Some(path.clone());
     }
     save_album_path(Some(&path));
+    sync_active_album(Some(&path));
     Ok(())
 }
 
 #[tauri::command]
-async fn reset_album_path() -> Result<String, String> {
+async fn reset_album_path() -> Result<String, AppError> {
     if let Ok(mut guard) = ALBUM_PATH.write() {
         *guard = None;
     }
     save_album_path(None);
+    sync_active_album(None);
     // Return the default path
     let exe_path = std::env::current_exe().map_err(|e| e.to_string())?;
     let exe_dir = exe_path
@@ -1543,40 +3098,164 @@ async fn reset_album_path() -> Result<String, String> {
     Ok(exe_dir.join("album").to_string_lossy().to_string())
 }
 
-// ============ LOCALE MANAGEMENT ============
+// Saved album locations ("Main", "Event songs", "NAS share"). Each folder keeps its
+// own .metadata_cache.json, so switching doesn't throw away parsed metadata.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AlbumPreset {
+    name: String,
+    path: String,
+}
 
-#[tauri::command]
-async fn get_locales_path() -> Result<String, String> {
-    let path = get_locales_folder()?;
-    Ok(path.to_string_lossy().to_string())
+#[derive(Debug, Clone, Serialize)]
+struct AlbumPresets {
+    active: Option<String>,
+    presets: Vec<AlbumPreset>,
 }
 
-#[tauri::command]
-async fn get_user_locale(lang: String) -> Result<Option<serde_json::Value>, String> {
-    let locales_dir = get_locales_folder()?;
-    let locale_file = locales_dir.join(format!("{}.json", lang));
+fn load_album_presets() -> Vec<AlbumPreset> {
+    serde_json::from_value(load_config()["album_presets"].clone()).unwrap_or_default()
+}
 
-    if !locale_file.exists() {
-        return Ok(None);
+fn save_album_presets(presets: &[AlbumPreset], active: Option<&str>) {
+    let mut config = load_config();
+    config["album_presets"] = serde_json::json!(presets);
+    match active {
+        Some(name) => config["active_album"] = serde_json::json!(name),
+        None => {
+            config.as_object_mut().map(|o| o.remove("active_album"));
+        }
     }
+    save_config(&config);
+}
 
-    let content = std::fs::read_to_string(&locale_file)
-        .map_err(|e| format!("Failed to read locale file: {}", e))?;
-
-    let json: serde_json::Value = serde_json::from_str(&content)
-        .map_err(|e| format!("Failed to parse locale JSON: {}", e))?;
+// A folder picked by hand is only the active album if a preset points at it
+fn sync_active_album(path: Option<&str>) {
+    let presets = load_album_presets();
+    let active = path.and_then(|path| presets.iter().find(|p| p.path == path));
+    save_album_presets(&presets, active.map(|p| p.name.as_str()));
+}
 
-    Ok(Some(json))
+fn active_album_preset() -> Option<String> {
+    load_config()["active_album"].as_str().map(String::from)
 }
 
 #[tauri::command]
-async fn save_user_locale(lang: String, data: serde_json::Value) -> Result<(), String> {
-    let locales_dir = get_locales_folder()?;
+async fn list_album_presets() -> Result<AlbumPresets, AppError> {
+    Ok(AlbumPresets {
+        active: active_album_preset(),
+        presets: load_album_presets(),
+    })
+}
 
-    // Create locales directory if it doesn't exist
-    if !locales_dir.exists() {
-        std::fs::create_dir_all(&locales_dir)
-            .map_err(|e| format!("Failed to create locales directory: {}", e))?;
+// Add or update a saved location; defaults to the current album folder
+#[tauri::command]
+async fn save_album_preset(name: String, path: Option<String>) -> Result<AlbumPresets, AppError> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(AppError::InvalidInput("Album name is empty".to_string()));
+    }
+    let path = match path {
+        Some(path) => path,
+        None => get_album_folder()?.to_string_lossy().to_string(),
+    };
+    if !std::path::Path::new(&path).is_dir() {
+        return Err(AppError::FileNotFound(format!(
+            "Album folder not found: {}",
+            path
+        )));
+    }
+
+    let mut presets = load_album_presets();
+    match presets
+        .iter_mut()
+        .find(|p| p.name.eq_ignore_ascii_case(&name))
+    {
+        Some(preset) => preset.path = path,
+        None => presets.push(AlbumPreset { name, path }),
+    }
+    let active = active_album_preset();
+    save_album_presets(&presets, active.as_deref());
+    Ok(AlbumPresets { active, presets })
+}
+
+#[tauri::command]
+async fn remove_album_preset(name: String) -> Result<AlbumPresets, AppError> {
+    let mut presets = load_album_presets();
+    let before = presets.len();
+    presets.retain(|p| !p.name.eq_ignore_ascii_case(&name));
+    if presets.len() == before {
+        return Err(AppError::InvalidInput(format!("Album not found: {}", name)));
+    }
+    // The folder stays in use; it just isn't a saved location any more
+    let active = active_album_preset().filter(|a| !a.eq_ignore_ascii_case(&name));
+    save_album_presets(&presets, active.as_deref());
+    Ok(AlbumPresets { active, presets })
+}
+
+// Swap the album folder to a saved location and reload the library from it
+#[tauri::command]
+async fn switch_album(window: Window, name: String) -> Result<Vec<MidiFileId>, AppError> {
+    let presets = load_album_presets();
+    let preset = presets
+        .iter()
+        .find(|p| p.name.eq_ignore_ascii_case(&name))
+        .ok_or_else(|| AppError::InvalidInput(format!("Album not found: {}", name)))?;
+    if !std::path::Path::new(&preset.path).is_dir() {
+        return Err(AppError::FileNotFound(format!(
+            "Album folder not found: {}",
+            preset.path
+        )));
+    }
+
+    if let Ok(mut guard) = ALBUM_PATH.write() {
+        *guard = Some(preset.path.clone());
+    }
+    save_album_path(Some(&preset.path));
+    save_album_presets(&presets, Some(&preset.name));
+    app_log!(
+        "[LIBRARY] Switched to album \"{}\" ({})",
+        preset.name,
+        preset.path
+    );
+
+    let _ = window.emit("album-switched", preset);
+    list_file_names(window).await
+}
+
+// ============ LOCALE MANAGEMENT ============
+
+#[tauri::command]
+async fn get_locales_path() -> Result<String, AppError> {
+    let path = get_locales_folder()?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+async fn get_user_locale(lang: String) -> Result<Option<serde_json::Value>, AppError> {
+    let locales_dir = get_locales_folder()?;
+    let locale_file = locales_dir.join(format!("{}.json", lang));
+
+    if !locale_file.exists() {
+        return Ok(None);
+    }
+
+    let content = std::fs::read_to_string(&locale_file)
+        .map_err(|e| format!("Failed to read locale file: {}", e))?;
+
+    let json: serde_json::Value = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse locale JSON: {}", e))?;
+
+    Ok(Some(json))
+}
+
+#[tauri::command]
+async fn save_user_locale(lang: String, data: serde_json::Value) -> Result<(), AppError> {
+    let locales_dir = get_locales_folder()?;
+
+    // Create locales directory if it doesn't exist
+    if !locales_dir.exists() {
+        std::fs::create_dir_all(&locales_dir)
+            .map_err(|e| format!("Failed to create locales directory: {}", e))?;
     }
 
     let locale_file = locales_dir.join(format!("{}.json", lang));
@@ -1590,7 +3269,7 @@ async fn save_user_locale(lang: String, data: serde_json::Value) -> Result<(), S
 }
 
 #[tauri::command]
-async fn get_available_user_locales() -> Result<Vec<String>, String> {
+async fn get_available_user_locales() -> Result<Vec<String>, AppError> {
     let locales_dir = get_locales_folder()?;
 
     if !locales_dir.exists() {
@@ -1616,7 +3295,7 @@ async fn get_available_user_locales() -> Result<Vec<String>, String> {
 #[tauri::command]
 async fn init_user_locales(
     default_locales: std::collections::HashMap<String, serde_json::Value>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     let locales_dir = get_locales_folder()?;
 
     // Create locales directory if it doesn't exist
@@ -1641,7 +3320,7 @@ async fn init_user_locales(
 }
 
 #[tauri::command]
-async fn open_locales_folder() -> Result<(), String> {
+async fn open_locales_folder() -> Result<(), AppError> {
     let locales_dir = get_locales_folder()?;
 
     // Create if doesn't exist
@@ -1661,18 +3340,542 @@ async fn open_locales_folder() -> Result<(), String> {
     Ok(())
 }
 
+// Built-in English strings - the reference translators diff against
+const BUILTIN_EN_LOCALE: &str = include_str!("../../src/lib/i18n/locales/en.json");
+
+#[derive(Clone, Serialize)]
+struct LocaleChanged {
+    lang: String,
+    data: Option<serde_json::Value>, // None if the file was deleted or is invalid
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct LocaleDiff {
+    lang: String,
+    missing: Vec<String>, // In English but not in this locale
+    extra: Vec<String>,   // In this locale but not in English (typos / removed keys)
+    total_keys: usize,
+}
+
+// Collect dotted key paths of all string leaves ("player.play", ...)
+fn flatten_locale_keys(
+    value: &serde_json::Value,
+    prefix: &str,
+    out: &mut std::collections::BTreeSet<String>,
+) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, child) in map {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten_locale_keys(child, &path, out);
+            }
+        }
+        _ => {
+            out.insert(prefix.to_string());
+        }
+    }
+}
+
+// Compare a user locale against the built-in English keys
+#[tauri::command]
+async fn diff_locale(lang: String) -> Result<LocaleDiff, AppError> {
+    let reference: serde_json::Value = serde_json::from_str(BUILTIN_EN_LOCALE)
+        .map_err(|e| format!("Failed to parse built-in locale: {}", e))?;
+    let locale = get_user_locale(lang.clone())
+        .await?
+        .ok_or_else(|| format!("Locale '{}' not found", lang))?;
+
+    let mut reference_keys = std::collections::BTreeSet::new();
+    let mut locale_keys = std::collections::BTreeSet::new();
+    flatten_locale_keys(&reference, "", &mut reference_keys);
+    flatten_locale_keys(&locale, "", &mut locale_keys);
+
+    Ok(LocaleDiff {
+        lang,
+        missing: reference_keys.difference(&locale_keys).cloned().collect(),
+        extra: locale_keys.difference(&reference_keys).cloned().collect(),
+        total_keys: reference_keys.len(),
+    })
+}
+
+const GAME_WINDOW_POLL_SECS: u64 = 2;
+const GAME_FOCUS_POLL_MS: u64 = 250; // Cheap; quick enough for the overlay's z-order
+
+// Keep the game window handle warm and valid; "game-window" fires when the game
+// appears or goes away (e.g. restarted), so the UI can warn before notes go nowhere.
+// A local or cloud window also picks the matching game profile ("game-profile").
+// Held keys are released when the game loses focus, and the overlay's z-order follows it.
+fn start_game_window_watcher(app_handle: AppHandle) {
+    thread::spawn(move || {
+        let mut last_found = None;
+        let mut last_focused = false;
+        let mut last_refresh: Option<std::time::Instant> = None;
+        overlay::update_topmost(&app_handle);
+        loop {
+            if last_refresh.is_none_or(|at| at.elapsed().as_secs() >= GAME_WINDOW_POLL_SECS) {
+                last_refresh = Some(std::time::Instant::now());
+                let found = keyboard::refresh_game_window();
+                if last_found != Some(found) {
+                    app_log!(
+                        "[WINDOW] Game window {}",
+                        if found { "found" } else { "not found" }
+                    );
+                    let _ = app_handle.emit("game-window", serde_json::json!({ "found": found }));
+                    last_found = Some(found);
+                }
+                if let Some(cloud) = keyboard::game_window_is_cloud() {
+                    if profiles::on_game_window(cloud).is_some() {
+                        let _ = app_handle.emit("game-profile", profiles::status());
+                    }
+                }
+            }
+            // Focus left the game: nothing we pressed should stay down (SendInput keys
+            // would otherwise wait for a release the focus guard swallows)
+            let focused = keyboard::is_wwm_focused().unwrap_or(false);
+            if last_focused && !focused {
+                keyboard::release_held_keys();
+            }
+            if last_focused != focused {
+                overlay::on_game_focus(&app_handle, focused);
+            }
+            last_focused = focused;
+            thread::sleep(std::time::Duration::from_millis(GAME_FOCUS_POLL_MS));
+        }
+    });
+}
+
+// Poll the locales folder and push changed translations to the frontend
+fn start_locale_watcher(app_handle: AppHandle) {
+    thread::spawn(move || {
+        let mut known: std::collections::HashMap<std::path::PathBuf, u64> =
+            std::collections::HashMap::new();
+        let mut first_scan = true;
+
+        loop {
+            if let Ok(locales_dir) = get_locales_folder() {
+                let mut seen = std::collections::HashSet::new();
+
+                if let Ok(entries) = std::fs::read_dir(&locales_dir) {
+                    for path in entries.flatten().map(|e| e.path()) {
+                        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                            continue;
+                        }
+                        seen.insert(path.clone());
+
+                        let mtime = get_file_mtime(&path);
+                        if known.insert(path.clone(), mtime) == Some(mtime) || first_scan {
+                            continue;
+                        }
+
+                        let lang = path
+                            .file_stem()
+                            .map(|s| s.to_string_lossy().to_string())
+                            .unwrap_or_default();
+                        let parsed = std::fs::read_to_string(&path)
+                            .map_err(|e| e.to_string())
+                            .and_then(|c| {
+                                serde_json::from_str::<serde_json::Value>(&c)
+                                    .map_err(|e| e.to_string())
+                            });
+                        let event = match parsed {
+                            Ok(data) => LocaleChanged {
+                                lang: lang.clone(),
+                                data: Some(data),
+                                error: None,
+                            },
+                            Err(e) => LocaleChanged {
+                                lang: lang.clone(),
+                                data: None,
+                                error: Some(e),
+                            },
+                        };
+                        app_log!("[LOCALE] Reloaded {}.json", lang);
+                        let _ = app_handle.emit("locale-changed", event);
+                    }
+                }
+
+                // Deleted files
+                known.retain(|path, _| {
+                    if seen.contains(path) {
+                        return true;
+                    }
+                    let lang = path
+                        .file_stem()
+                        .map(|s| s.to_string_lossy().to_string())
+                        .unwrap_or_default();
+                    let _ = app_handle.emit(
+                        "locale-changed",
+                        LocaleChanged {
+                            lang,
+                            data: None,
+                            error: None,
+                        },
+                    );
+                    false
+                });
+            }
+
+            first_scan = false;
+            thread::sleep(std::time::Duration::from_secs(1));
+        }
+    });
+}
+
+// Community locales live in the app repo; the contents API gives us each file's git blob SHA
+const REMOTE_LOCALES_API: &str =
+    "https://api.github.com/repos/SnowiyQ/Where-Winds-Meet-Midi-Player/contents/src/lib/i18n/locales";
+
+#[derive(Debug, Clone, Serialize)]
+struct RemoteLocale {
+    lang: String,
+    size: u64,
+    sha: String, // git blob SHA-1, used as the checksum
+    download_url: String,
+}
+
+// Git blob hash: sha1("blob <len>\0" + content)
+fn git_blob_sha1(data: &[u8]) -> String {
+    use sha1::{Digest, Sha1};
+    let mut hasher = Sha1::new();
+    hasher.update(format!("blob {}\0", data.len()).as_bytes());
+    hasher.update(data);
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn fetch_remote_locales() -> Result<Vec<RemoteLocale>, String> {
+    use std::io::Read;
+
+    let response = ureq::get(REMOTE_LOCALES_API)
+        .set("User-Agent", "WWM-Overlay")
+        .call()
+        .map_err(|e| format!("Failed to list remote locales: {}", e))?;
+
+    let mut body = String::new();
+    response
+        .into_reader()
+        .take(1024 * 1024)
+        .read_to_string(&mut body)
+        .map_err(|e| e.to_string())?;
+
+    let json: serde_json::Value = serde_json::from_str(&body).map_err(|e| e.to_string())?;
+    let entries = json.as_array().ok_or("Unexpected response from GitHub")?;
+
+    Ok(entries
+        .iter()
+        .filter_map(|entry| {
+            let name = entry["name"].as_str()?;
+            let lang = name.strip_suffix(".json")?;
+            Some(RemoteLocale {
+                lang: lang.to_string(),
+                size: entry["size"].as_u64().unwrap_or(0),
+                sha: entry["sha"].as_str()?.to_string(),
+                download_url: entry["download_url"].as_str()?.to_string(),
+            })
+        })
+        .collect())
+}
+
+#[tauri::command]
+async fn list_remote_locales() -> Result<Vec<RemoteLocale>, AppError> {
+    Ok(fetch_remote_locales()?)
+}
+
+// Download a community locale into the locales folder (existing file kept as .bak)
+#[tauri::command]
+async fn download_locale(lang: String) -> Result<serde_json::Value, AppError> {
+    use std::io::Read;
+
+    if lang.is_empty()
+        || !lang
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(AppError::InvalidInput("Invalid language code".to_string()));
+    }
+
+    let remote = fetch_remote_locales()?
+        .into_iter()
+        .find(|l| l.lang == lang)
+        .ok_or_else(|| format!("No community locale for '{}'", lang))?;
+
+    let response = ureq::get(&remote.download_url)
+        .set("User-Agent", "WWM-Overlay")
+        .call()
+        .map_err(|e| format!("Failed to download locale: {}", e))?;
+    let mut bytes = Vec::new();
+    response
+        .into_reader()
+        .take(5 * 1024 * 1024) // 5MB limit
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("Failed to read download: {}", e))?;
+
+    // Checksum validation
+    if bytes.len() as u64 != remote.size || git_blob_sha1(&bytes) != remote.sha {
+        return Err(AppError::Network(
+            "Checksum mismatch - download corrupted or tampered".to_string(),
+        ));
+    }
+
+    let data: serde_json::Value = serde_json::from_slice(&bytes)
+        .map_err(|e| format!("Failed to parse locale JSON: {}", e))?;
+    if !data.is_object() {
+        return Err("Locale file is not a JSON object".to_string().into());
+    }
+
+    let locales_dir = get_locales_folder()?;
+    if !locales_dir.exists() {
+        std::fs::create_dir_all(&locales_dir)
+            .map_err(|e| format!("Failed to create locales directory: {}", e))?;
+    }
+
+    let locale_file = locales_dir.join(format!("{}.json", lang));
+    if locale_file.exists() {
+        let _ = std::fs::copy(&locale_file, locales_dir.join(format!("{}.json.bak", lang)));
+    }
+    std::fs::write(&locale_file, &bytes)
+        .map_err(|e| format!("Failed to write locale file: {}", e))?;
+
+    app_log!("[LOCALE] Downloaded community locale {}.json", lang);
+    Ok(data)
+}
+
 // ============ END LOCALE MANAGEMENT ============
 
+// ============ Logging ============
+
+#[tauri::command]
+async fn get_log_level() -> Result<String, AppError> {
+    Ok(logging::get_level().to_string().to_lowercase())
+}
+
+// Change verbosity immediately and persist it for the next launch
+#[tauri::command]
+async fn set_log_level(level: String) -> Result<(), AppError> {
+    let filter =
+        logging::parse_level(&level).ok_or_else(|| format!("Unknown log level: {}", level))?;
+    logging::set_level(filter);
+
+    let mut config = load_config();
+    config["log_level"] = serde_json::json!(filter.to_string().to_lowercase());
+    save_config(&config);
+
+    app_log!("[LOG] Log level set to {}", filter);
+    Ok(())
+}
+
+// Recent log lines for the settings log console
+#[tauri::command]
+async fn read_log_lines(
+    count: usize,
+    level: Option<String>,
+) -> Result<Vec<logging::LogLine>, AppError> {
+    let min_level = match level {
+        Some(l) => logging::parse_level(&l).ok_or_else(|| format!("Unknown log level: {}", l))?,
+        None => simplelog::LevelFilter::Trace,
+    };
+    Ok(logging::tail(count.min(5000), min_level)?)
+}
+
+// Replace secrets and the user's home directory so the bundle is safe to post publicly
+fn sanitize_config_value(value: &mut serde_json::Value, home: &str) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, child) in map.iter_mut() {
+                let lower = key.to_lowercase();
+                if ["token", "password", "secret", "auth"]
+                    .iter()
+                    .any(|s| lower.contains(s))
+                {
+                    *child = serde_json::json!("<redacted>");
+                } else {
+                    sanitize_config_value(child, home);
+                }
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for item in items {
+                sanitize_config_value(item, home);
+            }
+        }
+        serde_json::Value::String(s) if !home.is_empty() && s.contains(home) => {
+            *s = s.replace(home, "~");
+        }
+        _ => {}
+    }
+}
+
+fn library_stats() -> serde_json::Value {
+    let album_dir = match get_album_folder() {
+        Ok(dir) => dir,
+        Err(e) => return serde_json::json!({ "error": e }),
+    };
+
+    let mut file_count = 0u64;
+    let mut total_bytes = 0u64;
+    if let Ok(entries) = std::fs::read_dir(&album_dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let is_midi = path
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase() == "mid")
+                .unwrap_or(false);
+            if is_midi {
+                file_count += 1;
+                total_bytes += entry.metadata().map(|m| m.len()).unwrap_or(0);
+            }
+        }
+    }
+
+    let cache = load_metadata_cache();
+    let repaired = cache.files.values().filter(|m| m.repaired).count();
+
+    serde_json::json!({
+        "album_exists": album_dir.exists(),
+        "custom_album_path": ALBUM_PATH.read().map(|p| p.is_some()).unwrap_or(false),
+        "midi_files": file_count,
+        "total_bytes": total_bytes,
+        "cached_entries": cache.files.len(),
+        "repaired_files": repaired,
+    })
+}
+
+// Last panic report, if one hasn't been dismissed yet
+#[tauri::command]
+async fn get_crash_report() -> Result<Option<crash::CrashReport>, AppError> {
+    Ok(crash::get_pending())
+}
+
+#[tauri::command]
+async fn dismiss_crash_report() -> Result<(), AppError> {
+    crash::dismiss_pending();
+    Ok(())
+}
+
+// Zip logs, sanitized config and environment info for bug reports
+#[tauri::command]
+async fn export_diagnostics(export_path: String) -> Result<(), AppError> {
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    let home = std::env::var("USERPROFILE")
+        .or_else(|_| std::env::var("HOME"))
+        .unwrap_or_default();
+
+    let file = std::fs::File::create(&export_path)
+        .map_err(|e| format!("Failed to create zip file: {}", e))?;
+    let mut zip = zip::ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+    // Logs (current + rotated)
+    for log_file in logging::log_files() {
+        let name = log_file
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("wwm-overlay.log")
+            .to_string();
+        match std::fs::read_to_string(&log_file) {
+            Ok(content) => {
+                let content = if home.is_empty() {
+                    content
+                } else {
+                    content.replace(&home, "~")
+                };
+                zip.start_file(format!("logs/{}", name), options)
+                    .map_err(|e| format!("Failed to add log to zip: {}", e))?;
+                zip.write_all(content.as_bytes())
+                    .map_err(|e| format!("Failed to write log: {}", e))?;
+            }
+            Err(e) => app_log!("[DIAG] Failed to read {}: {}", name, e),
+        }
+    }
+
+    // Sanitized config
+    let mut config = load_config();
+    sanitize_config_value(&mut config, &home);
+    let config_json = serde_json::to_string_pretty(&config)
+        .map_err(|e| format!("Failed to serialize config: {}", e))?;
+    zip.start_file("config.json", options)
+        .map_err(|e| format!("Failed to add config to zip: {}", e))?;
+    zip.write_all(config_json.as_bytes())
+        .map_err(|e| format!("Failed to write config: {}", e))?;
+
+    // Environment, library and input state
+    let game_window = keyboard::get_game_window_rect()
+        .map(|(x, y, width, height)| serde_json::json!([x, y, width, height]));
+    let hotkeys: Vec<serde_json::Value> = HOTKEY_STATUS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(name, ok)| serde_json::json!({ "hotkey": name, "registered": ok }))
+        .collect();
+
+    let info = serde_json::json!({
+        "generated_at": chrono_now(),
+        "app_version": env!("CARGO_PKG_VERSION"),
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+        "webview_version": tauri::webview_version().ok(),
+        "log_level": logging::get_level().to_string(),
+        "library": library_stats(),
+        "game_window": {
+            "found": keyboard::is_game_window_found(),
+            "focused": keyboard::is_wwm_focused().ok(),
+            "rect": game_window,
+            "custom_keywords": keyboard::get_custom_window_keywords(),
+        },
+        "input": {
+            "cloud_mode": keyboard::get_send_input_mode(),
+            "modifier_delay_ms": keyboard::get_modifier_delay(),
+        },
+        "hotkeys": hotkeys,
+    });
+    let info_json = serde_json::to_string_pretty(&info)
+        .map_err(|e| format!("Failed to serialize diagnostics: {}", e))?;
+    zip.start_file("diagnostics.json", options)
+        .map_err(|e| format!("Failed to add diagnostics to zip: {}", e))?;
+    zip.write_all(info_json.as_bytes())
+        .map_err(|e| format!("Failed to write diagnostics: {}", e))?;
+
+    zip.finish()
+        .map_err(|e| format!("Failed to finalize zip: {}", e))?;
+
+    app_log!("[DIAG] Diagnostics exported to {}", export_path);
+    Ok(())
+}
+
+// Startup self-check; refresh re-runs it (e.g. after starting the game)
+#[tauri::command]
+async fn get_health(
+    app_handle: tauri::AppHandle,
+    refresh: Option<bool>,
+) -> Result<health::HealthReport, AppError> {
+    if !refresh.unwrap_or(false) {
+        return Ok(health::last());
+    }
+    let report = health::run();
+    let _ = app_handle.emit("health-report", &report);
+    Ok(report)
+}
+
 // Band mode: Read MIDI file as base64 for transfer
 #[tauri::command]
-async fn read_midi_base64(path: String) -> Result<String, String> {
+async fn read_midi_base64(path: String) -> Result<String, AppError> {
     use base64::{engine::general_purpose::STANDARD, Engine as _};
 
-    let data = std::fs::read(&path).map_err(|e| format!("Failed to read file: {}", e))?;
+    let data = std::fs::read(&path).map_err(|e| AppError::io("read file", e))?;
 
     // Verify it's a valid MIDI file (starts with "MThd")
     if data.len() < 4 || &data[0..4] != b"MThd" {
-        return Err("Not a valid MIDI file".to_string());
+        return Err(AppError::InvalidMidi("Not a valid MIDI file".to_string()));
     }
 
     Ok(STANDARD.encode(&data))
@@ -1680,7 +3883,7 @@ async fn read_midi_base64(path: String) -> Result<String, String> {
 
 // Band mode: Check if MIDI file exists in album folder by name
 #[tauri::command]
-async fn check_midi_exists(filename: String) -> Result<Option<String>, String> {
+async fn check_midi_exists(filename: String) -> Result<Option<String>, AppError> {
     let album_path = get_album_folder()?;
     let file_path = album_path.join(&filename);
 
@@ -1699,7 +3902,7 @@ async fn check_file_exists(file_path: String) -> bool {
 
 // Band mode: Save MIDI file to temp for playback
 #[tauri::command]
-async fn save_temp_midi(filename: String, data_base64: String) -> Result<String, String> {
+async fn save_temp_midi(filename: String, data_base64: String) -> Result<String, AppError> {
     use base64::{engine::general_purpose::STANDARD, Engine as _};
 
     let data = STANDARD
@@ -1709,12 +3912,17 @@ async fn save_temp_midi(filename: String, data_base64: String) -> Result<String,
     // SECURITY: Check for executable signatures first
     if let Some(exe_type) = is_executable_data(&data) {
         println!("[SECURITY] BLOCKED temp save: {} detected", exe_type);
-        return Err(format!("Security: Blocked {} - refusing to save", exe_type));
+        return Err(AppError::InvalidMidi(format!(
+            "Security: Blocked {} - refusing to save",
+            exe_type
+        )));
     }
 
     // Verify it's a valid MIDI file (must start with MThd)
     if data.len() < 4 || &data[0..4] != b"MThd" {
-        return Err("Not a valid MIDI file (missing MThd header)".to_string());
+        return Err(AppError::InvalidMidi(
+            "Not a valid MIDI file (missing MThd header)".to_string(),
+        ));
     }
 
     // Sanitize filename to prevent path traversal
@@ -1724,7 +3932,7 @@ async fn save_temp_midi(filename: String, data_base64: String) -> Result<String,
         .collect();
 
     if safe_filename.is_empty() {
-        return Err("Invalid filename".to_string());
+        return Err(AppError::InvalidInput("Invalid filename".to_string()));
     }
 
     // Save to temp directory
@@ -1732,7 +3940,7 @@ async fn save_temp_midi(filename: String, data_base64: String) -> Result<String,
     std::fs::create_dir_all(&temp_dir).map_err(|e| format!("Failed to create temp dir: {}", e))?;
 
     let temp_path = temp_dir.join(&safe_filename);
-    std::fs::write(&temp_path, &data).map_err(|e| format!("Failed to write temp file: {}", e))?;
+    std::fs::write(&temp_path, &data).map_err(|e| AppError::io("write temp file", e))?;
 
     Ok(temp_path.to_string_lossy().to_string())
 }
@@ -1797,10 +4005,8 @@ fn is_executable_data(data: &[u8]) -> Option<&'static str> {
 
     // Check for PE header in first 1KB (embedded executables)
     let check_len = 1024.min(data.len());
-    for i in 0..check_len.saturating_sub(4) {
-        if &data[i..i + 4] == b"PE\x00\x00" {
-            return Some("Embedded PE executable");
-        }
+    if data[..check_len].windows(4).any(|w| w == b"PE\x00\x00") {
+        return Some("Embedded PE executable");
     }
 
     None
@@ -1808,17 +4014,24 @@ fn is_executable_data(data: &[u8]) -> Option<&'static str> {
 
 // Verify MIDI data is valid (for P2P library safety)
 #[tauri::command]
-async fn verify_midi_data(data_base64: String) -> Result<bool, String> {
+async fn verify_midi_data(data_base64: String) -> Result<bool, AppError> {
     use base64::{engine::general_purpose::STANDARD, Engine as _};
 
     let data = STANDARD
         .decode(&data_base64)
         .map_err(|e| format!("Failed to decode base64: {}", e))?;
+    check_midi_bytes(&data)
+}
 
+// The checks behind verify_midi_data: Ok(true) only for a well-formed MIDI file
+fn check_midi_bytes(data: &[u8]) -> Result<bool, AppError> {
     // SECURITY: Check for executable signatures first
-    if let Some(exe_type) = is_executable_data(&data) {
+    if let Some(exe_type) = is_executable_data(data) {
         println!("[SECURITY] BLOCKED: {} detected in received file", exe_type);
-        return Err(format!("Security: Blocked {} - not a MIDI file", exe_type));
+        return Err(AppError::InvalidMidi(format!(
+            "Security: Blocked {} - not a MIDI file",
+            exe_type
+        )));
     }
 
     // Check minimum size for valid MIDI
@@ -1858,8 +4071,13 @@ async fn verify_midi_data(data_base64: String) -> Result<bool, String> {
         return Ok(false);
     }
 
+    if !midi::has_valid_timing(data) {
+        println!("[SECURITY] Rejected: Invalid MIDI timing");
+        return Ok(false);
+    }
+
     // Final validation: parse with midly to ensure it's valid MIDI structure
-    match midly::Smf::parse(&data) {
+    match midly::Smf::parse(data) {
         Ok(_) => Ok(true),
         Err(e) => {
             println!("[SECURITY] Rejected: Invalid MIDI structure - {}", e);
@@ -1868,17 +4086,235 @@ async fn verify_midi_data(data_base64: String) -> Result<bool, String> {
     }
 }
 
-// Save MIDI file to album folder (for P2P library)
-#[tauri::command]
-async fn save_midi_from_base64(filename: String, data_base64: String) -> Result<String, String> {
-    use base64::{engine::general_purpose::STANDARD, Engine as _};
+fn sha256_hex(data: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
 
-    let data = STANDARD
-        .decode(&data_base64)
-        .map_err(|e| format!("Failed to decode base64: {}", e))?;
+lazy_static::lazy_static! {
+    // path -> (mtime, sha256), so re-publishing the library doesn't re-hash every file
+    static ref SHA256_CACHE: Mutex<std::collections::HashMap<String, (u64, String)>> =
+        Mutex::new(std::collections::HashMap::new());
+}
+
+fn file_sha256(path: &str) -> Option<String> {
+    let mtime = std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?
+        .as_secs();
+    if let Some((cached_mtime, sha)) = SHA256_CACHE.lock().unwrap().get(path) {
+        if *cached_mtime == mtime {
+            return Some(sha.clone());
+        }
+    }
+    let sha = sha256_hex(&std::fs::read(path).ok()?);
+    SHA256_CACHE
+        .lock()
+        .unwrap()
+        .insert(path.to_string(), (mtime, sha.clone()));
+    Some(sha)
+}
+
+// Song list to publish to the discovery server, with SHA-256 for transfer verification
+// and playability info (density, tracks, difficulty, tags) for filtering.
+// Only what the sharing policy allows is included.
+async fn shared_songs() -> Result<Vec<discovery::SharedSong>, String> {
+    let policy = discovery::load_sharing_policy();
+    if policy.scope == discovery::ShareScope::None {
+        return Ok(Vec::new());
+    }
+    let allowed: std::collections::HashSet<String> = match policy.scope {
+        discovery::ShareScope::Favorites => favorites::list().into_iter().map(|f| f.hash).collect(),
+        discovery::ShareScope::Playlists => policy
+            .playlist_ids
+            .iter()
+            .filter_map(|id| playlists::get(id))
+            .flat_map(|p| p.tracks.into_iter().map(|t| t.hash))
+            .collect(),
+        _ => std::collections::HashSet::new(),
+    };
+
+    let files = load_midi_files().await?;
+    let meta = song_meta::load_all();
+    let songs = files
+        .into_par_iter()
+        .map(|f| discovery::SharedSong {
+            sha256: file_sha256(&f.path),
+            track_count: midi::get_midi_tracks(&f.path)
+                .map(|tracks| tracks.iter().filter(|t| t.note_count > 0).count() as u32)
+                .unwrap_or(0),
+            tags: meta
+                .get(&f.hash)
+                .map(|m| m.tags.clone())
+                .unwrap_or_default(),
+            note_density: f.note_density,
+            difficulty: f.difficulty,
+            name: f.name,
+            hash: f.hash,
+            duration: f.duration,
+            bpm: f.bpm,
+            size: f.size,
+        })
+        .collect();
+    Ok(discovery::apply_sharing_policy(songs, &policy, &allowed))
+}
+
+#[tauri::command]
+async fn get_shared_songs() -> Result<Vec<discovery::SharedSong>, AppError> {
+    Ok(shared_songs().await?)
+}
+
+#[tauri::command]
+async fn get_sharing_policy() -> Result<discovery::SharingPolicy, AppError> {
+    Ok(discovery::load_sharing_policy())
+}
+
+#[tauri::command]
+async fn set_sharing_policy(policy: discovery::SharingPolicy) -> Result<(), AppError> {
+    discovery::save_sharing_policy(&policy);
+    println!("[DISCOVERY] Sharing policy set to: {:?}", policy);
+    Ok(())
+}
+
+// Register/heartbeat from the backend, with the song list built under the sharing policy
+#[tauri::command]
+async fn discovery_register(
+    server_url: String,
+    peer_id: String,
+    webrtc_id: Option<String>,
+    name: String,
+    heartbeat: Option<bool>,
+) -> Result<usize, AppError> {
+    let request = discovery::RegisterRequest {
+        peer_id,
+        webrtc_id,
+        name,
+        songs: shared_songs().await?,
+    };
+    let count = request.songs.len();
+    tokio::task::spawn_blocking(move || {
+        if heartbeat.unwrap_or(false) {
+            discovery::send_heartbeat(&server_url, &request)
+        } else {
+            discovery::send_register(&server_url, &request)
+        }
+    })
+    .await
+    .map_err(|e| format!("Failed to register: {}", e))??;
+    Ok(count)
+}
+
+#[tauri::command]
+async fn discovery_unregister(server_url: String, peer_id: String) -> Result<(), AppError> {
+    tokio::task::spawn_blocking(move || discovery::send_unregister(&server_url, &peer_id))
+        .await
+        .map_err(|e| format!("Failed to unregister: {}", e))?
+}
+
+// Queue songs from other players; the backend caps concurrent transfers and retries
+#[tauri::command]
+async fn queue_downloads(
+    requests: Vec<downloads::DownloadRequest>,
+    app_handle: AppHandle,
+) -> Result<downloads::DownloadProgress, AppError> {
+    Ok(
+        tokio::task::spawn_blocking(move || downloads::enqueue(&app_handle, requests))
+            .await
+            .map_err(|e| format!("Failed to queue downloads: {}", e))?,
+    )
+}
+
+// The UI fetched a peer job over WebRTC
+#[tauri::command]
+async fn p2p_download_complete(
+    job_id: u64,
+    data_base64: String,
+    app_handle: AppHandle,
+) -> Result<String, AppError> {
+    use base64::{engine::general_purpose::STANDARD, Engine as _};
+
+    let data = match STANDARD.decode(&data_base64) {
+        Ok(data) => data,
+        Err(e) => {
+            let error = format!("Failed to decode base64: {}", e);
+            downloads::fail(&app_handle, job_id, &error);
+            return Err(error.into());
+        }
+    };
+    Ok(downloads::complete(&app_handle, job_id, &data)?)
+}
+
+#[tauri::command]
+async fn p2p_download_failed(job_id: u64, error: String, app_handle: AppHandle) {
+    downloads::fail(&app_handle, job_id, &error);
+}
+
+#[tauri::command]
+async fn get_download_queue() -> Vec<downloads::DownloadJob> {
+    downloads::jobs()
+}
+
+#[tauri::command]
+async fn cancel_downloads(app_handle: AppHandle) {
+    downloads::cancel(&app_handle);
+}
+
+#[tauri::command]
+async fn set_download_concurrency(limit: usize) -> Result<usize, AppError> {
+    if limit == 0 || limit > downloads::MAX_CONCURRENCY {
+        return Err(AppError::InvalidInput(format!(
+            "Concurrency must be between 1 and {}",
+            downloads::MAX_CONCURRENCY
+        )));
+    }
+    downloads::set_concurrency(limit);
+    Ok(downloads::get_concurrency())
+}
+
+// Save MIDI file to album folder (for P2P library)
+#[tauri::command]
+async fn save_midi_from_base64(
+    filename: String,
+    data_base64: String,
+    expected_sha256: Option<String>,
+) -> Result<String, AppError> {
+    use base64::{engine::general_purpose::STANDARD, Engine as _};
+
+    let data = STANDARD
+        .decode(&data_base64)
+        .map_err(|e| format!("Failed to decode base64: {}", e))?;
+    Ok(save_midi_to_album(
+        &filename,
+        &data,
+        expected_sha256.as_deref(),
+    )?)
+}
+
+/// Validate received MIDI data and write it into the album folder under a free name
+fn save_midi_to_album(
+    filename: &str,
+    data: &[u8],
+    expected_sha256: Option<&str>,
+) -> Result<String, String> {
+    // Catch tampered or truncated transfers against the sender's published checksum
+    if let Some(expected) = expected_sha256.filter(|s| !s.is_empty()) {
+        let actual = sha256_hex(data);
+        if !actual.eq_ignore_ascii_case(expected.trim()) {
+            println!(
+                "[SECURITY] BLOCKED save: checksum mismatch for {} (expected {}, got {})",
+                filename, expected, actual
+            );
+            return Err("Checksum mismatch - transfer was corrupted or tampered with".to_string());
+        }
+    }
 
     // SECURITY: Check for executable signatures first
-    if let Some(exe_type) = is_executable_data(&data) {
+    if let Some(exe_type) = is_executable_data(data) {
         println!("[SECURITY] BLOCKED save: {} detected", exe_type);
         return Err(format!("Security: Blocked {} - refusing to save", exe_type));
     }
@@ -1889,7 +4325,10 @@ async fn save_midi_from_base64(filename: String, data_base64: String) -> Result<
     }
 
     // Try to parse to ensure it's valid MIDI structure
-    midly::Smf::parse(&data).map_err(|e| format!("Invalid MIDI file: {}", e))?;
+    if !midi::has_valid_timing(data) {
+        return Err("Invalid MIDI file: bad timing".to_string());
+    }
+    midly::Smf::parse(data).map_err(|e| format!("Invalid MIDI file: {}", e))?;
 
     // Get album folder
     let album_dir = get_album_folder()?;
@@ -1916,18 +4355,18 @@ async fn save_midi_from_base64(filename: String, data_base64: String) -> Result<
         counter += 1;
     }
 
-    std::fs::write(&save_path, &data).map_err(|e| format!("Failed to save file: {}", e))?;
+    std::fs::write(&save_path, data).map_err(|e| AppError::io("save file", e))?;
 
     Ok(save_path.to_string_lossy().to_string())
 }
 
 // Rename a MIDI file
 #[tauri::command]
-async fn rename_midi_file(old_path: String, new_name: String) -> Result<String, String> {
+async fn rename_midi_file(old_path: String, new_name: String) -> Result<String, AppError> {
     let source = std::path::Path::new(&old_path);
 
     if !source.exists() {
-        return Err("File not found".to_string());
+        return Err(AppError::FileNotFound("File not found".to_string()));
     }
 
     // Sanitize the new name
@@ -1937,7 +4376,7 @@ async fn rename_midi_file(old_path: String, new_name: String) -> Result<String,
         .collect();
 
     if safe_name.is_empty() {
-        return Err("Invalid filename".to_string());
+        return Err(AppError::InvalidInput("Invalid filename".to_string()));
     }
 
     // Ensure .mid extension
@@ -1953,41 +4392,46 @@ async fn rename_midi_file(old_path: String, new_name: String) -> Result<String,
 
     // Check if target already exists
     if new_path.exists() && new_path != source {
-        return Err("A file with that name already exists".to_string());
+        return Err(AppError::InvalidInput(
+            "A file with that name already exists".to_string(),
+        ));
     }
 
-    std::fs::rename(&source, &new_path).map_err(|e| format!("Failed to rename: {}", e))?;
+    std::fs::rename(&source, &new_path).map_err(|e| AppError::io("rename", e))?;
 
     Ok(new_path.to_string_lossy().to_string())
 }
 
 // Delete a MIDI file
 #[tauri::command]
-async fn delete_midi_file(path: String) -> Result<(), String> {
-    let file_path = std::path::Path::new(&path);
+async fn delete_midi_file(path: String) -> Result<(), AppError> {
+    let file_path = paths::long(&path);
 
     if !file_path.exists() {
-        return Err("File not found".to_string());
+        return Err(AppError::FileNotFound("File not found".to_string()));
     }
 
-    // Verify it's in the album folder for safety
+    // Verify it's in the album folder for safety (UNC and \\?\ forms included)
     let album_dir = get_album_folder()?;
-    if !file_path.starts_with(&album_dir) {
-        return Err("Can only delete files in album folder".to_string());
+    if !paths::is_inside(&file_path, &album_dir) {
+        return Err(AppError::InvalidInput(
+            "Can only delete files in album folder".to_string(),
+        ));
     }
 
-    std::fs::remove_file(&file_path).map_err(|e| format!("Failed to delete: {}", e))?;
+    paths::retry("delete file", || std::fs::remove_file(&file_path))
+        .map_err(|e| AppError::io("delete", e))?;
 
     Ok(())
 }
 
 // Open file location in explorer
 #[tauri::command]
-async fn open_file_location(path: String) -> Result<(), String> {
+async fn open_file_location(path: String) -> Result<(), AppError> {
     let file_path = std::path::Path::new(&path);
 
     if !file_path.exists() {
-        return Err("File not found".to_string());
+        return Err(AppError::FileNotFound("File not found".to_string()));
     }
 
     #[cfg(target_os = "windows")]
@@ -2001,6 +4445,278 @@ async fn open_file_location(path: String) -> Result<(), String> {
     Ok(())
 }
 
+// ============ MIDI Editing ============
+
+// Build a library entry for a freshly written file
+fn midi_file_info(path: &std::path::Path) -> MidiFile {
+    let path_str = path.to_string_lossy().to_string();
+    let name = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("Unknown")
+        .to_string();
+    let meta = midi::get_midi_metadata(&path_str).unwrap_or_default();
+    let size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    let hash = compute_file_hash(path).unwrap_or_else(|| format!("{:x}", size));
+
+    MidiFile {
+        name,
+        path: path_str,
+        duration: meta.duration,
+        bpm: meta.bpm,
+        note_density: meta.note_density,
+        difficulty: meta.difficulty,
+        tempo: meta.tempo,
+        repaired: meta.repaired,
+        note_count: meta.note_count,
+        track_count: meta.track_count,
+        multi_channel: meta.multi_channel,
+        hash,
+        size,
+    }
+}
+
+// Resolve a user-supplied name to a new .mid path in the album folder
+fn album_output_path(new_name: &str) -> Result<std::path::PathBuf, String> {
+    let safe_name: String = new_name
+        .chars()
+        .filter(|c| !['/', '\\', ':', '*', '?', '"', '<', '>', '|'].contains(c))
+        .collect();
+    let safe_name = safe_name.trim();
+
+    if safe_name.is_empty() {
+        return Err("Invalid filename".to_string());
+    }
+
+    let final_name = if safe_name.to_lowercase().ends_with(".mid") {
+        safe_name.to_string()
+    } else {
+        format!("{}.mid", safe_name)
+    };
+
+    let album_path = get_album_folder()?;
+    if !album_path.exists() {
+        std::fs::create_dir_all(&album_path).map_err(|e| e.to_string())?;
+    }
+
+    let dest = album_path.join(&final_name);
+    if dest.exists() {
+        return Err("A file with that name already exists".to_string());
+    }
+    Ok(dest)
+}
+
+// Save the section between start_sec and end_sec as a new file in the album
+#[tauri::command]
+async fn export_section(
+    path: String,
+    start_sec: f64,
+    end_sec: f64,
+    new_name: String,
+) -> Result<MidiFile, AppError> {
+    if start_sec < 0.0 || end_sec <= start_sec {
+        return Err(AppError::InvalidInput("Invalid section range".to_string()));
+    }
+
+    let dest = album_output_path(&new_name)?;
+    midi_edit::export_section(&path, &dest, start_sec * 1000.0, end_sec * 1000.0)?;
+
+    app_log!(
+        "[EDIT] Exported {:.1}s-{:.1}s of {} to {}",
+        start_sec,
+        end_sec,
+        path,
+        dest.display()
+    );
+    Ok(midi_file_info(&dest))
+}
+
+// Split a long file into "<name> - Part N" files no longer than max_duration seconds,
+// cutting at pauses where possible
+#[tauri::command]
+async fn split_midi(path: String, max_duration: f64) -> Result<Vec<MidiFile>, AppError> {
+    if max_duration < 10.0 {
+        return Err(AppError::InvalidInput(
+            "Parts must be at least 10 seconds long".to_string(),
+        ));
+    }
+    let cuts = midi_edit::find_split_points(&path, max_duration * 1000.0)?;
+    if cuts.is_empty() {
+        return Err(AppError::InvalidInput(
+            "File is already shorter than the part length".to_string(),
+        ));
+    }
+
+    let stem = std::path::Path::new(&path)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("Song")
+        .to_string();
+    let duration_ms = midi::get_midi_metadata(&path)?.duration * 1000.0;
+
+    let mut bounds = vec![0.0];
+    bounds.extend(&cuts);
+    bounds.push(duration_ms);
+
+    // Check every name before writing anything so a clash doesn't leave half the parts behind
+    let dests = (1..bounds.len())
+        .map(|part| album_output_path(&format!("{} - Part {}", stem, part)))
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let mut parts = Vec::new();
+    for (dest, range) in dests.iter().zip(bounds.windows(2)) {
+        midi_edit::export_section(&path, dest, range[0], range[1])?;
+        parts.push(midi_file_info(dest));
+    }
+
+    app_log!(
+        "[EDIT] Split {} into {} parts of at most {:.0}s",
+        path,
+        parts.len(),
+        max_duration
+    );
+    Ok(parts)
+}
+
+// Save a copy with notes already mapped into the instrument range
+// Modes default to the current player settings
+#[tauri::command]
+async fn bake_midi(
+    path: String,
+    new_name: String,
+    note_mode: Option<midi::NoteMode>,
+    key_mode: Option<midi::KeyMode>,
+    octave_shift: Option<i8>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<MidiFile, AppError> {
+    let (note_mode, key_mode, octave_shift) = {
+        let app_state = state.lock().unwrap();
+        (
+            note_mode.unwrap_or_else(|| app_state.get_note_mode()),
+            key_mode.unwrap_or_else(|| app_state.get_key_mode()),
+            octave_shift.unwrap_or_else(|| app_state.get_octave_shift()),
+        )
+    };
+
+    let dest = album_output_path(&new_name)?;
+    midi_edit::bake_midi(&path, &dest, key_mode, note_mode, octave_shift)?;
+
+    app_log!(
+        "[EDIT] Baked {} ({:?}, {:?}, octave {}) to {}",
+        path,
+        key_mode,
+        note_mode,
+        octave_shift,
+        dest.display()
+    );
+    Ok(midi_file_info(&dest))
+}
+
+// Save a copy without the selected tracks (ids from get_midi_tracks)
+#[tauri::command]
+async fn strip_midi_tracks(
+    path: String,
+    track_ids: Vec<usize>,
+    new_name: String,
+) -> Result<MidiFile, AppError> {
+    if track_ids.is_empty() {
+        return Err(AppError::InvalidInput("No tracks selected".to_string()));
+    }
+
+    let dest = album_output_path(&new_name)?;
+    midi_edit::strip_tracks(&path, &dest, &track_ids)?;
+
+    app_log!(
+        "[EDIT] Removed tracks {:?} from {} -> {}",
+        track_ids,
+        path,
+        dest.display()
+    );
+    Ok(midi_file_info(&dest))
+}
+
+// Save a copy with the selected tracks merged into one
+#[tauri::command]
+async fn merge_midi_tracks(
+    path: String,
+    track_ids: Vec<usize>,
+    new_name: String,
+) -> Result<MidiFile, AppError> {
+    let dest = album_output_path(&new_name)?;
+    midi_edit::merge_tracks(&path, &dest, &track_ids)?;
+
+    app_log!(
+        "[EDIT] Merged tracks {:?} of {} -> {}",
+        track_ids,
+        path,
+        dest.display()
+    );
+    Ok(midi_file_info(&dest))
+}
+
+// Save a copy with a rewritten tempo map
+// Pass either a speed factor (1.5 = 50% faster) or a target BPM for the song's average tempo
+#[tauri::command]
+async fn change_tempo(
+    path: String,
+    factor: Option<f64>,
+    bpm: Option<f64>,
+    new_name: String,
+) -> Result<MidiFile, AppError> {
+    let factor = match (factor, bpm) {
+        (Some(f), None) => f,
+        (None, Some(target)) => {
+            // Every tempo is scaled by the same factor, so the time-weighted average
+            // lands on the target even when the song changes tempo
+            let meta = midi::get_midi_metadata(&path)?;
+            if meta.tempo.bpm_avg <= 0.0 {
+                return Err(AppError::InvalidMidi(
+                    "Could not read the current tempo".to_string(),
+                ));
+            }
+            target / meta.tempo.bpm_avg as f64
+        }
+        _ => {
+            return Err(AppError::InvalidInput(
+                "Specify either a factor or a BPM".to_string(),
+            ))
+        }
+    };
+
+    let dest = album_output_path(&new_name)?;
+    midi_edit::change_tempo(&path, &dest, factor)?;
+
+    app_log!(
+        "[EDIT] Tempo x{:.2} for {} -> {}",
+        factor,
+        path,
+        dest.display()
+    );
+    Ok(midi_file_info(&dest))
+}
+
+// Save a cleaned copy: notes snapped to a grid, duplicates and empty tracks removed
+// grid: steps per beat (4 = 16th notes, default)
+#[tauri::command]
+async fn cleanup_midi(
+    path: String,
+    new_name: String,
+    grid: Option<u32>,
+) -> Result<MidiFile, AppError> {
+    let dest = album_output_path(&new_name)?;
+    let stats = midi_edit::cleanup_midi(&path, &dest, grid.unwrap_or(4))?;
+
+    app_log!(
+        "[EDIT] Cleaned {} -> {} ({} zero-length, {} duplicates, {} empty tracks removed)",
+        path,
+        dest.display(),
+        stats.zero_length_removed,
+        stats.duplicates_removed,
+        stats.empty_tracks_removed
+    );
+    Ok(midi_file_info(&dest))
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct WindowPosition {
     x: i32,
@@ -2009,42 +4725,85 @@ struct WindowPosition {
     height: u32,
 }
 
+// Saved in logical pixels ("logical": true); older configs hold physical ones
+fn saved_window_bounds() -> Option<(overlay::LogicalBounds, bool)> {
+    let pos = load_config().get("window_position")?.clone();
+    let bounds = overlay::LogicalBounds {
+        x: pos["x"].as_f64()?,
+        y: pos["y"].as_f64()?,
+        width: pos["width"].as_f64()?,
+        height: pos["height"].as_f64()?,
+    };
+    Some((bounds, pos["logical"].as_bool().unwrap_or(false)))
+}
+
+// In physical pixels at the window's current scale, as the UI applies them
 #[tauri::command]
-async fn get_window_position() -> Result<Option<WindowPosition>, String> {
-    let config = load_config();
-    if let Some(pos) = config.get("window_position") {
-        if let (Some(x), Some(y), Some(w), Some(h)) = (
-            pos["x"].as_i64(),
-            pos["y"].as_i64(),
-            pos["width"].as_u64(),
-            pos["height"].as_u64(),
-        ) {
-            return Ok(Some(WindowPosition {
-                x: x as i32,
-                y: y as i32,
-                width: w as u32,
-                height: h as u32,
-            }));
-        }
-    }
-    Ok(None)
+async fn get_window_position(window: Window) -> Result<Option<WindowPosition>, AppError> {
+    let Some((bounds, logical)) = saved_window_bounds() else {
+        return Ok(None);
+    };
+    let scale = if logical {
+        window.scale_factor().unwrap_or(1.0)
+    } else {
+        1.0
+    };
+    Ok(Some(WindowPosition {
+        x: (bounds.x * scale).round() as i32,
+        y: (bounds.y * scale).round() as i32,
+        width: (bounds.width * scale).round() as u32,
+        height: (bounds.height * scale).round() as u32,
+    }))
 }
 
+// Takes physical pixels, as the UI reads them, and stores them as logical ones
 #[tauri::command]
-async fn save_window_position(x: i32, y: i32, width: u32, height: u32) -> Result<(), String> {
+async fn save_window_position(
+    window: Window,
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+) -> Result<(), AppError> {
+    let scale = window.scale_factor().unwrap_or(1.0);
     let mut config = load_config();
     config["window_position"] = serde_json::json!({
-        "x": x,
-        "y": y,
-        "width": width,
-        "height": height
+        "x": x as f64 / scale,
+        "y": y as f64 / scale,
+        "width": width as f64 / scale,
+        "height": height as f64 / scale,
+        "logical": true
     });
     save_config(&config);
     Ok(())
 }
 
+// Put the window back where it was saved, converted for the monitor it lands on
+#[tauri::command]
+async fn restore_window_position(window: Window) -> Result<bool, AppError> {
+    let Some((mut bounds, logical)) = saved_window_bounds() else {
+        return Ok(false);
+    };
+    if !logical {
+        let scale = window.scale_factor().unwrap_or(1.0);
+        bounds = overlay::LogicalBounds {
+            x: bounds.x / scale,
+            y: bounds.y / scale,
+            width: bounds.width / scale,
+            height: bounds.height / scale,
+        };
+    }
+    overlay::apply(&window, bounds)?;
+    Ok(true)
+}
+
+#[tauri::command]
+async fn set_mini_mode(window: Window, enabled: bool) -> Result<(), AppError> {
+    Ok(overlay::set_mini_mode(&window, enabled)?)
+}
+
 #[tauri::command]
-async fn get_game_window_bounds() -> Result<Option<WindowPosition>, String> {
+async fn get_game_window_bounds() -> Result<Option<WindowPosition>, AppError> {
     #[cfg(target_os = "windows")]
     {
         if let Some((x, y, width, height)) = keyboard::get_game_window_rect() {
@@ -2059,49 +4818,190 @@ async fn get_game_window_bounds() -> Result<Option<WindowPosition>, String> {
     Ok(None)
 }
 
-#[tauri::command]
-async fn get_always_on_top() -> Result<bool, String> {
+fn load_saved_topmost() {
     let config = load_config();
-    Ok(config["always_on_top"].as_bool().unwrap_or(true))
+    if let Some(settings) = config
+        .get("topmost")
+        .and_then(|v| serde_json::from_value::<overlay::TopmostSettings>(v.clone()).ok())
+    {
+        overlay::set_topmost_settings(settings);
+    } else if config["always_on_top"].as_bool() == Some(false) {
+        // Before per-layout modes there was one switch
+        overlay::set_topmost_settings(overlay::TopmostSettings {
+            main: overlay::TopmostMode::Never,
+            mini: overlay::TopmostMode::Never,
+        });
+    }
+}
+
+fn save_topmost(app_handle: &AppHandle, settings: overlay::TopmostSettings) {
+    overlay::set_topmost_settings(settings);
+    overlay::update_topmost(app_handle);
+    let mut config = load_config();
+    config["topmost"] = serde_json::json!(settings);
+    save_config(&config);
+}
+
+fn load_saved_click_through(app_handle: &AppHandle) {
+    let enabled = load_config()["click_through_while_playing"]
+        .as_bool()
+        .unwrap_or(false);
+    overlay::set_click_through_while_playing(app_handle, enabled);
 }
 
 #[tauri::command]
-async fn save_always_on_top(enabled: bool) -> Result<(), String> {
+async fn set_click_through_while_playing(
+    app_handle: AppHandle,
+    enabled: bool,
+) -> Result<(), AppError> {
+    overlay::set_click_through_while_playing(&app_handle, enabled);
     let mut config = load_config();
-    config["always_on_top"] = serde_json::json!(enabled);
+    config["click_through_while_playing"] = serde_json::json!(enabled);
     save_config(&config);
     Ok(())
 }
 
 #[tauri::command]
-async fn get_visualizer_notes(
-    state: State<'_, Arc<Mutex<AppState>>>,
-) -> Result<Vec<VisualizerNote>, String> {
-    let app_state = state.lock().unwrap();
-    Ok(app_state.get_visualizer_notes())
+async fn get_click_through_while_playing() -> Result<bool, AppError> {
+    Ok(overlay::get_click_through_while_playing())
 }
 
-#[tauri::command]
-async fn download_midi_from_url(url: String) -> Result<MidiFile, String> {
-    use std::io::Read;
+#[cfg(target_os = "windows")]
+const OPACITY_HOTKEY_STEP: i16 = 10;
 
-    // Validate URL
-    if !url.starts_with("http://") && !url.starts_with("https://") {
-        return Err("Invalid URL format".to_string());
+fn load_saved_overlay_appearance(app_handle: &AppHandle) {
+    if let Some(appearance) = load_config()
+        .get("overlay_appearance")
+        .and_then(|v| serde_json::from_value::<overlay::Appearance>(v.clone()).ok())
+    {
+        overlay::set_appearance(app_handle, appearance);
     }
+}
 
-    // Try to extract filename from URL
-    let url_path = url.split('?').next().unwrap_or(&url);
-    let filename = url_path
-        .rsplit('/')
-        .next()
-        .filter(|s| !s.is_empty() && s.ends_with(".mid"))
-        .map(|s| s.to_string())
-        .unwrap_or_else(|| {
-            // Generate filename from timestamp if no valid filename in URL
-            let timestamp = std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap_or_default()
+fn save_overlay_appearance(appearance: overlay::Appearance) {
+    let mut config = load_config();
+    config["overlay_appearance"] = serde_json::json!(appearance);
+    save_config(&config);
+}
+
+#[tauri::command]
+async fn get_overlay_appearance() -> Result<overlay::Appearance, AppError> {
+    Ok(overlay::get_appearance())
+}
+
+#[tauri::command]
+async fn set_overlay_opacity(
+    app_handle: AppHandle,
+    opacity: u8,
+) -> Result<overlay::Appearance, AppError> {
+    let mut appearance = overlay::get_appearance();
+    appearance.opacity = opacity;
+    let appearance = overlay::set_appearance(&app_handle, appearance);
+    save_overlay_appearance(appearance);
+    Ok(appearance)
+}
+
+#[tauri::command]
+async fn set_overlay_layout(
+    app_handle: AppHandle,
+    layout: overlay::LayoutPreset,
+) -> Result<overlay::Appearance, AppError> {
+    let mut appearance = overlay::get_appearance();
+    appearance.layout = layout;
+    let appearance = overlay::set_appearance(&app_handle, appearance);
+    save_overlay_appearance(appearance);
+    Ok(appearance)
+}
+
+// Whether the full layout stays on top at all (any mode but never)
+#[tauri::command]
+async fn get_always_on_top() -> Result<bool, AppError> {
+    Ok(overlay::get_topmost_settings().main != overlay::TopmostMode::Never)
+}
+
+#[tauri::command]
+async fn save_always_on_top(app_handle: AppHandle, enabled: bool) -> Result<(), AppError> {
+    let mut settings = overlay::get_topmost_settings();
+    settings.main = if enabled {
+        overlay::TopmostMode::Always
+    } else {
+        overlay::TopmostMode::Never
+    };
+    save_topmost(&app_handle, settings);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_topmost_settings() -> Result<overlay::TopmostSettings, AppError> {
+    Ok(overlay::get_topmost_settings())
+}
+
+#[tauri::command]
+async fn set_topmost_settings(
+    app_handle: AppHandle,
+    settings: overlay::TopmostSettings,
+) -> Result<(), AppError> {
+    save_topmost(&app_handle, settings);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_visualizer_notes(
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<Vec<VisualizerNote>, AppError> {
+    let app_state = state.lock().unwrap();
+    Ok(app_state.get_visualizer_notes())
+}
+
+// Diagnostics: what the backend keeps resident for songs (sizes are estimates)
+#[derive(Debug, Clone, Serialize)]
+struct MemoryStats {
+    parsed_cache: midi::ParsedCacheStats,
+    loaded_song_events: usize,
+    loaded_song_bytes: usize,
+    sha256_cache_entries: usize,
+}
+
+#[tauri::command]
+async fn get_memory_stats(state: State<'_, Arc<Mutex<AppState>>>) -> Result<MemoryStats, AppError> {
+    let (loaded_song_events, loaded_song_bytes) = state.lock().unwrap().loaded_song_size();
+    Ok(MemoryStats {
+        parsed_cache: midi::parsed_cache_stats(),
+        loaded_song_events,
+        loaded_song_bytes,
+        sha256_cache_entries: SHA256_CACHE.lock().unwrap().len(),
+    })
+}
+
+// Drop parsed songs kept for quick reloads (the loaded song stays)
+#[tauri::command]
+async fn clear_memory_caches() -> Result<(), AppError> {
+    midi::clear_parsed_cache();
+    SHA256_CACHE.lock().unwrap().clear();
+    Ok(())
+}
+
+#[tauri::command]
+async fn download_midi_from_url(url: String) -> Result<MidiFile, AppError> {
+    use std::io::Read;
+
+    // Validate URL
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err(AppError::InvalidInput("Invalid URL format".to_string()));
+    }
+
+    // Try to extract filename from URL
+    let url_path = url.split('?').next().unwrap_or(&url);
+    let filename = url_path
+        .rsplit('/')
+        .next()
+        .filter(|s| !s.is_empty() && s.ends_with(".mid"))
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| {
+            // Generate filename from timestamp if no valid filename in URL
+            let timestamp = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
                 .as_secs();
             format!("download_{}.mid", timestamp)
         });
@@ -2114,7 +5014,10 @@ async fn download_midi_from_url(url: String) -> Result<MidiFile, String> {
     // Check content type or status
     let status = response.status();
     if status != 200 {
-        return Err(format!("Server returned status {}", status));
+        return Err(AppError::Network(format!(
+            "Server returned status {}",
+            status
+        )));
     }
 
     // Read response body
@@ -2127,7 +5030,9 @@ async fn download_midi_from_url(url: String) -> Result<MidiFile, String> {
 
     // Validate it looks like a MIDI file (starts with "MThd")
     if bytes.len() < 4 || &bytes[0..4] != b"MThd" {
-        return Err("Downloaded file is not a valid MIDI file".to_string());
+        return Err(AppError::InvalidMidi(
+            "Downloaded file is not a valid MIDI file".to_string(),
+        ));
     }
 
     // Get album folder path
@@ -2153,7 +5058,9 @@ async fn download_midi_from_url(url: String) -> Result<MidiFile, String> {
             }
             counter += 1;
             if counter > 100 {
-                return Err("Too many files with same name".to_string());
+                return Err(AppError::InvalidInput(
+                    "Too many files with same name".to_string(),
+                ));
             }
         }
     } else {
@@ -2161,7 +5068,7 @@ async fn download_midi_from_url(url: String) -> Result<MidiFile, String> {
     };
 
     // Write file
-    std::fs::write(&final_path, &bytes).map_err(|e| format!("Failed to save file: {}", e))?;
+    std::fs::write(&final_path, &bytes).map_err(|e| AppError::io("save file", e))?;
 
     // Get metadata and return file info
     let name = final_path
@@ -2170,13 +5077,7 @@ async fn download_midi_from_url(url: String) -> Result<MidiFile, String> {
         .unwrap_or("Unknown")
         .to_string();
 
-    let meta =
-        midi::get_midi_metadata(&final_path.to_string_lossy()).unwrap_or(midi::MidiMetadata {
-            duration: 0.0,
-            bpm: 120,
-            note_count: 0,
-            note_density: 0.0,
-        });
+    let meta = midi::get_midi_metadata(&final_path.to_string_lossy()).unwrap_or_default();
 
     let file_size = std::fs::metadata(&final_path).map(|m| m.len()).unwrap_or(0);
     let file_hash = compute_file_hash(&final_path).unwrap_or_else(|| format!("{:x}", file_size));
@@ -2187,25 +5088,27 @@ async fn download_midi_from_url(url: String) -> Result<MidiFile, String> {
         duration: meta.duration,
         bpm: meta.bpm,
         note_density: meta.note_density,
+        difficulty: meta.difficulty,
+        tempo: meta.tempo,
+        repaired: meta.repaired,
+        note_count: meta.note_count,
+        track_count: meta.track_count,
+        multi_channel: meta.multi_channel,
         hash: file_hash,
         size: file_size,
     })
 }
 
 #[tauri::command]
-async fn seek(
-    position: f64,
-    state: State<'_, Arc<Mutex<AppState>>>,
-    window: Window,
-) -> Result<(), String> {
+async fn seek(position: f64, state: State<'_, Arc<Mutex<AppState>>>) -> Result<(), AppError> {
     let mut app_state = state.lock().unwrap();
-    app_state.seek(position, window)?;
+    app_state.seek(position)?;
     Ok(())
 }
 
 #[tauri::command]
-async fn open_url(url: String) -> Result<(), String> {
-    open::that(&url).map_err(|e| e.to_string())
+async fn open_url(url: String) -> Result<(), AppError> {
+    open::that(&url).map_err(|e| AppError::Other(e.to_string()))
 }
 
 // ============ Auto-Updater ============
@@ -2219,7 +5122,7 @@ struct UpdateInfo {
 }
 
 #[tauri::command]
-async fn check_for_update(current_version: String) -> Result<Option<UpdateInfo>, String> {
+async fn check_for_update(current_version: String) -> Result<Option<UpdateInfo>, AppError> {
     use std::io::Read;
 
     let response = ureq::get(
@@ -2296,87 +5199,997 @@ async fn check_for_update(current_version: String) -> Result<Option<UpdateInfo>,
     }
 }
 
-fn is_newer_version(latest: &str, current: &str) -> bool {
-    let parse = |v: &str| -> Vec<u32> { v.split('.').filter_map(|s| s.parse().ok()).collect() };
+fn is_newer_version(latest: &str, current: &str) -> bool {
+    let parse = |v: &str| -> Vec<u32> { v.split('.').filter_map(|s| s.parse().ok()).collect() };
+
+    let latest_parts = parse(latest);
+    let current_parts = parse(current);
+
+    for i in 0..latest_parts.len().max(current_parts.len()) {
+        let l = latest_parts.get(i).unwrap_or(&0);
+        let c = current_parts.get(i).unwrap_or(&0);
+        if l > c {
+            return true;
+        }
+        if l < c {
+            return false;
+        }
+    }
+    false
+}
+
+#[tauri::command]
+async fn download_update(download_url: String, file_name: String) -> Result<String, AppError> {
+    use std::io::Read;
+
+    // Download to temp directory
+    let temp_dir = std::env::temp_dir();
+    let download_path = temp_dir.join(&file_name);
+
+    app_log!("[UPDATE] Downloading from: {}", download_url);
+    app_log!("[UPDATE] Saving to: {:?}", download_path);
+
+    let response = ureq::get(&download_url)
+        .set("User-Agent", "WWM-Overlay")
+        .call()
+        .map_err(|e| format!("Failed to download update: {}", e))?;
+
+    let mut bytes = Vec::new();
+    response
+        .into_reader()
+        .take(100 * 1024 * 1024) // 100MB limit
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("Failed to read download: {}", e))?;
+
+    std::fs::write(&download_path, &bytes).map_err(|e| AppError::io("save update", e))?;
+
+    app_log!("[UPDATE] Downloaded {} bytes", bytes.len());
+
+    Ok(download_path.to_string_lossy().to_string())
+}
+
+// ============ Discovery Server ============
+
+// Admin token for the discovery server's /stats and /status pages, created on first use
+fn discovery_admin_token() -> String {
+    stored_token("discovery_admin_token")
+}
+
+// Random token kept in config under `key`, created on first use
+fn stored_token(key: &str) -> String {
+    let mut config = load_config();
+    if let Some(token) = config
+        .get(key)
+        .and_then(|v| v.as_str())
+        .filter(|t| !t.is_empty())
+    {
+        return token.to_string();
+    }
+    use sha2::{Digest, Sha256};
+    let mut hasher = Sha256::new();
+    hasher.update(format!(
+        "{:?}{}{:p}",
+        std::time::SystemTime::now(),
+        std::process::id(),
+        &config
+    ));
+    let token: String = hasher.finalize()[..16]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    config[key] = serde_json::json!(token);
+    save_config(&config);
+    token
+}
+
+#[tauri::command]
+async fn get_discovery_admin_token() -> Result<String, AppError> {
+    Ok(discovery_admin_token())
+}
+
+#[tauri::command]
+async fn start_discovery_server(port: u16) -> Result<(), AppError> {
+    let admin_token = discovery_admin_token();
+    tokio::spawn(async move {
+        if let Err(e) = discovery::start_discovery_server(port, Some(admin_token)).await {
+            app_error!("[DISCOVERY] Server error: {}", e);
+        }
+    });
+
+    // Give server time to start
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    if discovery::is_server_running() {
+        Ok(())
+    } else {
+        Err("Failed to start server".to_string().into())
+    }
+}
+
+#[tauri::command]
+async fn is_discovery_server_running() -> Result<bool, AppError> {
+    Ok(discovery::is_server_running())
+}
+
+#[tauri::command]
+async fn stop_discovery_server() -> Result<(), AppError> {
+    Ok(discovery::stop_discovery_server()?)
+}
+
+// ============ Shared Library Server ============
+
+#[derive(Debug, Clone, Serialize)]
+struct LibraryServerInfo {
+    port: u16,
+    token: String, // Give this to the other player
+}
+
+// Serve our album on the LAN so another instance can browse and pull songs directly
+#[tauri::command]
+async fn start_library_server(
+    port: Option<u16>,
+    token: Option<String>,
+) -> Result<LibraryServerInfo, AppError> {
+    let token = match token
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+    {
+        Some(token) => {
+            let mut config = load_config();
+            config["library_server_token"] = serde_json::json!(token);
+            save_config(&config);
+            token
+        }
+        None => stored_token("library_server_token"),
+    };
+    let port = port.unwrap_or(library_server::DEFAULT_PORT);
+    library_server::start(port, token.clone()).await?;
+    Ok(LibraryServerInfo { port, token })
+}
+
+#[tauri::command]
+async fn stop_library_server() -> Result<(), AppError> {
+    Ok(library_server::stop()?)
+}
+
+#[tauri::command]
+async fn is_library_server_running() -> bool {
+    library_server::is_running()
+}
+
+#[tauri::command]
+async fn browse_library_server(
+    server_url: String,
+    token: String,
+) -> Result<Vec<discovery::SharedSong>, AppError> {
+    tokio::task::spawn_blocking(move || library_server::fetch_library(&server_url, &token))
+        .await
+        .map_err(|e| format!("Failed to browse library: {}", e))?
+}
+
+// Pull songs from another instance's library server through the download queue
+#[tauri::command]
+async fn pull_from_library_server(
+    server_url: String,
+    token: String,
+    songs: Vec<discovery::SharedSong>,
+    app_handle: AppHandle,
+) -> Result<downloads::DownloadProgress, AppError> {
+    let requests = songs
+        .into_iter()
+        .map(|song| downloads::DownloadRequest {
+            source: downloads::DownloadSource::Http {
+                url: library_server::song_url(&server_url, &song.hash),
+                token: Some(token.clone()),
+            },
+            hash: song.hash,
+            name: song.name,
+            sha256: song.sha256,
+        })
+        .collect();
+    Ok(
+        tokio::task::spawn_blocking(move || downloads::enqueue(&app_handle, requests))
+            .await
+            .map_err(|e| format!("Failed to queue downloads: {}", e))?,
+    )
+}
+
+// Peer list with the user's blocklist applied
+#[tauri::command]
+async fn get_discovery_peers(server_url: String) -> Result<discovery::Catalog, AppError> {
+    tokio::task::spawn_blocking(move || discovery::load_catalog(&server_url))
+        .await
+        .map_err(|e| format!("Failed to fetch peers: {}", e))?
+}
+
+// Stay registered with a discovery server, reconnecting with backoff if it drops out
+#[tauri::command]
+async fn discovery_connect(
+    server_url: String,
+    peer_id: String,
+    webrtc_id: Option<String>,
+    name: String,
+    app_handle: AppHandle,
+) -> Result<(), AppError> {
+    discovery::connect(app_handle, server_url, move || {
+        Ok(discovery::RegisterRequest {
+            peer_id: peer_id.clone(),
+            webrtc_id: webrtc_id.clone(),
+            name: name.clone(),
+            songs: tauri::async_runtime::block_on(shared_songs())?,
+        })
+    });
+    Ok(())
+}
+
+#[tauri::command]
+async fn discovery_disconnect(peer_id: Option<String>, app_handle: AppHandle) {
+    tokio::task::spawn_blocking(move || discovery::disconnect(&app_handle, peer_id.as_deref()))
+        .await
+        .ok();
+}
+
+#[tauri::command]
+async fn get_discovery_status() -> discovery::ConnectionStatus {
+    discovery::connection_status()
+}
+
+#[tauri::command]
+async fn get_cached_discovery_peers(server_url: String) -> Option<discovery::Catalog> {
+    discovery::cached_catalog(&server_url)
+}
+
+#[tauri::command]
+async fn block_peer(
+    peer_id: Option<String>,
+    name: Option<String>,
+) -> Result<discovery::PeerBlocklist, AppError> {
+    Ok(discovery::block_peer(peer_id.as_deref(), name.as_deref()))
+}
+
+#[tauri::command]
+async fn unblock_peer(
+    peer_id: Option<String>,
+    name: Option<String>,
+) -> Result<discovery::PeerBlocklist, AppError> {
+    Ok(discovery::unblock_peer(peer_id.as_deref(), name.as_deref()))
+}
+
+#[tauri::command]
+async fn get_peer_blocklist() -> Result<discovery::PeerBlocklist, AppError> {
+    Ok(discovery::load_blocklist())
+}
+
+// ============ Player State Events ============
+
+// Everything the transport UI shows, pushed as "player-state" so the webview doesn't poll
+#[derive(Debug, Clone, Serialize)]
+struct PlayerStateEvent {
+    playback: PlaybackState,
+    queue: Vec<queue::QueueEntry>,
+    band_filter: Option<midi::BandFilter>,
+    auto_advance: bool,
+    active_playlist: Option<queue::ActivePlaylist>,
+    max_polyphony: u8,
+    external: Vec<MidiFile>, // Songs played from outside the album this session
+}
+
+const PLAYER_STATE_POLL_MS: u64 = 200;
+const PLAYER_STATE_HEARTBEAT_MS: u64 = 2000;
+
+fn player_state_snapshot(app_handle: &AppHandle) -> PlayerStateEvent {
+    let state = app_handle.state::<Arc<Mutex<AppState>>>();
+    let (mut playback, position, band_filter) = {
+        let app_state = state.lock().unwrap();
+        (
+            app_state.get_playback_state(),
+            app_state.current_position(),
+            app_state.get_band_filter(),
+        )
+    };
+    playback.current_position = position;
+
+    PlayerStateEvent {
+        playback,
+        queue: queue::get_queue(),
+        band_filter,
+        auto_advance: queue::get_auto_advance(),
+        active_playlist: queue::get_active_playlist(),
+        max_polyphony: midi::get_max_polyphony(),
+        external: EXTERNAL_FILES.lock().unwrap().clone(),
+    }
+}
+
+// "key-sent" per real key press, so the UI lights keys only when the OS call went
+// through (note-event fires either way)
+fn start_key_sent_emitter(app_handle: AppHandle) {
+    keyboard::set_key_sent_listener(move |sent| {
+        let _ = app_handle.emit("key-sent", sent);
+    });
+}
+
+// Emits on any change (position aside, which playback-progress covers) plus a
+// periodic heartbeat so a webview that missed an event catches up
+fn start_player_state_emitter(app_handle: AppHandle) {
+    let spawned = thread::Builder::new()
+        .name("player-state".to_string())
+        .spawn(move || {
+            let mut last_sent: Option<PlayerStateEvent> = None;
+            let mut last_heartbeat = std::time::Instant::now();
+            loop {
+                thread::sleep(std::time::Duration::from_millis(PLAYER_STATE_POLL_MS));
+                let snapshot = player_state_snapshot(&app_handle);
+
+                let changed = last_sent.as_ref().is_none_or(|last| {
+                    let mut previous = last.playback.clone();
+                    previous.current_position = snapshot.playback.current_position;
+                    previous != snapshot.playback
+                        || last.band_filter != snapshot.band_filter
+                        || last.auto_advance != snapshot.auto_advance
+                        || last.max_polyphony != snapshot.max_polyphony
+                        || last.active_playlist.as_ref().map(|a| (&a.id, a.index))
+                            != snapshot.active_playlist.as_ref().map(|a| (&a.id, a.index))
+                        || last
+                            .queue
+                            .iter()
+                            .map(|e| &e.path)
+                            .ne(snapshot.queue.iter().map(|e| &e.path))
+                        || last
+                            .external
+                            .iter()
+                            .map(|f| &f.path)
+                            .ne(snapshot.external.iter().map(|f| &f.path))
+                });
+                let heartbeat =
+                    last_heartbeat.elapsed().as_millis() as u64 >= PLAYER_STATE_HEARTBEAT_MS;
+
+                if changed || heartbeat {
+                    let _ = app_handle.emit("player-state", &snapshot);
+                    last_heartbeat = std::time::Instant::now();
+                    last_sent = Some(snapshot);
+                }
+            }
+        });
+    if let Err(e) = spawned {
+        app_error!("[STATE] Failed to start player state emitter: {}", e);
+    }
+}
+
+#[tauri::command]
+async fn get_player_state(app_handle: AppHandle) -> Result<PlayerStateEvent, AppError> {
+    Ok(player_state_snapshot(&app_handle))
+}
+
+// ============ Session Resume & History ============
+
+// Saved every few seconds while playing so a crash or accidental exit can pick up where it left off
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LastSession {
+    file: String,
+    position: f64, // seconds
+    #[serde(default)]
+    playlist: Option<queue::ActivePlaylist>,
+    saved_at: String,
+}
+
+const SESSION_SAVE_INTERVAL_SECS: u64 = 5;
+
+fn load_session() -> Option<LastSession> {
+    let path = get_data_path("session.json").ok()?;
+    let content = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+fn save_session(session: &LastSession) {
+    if let Ok(path) = get_data_path("session.json") {
+        if let Ok(content) = serde_json::to_string_pretty(session) {
+            let _ = std::fs::write(path, content);
+        }
+    }
+}
+
+fn clear_session() {
+    if let Ok(path) = get_data_path("session.json") {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+fn start_session_saver(app_handle: AppHandle) {
+    let spawned = thread::Builder::new()
+        .name("session".to_string())
+        .spawn(move || loop {
+            thread::sleep(std::time::Duration::from_secs(SESSION_SAVE_INTERVAL_SECS));
+            let state = app_handle.state::<Arc<Mutex<AppState>>>();
+            let (status, position) = {
+                let app_state = state.lock().unwrap();
+                (app_state.get_playback_state(), app_state.current_position())
+            };
+            if !status.is_playing {
+                continue;
+            }
+            if let Some(file) = status.current_file {
+                save_session(&LastSession {
+                    file,
+                    position,
+                    playlist: queue::get_active_playlist(),
+                    saved_at: chrono_now(),
+                });
+            }
+        });
+    if let Err(e) = spawned {
+        app_error!("[SESSION] Failed to start session saver: {}", e);
+    }
+}
+
+#[tauri::command]
+async fn get_last_session() -> Result<Option<LastSession>, AppError> {
+    Ok(load_session().filter(|s| std::path::Path::new(&s.file).exists()))
+}
+
+#[tauri::command]
+async fn resume_last_session(
+    state: State<'_, Arc<Mutex<AppState>>>,
+    window: Window,
+) -> Result<LastSession, AppError> {
+    let session = load_session().ok_or("No session to resume")?;
+    if !std::path::Path::new(&session.file).exists() {
+        return Err(AppError::FileNotFound(
+            "File from last session no longer exists".to_string(),
+        ));
+    }
+
+    let mut app_state = state.lock().unwrap();
+    app_state.stop_playback()?;
+    app_state.load_midi(&session.file)?;
+    app_state.seek(session.position)?;
+    app_state.start_playback(window)?;
+    drop(app_state);
+
+    queue::set_active_playlist(session.playlist.clone());
+    app_log!(
+        "[SESSION] Resumed {} at {:.1}s",
+        session.file,
+        session.position
+    );
+
+    keyboard::auto_focus_game_async();
+
+    Ok(session)
+}
+
+#[tauri::command]
+async fn clear_last_session() -> Result<(), AppError> {
+    clear_session();
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_recently_played(limit: Option<usize>) -> Result<Vec<history::HistoryEntry>, AppError> {
+    Ok(history::recently_played(limit.unwrap_or(20)))
+}
+
+#[tauri::command]
+async fn get_recently_added(limit: Option<usize>) -> Result<Vec<history::RecentFile>, AppError> {
+    Ok(history::recently_added(limit.unwrap_or(20))?)
+}
+
+#[tauri::command]
+async fn get_continue_listening(
+    limit: Option<usize>,
+) -> Result<Vec<history::HistoryEntry>, AppError> {
+    Ok(history::continue_listening(limit.unwrap_or(10)))
+}
+
+// ============ Queue & Song Requests ============
+
+#[tauri::command]
+async fn get_queue() -> Result<Vec<queue::QueueEntry>, AppError> {
+    Ok(queue::get_queue())
+}
+
+#[tauri::command]
+async fn enqueue_song(app_handle: tauri::AppHandle, path: String) -> Result<usize, AppError> {
+    enqueue_path(&app_handle, path)
+}
+
+fn enqueue_path(app_handle: &AppHandle, path: String) -> Result<usize, AppError> {
+    let file_path = std::path::Path::new(&path);
+    if !file_path.exists() {
+        return Err(AppError::FileNotFound("File not found".to_string()));
+    }
+    let name = file_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("Unknown")
+        .to_string();
+    let hash = compute_file_hash(file_path).unwrap_or_default();
+
+    Ok(queue::enqueue(
+        queue::QueueEntry {
+            path,
+            name,
+            hash,
+            requested_by: None,
+            source: "local".to_string(),
+        },
+        app_handle,
+    ))
+}
+
+// What happens to .mid files opened from Explorer ("Open with", a file association)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum OpenFileAction {
+    Queue,  // Add to the queue, played from where they are
+    Play,   // The UI plays the first one right away, from where it is (play_external_file)
+    Import, // Copy into the album folder first
+}
+
+fn open_file_action() -> OpenFileAction {
+    serde_json::from_value(load_config()["open_file_action"].clone())
+        .unwrap_or(OpenFileAction::Queue)
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct OpenedFiles {
+    paths: Vec<String>,
+    action: OpenFileAction,
+}
+
+// Files given on the command line at startup, kept until the UI is up to ask for them
+static PENDING_OPENED_FILES: Mutex<Option<OpenedFiles>> = Mutex::new(None);
+
+// Hash -> path of every song in the album, for skipping duplicates on import
+fn album_hashes(album_dir: &std::path::Path) -> std::collections::HashMap<String, String> {
+    paths::list_midi(album_dir)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|path| {
+            let hash = compute_file_hash(&path)?;
+            Some((hash, path.to_string_lossy().to_string()))
+        })
+        .collect()
+}
+
+// Copy a file into the album, unless the album already has the same song.
+// Returns the song's album path and whether it was copied; `known` is from album_hashes.
+fn import_into_album(
+    path: &str,
+    known: &mut std::collections::HashMap<String, String>,
+) -> Result<(String, bool), AppError> {
+    let album_dir = get_album_folder()?;
+    if paths::is_inside(std::path::Path::new(path), &album_dir) {
+        return Ok((path.to_string(), false));
+    }
+    let contents = std::fs::read(paths::long(path)).map_err(|e| AppError::io("read file", e))?;
+    if !check_midi_bytes(&contents)? {
+        return Err(AppError::InvalidMidi(format!("Not a MIDI file: {}", path)));
+    }
+    let hash = compute_hash_from_bytes(&contents);
+    if let Some(existing) = known.get(&hash) {
+        return Ok((existing.clone(), false));
+    }
+
+    std::fs::create_dir_all(&album_dir)
+        .map_err(|e| format!("Failed to create album folder: {}", e))?;
+    let stem = std::path::Path::new(path)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("Imported");
+    let mut save_path = album_dir.join(format!("{}.mid", stem));
+    let mut counter = 1;
+    while save_path.exists() {
+        save_path = album_dir.join(format!("{} ({}).mid", stem, counter));
+        counter += 1;
+    }
+    std::fs::write(&save_path, &contents).map_err(|e| AppError::io("save file", e))?;
+    let save_path = save_path.to_string_lossy().to_string();
+    known.insert(hash, save_path.clone());
+    Ok((save_path, true))
+}
+
+// MIDI files opened from outside: on the command line at startup, or handed over by a
+// second launch. Handled per the open_file_action setting.
+fn open_files(app_handle: &AppHandle, args: Vec<String>, startup: bool) {
+    let action = open_file_action();
+    let mut known = match action {
+        OpenFileAction::Import => get_album_folder()
+            .map(|album| album_hashes(&album))
+            .unwrap_or_default(),
+        _ => std::collections::HashMap::new(),
+    };
+    let paths: Vec<String> = args
+        .into_iter()
+        .filter(|arg| {
+            let path = std::path::Path::new(arg);
+            path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("mid"))
+        })
+        .filter_map(|path| {
+            let opened = match action {
+                OpenFileAction::Queue => {
+                    enqueue_path(app_handle, path.clone()).map(|_| path.clone())
+                }
+                OpenFileAction::Play => add_external_file(&path).map(|_| path.clone()),
+                OpenFileAction::Import => import_into_album(&path, &mut known).map(|(p, _)| p),
+            };
+            match opened {
+                Ok(opened) => Some(opened),
+                Err(e) => {
+                    app_error!("[OPEN] Failed to open {}: {}", path, e);
+                    None
+                }
+            }
+        })
+        .collect();
+    if paths.is_empty() {
+        return;
+    }
+    app_log!("[OPEN] Opened {} files ({:?})", paths.len(), action);
+
+    let opened = OpenedFiles { paths, action };
+    if startup {
+        *PENDING_OPENED_FILES.lock().unwrap() = Some(opened.clone());
+    } else if let Some(window) = app_handle.get_webview_window("main") {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+    let _ = app_handle.emit("instance-opened", opened);
+}
+
+// Everything dropped on the window in one go: MIDI files, zips, .url shortcuts and
+// folders of them, with "drop-import-progress" per item and one summary at the end
+#[tauri::command]
+async fn import_dropped(
+    window: Window,
+    paths: Vec<String>,
+) -> Result<dropped::DropSummary, AppError> {
+    let (items, unsupported) = dropped::classify(&paths);
+    let album_dir = get_album_folder()?;
+    let mut known = album_hashes(&album_dir);
+    let mut summary = dropped::DropSummary {
+        unsupported,
+        ..Default::default()
+    };
+
+    let total = items.len();
+    for (done, item) in items.into_iter().enumerate() {
+        let current = item.path().to_string_lossy().to_string();
+        let _ = window.emit(
+            "drop-import-progress",
+            dropped::DropProgress {
+                done,
+                total,
+                current: current.clone(),
+            },
+        );
+        let result = match &item {
+            dropped::DroppedItem::Midi(_) => {
+                import_into_album(&current, &mut known).map(|(path, copied)| {
+                    if copied {
+                        summary.imported.push(path);
+                    } else {
+                        summary.duplicates.push(path);
+                    }
+                })
+            }
+            dropped::DroppedItem::Zip(_) => import_zip(current.clone()).await.map(|result| {
+                // import_zip lists songs it skipped as duplicates under their album path
+                for file in result.imported_files {
+                    if known.get(&file.hash) == Some(&file.path) {
+                        summary.duplicates.push(file.path);
+                    } else {
+                        known.insert(file.hash.clone(), file.path.clone());
+                        summary.imported.push(file.path);
+                    }
+                }
+            }),
+            dropped::DroppedItem::Shortcut { url, .. } => {
+                download_midi_from_url(url.clone()).await.map(|file| {
+                    known.insert(file.hash.clone(), file.path.clone());
+                    summary.imported.push(file.path);
+                })
+            }
+        };
+        if let Err(e) = result {
+            app_error!("[DROP] Failed to import {}: {}", current, e);
+            summary.failed.push(dropped::DropFailure {
+                path: current,
+                error: e.to_string(),
+            });
+        }
+    }
+    let _ = window.emit(
+        "drop-import-progress",
+        dropped::DropProgress {
+            done: total,
+            total,
+            current: String::new(),
+        },
+    );
+
+    app_log!(
+        "[DROP] Imported {}, {} already in the album, {} failed, {} unsupported",
+        summary.imported.len(),
+        summary.duplicates.len(),
+        summary.failed.len(),
+        summary.unsupported.len()
+    );
+    Ok(summary)
+}
+
+// Files from the command line, for a UI that wasn't listening yet when they were opened
+#[tauri::command]
+async fn take_opened_files() -> Result<Option<OpenedFiles>, AppError> {
+    Ok(PENDING_OPENED_FILES.lock().unwrap().take())
+}
+
+#[tauri::command]
+async fn set_open_file_action(action: OpenFileAction) -> Result<(), AppError> {
+    let mut config = load_config();
+    config["open_file_action"] = serde_json::json!(action);
+    save_config(&config);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_open_file_action() -> Result<OpenFileAction, AppError> {
+    Ok(open_file_action())
+}
+
+#[tauri::command]
+async fn set_file_association(enabled: bool) -> Result<bool, AppError> {
+    if enabled {
+        file_association::register()?;
+    } else {
+        file_association::unregister()?;
+    }
+    Ok(file_association::is_registered())
+}
+
+#[tauri::command]
+async fn get_file_association() -> Result<bool, AppError> {
+    Ok(file_association::is_registered())
+}
+
+#[tauri::command]
+async fn remove_from_queue(app_handle: tauri::AppHandle, index: usize) -> Result<(), AppError> {
+    Ok(queue::remove(index, &app_handle).map(|_| ())?)
+}
+
+#[tauri::command]
+async fn clear_queue(app_handle: tauri::AppHandle) -> Result<(), AppError> {
+    queue::clear(&app_handle);
+    Ok(())
+}
+
+#[tauri::command]
+async fn set_auto_advance(enabled: bool) -> Result<(), AppError> {
+    queue::set_auto_advance(enabled);
+    let mut config = load_config();
+    config["auto_advance"] = serde_json::json!(enabled);
+    save_config(&config);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_auto_advance() -> Result<bool, AppError> {
+    Ok(queue::get_auto_advance())
+}
+
+/// Silence between auto-advanced songs (0-10s) and whether to skip each song's lead-in
+#[tauri::command]
+async fn set_song_transition(gap_ms: u64, pickup: bool) -> Result<(), AppError> {
+    queue::set_song_gap_ms(gap_ms);
+    queue::set_song_pickup(pickup);
+    let mut config = load_config();
+    config["song_gap_ms"] = serde_json::json!(queue::get_song_gap_ms());
+    config["song_pickup"] = serde_json::json!(pickup);
+    save_config(&config);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_song_transition() -> Result<serde_json::Value, AppError> {
+    Ok(serde_json::json!({
+        "gap_ms": queue::get_song_gap_ms(),
+        "pickup": queue::get_song_pickup()
+    }))
+}
+
+/// Tell the backend which saved playlist is playing (None when playing loose files)
+#[tauri::command]
+async fn set_active_playlist(
+    playlist_id: Option<String>,
+    index: Option<usize>,
+) -> Result<(), AppError> {
+    queue::set_active_playlist(playlist_id.map(|id| queue::ActivePlaylist {
+        id,
+        index: index.unwrap_or(0),
+    }));
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_active_playlist() -> Result<Option<queue::ActivePlaylist>, AppError> {
+    Ok(queue::get_active_playlist())
+}
 
-    let latest_parts = parse(latest);
-    let current_parts = parse(current);
+fn load_saved_auto_advance() {
+    let config = load_config();
+    if let Some(enabled) = config.get("auto_advance").and_then(|v| v.as_bool()) {
+        queue::set_auto_advance(enabled);
+    }
+    if let Some(gap_ms) = config.get("song_gap_ms").and_then(|v| v.as_u64()) {
+        queue::set_song_gap_ms(gap_ms);
+    }
+    if let Some(pickup) = config.get("song_pickup").and_then(|v| v.as_bool()) {
+        queue::set_song_pickup(pickup);
+    }
+}
 
-    for i in 0..latest_parts.len().max(current_parts.len()) {
-        let l = latest_parts.get(i).unwrap_or(&0);
-        let c = current_parts.get(i).unwrap_or(&0);
-        if l > c {
-            return true;
+// Favorites and playlists are saved without paths, so files are looked up by content hash.
+// The metadata cache answers most lookups; anything missing falls back to hashing the album.
+fn resolve_paths_by_hash(
+    hashes: &std::collections::HashSet<String>,
+) -> std::collections::HashMap<String, String> {
+    let mut found = std::collections::HashMap::new();
+    for (path, meta) in load_metadata_cache().files {
+        if hashes.contains(&meta.hash) && std::path::Path::new(&path).exists() {
+            found.insert(meta.hash, path);
         }
-        if l < c {
-            return false;
+    }
+    if found.len() == hashes.len() {
+        return found;
+    }
+
+    fn scan(
+        dir: &std::path::Path,
+        hashes: &std::collections::HashSet<String>,
+        found: &mut std::collections::HashMap<String, String>,
+    ) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            if found.len() == hashes.len() {
+                return;
+            }
+            let path = entry.path();
+            if path.is_dir() {
+                scan(&path, hashes, found);
+            } else if path.extension().and_then(|s| s.to_str()) == Some("mid") {
+                if let Some(hash) = compute_file_hash(&path) {
+                    if hashes.contains(&hash) && !found.contains_key(&hash) {
+                        found.insert(hash, path.to_string_lossy().to_string());
+                    }
+                }
+            }
         }
     }
-    false
+    if let Ok(album) = get_album_folder() {
+        scan(&album, hashes, &mut found);
+    }
+    found
 }
 
-#[tauri::command]
-async fn download_update(download_url: String, file_name: String) -> Result<String, String> {
-    use std::io::Read;
+fn find_path_by_hash(hash: &str) -> Option<String> {
+    resolve_paths_by_hash(&std::iter::once(hash.to_string()).collect()).remove(hash)
+}
 
-    // Download to temp directory
-    let temp_dir = std::env::temp_dir();
-    let download_path = temp_dir.join(&file_name);
+// Next song to auto-play: the queue first, then the active playlist
+fn next_auto_advance_entry(app: &tauri::AppHandle) -> Option<queue::QueueEntry> {
+    if let Some(entry) = queue::pop_next(app) {
+        return Some(entry);
+    }
 
-    app_log!("[UPDATE] Downloading from: {}", download_url);
-    app_log!("[UPDATE] Saving to: {:?}", download_path);
+    let mut active = queue::get_active_playlist()?;
+    let playlist = playlists::get(&active.id)?;
 
-    let response = ureq::get(&download_url)
-        .set("User-Agent", "WWM-Overlay")
-        .call()
-        .map_err(|e| format!("Failed to download update: {}", e))?;
+    // Skip tracks that are no longer in the library
+    for (index, track) in playlist
+        .tracks
+        .into_iter()
+        .enumerate()
+        .skip(active.index + 1)
+    {
+        if let Some(path) = find_path_by_hash(&track.hash) {
+            active.index = index;
+            queue::set_active_playlist(Some(active));
+            return Some(queue::QueueEntry {
+                path,
+                name: track.name,
+                hash: track.hash,
+                requested_by: None,
+                source: "playlist".to_string(),
+            });
+        }
+    }
+    None
+}
 
-    let mut bytes = Vec::new();
-    response
-        .into_reader()
-        .take(100 * 1024 * 1024) // 100MB limit
-        .read_to_end(&mut bytes)
-        .map_err(|e| format!("Failed to read download: {}", e))?;
+/// Called by the playback thread when a song finishes on its own.
+/// Returns true if the next song was started (or the user took over during the gap).
+fn auto_advance(window: &Window) -> bool {
+    if !queue::get_auto_advance() {
+        return false;
+    }
 
-    std::fs::write(&download_path, &bytes).map_err(|e| format!("Failed to save update: {}", e))?;
+    // Wait out the gap; a stop or a manually started song cancels the advance
+    let gap_ms = queue::get_song_gap_ms();
+    if gap_ms > 0 {
+        let generation = queue::advance_generation();
+        let _ = window.emit("playback-advancing", gap_ms);
+        let until = std::time::Instant::now() + std::time::Duration::from_millis(gap_ms);
+        while std::time::Instant::now() < until {
+            if queue::advance_generation() != generation {
+                return true;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+    }
 
-    app_log!("[UPDATE] Downloaded {} bytes", bytes.len());
+    let Some(entry) = next_auto_advance_entry(window.app_handle()) else {
+        return false;
+    };
 
-    Ok(download_path.to_string_lossy().to_string())
-}
+    let state = window.state::<Arc<Mutex<AppState>>>();
+    let mut app_state = state.lock().unwrap();
+    let started = app_state.load_midi(&entry.path).and_then(|_| {
+        // Pickup: start right on the first note instead of playing the lead-in silence
+        if queue::get_song_pickup() {
+            let start = app_state.first_note_time();
+            app_state.seek(start)?;
+        }
+        Ok(app_state.start_playback(window.clone())?)
+    });
+    drop(app_state);
 
-// ============ Discovery Server ============
+    match started {
+        Ok(()) => {
+            app_log!("[QUEUE] Auto-advanced to {}", entry.name);
+            let _ = window.emit("playback-advanced", &entry);
+            true
+        }
+        Err(e) => {
+            app_error!("[QUEUE] Failed to auto-advance to {}: {}", entry.name, e);
+            false
+        }
+    }
+}
 
 #[tauri::command]
-async fn start_discovery_server(port: u16) -> Result<(), String> {
-    tokio::spawn(async move {
-        if let Err(e) = discovery::start_discovery_server(port).await {
-            app_error!("[DISCOVERY] Server error: {}", e);
-        }
-    });
+async fn get_song_request_config() -> Result<song_requests::SongRequestConfig, AppError> {
+    Ok(song_requests::load_config())
+}
 
-    // Give server time to start
-    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+#[tauri::command]
+async fn set_song_request_config(config: song_requests::SongRequestConfig) -> Result<(), AppError> {
+    song_requests::save_config(&config);
+    Ok(())
+}
 
-    if discovery::is_server_running() {
-        Ok(())
-    } else {
-        Err("Failed to start server".to_string())
-    }
+#[tauri::command]
+async fn start_song_requests(app_handle: tauri::AppHandle) -> Result<(), AppError> {
+    Ok(song_requests::start(app_handle)?)
 }
 
 #[tauri::command]
-async fn is_discovery_server_running() -> Result<bool, String> {
-    Ok(discovery::is_server_running())
+async fn stop_song_requests(app_handle: tauri::AppHandle) -> Result<(), AppError> {
+    song_requests::stop(&app_handle);
+    Ok(())
 }
 
 #[tauri::command]
-async fn stop_discovery_server() -> Result<(), String> {
-    discovery::stop_discovery_server()
+async fn get_song_request_status() -> Result<song_requests::SongRequestStatus, AppError> {
+    Ok(song_requests::get_status())
 }
 
 #[tauri::command]
-async fn load_favorites() -> Result<serde_json::Value, String> {
+async fn load_favorites() -> Result<serde_json::Value, AppError> {
     let path = get_data_path("favorites.json")?;
     if path.exists() {
         let content = std::fs::read_to_string(&path)
@@ -2390,16 +6203,69 @@ async fn load_favorites() -> Result<serde_json::Value, String> {
 }
 
 #[tauri::command]
-async fn save_favorites(favorites: serde_json::Value) -> Result<(), String> {
-    let path = get_data_path("favorites.json")?;
+async fn save_favorites(
+    app_handle: tauri::AppHandle,
+    favorites: serde_json::Value,
+) -> Result<(), AppError> {
     let content = serde_json::to_string_pretty(&favorites)
         .map_err(|e| format!("Failed to serialize favorites: {}", e))?;
-    std::fs::write(&path, content).map_err(|e| format!("Failed to write favorites: {}", e))?;
+    data_watch::write("favorites.json", content).map_err(|e| AppError::io("write favorites", e))?;
+    favorites::notify_changed(&app_handle);
+    Ok(())
+}
+
+#[tauri::command]
+async fn list_favorites() -> Result<Vec<favorites::Favorite>, AppError> {
+    Ok(favorites::list())
+}
+
+#[tauri::command]
+async fn add_favorite(
+    app_handle: tauri::AppHandle,
+    file: favorites::Favorite,
+) -> Result<bool, AppError> {
+    Ok(favorites::add(&app_handle, file)?)
+}
+
+#[tauri::command]
+async fn remove_favorite(app_handle: tauri::AppHandle, hash: String) -> Result<bool, AppError> {
+    Ok(favorites::remove(&app_handle, &hash)?)
+}
+
+#[tauri::command]
+async fn toggle_favorite(
+    app_handle: tauri::AppHandle,
+    file: favorites::Favorite,
+) -> Result<bool, AppError> {
+    Ok(favorites::toggle(&app_handle, file)?)
+}
+
+#[tauri::command]
+async fn is_favorite(hash: String) -> Result<bool, AppError> {
+    Ok(favorites::is_favorite(&hash))
+}
+
+#[tauri::command]
+async fn list_data_backups(file: String) -> Result<Vec<data_backups::DataBackup>, AppError> {
+    Ok(data_backups::list(&file))
+}
+
+#[tauri::command]
+async fn restore_data_backup(
+    app_handle: tauri::AppHandle,
+    file: String,
+    name: String,
+) -> Result<(), AppError> {
+    data_backups::restore(&file, &name)?;
+    match file.as_str() {
+        "favorites.json" => favorites::notify_changed(&app_handle),
+        _ => playlists::notify_changed(&app_handle),
+    }
     Ok(())
 }
 
 #[tauri::command]
-async fn load_playlists() -> Result<serde_json::Value, String> {
+async fn load_playlists() -> Result<serde_json::Value, AppError> {
     let path = get_data_path("playlists.json")?;
     if path.exists() {
         let content = std::fs::read_to_string(&path)
@@ -2413,14 +6279,86 @@ async fn load_playlists() -> Result<serde_json::Value, String> {
 }
 
 #[tauri::command]
-async fn save_playlists(playlists: serde_json::Value) -> Result<(), String> {
-    let path = get_data_path("playlists.json")?;
+async fn save_playlists(
+    app_handle: tauri::AppHandle,
+    playlists: serde_json::Value,
+) -> Result<(), AppError> {
     let content = serde_json::to_string_pretty(&playlists)
         .map_err(|e| format!("Failed to serialize playlists: {}", e))?;
-    std::fs::write(&path, content).map_err(|e| format!("Failed to write playlists: {}", e))?;
+    data_watch::write("playlists.json", content).map_err(|e| AppError::io("write playlists", e))?;
+    playlists::notify_changed(&app_handle);
     Ok(())
 }
 
+#[tauri::command]
+async fn list_playlists() -> Result<Vec<playlists::Playlist>, AppError> {
+    Ok(playlists::list())
+}
+
+#[tauri::command]
+async fn create_playlist(
+    app_handle: tauri::AppHandle,
+    name: String,
+) -> Result<playlists::Playlist, AppError> {
+    Ok(playlists::create(&app_handle, &name)?)
+}
+
+#[tauri::command]
+async fn rename_playlist(
+    app_handle: tauri::AppHandle,
+    id: String,
+    name: String,
+) -> Result<(), AppError> {
+    Ok(playlists::rename(&app_handle, &id, &name)?)
+}
+
+#[tauri::command]
+async fn delete_playlist(app_handle: tauri::AppHandle, id: String) -> Result<(), AppError> {
+    Ok(playlists::delete(&app_handle, &id)?)
+}
+
+#[tauri::command]
+async fn duplicate_playlist(
+    app_handle: tauri::AppHandle,
+    id: String,
+    name: Option<String>,
+) -> Result<playlists::Playlist, AppError> {
+    Ok(playlists::duplicate(&app_handle, &id, name.as_deref())?)
+}
+
+#[tauri::command]
+async fn add_playlist_tracks(
+    app_handle: tauri::AppHandle,
+    id: String,
+    tracks: Vec<playlists::PlaylistTrack>,
+) -> Result<usize, AppError> {
+    Ok(playlists::add_tracks(&app_handle, &id, tracks)?)
+}
+
+#[tauri::command]
+async fn remove_playlist_track(
+    app_handle: tauri::AppHandle,
+    id: String,
+    hash: String,
+) -> Result<(), AppError> {
+    Ok(playlists::remove_track(&app_handle, &id, &hash)?)
+}
+
+#[tauri::command]
+async fn move_playlist_track(
+    app_handle: tauri::AppHandle,
+    id: String,
+    from: usize,
+    to: usize,
+) -> Result<(), AppError> {
+    Ok(playlists::move_track(&app_handle, &id, from, to)?)
+}
+
+#[tauri::command]
+async fn reorder_playlists(app_handle: tauri::AppHandle, ids: Vec<String>) -> Result<(), AppError> {
+    Ok(playlists::reorder(&app_handle, &ids)?)
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct ExportTrack {
     name: String,
@@ -2444,7 +6382,7 @@ struct ExportMetadata {
 async fn export_favorites(
     favorites: Vec<serde_json::Value>,
     export_path: String,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     use std::io::Write;
     use zip::write::SimpleFileOptions;
 
@@ -2530,7 +6468,7 @@ async fn export_playlist(
     playlist_name: String,
     tracks: Vec<serde_json::Value>,
     export_path: String,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     use std::io::Write;
     use zip::write::SimpleFileOptions;
 
@@ -2612,13 +6550,13 @@ async fn export_playlist(
 
 // Export entire library to a zip file
 #[tauri::command]
-async fn export_library(export_path: String, window: Window) -> Result<u32, String> {
+async fn export_library(export_path: String, window: Window) -> Result<u32, AppError> {
     use std::io::Write;
     use zip::write::SimpleFileOptions;
 
     let album_dir = get_album_folder().map_err(|e| e.to_string())?;
     if !album_dir.exists() {
-        return Err("Album folder not found".to_string());
+        return Err(AppError::FileNotFound("Album folder not found".to_string()));
     }
 
     // Collect all MIDI files
@@ -2636,7 +6574,9 @@ async fn export_library(export_path: String, window: Window) -> Result<u32, Stri
 
     let total_files = midi_files.len();
     if total_files == 0 {
-        return Err("No MIDI files found in library".to_string());
+        return Err(AppError::FileNotFound(
+            "No MIDI files found in library".to_string(),
+        ));
     }
 
     let file = std::fs::File::create(&export_path)
@@ -2747,14 +6687,7 @@ fn get_existing_files_by_hash(
                         .unwrap_or("Unknown")
                         .to_string();
 
-                    let meta = midi::get_midi_metadata(&path.to_string_lossy()).unwrap_or(
-                        midi::MidiMetadata {
-                            duration: 0.0,
-                            bpm: 120,
-                            note_count: 0,
-                            note_density: 0.0,
-                        },
-                    );
+                    let meta = midi::get_midi_metadata(&path.to_string_lossy()).unwrap_or_default();
 
                     let file_size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
 
@@ -2766,6 +6699,12 @@ fn get_existing_files_by_hash(
                             duration: meta.duration,
                             bpm: meta.bpm,
                             note_density: meta.note_density,
+                            difficulty: meta.difficulty,
+                            tempo: meta.tempo,
+                            repaired: meta.repaired,
+                            note_count: meta.note_count,
+                            track_count: meta.track_count,
+                            multi_channel: meta.multi_channel,
                             hash,
                             size: file_size,
                         },
@@ -2780,13 +6719,8 @@ fn get_existing_files_by_hash(
 
 // Import a zip file containing MIDI files (from exported favorites/playlist)
 #[tauri::command]
-async fn import_zip(zip_path: String) -> Result<ImportResult, String> {
-    use std::io::Read;
-
-    let file =
-        std::fs::File::open(&zip_path).map_err(|e| format!("Failed to open zip file: {}", e))?;
-    let mut archive =
-        zip::ZipArchive::new(file).map_err(|e| format!("Failed to read zip archive: {}", e))?;
+async fn import_zip(zip_path: String) -> Result<ImportResult, AppError> {
+    let mut archive = open_zip_checked(&zip_path)?;
 
     let album_dir = get_album_folder()?;
 
@@ -2802,13 +6736,20 @@ async fn import_zip(zip_path: String) -> Result<ImportResult, String> {
     let mut imported_files = Vec::new();
     let mut export_type = "unknown".to_string();
     let mut export_name = "Import".to_string();
+    let mut total_bytes = 0u64;
 
-    // First pass: read metadata if exists
+    // First pass: read metadata if exists (only at the archive root)
     for i in 0..archive.len() {
-        let mut file = archive.by_index(i).map_err(|e| e.to_string())?;
-        if file.name() == "metadata.json" {
-            let mut contents = String::new();
-            file.read_to_string(&mut contents).ok();
+        let file = archive.by_index(i).map_err(|e| e.to_string())?;
+        let is_metadata = file
+            .enclosed_name()
+            .map(|p| p.as_os_str() == "metadata.json")
+            .unwrap_or(false);
+        if is_metadata {
+            let mut ignored = 0u64;
+            let contents = read_entry_limited(file, ZIP_MAX_METADATA_BYTES, &mut ignored)
+                .map(|bytes| String::from_utf8_lossy(&bytes).to_string())
+                .unwrap_or_default();
             if let Ok(meta) = serde_json::from_str::<serde_json::Value>(&contents) {
                 export_type = meta["export_type"]
                     .as_str()
@@ -2820,26 +6761,32 @@ async fn import_zip(zip_path: String) -> Result<ImportResult, String> {
         }
     }
 
-    // Re-open archive for extraction (can't reuse after iteration)
-    let file =
-        std::fs::File::open(&zip_path).map_err(|e| format!("Failed to reopen zip file: {}", e))?;
-    let mut archive =
-        zip::ZipArchive::new(file).map_err(|e| format!("Failed to read zip archive: {}", e))?;
-
     // Second pass: extract MIDI files
     for i in 0..archive.len() {
-        let mut file = archive.by_index(i).map_err(|e| e.to_string())?;
-        let filename = file.name().to_string();
+        let file = archive.by_index(i).map_err(|e| e.to_string())?;
 
-        // Skip metadata and non-MIDI files
+        // Skip entries that could escape the album folder, metadata and non-MIDI files
+        let filename = match safe_entry_name(file.enclosed_name().as_deref()) {
+            Some(n) => n,
+            None => continue,
+        };
         if filename == "metadata.json" || !filename.to_lowercase().ends_with(".mid") {
             continue;
         }
 
-        // Read file contents
-        let mut contents = Vec::new();
-        file.read_to_end(&mut contents)
-            .map_err(|e| format!("Failed to read {}: {}", filename, e))?;
+        // Read file contents (bounded - the declared size can't be trusted)
+        if file.size() > ZIP_MAX_FILE_BYTES {
+            app_log!("[IMPORT] Skipping oversized entry: {}", filename);
+            continue;
+        }
+        let contents = match read_entry_limited(file, ZIP_MAX_FILE_BYTES, &mut total_bytes) {
+            Ok(c) => c,
+            Err(e) if total_bytes > ZIP_MAX_TOTAL_BYTES => return Err(e.into()),
+            Err(e) => {
+                app_log!("[IMPORT] Skipping {}: {}", filename, e);
+                continue;
+            }
+        };
 
         // Verify it's a valid MIDI file
         if contents.len() < 4 || &contents[0..4] != b"MThd" {
@@ -2881,13 +6828,7 @@ async fn import_zip(zip_path: String) -> Result<ImportResult, String> {
             .unwrap_or("Unknown")
             .to_string();
 
-        let meta =
-            midi::get_midi_metadata(&save_path.to_string_lossy()).unwrap_or(midi::MidiMetadata {
-                duration: 0.0,
-                bpm: 120,
-                note_count: 0,
-                note_density: 0.0,
-            });
+        let meta = midi::get_midi_metadata(&save_path.to_string_lossy()).unwrap_or_default();
 
         let file_size = contents.len() as u64;
 
@@ -2897,6 +6838,12 @@ async fn import_zip(zip_path: String) -> Result<ImportResult, String> {
             duration: meta.duration,
             bpm: meta.bpm,
             note_density: meta.note_density,
+            difficulty: meta.difficulty,
+            tempo: meta.tempo,
+            repaired: meta.repaired,
+            note_count: meta.note_count,
+            track_count: meta.track_count,
+            multi_channel: meta.multi_channel,
             hash: file_hash,
             size: file_size,
         });
@@ -2911,6 +6858,109 @@ async fn import_zip(zip_path: String) -> Result<ImportResult, String> {
     })
 }
 
+// Bundle one song with its settings, note and tags plus the current mode, key mode,
+// octave shift and speed, so another player gets the same setup
+#[tauri::command]
+async fn export_performance_package(
+    path: String,
+    export_path: String,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<performance::PerformancePackage, AppError> {
+    let hash = compute_file_hash(std::path::Path::new(&path))
+        .ok_or_else(|| AppError::FileNotFound(format!("File not found: {}", path)))?;
+    let setup = {
+        let app_state = state.lock().unwrap();
+        performance::PlaySetup {
+            note_mode: app_state.get_note_mode(),
+            key_mode: app_state.get_key_mode(),
+            octave_shift: app_state.get_octave_shift(),
+            speed: app_state.get_speed(),
+        }
+    };
+    let package = performance::write(&export_path, &path, song_meta::get(&hash), Some(setup))?;
+    app_log!(
+        "[EXPORT] Performance package for {} -> {}",
+        package.name,
+        export_path
+    );
+    Ok(package)
+}
+
+#[derive(Debug, Serialize)]
+struct ImportedPerformance {
+    file: MidiFile,
+    package: performance::PerformancePackage,
+    setup_applied: bool,
+}
+
+// Add the package's song to the album (or reuse the copy already there), take over its
+// per-song settings and, unless apply_setup is false, its mode, key mode, shift and speed
+#[tauri::command]
+async fn import_performance_package(
+    path: String,
+    apply_setup: Option<bool>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<ImportedPerformance, AppError> {
+    let (package, contents) = performance::read(&path)?;
+    let album_dir = get_album_folder()?;
+    if !album_dir.exists() {
+        std::fs::create_dir_all(&album_dir)
+            .map_err(|e| format!("Failed to create album folder: {}", e))?;
+    }
+
+    let file_hash = compute_hash_from_bytes(&contents);
+    let file = match get_existing_files_by_hash(&album_dir).remove(&file_hash) {
+        Some(existing) => existing,
+        None => {
+            let mut save_path = album_dir.join(&package.filename);
+            let mut counter = 1;
+            while save_path.exists() {
+                let stem = package.filename.trim_end_matches(".mid");
+                save_path = album_dir.join(format!("{} ({}).mid", stem, counter));
+                counter += 1;
+            }
+            std::fs::write(&save_path, &contents)
+                .map_err(|e| format!("Failed to save {}: {}", package.filename, e))?;
+            let path_str = save_path.to_string_lossy().to_string();
+            let meta = midi::get_midi_metadata(&path_str).unwrap_or_default();
+            MidiFile {
+                name: package.filename.trim_end_matches(".mid").to_string(),
+                path: path_str,
+                duration: meta.duration,
+                bpm: meta.bpm,
+                note_density: meta.note_density,
+                difficulty: meta.difficulty,
+                tempo: meta.tempo,
+                repaired: meta.repaired,
+                note_count: meta.note_count,
+                track_count: meta.track_count,
+                multi_channel: meta.multi_channel,
+                hash: file_hash.clone(),
+                size: contents.len() as u64,
+            }
+        }
+    };
+    let song = song_meta::import(&file_hash, package.song.clone())?;
+
+    let setup_applied = apply_setup.unwrap_or(true) && package.setup.is_some();
+    {
+        let mut app_state = state.lock().unwrap();
+        app_state.update_song_settings_live(&file_hash, &song.settings);
+        if let Some(setup) = package.setup.as_ref().filter(|_| setup_applied) {
+            app_state.set_note_mode(setup.note_mode);
+            app_state.set_key_mode(setup.key_mode);
+            app_state.set_octave_shift(setup.octave_shift);
+            app_state.set_speed(setup.speed);
+        }
+    }
+    app_log!("[IMPORT] Performance package: {}", file.path);
+    Ok(ImportedPerformance {
+        file,
+        package,
+        setup_applied,
+    })
+}
+
 // Helper to get current timestamp (simple implementation without chrono crate)
 fn chrono_now() -> String {
     use std::time::{SystemTime, UNIX_EPOCH};
@@ -2936,7 +6986,7 @@ fn chrono_now() -> String {
 }
 
 #[tauri::command]
-async fn install_update(zip_path: String, app_handle: AppHandle) -> Result<(), String> {
+async fn install_update(zip_path: String, app_handle: AppHandle) -> Result<(), AppError> {
     app_log!("[UPDATE] Installing from: {}", zip_path);
 
     let exe_path = std::env::current_exe().map_err(|e| e.to_string())?;
@@ -2979,6 +7029,7 @@ del "%~f0"
     Ok(())
 }
 
+#[cfg(target_os = "windows")]
 fn register_global_hotkeys() -> Vec<(String, bool)> {
     let mut results = Vec::new();
     let kb = get_keybindings();
@@ -3025,10 +7076,15 @@ static mut CACHED_NEXT_VK: u32 = 0x7A; // F11
 static mut CACHED_MODE_PREV_VK: u32 = 0xDB; // [
 static mut CACHED_MODE_NEXT_VK: u32 = 0xDD; // ]
 static mut CACHED_TOGGLE_MINI_VK: u32 = 0x2D; // Insert
+static mut CACHED_TOGGLE_INPUT_VK: u32 = 0; // Unbound
+static mut CACHED_OPACITY_UP_VK: u32 = 0; // Unbound
+static mut CACHED_OPACITY_DOWN_VK: u32 = 0; // Unbound
+static mut CACHED_CYCLE_LAYOUT_VK: u32 = 0; // Unbound
 static mut KEYBINDINGS_DISABLED: bool = false; // Disable during recording
 static mut RECORDING_MODE: bool = false; // When true, emit key names instead of actions
 
 // Convert VK code to key name string
+#[cfg(target_os = "windows")]
 fn vk_to_key(vk: u32) -> Option<String> {
     match vk {
         0x1B => Some("Escape".into()),
@@ -3089,6 +7145,10 @@ fn cache_keybinding_vks() {
         CACHED_MODE_PREV_VK = key_to_vk(&kb.mode_prev).unwrap_or(0xDB);
         CACHED_MODE_NEXT_VK = key_to_vk(&kb.mode_next).unwrap_or(0xDD);
         CACHED_TOGGLE_MINI_VK = key_to_vk(&kb.toggle_mini).unwrap_or(0x2D);
+        CACHED_TOGGLE_INPUT_VK = key_to_vk(&kb.toggle_input_mode).unwrap_or(0);
+        CACHED_OPACITY_UP_VK = key_to_vk(&kb.opacity_up).unwrap_or(0);
+        CACHED_OPACITY_DOWN_VK = key_to_vk(&kb.opacity_down).unwrap_or(0);
+        CACHED_CYCLE_LAYOUT_VK = key_to_vk(&kb.cycle_layout).unwrap_or(0);
     }
     app_log!(
         "[KEYBINDINGS] Reloaded: pause={:02X} stop={:02X} prev={:02X} next={:02X}",
@@ -3099,7 +7159,45 @@ fn cache_keybinding_vks() {
     );
 }
 
+#[cfg(target_os = "windows")]
+fn is_modifier_vk(vk: u32) -> bool {
+    matches!(
+        vk,
+        0x10 | 0x11 | 0x12 | 0xA0 | 0xA1 | 0xA2 | 0xA3 | 0xA4 | 0xA5 | 0x5B | 0x5C
+    )
+}
+
+// Escape cancels; held shift/ctrl are part of the key (36-key accidentals)
+#[cfg(target_os = "windows")]
+fn on_note_key_captured(app_handle: &AppHandle, vk: u32) {
+    use windows::Win32::UI::Input::KeyboardAndMouse::GetAsyncKeyState;
+
+    let update = if vk == 0x1B {
+        key_capture::cancel()
+    } else {
+        let held = |vk: i32| unsafe { GetAsyncKeyState(vk) } < 0;
+        let key = keyboard::vk_to_note_key(vk).map(|key| {
+            if held(0x10) {
+                format!("shift+{}", key)
+            } else if held(0x11) {
+                format!("ctrl+{}", key)
+            } else {
+                key
+            }
+        });
+        key_capture::on_key(key)
+    };
+    if let Some(update) = update {
+        if update.status == key_capture::CaptureStatus::Finished {
+            // Off the hook thread, it has to return quickly
+            std::thread::spawn(save_note_key_sets);
+        }
+        let _ = app_handle.emit("note-key-capture", update);
+    }
+}
+
 // Low-level keyboard hook callback for all keybindings
+#[cfg(target_os = "windows")]
 unsafe extern "system" fn low_level_keyboard_proc(
     ncode: i32,
     wparam: windows::Win32::Foundation::WPARAM,
@@ -3110,24 +7208,21 @@ unsafe extern "system" fn low_level_keyboard_proc(
         let is_keydown = wparam.0 as u32 == WM_KEYDOWN || wparam.0 as u32 == WM_SYSKEYDOWN;
 
         if is_keydown {
+            keyboard::note_hooked_key(kb_struct.vkCode, kb_struct.flags.0 & LLKHF_INJECTED.0 != 0);
             if let Some(ref app_handle) = GLOBAL_APP_HANDLE {
                 let vk = kb_struct.vkCode;
 
+                // Note key capture: the player's presses become note bindings
+                if key_capture::is_active() {
+                    let injected = kb_struct.flags.0 & LLKHF_INJECTED.0 != 0;
+                    if !injected && !is_modifier_vk(vk) {
+                        on_note_key_captured(app_handle, vk);
+                    }
+                }
                 // Recording mode: emit key name for binding capture
-                if RECORDING_MODE {
+                else if RECORDING_MODE {
                     // Skip modifier keys
-                    if vk != 0x10
-                        && vk != 0x11
-                        && vk != 0x12
-                        && vk != 0xA0
-                        && vk != 0xA1
-                        && vk != 0xA2
-                        && vk != 0xA3
-                        && vk != 0xA4
-                        && vk != 0xA5
-                        && vk != 0x5B
-                        && vk != 0x5C
-                    {
+                    if !is_modifier_vk(vk) {
                         if let Some(key_name) = vk_to_key(vk) {
                             let _ = app_handle.emit("key-captured", key_name);
                         }
@@ -3149,6 +7244,30 @@ unsafe extern "system" fn low_level_keyboard_proc(
                         let _ = app_handle.emit("global-shortcut", "mode_next");
                     } else if vk == CACHED_TOGGLE_MINI_VK {
                         let _ = app_handle.emit("global-shortcut", "toggle_mini");
+                    } else if vk == CACHED_TOGGLE_INPUT_VK && vk != 0 {
+                        // Handled here so it works mid-song with the app in the background
+                        let cloud_mode = keyboard::toggle_send_input_mode();
+                        let _ = app_handle.emit(
+                            "input-mode-changed",
+                            serde_json::json!({ "cloud_mode": cloud_mode, "hotkey": true }),
+                        );
+                    } else if vk != 0
+                        && (vk == CACHED_OPACITY_UP_VK
+                            || vk == CACHED_OPACITY_DOWN_VK
+                            || vk == CACHED_CYCLE_LAYOUT_VK)
+                    {
+                        // Off the hook thread, it has to return quickly
+                        let app_handle = app_handle.clone();
+                        std::thread::spawn(move || {
+                            let appearance = if vk == CACHED_CYCLE_LAYOUT_VK {
+                                overlay::cycle_layout(&app_handle)
+                            } else if vk == CACHED_OPACITY_UP_VK {
+                                overlay::step_opacity(&app_handle, OPACITY_HOTKEY_STEP)
+                            } else {
+                                overlay::step_opacity(&app_handle, -OPACITY_HOTKEY_STEP)
+                            };
+                            save_overlay_appearance(appearance);
+                        });
                     }
                 }
             }
@@ -3158,6 +7277,8 @@ unsafe extern "system" fn low_level_keyboard_proc(
     CallNextHookEx(HHOOK::default(), ncode, wparam, lparam)
 }
 
+// Global hotkeys need the Win32 keyboard hook; elsewhere only the in-app shortcuts work
+#[cfg(target_os = "windows")]
 fn start_hotkey_listener(app_handle: AppHandle) {
     // Cache keybinding VK codes for low-level hook
     cache_keybinding_vks();
@@ -3167,73 +7288,82 @@ fn start_hotkey_listener(app_handle: AppHandle) {
         GLOBAL_APP_HANDLE = Some(app_handle.clone());
     }
 
-    thread::spawn(move || {
-        // Register hotkeys in this thread (they will be associated with this thread's message queue)
-        let hotkey_results = register_global_hotkeys();
-
-        // Log results
-        println!("=== Global Hotkey Registration ===");
-        for (name, success) in &hotkey_results {
-            if *success {
-                println!("  ✓ {}", name);
-            } else {
-                println!("  ✗ {} (failed - may be in use by another app)", name);
+    let spawned = thread::Builder::new()
+        .name("hotkeys".to_string())
+        .spawn(move || {
+            // Register hotkeys in this thread (they will be associated with this thread's message queue)
+            let hotkey_results = register_global_hotkeys();
+
+            // Log results
+            println!("=== Global Hotkey Registration ===");
+            for (name, success) in &hotkey_results {
+                if *success {
+                    println!("  ✓ {}", name);
+                } else {
+                    println!("  ✗ {} (failed - may be in use by another app)", name);
+                }
             }
-        }
-        println!("==================================");
-
-        // Install low-level keyboard hook for F12 as fallback
-        unsafe {
-            let hook = SetWindowsHookExW(WH_KEYBOARD_LL, Some(low_level_keyboard_proc), None, 0);
-
-            if hook.is_err() {
-                app_error!("Failed to install low-level keyboard hook for F12");
-            } else {
-                println!("  ✓ Low-level keyboard hook installed (F12 fallback)");
+            println!("==================================");
+            *HOTKEY_STATUS.lock().unwrap() = hotkey_results;
+
+            // Install low-level keyboard hook for F12 as fallback
+            unsafe {
+                let hook =
+                    SetWindowsHookExW(WH_KEYBOARD_LL, Some(low_level_keyboard_proc), None, 0);
+
+                if hook.is_err() {
+                    app_error!("Failed to install low-level keyboard hook for F12");
+                } else {
+                    health::HOOK_INSTALLED.store(true, std::sync::atomic::Ordering::SeqCst);
+                    println!("  ✓ Low-level keyboard hook installed (F12 fallback)");
+                }
             }
-        }
 
-        // Run message loop to receive hotkey and hook messages
-        unsafe {
-            let mut msg: MSG = std::mem::zeroed();
+            // Run message loop to receive hotkey and hook messages
+            unsafe {
+                let mut msg: MSG = std::mem::zeroed();
 
-            loop {
-                // GetMessageW blocks until a message is available
-                // For low-level hooks, we need to call it even if no hotkeys registered
-                let result = GetMessageW(&mut msg, None, 0, 0);
+                loop {
+                    // GetMessageW blocks until a message is available
+                    // For low-level hooks, we need to call it even if no hotkeys registered
+                    let result = GetMessageW(&mut msg, None, 0, 0);
 
-                if result.0 == -1 {
-                    app_error!("GetMessageW error");
-                    break;
-                }
-                if result.0 == 0 {
-                    // WM_QUIT received
-                    break;
-                }
+                    if result.0 == -1 {
+                        app_error!("GetMessageW error");
+                        break;
+                    }
+                    if result.0 == 0 {
+                        // WM_QUIT received
+                        break;
+                    }
 
-                if msg.message == WM_HOTKEY && !KEYBINDINGS_DISABLED {
-                    let hotkey_id = msg.wParam.0 as i32;
+                    if msg.message == WM_HOTKEY && !KEYBINDINGS_DISABLED {
+                        let hotkey_id = msg.wParam.0 as i32;
 
-                    let action = match hotkey_id {
-                        HOTKEY_PAUSE_RESUME => "pause_resume",
-                        HOTKEY_STOP_END | HOTKEY_STOP_F12 => "stop",
-                        HOTKEY_PREV_F10 => "previous",
-                        HOTKEY_NEXT_F11 => "next",
-                        _ => continue,
-                    };
+                        let action = match hotkey_id {
+                            HOTKEY_PAUSE_RESUME => "pause_resume",
+                            HOTKEY_STOP_END | HOTKEY_STOP_F12 => "stop",
+                            HOTKEY_PREV_F10 => "previous",
+                            HOTKEY_NEXT_F11 => "next",
+                            _ => continue,
+                        };
 
-                    let _ = app_handle.emit("global-shortcut", action);
-                }
+                        let _ = app_handle.emit("global-shortcut", action);
+                    }
 
-                // Dispatch other messages (needed for low-level hook to work)
-                let _ = TranslateMessage(&msg);
-                let _ = DispatchMessageW(&msg);
+                    // Dispatch other messages (needed for low-level hook to work)
+                    let _ = TranslateMessage(&msg);
+                    let _ = DispatchMessageW(&msg);
+                }
             }
-        }
-    });
+        });
+    if let Err(e) = spawned {
+        app_error!("Failed to start hotkey listener: {}", e);
+    }
 }
 
 /// Set process priority to HIGH for better timing accuracy
+#[cfg(target_os = "windows")]
 fn set_high_priority() {
     unsafe {
         let process = GetCurrentProcess();
@@ -3255,7 +7385,7 @@ use midi_input::{LiveNoteEvent, MidiConnectionState};
 #[tauri::command]
 async fn list_midi_input_devices(
     state: State<'_, Arc<Mutex<AppState>>>,
-) -> Result<Vec<String>, String> {
+) -> Result<Vec<String>, AppError> {
     let app_state = state.lock().map_err(|e| format!("Lock error: {}", e))?;
     let midi_state = app_state.get_midi_input_state();
     let mut midi_state_guard = midi_state
@@ -3268,7 +7398,7 @@ async fn list_midi_input_devices(
 #[tauri::command]
 async fn get_midi_connection_state(
     state: State<'_, Arc<Mutex<AppState>>>,
-) -> Result<MidiConnectionState, String> {
+) -> Result<MidiConnectionState, AppError> {
     let app_state = state.lock().map_err(|e| format!("Lock error: {}", e))?;
     let midi_state = app_state.get_midi_input_state();
     let midi_state_guard = midi_state
@@ -3283,12 +7413,12 @@ async fn start_midi_listening(
     device_index: usize,
     state: State<'_, Arc<Mutex<AppState>>>,
     app_handle: AppHandle,
-) -> Result<String, String> {
+) -> Result<String, AppError> {
     // First stop any file playback (exclusive mode)
     {
         let mut app_state = state.lock().map_err(|e| format!("Lock error: {}", e))?;
         if app_state.get_playback_state().is_playing {
-            app_state.stop_playback();
+            app_state.stop_playback()?;
         }
     }
 
@@ -3300,7 +7430,7 @@ async fn start_midi_listening(
     let live_transpose = app_state.get_live_transpose();
     let is_listening = app_state.get_is_live_mode_active();
 
-    midi_input::start_listening(
+    Ok(midi_input::start_listening(
         midi_state,
         device_index,
         app_handle,
@@ -3309,7 +7439,7 @@ async fn start_midi_listening(
         octave_shift,
         live_transpose,
         is_listening,
-    )
+    )?)
 }
 
 /// Stop listening to MIDI device
@@ -3317,17 +7447,21 @@ async fn start_midi_listening(
 async fn stop_midi_listening(
     state: State<'_, Arc<Mutex<AppState>>>,
     app_handle: AppHandle,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     let app_state = state.lock().map_err(|e| format!("Lock error: {}", e))?;
     let midi_state = app_state.get_midi_input_state();
     let is_listening = app_state.get_is_live_mode_active();
 
-    midi_input::stop_listening(midi_state, is_listening, &app_handle)
+    Ok(midi_input::stop_listening(
+        midi_state,
+        is_listening,
+        &app_handle,
+    )?)
 }
 
 /// Check if live mode is active
 #[tauri::command]
-async fn is_live_mode_active(state: State<'_, Arc<Mutex<AppState>>>) -> Result<bool, String> {
+async fn is_live_mode_active(state: State<'_, Arc<Mutex<AppState>>>) -> Result<bool, AppError> {
     let app_state = state.lock().map_err(|e| format!("Lock error: {}", e))?;
     Ok(app_state
         .is_live_mode_active
@@ -3339,7 +7473,7 @@ async fn is_live_mode_active(state: State<'_, Arc<Mutex<AppState>>>) -> Result<b
 async fn set_live_transpose(
     value: i8,
     state: State<'_, Arc<Mutex<AppState>>>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     let app_state = state.lock().map_err(|e| format!("Lock error: {}", e))?;
     app_state.set_live_transpose(value);
     Ok(())
@@ -3347,7 +7481,7 @@ async fn set_live_transpose(
 
 /// Get live mode transpose
 #[tauri::command]
-async fn get_live_transpose(state: State<'_, Arc<Mutex<AppState>>>) -> Result<i8, String> {
+async fn get_live_transpose(state: State<'_, Arc<Mutex<AppState>>>) -> Result<i8, AppError> {
     let app_state = state.lock().map_err(|e| format!("Lock error: {}", e))?;
     Ok(app_state
         .live_transpose
@@ -3360,7 +7494,7 @@ async fn simulate_midi_note(
     midi_note: u8,
     state: State<'_, Arc<Mutex<AppState>>>,
     app_handle: AppHandle,
-) -> Result<LiveNoteEvent, String> {
+) -> Result<LiveNoteEvent, AppError> {
     use std::sync::atomic::Ordering;
 
     let app_state = state.lock().map_err(|e| format!("Lock error: {}", e))?;
@@ -3401,28 +7535,55 @@ async fn simulate_midi_note(
     // Actually press the key (instant tap like MIDI playback)
     keyboard::key_down(&key);
 
-    // Release in background thread (non-blocking, same as live MIDI input)
-    std::thread::spawn({
-        let key = key.clone();
-        move || {
-            std::thread::sleep(std::time::Duration::from_millis(30));
-            keyboard::key_up(&key);
-        }
-    });
+    // Release after a short tap (non-blocking, same as live MIDI input)
+    keyboard::schedule_release(&key, std::time::Duration::from_millis(30));
 
     Ok(event)
 }
 
 fn main() {
     // Initialize logging first
-    init_logger();
+    logging::init();
+    crash::install_panic_hook();
+
+    // A second copy hands its files to the running one instead of starting
+    // Relative paths mean nothing to the other copy, which runs in its own folder
+    let args: Vec<String> = std::env::args()
+        .skip(1)
+        .map(|arg| match std::path::absolute(&arg) {
+            Ok(path) if path.exists() => path.to_string_lossy().to_string(),
+            _ => arg,
+        })
+        .collect();
+    let instance_listener = match single_instance::acquire(&args) {
+        single_instance::Instance::First(listener) => Some(listener),
+        single_instance::Instance::HandedOff => return,
+        single_instance::Instance::Unguarded => None,
+    };
+
+    // Before any window exists; see overlay.rs
+    overlay::enable_dpi_awareness();
 
     // Set high priority for accurate MIDI timing
+    #[cfg(target_os = "windows")]
     set_high_priority();
 
     // Load saved settings from config
     load_saved_album_path();
     load_saved_note_keys();
+    load_saved_max_polyphony();
+    load_saved_velocity_emphasis();
+    load_saved_metronome();
+    load_saved_note_event_batch();
+    load_saved_silent_rehearsal();
+    load_saved_require_game_window();
+    load_saved_key_duration();
+    load_saved_game_profiles();
+    load_saved_auto_focus();
+    load_saved_topmost();
+    load_saved_octave_splits();
+    load_saved_auto_octave_shift();
+    load_saved_auto_advance();
     load_custom_window_keywords();
     load_saved_keybindings();
 
@@ -3431,25 +7592,94 @@ fn main() {
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
         .manage(app_state)
-        .setup(|app| {
+        .on_window_event(overlay::on_window_event)
+        .setup(move |app| {
+            if let Some(listener) = instance_listener {
+                let app_handle = app.handle().clone();
+                single_instance::listen(listener, move |args| open_files(&app_handle, args, false));
+            }
+            open_files(app.handle(), args, true);
+            #[cfg(target_os = "windows")]
             start_hotkey_listener(app.handle().clone());
+            song_requests::autostart(app.handle().clone());
+            start_locale_watcher(app.handle().clone());
+            start_game_window_watcher(app.handle().clone());
+            crash::notify_pending(app.handle());
+            start_session_saver(app.handle().clone());
+            start_player_state_emitter(app.handle().clone());
+            overlay::start_click_through(app.handle().clone());
+            load_saved_click_through(app.handle());
+            load_saved_overlay_appearance(app.handle());
+            start_key_sent_emitter(app.handle().clone());
+            data_watch::start(app.handle().clone());
+            health::start(app.handle().clone());
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             load_midi_files,
             load_midi_files_streaming,
+            list_midi_ids,
+            list_file_names,
+            list_album_presets,
+            save_album_preset,
+            remove_album_preset,
+            switch_album,
+            get_midi_details,
             count_midi_files,
             get_library_info,
+            search_library,
+            query_library,
+            export_catalog,
+            get_song_meta,
+            set_song_rating,
+            set_song_tags,
+            get_song_note,
+            set_song_note,
+            set_song_settings,
+            set_track_transpose,
+            set_muted_tracks,
+            set_track_mutes,
+            get_track_mutes,
             get_midi_tracks,
+            get_tempo_map,
+            get_density_profile,
+            preview_keys,
             play_midi,
+            play_external_file,
+            list_external_files,
+            remove_external_file,
+            clear_external_files,
             play_midi_band,
+            suggest_band_assignment,
+            band_set_setlist,
+            band_get_setlist,
+            band_setlist_provide,
+            band_setlist_goto,
+            band_setlist_next,
+            band_clear_setlist,
+            band_session_get,
+            band_session_join,
+            band_session_leave,
+            band_session_update_member,
+            band_session_ready_check,
+            band_session_countdown,
+            band_session_reset,
+            band_estimate_clock,
+            band_play_test_pattern,
+            band_estimate_latency,
+            set_band_peer_offset,
+            get_band_peer_offsets,
             pause_resume,
             stop_playback,
             get_playback_status,
+            get_player_state,
             set_loop_mode,
+            set_loop_count,
             set_note_mode,
             get_note_mode,
+            analyze_note_modes,
             set_track_filter,
+            set_band_filter_live,
             set_key_mode,
             get_key_mode,
             set_octave_shift,
@@ -3458,10 +7688,45 @@ fn main() {
             get_speed,
             set_modifier_delay,
             get_modifier_delay,
+            set_key_duration,
+            get_key_duration,
+            get_game_profile,
+            set_game_profile_auto,
+            set_cloud_profile,
+            reset_cloud_profile,
+            set_max_polyphony,
+            get_max_polyphony,
+            set_velocity_emphasis,
+            get_velocity_emphasis,
+            set_metronome,
+            get_metronome,
+            set_auto_octave_shift,
+            get_auto_octave_shift,
+            set_note_event_batch,
+            get_note_event_batch,
+            set_silent_rehearsal,
+            get_silent_rehearsal,
+            set_require_game_window,
+            get_require_game_window,
+            set_octave_splits,
+            get_octave_splits,
             set_cloud_mode,
             get_cloud_mode,
+            test_input_path,
             set_note_keys,
             get_note_keys,
+            set_note_key_modifiers,
+            set_accidental_keys,
+            get_accidental_keys,
+            reset_accidental_keys,
+            start_note_key_capture,
+            skip_note_key_capture,
+            cancel_note_key_capture,
+            list_key_presets,
+            apply_key_preset,
+            export_key_preset,
+            import_key_preset,
+            get_note_key_sets,
             reset_note_keys,
             set_custom_window_keywords,
             get_custom_window_keywords,
@@ -3482,12 +7747,16 @@ fn main() {
             spam_test_chord,
             set_interaction_mode,
             focus_game_window,
+            set_auto_focus,
+            get_auto_focus,
             seek,
             import_midi_file,
             import_from_zip,
             list_midi_in_folder,
             download_midi_from_url,
             get_visualizer_notes,
+            get_memory_stats,
+            clear_memory_caches,
             open_url,
             get_album_path,
             set_album_path,
@@ -3498,34 +7767,133 @@ fn main() {
             get_available_user_locales,
             init_user_locales,
             open_locales_folder,
+            diff_locale,
+            list_remote_locales,
+            download_locale,
             read_midi_base64,
             check_midi_exists,
             check_file_exists,
             save_temp_midi,
             verify_midi_data,
             save_midi_from_base64,
+            get_shared_songs,
+            get_sharing_policy,
+            set_sharing_policy,
+            discovery_register,
+            discovery_unregister,
+            queue_downloads,
+            p2p_download_complete,
+            p2p_download_failed,
+            get_download_queue,
+            cancel_downloads,
+            set_download_concurrency,
             rename_midi_file,
             delete_midi_file,
             open_file_location,
+            export_section,
+            split_midi,
+            bake_midi,
+            strip_midi_tracks,
+            merge_midi_tracks,
+            change_tempo,
+            cleanup_midi,
             get_window_position,
             get_game_window_bounds,
             save_window_position,
+            restore_window_position,
+            set_mini_mode,
             get_always_on_top,
             save_always_on_top,
+            get_topmost_settings,
+            set_topmost_settings,
+            set_click_through_while_playing,
+            get_click_through_while_playing,
+            get_overlay_appearance,
+            set_overlay_opacity,
+            set_overlay_layout,
             check_for_update,
             download_update,
             install_update,
             start_discovery_server,
             is_discovery_server_running,
             stop_discovery_server,
+            start_library_server,
+            stop_library_server,
+            is_library_server_running,
+            browse_library_server,
+            pull_from_library_server,
+            get_discovery_admin_token,
+            get_discovery_peers,
+            discovery_connect,
+            discovery_disconnect,
+            get_discovery_status,
+            get_cached_discovery_peers,
+            block_peer,
+            unblock_peer,
+            get_peer_blocklist,
             load_favorites,
             save_favorites,
+            list_favorites,
+            add_favorite,
+            remove_favorite,
+            toggle_favorite,
+            is_favorite,
+            list_data_backups,
+            restore_data_backup,
             load_playlists,
             save_playlists,
+            list_playlists,
+            create_playlist,
+            rename_playlist,
+            delete_playlist,
+            duplicate_playlist,
+            add_playlist_tracks,
+            remove_playlist_track,
+            move_playlist_track,
+            reorder_playlists,
             export_favorites,
             export_playlist,
             export_library,
             import_zip,
+            import_dropped,
+            export_performance_package,
+            import_performance_package,
+            // Session resume & history
+            get_last_session,
+            resume_last_session,
+            clear_last_session,
+            get_recently_played,
+            get_recently_added,
+            get_continue_listening,
+            // Queue & song requests
+            get_queue,
+            enqueue_song,
+            take_opened_files,
+            set_open_file_action,
+            get_open_file_action,
+            set_file_association,
+            get_file_association,
+            remove_from_queue,
+            clear_queue,
+            set_auto_advance,
+            get_auto_advance,
+            set_song_transition,
+            get_song_transition,
+            set_active_playlist,
+            get_active_playlist,
+            get_song_request_config,
+            set_song_request_config,
+            start_song_requests,
+            stop_song_requests,
+            get_song_request_status,
+            // Logging
+            get_log_level,
+            set_log_level,
+            read_log_lines,
+            export_diagnostics,
+            get_health,
+            get_crash_report,
+            dismiss_crash_report,
             // Live MIDI input
             list_midi_input_devices,
             get_midi_connection_state,
@@ -0,0 +1,141 @@
+// Metronome for rehearsals: follows the song's beat grid during playback and either
+// emits "metronome-tick" for the UI to sound a click or taps a spare game key.
+// Optional count-in before playback starts from the top.
+
+use crate::midi::Beat;
+use crate::player::PlayerSnapshot;
+use serde::{Deserialize, Serialize};
+use std::sync::RwLock;
+use std::time::Duration;
+use tauri::{Emitter, Window};
+use tokio::sync::watch;
+
+// Beats reached more than this late (after a pause or seek) are skipped, not clicked
+const LATE_BEAT_MS: f64 = 50.0;
+const POLL_MS: u64 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MetronomeOutput {
+    Audio, // "metronome-tick" event only; the UI plays the click
+    Key,   // Also tap `key` in game
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetronomeSettings {
+    pub enabled: bool,
+    pub output: MetronomeOutput,
+    pub key: String,  // Spare key for Key output (must not be a note key)
+    pub count_in: u8, // Beats before playback starts from the top (0 = none)
+}
+
+impl Default for MetronomeSettings {
+    fn default() -> Self {
+        MetronomeSettings {
+            enabled: false,
+            output: MetronomeOutput::Audio,
+            key: "p".to_string(),
+            count_in: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct MetronomeTick {
+    beat: u32, // 1-based within the song (or within the count-in)
+    downbeat: bool,
+    count_in: bool,
+}
+
+lazy_static::lazy_static! {
+    static ref SETTINGS: RwLock<MetronomeSettings> = RwLock::new(MetronomeSettings::default());
+}
+
+pub fn set_settings(settings: MetronomeSettings) {
+    *SETTINGS.write().unwrap() = settings;
+}
+
+pub fn get_settings() -> MetronomeSettings {
+    SETTINGS.read().unwrap().clone()
+}
+
+fn tick(window: &Window, settings: &MetronomeSettings, payload: MetronomeTick) {
+    if settings.output == MetronomeOutput::Key && !settings.key.is_empty() {
+        crate::keyboard::key_down(&settings.key);
+        crate::keyboard::key_up(&settings.key);
+    }
+    let _ = window.emit("metronome-tick", payload);
+}
+
+/// Length of one count-in beat: the song's opening beat, or 120 BPM without a grid
+pub fn count_in_beat_ms(beats: &[Beat]) -> f64 {
+    match beats {
+        [first, second, ..] => second.time_ms.saturating_sub(first.time_ms).max(1) as f64,
+        _ => 500.0,
+    }
+}
+
+/// Click count-in beat `beat` (1-based); the player schedules these before the song starts
+pub fn count_in_tick(window: &Window, beat: u32) {
+    tick(
+        window,
+        &get_settings(),
+        MetronomeTick {
+            beat,
+            downbeat: beat == 1,
+            count_in: true,
+        },
+    );
+}
+
+/// Follow the playback position and click each beat as it's reached, until this
+/// playback session ends. Settings are re-read every poll, so the metronome can be
+/// toggled mid-song.
+pub fn spawn(
+    beats: Vec<Beat>,
+    snapshots: watch::Receiver<PlayerSnapshot>,
+    session: u64,
+    window: Window,
+) {
+    if beats.is_empty() {
+        return;
+    }
+    std::thread::spawn(move || {
+        let mut last_ms = -1.0;
+        let mut next = 0;
+        loop {
+            std::thread::sleep(Duration::from_millis(POLL_MS));
+            let snapshot = snapshots.borrow().clone();
+            if snapshot.session != session || !snapshot.is_playing {
+                break;
+            }
+            if snapshot.is_paused {
+                continue;
+            }
+            let position_ms = snapshot.position * 1000.0;
+            // Position went back (loop restart or seek): find our place again
+            if position_ms < last_ms {
+                next = beats.partition_point(|b| (b.time_ms as f64) < position_ms);
+            }
+            last_ms = position_ms;
+
+            let settings = get_settings();
+            while next < beats.len() && beats[next].time_ms as f64 <= position_ms {
+                let beat = beats[next];
+                next += 1;
+                if settings.enabled && position_ms - beat.time_ms as f64 <= LATE_BEAT_MS {
+                    tick(
+                        &window,
+                        &settings,
+                        MetronomeTick {
+                            beat: next as u32,
+                            downbeat: beat.downbeat,
+                            count_in: false,
+                        },
+                    );
+                }
+            }
+        }
+    });
+}
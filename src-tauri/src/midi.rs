@@ -1,9 +1,7 @@
+use crate::error::AppError;
 use midly::{MidiMessage, Smf, TrackEventKind};
 use serde::{Deserialize, Serialize};
-use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
-use std::sync::Arc;
-use std::time::{Duration, Instant};
-use tauri::{Emitter, Window};
+use std::sync::atomic::{AtomicU8, Ordering};
 
 /// Note calculation mode - how MIDI notes are mapped to game keys
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -18,6 +16,7 @@ pub enum NoteMode {
     Python = 6,        // Exact 1:1 copy of Python main.py logic
     Wide = 7,          // Spread notes evenly across all 3 octaves (uses high/low more)
     Sharps = 8,        // 36-key mode: shifts notes to use more Shift/Ctrl modifiers
+    Smart = 9,         // Picks the concrete mode that loses the fewest notes, per song
 }
 
 impl From<u8> for NoteMode {
@@ -32,6 +31,7 @@ impl From<u8> for NoteMode {
             6 => NoteMode::Python,
             7 => NoteMode::Wide,
             8 => NoteMode::Sharps,
+            9 => NoteMode::Smart,
             _ => NoteMode::Closest,
         }
     }
@@ -56,12 +56,17 @@ impl From<u8> for KeyMode {
 }
 
 /// Band mode filter - how to filter notes for multiplayer
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "lowercase")]
 pub enum BandFilter {
     /// Split mode: player plays every Nth note starting from slot
     Split { slot: usize, total_players: usize },
     /// Track mode: player plays only notes from a specific track
     Track { track_id: usize },
+    /// Several tracks: player plays notes from any of them
+    Tracks { track_ids: Vec<usize> },
+    /// Pitch range: player plays notes between low and high (inclusive, before transpose)
+    Range { low: u8, high: u8 },
 }
 
 #[derive(Debug, Clone)]
@@ -69,6 +74,87 @@ pub struct MidiData {
     pub events: Vec<TimedEvent>,
     pub duration: f64,
     pub transpose: i32,
+    pub beats: Vec<Beat>, // Beat grid from the tempo map and time signatures (for the metronome)
+}
+
+impl MidiData {
+    /// Rough heap size of the parsed song (events and beat grid)
+    pub fn approx_bytes(&self) -> usize {
+        self.events.capacity() * std::mem::size_of::<TimedEvent>()
+            + self.beats.capacity() * std::mem::size_of::<Beat>()
+    }
+
+    /// Move every event later (positive) or earlier (negative) by `offset_ms`.
+    /// Events that would land before the start are played at the start instead;
+    /// beats before the start are dropped so the metronome doesn't stack them.
+    pub fn shift_events(&mut self, offset_ms: i64) {
+        if offset_ms == 0 {
+            return;
+        }
+        for e in &mut self.events {
+            e.time_ms = (e.time_ms as i64 + offset_ms).max(0) as u64;
+        }
+        let shift = |time_ms: u64| u64::try_from(time_ms as i64 + offset_ms).ok();
+        self.beats.retain_mut(|b| match shift(b.time_ms) {
+            Some(t) => {
+                b.time_ms = t;
+                true
+            }
+            None => false,
+        });
+        self.duration = self
+            .events
+            .last()
+            .map_or(0.0, |e| e.time_ms as f64 / 1000.0);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Beat {
+    pub time_ms: u64,
+    pub downbeat: bool, // First beat of a bar
+}
+
+// Guard against absurd grids from broken headers
+const MAX_BEATS: usize = 100_000;
+
+/// Beats from the start of the song up to `end_tick`, following time signature changes
+fn beat_grid(smf: &Smf, tempo_map: &TempoMap, end_tick: u64) -> Vec<Beat> {
+    // (tick, beats per bar, beat length in ticks); 4/4 until told otherwise
+    let mut signatures = vec![(0u64, 4u32, tempo_map.ticks_per_quarter)];
+    for (track, offset) in smf.tracks.iter().zip(track_offsets(smf)) {
+        let mut tick = offset;
+        for event in track {
+            tick += event.delta.as_int() as u64;
+            if let TrackEventKind::Meta(midly::MetaMessage::TimeSignature(num, denom_pow, ..)) =
+                event.kind
+            {
+                let beat_ticks = tempo_map.ticks_per_quarter * 4.0 / 2f64.powi(denom_pow as i32);
+                signatures.push((tick, num.max(1) as u32, beat_ticks));
+            }
+        }
+    }
+    signatures.sort_by_key(|(tick, ..)| *tick);
+
+    let mut beats = Vec::new();
+    let mut tick = 0.0f64;
+    let mut beat_in_bar = 0u32;
+    let mut sig_index = 0;
+    while tick <= end_tick as f64 && beats.len() < MAX_BEATS {
+        // A new time signature starts a new bar
+        while sig_index + 1 < signatures.len() && signatures[sig_index + 1].0 as f64 <= tick {
+            sig_index += 1;
+            beat_in_bar = 0;
+        }
+        let (_, beats_per_bar, beat_ticks) = signatures[sig_index];
+        beats.push(Beat {
+            time_ms: tempo_map.ticks_to_ms(tick as u64) as u64,
+            downbeat: beat_in_bar == 0,
+        });
+        tick += beat_ticks.max(1.0);
+        beat_in_bar = (beat_in_bar + 1) % beats_per_bar;
+    }
+    beats
 }
 
 #[derive(Debug, Clone)]
@@ -76,6 +162,7 @@ pub struct TimedEvent {
     pub time_ms: u64,
     pub event_type: EventType,
     pub note: u8,
+    pub velocity: u8,    // 0 for NoteOff
     pub track_id: usize, // Track index for band mode filtering
 }
 
@@ -85,6 +172,402 @@ pub enum EventType {
     NoteOff,
 }
 
+// Max simultaneous notes per chord (0 = unlimited)
+static MAX_POLYPHONY: AtomicU8 = AtomicU8::new(0);
+
+// Notes starting within this window are treated as one chord
+const CHORD_WINDOW_MS: u64 = 15;
+
+pub fn set_max_polyphony(max: u8) {
+    MAX_POLYPHONY.store(max, Ordering::SeqCst);
+}
+
+pub fn get_max_polyphony() -> u8 {
+    MAX_POLYPHONY.load(Ordering::SeqCst)
+}
+
+/// Velocity-based dynamics. The game has no note length or loudness, so quiet
+/// (ghost) notes are dropped and accented notes are exempt from the polyphony cap.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct VelocityEmphasis {
+    pub enabled: bool,
+    pub ghost_below: u8, // Notes softer than this are skipped
+    pub accent_from: u8, // Notes at least this loud always play
+}
+
+impl Default for VelocityEmphasis {
+    fn default() -> Self {
+        VelocityEmphasis {
+            enabled: false,
+            ghost_below: 20,
+            accent_from: 100,
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref VELOCITY_EMPHASIS: std::sync::RwLock<VelocityEmphasis> =
+        std::sync::RwLock::new(VelocityEmphasis::default());
+}
+
+pub fn set_velocity_emphasis(emphasis: VelocityEmphasis) {
+    *VELOCITY_EMPHASIS.write().unwrap() = emphasis;
+}
+
+pub fn get_velocity_emphasis() -> VelocityEmphasis {
+    *VELOCITY_EMPHASIS.read().unwrap()
+}
+
+/// Per-song playback options, stored with the song's metadata (keyed by hash)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SongSettings {
+    /// Play only one note of simultaneous notes an octave or two apart
+    #[serde(default)]
+    pub dedupe_octaves: bool,
+    /// Drop notes outside this range instead of folding them into the instrument's octaves
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub drop_outside: Option<NoteRange>,
+    /// Semitones added to individual tracks (track id -> offset)
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub track_transpose: std::collections::BTreeMap<usize, i32>,
+    /// Modes NoteMode::Smart picked for this song (filled in by the analyzer)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub smart_mode: Option<SmartChoice>,
+    /// Play each chord as a single note, for songs whose chords sound bad in game
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub single_note: Option<ChordReduction>,
+    /// Tracks never played in solo playback (band parts pick their own tracks)
+    #[serde(default, skip_serializing_if = "std::collections::BTreeSet::is_empty")]
+    pub muted_tracks: std::collections::BTreeSet<usize>,
+}
+
+/// Which note of a chord survives when chords are reduced to one note
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChordReduction {
+    Root, // The lowest note
+    Highest,
+}
+
+/// Inclusive MIDI note range, compared after the song's transpose.
+/// A missing bound defaults to the instrument's range.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NoteRange {
+    pub low: i32,
+    pub high: i32,
+}
+
+impl Default for NoteRange {
+    // The instrument's own range, C3-B5
+    fn default() -> Self {
+        NoteRange { low: 48, high: 83 }
+    }
+}
+
+impl NoteRange {
+    pub fn contains(&self, note: i32) -> bool {
+        note >= self.low && note <= self.high
+    }
+}
+
+/// Concrete note mode chosen by Smart for each key mode
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SmartChoice {
+    pub keys21: NoteMode,
+    pub keys36: NoteMode,
+}
+
+pub const MAX_TRACK_TRANSPOSE: i32 = 24;
+
+impl SongSettings {
+    pub fn track_offset(&self, track_id: usize) -> i32 {
+        self.track_transpose.get(&track_id).copied().unwrap_or(0)
+    }
+
+    /// The mode to actually map with: Smart resolves to the analyzed choice (Python until analyzed)
+    pub fn resolve_note_mode(&self, note_mode: NoteMode, key_mode: KeyMode) -> NoteMode {
+        if note_mode != NoteMode::Smart {
+            return note_mode;
+        }
+        match (self.smart_mode, key_mode) {
+            (Some(choice), KeyMode::Keys21) => choice.keys21,
+            (Some(choice), KeyMode::Keys36) => choice.keys36,
+            (None, _) => NoteMode::Python,
+        }
+    }
+}
+
+/// How well one note mode plays a song
+#[derive(Debug, Clone, Serialize)]
+pub struct MappingStats {
+    pub note_mode: NoteMode,
+    pub total_notes: u32,
+    pub collided_notes: u32, // Chord notes that landed on a key already pressed in the chord
+    pub off_pitch_notes: u32, // Notes whose key plays a different pitch class
+}
+
+impl MappingStats {
+    /// Lower is better; a collided note is lost entirely so it counts double
+    pub fn lost_score(&self) -> u32 {
+        self.collided_notes * 2 + self.off_pitch_notes
+    }
+}
+
+// Modes Smart chooses between (first wins ties)
+const SMART_CANDIDATES_21: [NoteMode; 4] = [
+    NoteMode::Python,
+    NoteMode::Wide,
+    NoteMode::Closest,
+    NoteMode::Pentatonic,
+];
+const SMART_CANDIDATES_36: [NoteMode; 5] = [
+    NoteMode::Python,
+    NoteMode::Wide,
+    NoteMode::Closest,
+    NoteMode::Chromatic,
+    NoteMode::Sharps,
+];
+
+/// Lost-note statistics for one mode over the whole song
+pub fn analyze_mapping(
+    midi_data: &MidiData,
+    key_mode: KeyMode,
+    note_mode: NoteMode,
+) -> MappingStats {
+    let mut stats = MappingStats {
+        note_mode,
+        total_notes: 0,
+        collided_notes: 0,
+        off_pitch_notes: 0,
+    };
+    for chord in chords(&midi_data.events, None) {
+        let mut pressed = std::collections::HashSet::new();
+        for i in chord {
+            let note = midi_data.events[i].note as i32;
+            let key = note_to_game_key(note, key_mode, note_mode, midi_data.transpose, 0);
+            stats.total_notes += 1;
+            if let Some(played) = game_key_to_note(&key) {
+                if (played - (note + midi_data.transpose)).rem_euclid(12) != 0 {
+                    stats.off_pitch_notes += 1;
+                }
+            }
+            if !pressed.insert(key) {
+                stats.collided_notes += 1;
+            }
+        }
+    }
+    stats
+}
+
+/// Stats for every mode Smart considers, best first
+pub fn analyze_modes(midi_data: &MidiData, key_mode: KeyMode) -> Vec<MappingStats> {
+    let candidates: &[NoteMode] = match key_mode {
+        KeyMode::Keys21 => &SMART_CANDIDATES_21,
+        KeyMode::Keys36 => &SMART_CANDIDATES_36,
+    };
+    let mut stats: Vec<MappingStats> = candidates
+        .iter()
+        .map(|&mode| analyze_mapping(midi_data, key_mode, mode))
+        .collect();
+    stats.sort_by_key(|s| s.lost_score()); // Stable, so candidate order breaks ties
+    stats
+}
+
+pub fn pick_smart_modes(midi_data: &MidiData) -> SmartChoice {
+    let best = |key_mode| {
+        analyze_modes(midi_data, key_mode)
+            .first()
+            .map(|s| s.note_mode)
+            .unwrap_or(NoteMode::Python)
+    };
+    SmartChoice {
+        keys21: best(KeyMode::Keys21),
+        keys36: best(KeyMode::Keys36),
+    }
+}
+
+/// NoteOn event indices grouped into chords (starts within CHORD_WINDOW_MS),
+/// optionally only for one track
+fn chords(events: &[TimedEvent], track_id: Option<usize>) -> Vec<Vec<usize>> {
+    let mut chords: Vec<Vec<usize>> = Vec::new();
+    let mut chord_start = 0u64;
+    for (i, event) in events.iter().enumerate() {
+        if !matches!(event.event_type, EventType::NoteOn) {
+            continue;
+        }
+        if track_id.is_some_and(|t| event.track_id != t) {
+            continue;
+        }
+        match chords.last_mut() {
+            Some(chord) if event.time_ms.saturating_sub(chord_start) <= CHORD_WINDOW_MS => {
+                chord.push(i)
+            }
+            _ => {
+                chord_start = event.time_ms;
+                chords.push(vec![i]);
+            }
+        }
+    }
+    chords
+}
+
+/// Indices of notes that double another note of the chord at the unison, octave or
+/// two octaves. One note per doubled group survives: the one inside the instrument
+/// range if any, otherwise the highest.
+fn octave_duplicate_drops(
+    events: &[TimedEvent],
+    chords: &[Vec<usize>],
+) -> std::collections::HashSet<usize> {
+    let (lo, hi) = (INSTRUMENT_NOTES[0], INSTRUMENT_NOTES[20]);
+    let mut drops = std::collections::HashSet::new();
+    for chord in chords {
+        let mut sorted = chord.clone();
+        sorted.sort_by_key(|&i| (events[i].note % 12, events[i].note));
+
+        // Runs of the same pitch class at most two octaves apart
+        let mut start = 0;
+        while start < sorted.len() {
+            let mut end = start + 1;
+            while end < sorted.len()
+                && events[sorted[end]].note % 12 == events[sorted[start]].note % 12
+                && events[sorted[end]].note - events[sorted[end - 1]].note <= 24
+            {
+                end += 1;
+            }
+            let group = &sorted[start..end];
+            let keep = group
+                .iter()
+                .copied()
+                .max_by_key(|&i| {
+                    let note = events[i].note as i32;
+                    (note >= lo && note <= hi, note)
+                })
+                .unwrap_or(group[0]);
+            drops.extend(group.iter().copied().filter(|&i| i != keep));
+            start = end;
+        }
+    }
+    drops
+}
+
+/// Indices of NoteOn events to skip so no chord exceeds `cap` notes.
+/// Keeps the melody (highest) and bass (lowest) and drops inner voices first,
+/// starting with notes whose pitch class is already doubled in the chord.
+/// Notes at or above `accent_from` velocity are never dropped, even if the chord stays over the cap.
+fn polyphony_drops(
+    events: &[TimedEvent],
+    chords: &[Vec<usize>],
+    cap: usize,
+    accent_from: Option<u8>,
+) -> std::collections::HashSet<usize> {
+    let mut drops = std::collections::HashSet::new();
+    if cap == 0 {
+        return drops;
+    }
+
+    for chord in chords {
+        let mut chord = chord.clone();
+        chord.sort_by_key(|&i| events[i].note);
+        while chord.len() > cap {
+            let len = chord.len();
+            // With a cap of 1 only the melody survives
+            let first = if cap >= 2 { 1 } else { 0 };
+            let victim = (first..len - 1)
+                .filter(|&pos| accent_from.is_none_or(|v| events[chord[pos]].velocity < v))
+                .max_by_key(|&pos| {
+                    let pc = events[chord[pos]].note % 12;
+                    let doubled = chord
+                        .iter()
+                        .enumerate()
+                        .any(|(p, &i)| p != pos && events[i].note % 12 == pc);
+                    (doubled, pos.min(len - 1 - pos))
+                });
+            let Some(victim) = victim else {
+                break; // Only accented notes left to drop
+            };
+            drops.insert(chord.remove(victim));
+        }
+    }
+    drops
+}
+
+/// All NoteOn events playback should skip for the current thinning options
+/// (muted tracks, ghost notes, out of range, octave doubles, chord reduction, polyphony cap)
+pub fn dropped_notes(
+    events: &[TimedEvent],
+    track_id: Option<usize>,
+    cap: usize,
+    transpose: i32,
+    settings: &SongSettings,
+    emphasis: VelocityEmphasis,
+) -> std::collections::HashSet<usize> {
+    let mut chords = chords(events, track_id);
+    let mut drops = std::collections::HashSet::new();
+
+    // Muted tracks go before anything else so they don't shape the other passes
+    if !settings.muted_tracks.is_empty() {
+        for chord in &mut chords {
+            chord.retain(|&i| {
+                let keep = !settings.muted_tracks.contains(&events[i].track_id);
+                if !keep {
+                    drops.insert(i);
+                }
+                keep
+            });
+        }
+    }
+
+    if emphasis.enabled {
+        for chord in &mut chords {
+            chord.retain(|&i| {
+                let keep = events[i].velocity >= emphasis.ghost_below;
+                if !keep {
+                    drops.insert(i);
+                }
+                keep
+            });
+        }
+    }
+
+    // Out-of-range notes go first so they don't take a chord slot from notes that play
+    if let Some(range) = settings.drop_outside {
+        for chord in &mut chords {
+            chord.retain(|&i| {
+                let note =
+                    events[i].note as i32 + transpose + settings.track_offset(events[i].track_id);
+                let keep = range.contains(note);
+                if !keep {
+                    drops.insert(i);
+                }
+                keep
+            });
+        }
+    }
+    if settings.dedupe_octaves {
+        drops.extend(octave_duplicate_drops(events, &chords));
+        for chord in &mut chords {
+            chord.retain(|i| !drops.contains(i));
+        }
+    }
+    if let Some(reduction) = settings.single_note {
+        for chord in &mut chords {
+            let pitch =
+                |i: &&usize| events[**i].note as i32 + settings.track_offset(events[**i].track_id);
+            let keep = match reduction {
+                ChordReduction::Root => chord.iter().min_by_key(pitch),
+                ChordReduction::Highest => chord.iter().max_by_key(pitch),
+            }
+            .copied();
+            drops.extend(chord.iter().filter(|&&i| Some(i) != keep));
+            chord.retain(|&i| Some(i) == keep);
+        }
+    }
+    let accent_from = emphasis.enabled.then_some(emphasis.accent_from);
+    drops.extend(polyphony_drops(events, &chords, cap, accent_from));
+    drops
+}
+
 // 21-key mode: Basic keys for 3 octaves (7 notes each)
 const LOW_KEYS: [&str; 7] = ["z", "x", "c", "v", "b", "n", "m"];
 const MID_KEYS: [&str; 7] = ["a", "s", "d", "f", "g", "h", "j"];
@@ -99,6 +582,147 @@ pub struct MidiMetadata {
     pub bpm: u16,          // beats per minute (initial tempo)
     pub note_count: u32,   // total note-on events
     pub note_density: f32, // notes per second
+    pub difficulty: u8,    // 0-100 playability score (higher = harder / more lossy)
+    pub tempo: TempoStats,
+    pub repaired: bool,      // file was damaged and only partially readable
+    pub track_count: u16,    // tracks with notes (tempo-only tracks don't count)
+    pub multi_channel: bool, // notes on more than one MIDI channel
+}
+
+impl Default for MidiMetadata {
+    // What an unreadable file shows in the library
+    fn default() -> Self {
+        MidiMetadata {
+            duration: 0.0,
+            bpm: 120,
+            note_count: 0,
+            note_density: 0.0,
+            difficulty: 0,
+            tempo: TempoStats::default(),
+            repaired: false,
+            track_count: 0,
+            multi_channel: false,
+        }
+    }
+}
+
+/// Tempo summary over the whole song (bpm above is only the first tempo)
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct TempoStats {
+    pub bpm_min: f32,
+    pub bpm_max: f32,
+    pub bpm_avg: f32,       // time-weighted average
+    pub tempo_changes: u32, // number of tempo events in the file
+}
+
+/// One point of the tempo map (for the seek bar)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TempoPoint {
+    pub time: f64, // seconds
+    pub bpm: f32,
+}
+
+/// Tick offset each track starts at: 0 for format 0/1, cumulative for format 2
+/// (format 2 tracks are independent sequences played one after another)
+pub fn track_offsets(smf: &Smf) -> Vec<u64> {
+    let mut offsets = Vec::with_capacity(smf.tracks.len());
+    let mut next: u64 = 0;
+    for track in &smf.tracks {
+        if smf.header.format == midly::Format::Sequential {
+            offsets.push(next);
+            next += track.iter().map(|e| e.delta.as_int() as u64).sum::<u64>();
+        } else {
+            offsets.push(0);
+        }
+    }
+    offsets
+}
+
+/// Converts ticks to milliseconds for metrical (tempo-based) and SMPTE timing
+pub struct TempoMap {
+    ticks_per_quarter: f64,
+    ticks_per_second: Option<f64>, // SMPTE: fixed rate, tempo events don't affect timing
+    changes: Vec<(u64, f64)>,      // (tick, microseconds per quarter), sorted
+}
+
+impl TempoMap {
+    pub fn new(timing: midly::Timing, mut changes: Vec<(u64, f64)>) -> Self {
+        changes.sort_by_key(|(tick, _)| *tick);
+        let (ticks_per_quarter, ticks_per_second) = match timing {
+            midly::Timing::Metrical(tpq) => (tpq.as_int().max(1) as f64, None),
+            midly::Timing::Timecode(fps, subframes) => {
+                (480.0, Some(fps.as_f32() as f64 * subframes.max(1) as f64))
+            }
+        };
+        TempoMap {
+            ticks_per_quarter,
+            ticks_per_second,
+            changes,
+        }
+    }
+
+    /// Collect tempo events from all tracks (format 2 tracks placed back to back)
+    pub fn from_smf(smf: &Smf) -> Self {
+        let mut changes = Vec::new();
+        for (track, offset) in smf.tracks.iter().zip(track_offsets(smf)) {
+            let mut tick = offset;
+            for event in track {
+                tick += event.delta.as_int() as u64;
+                if let TrackEventKind::Meta(midly::MetaMessage::Tempo(t)) = event.kind {
+                    changes.push((tick, t.as_int() as f64));
+                }
+            }
+        }
+        TempoMap::new(smf.header.timing, changes)
+    }
+
+    pub fn changes(&self) -> &[(u64, f64)] {
+        &self.changes
+    }
+
+    /// Length in ms of `delta_ticks` at the given tempo
+    pub fn segment_ms(&self, delta_ticks: u64, tempo: f64) -> f64 {
+        match self.ticks_per_second {
+            Some(tps) => delta_ticks as f64 * 1000.0 / tps,
+            None => delta_ticks as f64 / self.ticks_per_quarter * tempo / 1000.0,
+        }
+    }
+
+    pub fn ticks_to_ms(&self, ticks: u64) -> f64 {
+        let mut result_ms = 0.0;
+        let mut last_tick = 0u64;
+        let mut current_tempo = 500_000.0;
+
+        for &(change_tick, new_tempo) in &self.changes {
+            if change_tick >= ticks {
+                break;
+            }
+            result_ms += self.segment_ms(change_tick - last_tick, current_tempo);
+            last_tick = change_tick;
+            current_tempo = new_tempo;
+        }
+
+        result_ms + self.segment_ms(ticks - last_tick, current_tempo)
+    }
+
+    pub fn ms_to_ticks(&self, ms: f64) -> u64 {
+        let mut elapsed_ms = 0.0;
+        let mut last_tick = 0u64;
+        let mut current_tempo = 500_000.0;
+
+        for &(change_tick, new_tempo) in &self.changes {
+            let segment_ms = self.segment_ms(change_tick - last_tick, current_tempo);
+            if elapsed_ms + segment_ms >= ms {
+                break;
+            }
+            elapsed_ms += segment_ms;
+            last_tick = change_tick;
+            current_tempo = new_tempo;
+        }
+
+        let ms_per_tick = self.segment_ms(1, current_tempo);
+        last_tick + ((ms - elapsed_ms).max(0.0) / ms_per_tick).round() as u64
+    }
 }
 
 /// MIDI track information for band mode
@@ -108,27 +732,220 @@ pub struct MidiTrackInfo {
     pub name: String,        // track name (from MIDI metadata or generated)
     pub note_count: u32,     // number of notes in this track
     pub channel: Option<u8>, // MIDI channel (0-15) if consistent
+    #[serde(default)]
+    pub instrument: Option<String>, // General MIDI name from the first Program Change
 }
 
-/// Get all MIDI metadata in a single parse (efficient for bulk loading)
-pub fn get_midi_metadata(path: &str) -> Result<MidiMetadata, String> {
-    let data = std::fs::read(path).map_err(|e| e.to_string())?;
-    let smf = Smf::parse(&data).map_err(|e| e.to_string())?;
+/// General MIDI program names (program 0-127)
+const GM_INSTRUMENTS: [&str; 128] = [
+    "Acoustic Grand Piano",
+    "Bright Acoustic Piano",
+    "Electric Grand Piano",
+    "Honky-tonk Piano",
+    "Electric Piano 1",
+    "Electric Piano 2",
+    "Harpsichord",
+    "Clavinet",
+    "Celesta",
+    "Glockenspiel",
+    "Music Box",
+    "Vibraphone",
+    "Marimba",
+    "Xylophone",
+    "Tubular Bells",
+    "Dulcimer",
+    "Drawbar Organ",
+    "Percussive Organ",
+    "Rock Organ",
+    "Church Organ",
+    "Reed Organ",
+    "Accordion",
+    "Harmonica",
+    "Tango Accordion",
+    "Acoustic Guitar (nylon)",
+    "Acoustic Guitar (steel)",
+    "Electric Guitar (jazz)",
+    "Electric Guitar (clean)",
+    "Electric Guitar (muted)",
+    "Overdriven Guitar",
+    "Distortion Guitar",
+    "Guitar Harmonics",
+    "Acoustic Bass",
+    "Electric Bass (finger)",
+    "Electric Bass (pick)",
+    "Fretless Bass",
+    "Slap Bass 1",
+    "Slap Bass 2",
+    "Synth Bass 1",
+    "Synth Bass 2",
+    "Violin",
+    "Viola",
+    "Cello",
+    "Contrabass",
+    "Tremolo Strings",
+    "Pizzicato Strings",
+    "Orchestral Harp",
+    "Timpani",
+    "String Ensemble 1",
+    "String Ensemble 2",
+    "Synth Strings 1",
+    "Synth Strings 2",
+    "Choir Aahs",
+    "Voice Oohs",
+    "Synth Voice",
+    "Orchestra Hit",
+    "Trumpet",
+    "Trombone",
+    "Tuba",
+    "Muted Trumpet",
+    "French Horn",
+    "Brass Section",
+    "Synth Brass 1",
+    "Synth Brass 2",
+    "Soprano Sax",
+    "Alto Sax",
+    "Tenor Sax",
+    "Baritone Sax",
+    "Oboe",
+    "English Horn",
+    "Bassoon",
+    "Clarinet",
+    "Piccolo",
+    "Flute",
+    "Recorder",
+    "Pan Flute",
+    "Blown Bottle",
+    "Shakuhachi",
+    "Whistle",
+    "Ocarina",
+    "Lead 1 (square)",
+    "Lead 2 (sawtooth)",
+    "Lead 3 (calliope)",
+    "Lead 4 (chiff)",
+    "Lead 5 (charang)",
+    "Lead 6 (voice)",
+    "Lead 7 (fifths)",
+    "Lead 8 (bass + lead)",
+    "Pad 1 (new age)",
+    "Pad 2 (warm)",
+    "Pad 3 (polysynth)",
+    "Pad 4 (choir)",
+    "Pad 5 (bowed)",
+    "Pad 6 (metallic)",
+    "Pad 7 (halo)",
+    "Pad 8 (sweep)",
+    "FX 1 (rain)",
+    "FX 2 (soundtrack)",
+    "FX 3 (crystal)",
+    "FX 4 (atmosphere)",
+    "FX 5 (brightness)",
+    "FX 6 (goblins)",
+    "FX 7 (echoes)",
+    "FX 8 (sci-fi)",
+    "Sitar",
+    "Banjo",
+    "Shamisen",
+    "Koto",
+    "Kalimba",
+    "Bagpipe",
+    "Fiddle",
+    "Shanai",
+    "Tinkle Bell",
+    "Agogo",
+    "Steel Drums",
+    "Woodblock",
+    "Taiko Drum",
+    "Melodic Tom",
+    "Synth Drum",
+    "Reverse Cymbal",
+    "Guitar Fret Noise",
+    "Breath Noise",
+    "Seashore",
+    "Bird Tweet",
+    "Telephone Ring",
+    "Helicopter",
+    "Applause",
+    "Gunshot",
+];
+
+/// Channel 10 (index 9) is always percussion in General MIDI
+const GM_DRUM_CHANNEL: u8 = 9;
 
-    let ticks_per_quarter = match smf.header.timing {
-        midly::Timing::Metrical(tpq) => tpq.as_int() as f64,
-        _ => 480.0,
+pub fn gm_instrument_name(program: u8, channel: u8) -> &'static str {
+    if channel == GM_DRUM_CHANNEL {
+        "Drums"
+    } else {
+        GM_INSTRUMENTS[(program & 0x7F) as usize]
+    }
+}
+
+/// midly 0.5 negates the SMPTE frame rate as an i8, which overflows (a panic in debug
+/// builds) on a division byte of 0x80. Refuse such headers before midly sees them.
+pub fn has_valid_timing(data: &[u8]) -> bool {
+    // The header is at the start, or a little further in for RIFF-wrapped (RMID) files
+    let header = data.windows(4).take(64).position(|w| w == b"MThd");
+    header.is_none_or(|pos| data.get(pos + 12) != Some(&0x80))
+}
+
+/// Parse a MIDI file, salvaging what is readable from damaged files
+/// (truncated tracks, bad running status). Returns the file and whether
+/// anything had to be dropped to read it.
+pub fn parse_smf(data: &[u8]) -> Result<(Smf<'_>, bool), AppError> {
+    if !has_valid_timing(data) {
+        return Err(AppError::InvalidMidi(
+            "Invalid MIDI timing (SMPTE frame rate)".to_string(),
+        ));
+    }
+    let strict_err = match Smf::parse(data) {
+        Ok(smf) => return Ok((smf, false)),
+        Err(e) => e.to_string(),
     };
 
+    // Lenient path: read track by track and keep events up to the first error
+    let (header, tracks) =
+        midly::parse(data).map_err(|_| AppError::InvalidMidi(strict_err.clone()))?;
+    let mut smf = Smf::new(header);
+    for track in tracks {
+        let events = match track {
+            Ok(events) => events,
+            Err(_) => continue,
+        };
+        let salvaged: Vec<_> = events.map_while(|e| e.ok()).collect();
+        if !salvaged.is_empty() {
+            smf.tracks.push(salvaged);
+        }
+    }
+
+    if smf.tracks.is_empty() {
+        return Err(AppError::InvalidMidi(strict_err));
+    }
+    println!(
+        "[MIDI] Repaired damaged file ({}), salvaged {} track(s)",
+        strict_err,
+        smf.tracks.len()
+    );
+    Ok((smf, true))
+}
+
+/// Get all MIDI metadata in a single parse (efficient for bulk loading)
+pub fn get_midi_metadata(path: &str) -> Result<MidiMetadata, AppError> {
+    let data =
+        std::fs::read(crate::paths::long(path)).map_err(|e| AppError::io("read MIDI file", e))?;
+    let (smf, repaired) = parse_smf(&data)?;
+
     let mut tempo_changes: Vec<(u64, f64)> = Vec::new();
     let mut max_ticks: u64 = 0;
     let mut note_count: u32 = 0;
+    let mut note_ons: Vec<(u64, u8)> = Vec::new(); // (tick, key) for difficulty scoring
     let mut initial_tempo: f64 = 500_000.0; // Default 120 BPM
     let mut found_initial_tempo = false;
+    let mut track_count: u16 = 0;
+    let mut channels: u16 = 0; // Bit per channel with notes
 
     // Single pass: collect tempo, duration, and note count
-    for track in &smf.tracks {
-        let mut track_time_ticks: u64 = 0;
+    for (track, offset) in smf.tracks.iter().zip(track_offsets(&smf)) {
+        let mut track_time_ticks: u64 = offset;
+        let mut track_has_notes = false;
         for event in track {
             track_time_ticks += event.delta.as_int() as u64;
 
@@ -142,39 +959,60 @@ pub fn get_midi_metadata(path: &str) -> Result<MidiMetadata, String> {
                     tempo_changes.push((track_time_ticks, tempo_val));
                 }
                 TrackEventKind::Midi {
-                    message: MidiMessage::NoteOn { vel, .. },
-                    ..
+                    channel,
+                    message: MidiMessage::NoteOn { key, vel },
                 } => {
                     if vel.as_int() > 0 {
                         note_count += 1;
+                        note_ons.push((track_time_ticks, key.as_int()));
+                        track_has_notes = true;
+                        channels |= 1 << channel.as_int();
                     }
                 }
                 _ => {}
             }
         }
+        if track_has_notes {
+            track_count += 1;
+        }
         if track_time_ticks > max_ticks {
             max_ticks = track_time_ticks;
         }
     }
-    tempo_changes.sort_by_key(|(time, _)| *time);
+    let tempo_map = TempoMap::new(smf.header.timing, tempo_changes);
 
-    // Calculate duration in seconds
+    // Calculate duration in seconds, tracking the slowest/fastest tempo actually in effect
     let mut result_ms = 0.0;
     let mut last_tick = 0u64;
     let mut current_tempo = 500_000.0;
+    let mut min_tempo = f64::MAX;
+    let mut max_tempo = 0.0_f64;
+    let mut weighted_bpm = 0.0; // sum of bpm * ms for the average
 
-    for &(change_tick, new_tempo) in &tempo_changes {
+    for &(change_tick, new_tempo) in tempo_map.changes() {
         if change_tick >= max_ticks {
             break;
         }
         let delta_ticks = change_tick - last_tick;
-        result_ms += delta_ticks as f64 / ticks_per_quarter * current_tempo / 1000.0;
+        if delta_ticks > 0 {
+            min_tempo = min_tempo.min(current_tempo);
+            max_tempo = max_tempo.max(current_tempo);
+        }
+        let segment_ms = tempo_map.segment_ms(delta_ticks, current_tempo);
+        weighted_bpm += 60_000_000.0 / current_tempo * segment_ms;
+        result_ms += segment_ms;
         last_tick = change_tick;
         current_tempo = new_tempo;
     }
 
     let delta_ticks = max_ticks - last_tick;
-    result_ms += delta_ticks as f64 / ticks_per_quarter * current_tempo / 1000.0;
+    if delta_ticks > 0 || min_tempo == f64::MAX {
+        min_tempo = min_tempo.min(current_tempo);
+        max_tempo = max_tempo.max(current_tempo);
+    }
+    let segment_ms = tempo_map.segment_ms(delta_ticks, current_tempo);
+    weighted_bpm += 60_000_000.0 / current_tempo * segment_ms;
+    result_ms += segment_ms;
 
     let duration = result_ms / 1000.0; // seconds
     let bpm = (60_000_000.0 / initial_tempo).round() as u16;
@@ -184,35 +1022,282 @@ pub fn get_midi_metadata(path: &str) -> Result<MidiMetadata, String> {
         0.0
     };
 
+    let difficulty = compute_difficulty(&mut note_ons, note_density);
+
+    let bpm_avg = if result_ms > 0.0 {
+        weighted_bpm / result_ms
+    } else {
+        60_000_000.0 / current_tempo
+    };
+    let tempo = TempoStats {
+        bpm_min: (60_000_000.0 / max_tempo) as f32,
+        bpm_max: (60_000_000.0 / min_tempo) as f32,
+        bpm_avg: bpm_avg as f32,
+        tempo_changes: tempo_map.changes().len() as u32,
+    };
+
     Ok(MidiMetadata {
         duration,
         bpm,
         note_count,
         note_density,
+        difficulty,
+        tempo,
+        repaired,
+        track_count,
+        multi_channel: channels.count_ones() > 1,
     })
 }
 
-/// Clean track name - keep only printable ASCII chars (A-Z, a-z, 0-9, space, common punctuation)
+/// Full tempo map as (time, bpm) points, with repeated tempos collapsed
+pub fn get_tempo_map(path: &str) -> Result<Vec<TempoPoint>, AppError> {
+    let data =
+        std::fs::read(crate::paths::long(path)).map_err(|e| AppError::io("read MIDI file", e))?;
+    let (smf, _) = parse_smf(&data)?;
+
+    let tempo_map = TempoMap::from_smf(&smf);
+
+    let mut points = vec![TempoPoint {
+        time: 0.0,
+        bpm: 120.0,
+    }];
+    let mut elapsed_ms = 0.0;
+    let mut last_tick = 0u64;
+    let mut current_tempo = 500_000.0;
+
+    for &(change_tick, new_tempo) in tempo_map.changes() {
+        elapsed_ms += tempo_map.segment_ms(change_tick - last_tick, current_tempo);
+        last_tick = change_tick;
+        current_tempo = new_tempo;
+
+        let point = TempoPoint {
+            time: elapsed_ms / 1000.0,
+            bpm: (60_000_000.0 / new_tempo) as f32,
+        };
+        let last = points.last_mut().unwrap();
+        if (last.bpm - point.bpm).abs() < 0.01 {
+            continue;
+        }
+        // A change at the same instant replaces the previous point
+        if (last.time - point.time).abs() < f64::EPSILON {
+            *last = point;
+        } else {
+            points.push(point);
+        }
+    }
+
+    Ok(points)
+}
+
+/// Notes per second over time, for the seek bar heatmap
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DensityProfile {
+    pub duration: f64,    // seconds
+    pub bucket_secs: f64, // width of each bucket
+    pub notes_per_sec: Vec<f32>,
+    pub peak: f32, // highest bucket, for normalizing colors
+}
+
+pub fn get_density_profile(path: &str, buckets: usize) -> Result<DensityProfile, AppError> {
+    let midi_data = load_midi(path)?;
+    let buckets = buckets.clamp(1, 2000);
+    let duration_ms = (midi_data.duration * 1000.0).max(1.0);
+    let bucket_ms = duration_ms / buckets as f64;
+
+    let mut counts = vec![0u32; buckets];
+    for event in &midi_data.events {
+        if matches!(event.event_type, EventType::NoteOn) {
+            let index = ((event.time_ms as f64 / bucket_ms) as usize).min(buckets - 1);
+            counts[index] += 1;
+        }
+    }
+
+    let bucket_secs = bucket_ms / 1000.0;
+    let notes_per_sec: Vec<f32> = counts
+        .iter()
+        .map(|&c| (c as f64 / bucket_secs) as f32)
+        .collect();
+    let peak = notes_per_sec.iter().copied().fold(0.0, f32::max);
+
+    Ok(DensityProfile {
+        duration: midi_data.duration,
+        bucket_secs,
+        notes_per_sec,
+        peak,
+    })
+}
+
+/// One key press from a dry run of the mapping
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyPreview {
+    pub time: f64, // seconds from song start
+    pub key: String,
+    pub note: u8,
+    pub track_id: usize,
+}
+
+/// The keys playback would press between `from_sec` and `to_sec`, without sending
+/// anything. Applies the same transpose, octave shift and note thinning as playback.
+pub fn preview_keys(
+    midi_data: &MidiData,
+    from_sec: f64,
+    to_sec: f64,
+    note_mode: NoteMode,
+    key_mode: KeyMode,
+    octave_shift: i8,
+    settings: &SongSettings,
+) -> Vec<KeyPreview> {
+    let from_ms = (from_sec.max(0.0) * 1000.0) as u64;
+    let to_ms = (to_sec.max(0.0) * 1000.0) as u64;
+    let note_mode = settings.resolve_note_mode(note_mode, key_mode);
+    let skipped = dropped_notes(
+        &midi_data.events,
+        None,
+        get_max_polyphony() as usize,
+        midi_data.transpose,
+        settings,
+        get_velocity_emphasis(),
+    );
+
+    midi_data
+        .events
+        .iter()
+        .enumerate()
+        .filter(|(i, e)| {
+            matches!(e.event_type, EventType::NoteOn)
+                && e.time_ms >= from_ms
+                && e.time_ms <= to_ms
+                && !skipped.contains(i)
+        })
+        .map(|(_, e)| {
+            let shift_semitones = octave_shift as i32 * 12 + settings.track_offset(e.track_id);
+            KeyPreview {
+                time: e.time_ms as f64 / 1000.0,
+                key: note_to_game_key(
+                    e.note as i32,
+                    key_mode,
+                    note_mode,
+                    midi_data.transpose + shift_semitones,
+                    shift_semitones,
+                ),
+                note: e.note,
+                track_id: e.track_id,
+            }
+        })
+        .collect()
+}
+
+/// Playability score 0-100 from note density, chord sizes, how many notes need
+/// octave folding into the 3-octave range and how many accidentals a 21-key
+/// instrument can't play (at the best key for the song)
+fn compute_difficulty(note_ons: &mut [(u64, u8)], note_density: f32) -> u8 {
+    if note_ons.is_empty() {
+        return 0;
+    }
+    note_ons.sort_unstable();
+    let total = note_ons.len() as f32;
+
+    // Chords: notes starting on the same tick
+    let mut chord_count = 0usize;
+    let mut last_tick = None;
+    for &(tick, _) in note_ons.iter() {
+        if last_tick != Some(tick) {
+            chord_count += 1;
+            last_tick = Some(tick);
+        }
+    }
+    let avg_chord = total / chord_count as f32;
+
+    // Range folding: notes outside C3-B5 that must be shifted by octaves
+    let (lo, hi) = (INSTRUMENT_NOTES[0] as u8, INSTRUMENT_NOTES[20] as u8);
+    let out_of_range = note_ons
+        .iter()
+        .filter(|(_, key)| *key < lo || *key > hi)
+        .count() as f32;
+
+    // Accidentals: notes off the major scale at the best of 12 keys
+    let mut pitch_classes = [0usize; 12];
+    for &(_, key) in note_ons.iter() {
+        pitch_classes[(key % 12) as usize] += 1;
+    }
+    const SCALE: [usize; 7] = [0, 2, 4, 5, 7, 9, 11];
+    let in_scale = (0..12)
+        .map(|shift| {
+            SCALE
+                .iter()
+                .map(|s| pitch_classes[(s + shift) % 12])
+                .sum::<usize>()
+        })
+        .max()
+        .unwrap_or(0) as f32;
+    let accidentals = total - in_scale;
+
+    let score = (note_density / 12.0).min(1.0) * 40.0
+        + ((avg_chord - 1.0) / 3.0).clamp(0.0, 1.0) * 20.0
+        + (out_of_range / total) * 20.0
+        + (accidentals / total) * 20.0;
+    score.round().clamp(0.0, 100.0) as u8
+}
+
+/// Decode MIDI text bytes (track/instrument names)
+/// UTF-8 first, then the legacy CJK code pages common in community files
+/// (GBK, Shift-JIS, EUC-KR) - picking whichever decodes to the most plausible text
+fn decode_midi_text(bytes: &[u8]) -> String {
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return text.to_string();
+    }
+
+    // Score how much of the text falls in the script each encoding is meant for
+    let score = |text: &str, encoding: &'static encoding_rs::Encoding| -> usize {
+        text.chars()
+            .filter(|c| {
+                let c = *c as u32;
+                if encoding == encoding_rs::SHIFT_JIS {
+                    (0x3040..=0x30FF).contains(&c) // Hiragana / Katakana
+                } else if encoding == encoding_rs::EUC_KR {
+                    (0xAC00..=0xD7AF).contains(&c) // Hangul syllables
+                } else {
+                    (0x4E00..=0x9FFF).contains(&c) // CJK ideographs
+                }
+            })
+            .count()
+    };
+
+    // Ties go to the earlier encoding (GBK is the most common)
+    let mut best: Option<(usize, String)> = None;
+    for encoding in [
+        encoding_rs::GBK,
+        encoding_rs::SHIFT_JIS,
+        encoding_rs::EUC_KR,
+    ] {
+        if let Some(text) = encoding.decode_without_bom_handling_and_without_replacement(bytes) {
+            let text_score = score(&text, encoding);
+            let best_score = best.as_ref().map(|(s, _)| *s).unwrap_or(0);
+            if text_score > best_score {
+                best = Some((text_score, text.into_owned()));
+            }
+        }
+    }
+
+    best.map(|(_, text)| text)
+        .unwrap_or_else(|| String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// Clean track name - keep printable text (any script), drop control and replacement chars
 fn clean_track_name(raw: &str) -> String {
     raw.chars()
-        .filter(|c| {
-            c.is_ascii_alphanumeric()
-                || *c == ' '
-                || *c == '-'
-                || *c == '_'
-                || *c == '.'
-                || *c == '('
-                || *c == ')'
-        })
+        .filter(|c| !c.is_control() && *c != '\u{FFFD}')
         .collect::<String>()
-        .trim()
-        .to_string()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
 /// Get track information from a MIDI file (for band mode)
-pub fn get_midi_tracks(path: &str) -> Result<Vec<MidiTrackInfo>, String> {
-    let data = std::fs::read(path).map_err(|e| e.to_string())?;
-    let smf = Smf::parse(&data).map_err(|e| e.to_string())?;
+pub fn get_midi_tracks(path: &str) -> Result<Vec<MidiTrackInfo>, AppError> {
+    let data =
+        std::fs::read(crate::paths::long(path)).map_err(|e| AppError::io("read MIDI file", e))?;
+    let (smf, _) = parse_smf(&data)?;
 
     let mut tracks = Vec::new();
 
@@ -220,15 +1305,23 @@ pub fn get_midi_tracks(path: &str) -> Result<Vec<MidiTrackInfo>, String> {
         let mut name = String::new();
         let mut note_count: u32 = 0;
         let mut channels: std::collections::HashSet<u8> = std::collections::HashSet::new();
+        let mut instrument: Option<String> = None;
 
         for event in track {
             match event.kind {
+                TrackEventKind::Midi {
+                    channel,
+                    message: MidiMessage::ProgramChange { program },
+                } if instrument.is_none() => {
+                    instrument =
+                        Some(gm_instrument_name(program.as_int(), channel.as_int()).into());
+                }
                 TrackEventKind::Meta(midly::MetaMessage::TrackName(n)) => {
-                    name = clean_track_name(&String::from_utf8_lossy(n));
+                    name = clean_track_name(&decode_midi_text(n));
                 }
                 TrackEventKind::Meta(midly::MetaMessage::InstrumentName(n)) => {
                     if name.is_empty() {
-                        name = clean_track_name(&String::from_utf8_lossy(n));
+                        name = clean_track_name(&decode_midi_text(n));
                     }
                 }
                 TrackEventKind::Midi {
@@ -252,9 +1345,19 @@ pub fn get_midi_tracks(path: &str) -> Result<Vec<MidiTrackInfo>, String> {
                 None
             };
 
-            // Generate name if not found
+            // No Program Change: GM default is piano, drums on channel 10
+            if instrument.is_none() {
+                if let Some(ch) = channel {
+                    instrument = Some(gm_instrument_name(0, ch).into());
+                }
+            }
+
+            // Generate name if not found, preferring the instrument
             if name.is_empty() {
-                name = format!("Track {}", idx + 1);
+                name = match &instrument {
+                    Some(inst) => format!("{} ({})", inst, idx + 1),
+                    None => format!("Track {}", idx + 1),
+                };
             }
 
             tracks.push(MidiTrackInfo {
@@ -262,6 +1365,7 @@ pub fn get_midi_tracks(path: &str) -> Result<Vec<MidiTrackInfo>, String> {
                 name,
                 note_count,
                 channel,
+                instrument,
             });
         }
     }
@@ -269,60 +1373,111 @@ pub fn get_midi_tracks(path: &str) -> Result<Vec<MidiTrackInfo>, String> {
     Ok(tracks)
 }
 
-pub fn load_midi(path: &str) -> Result<MidiData, String> {
-    let data = std::fs::read(path).map_err(|e| e.to_string())?;
-    let smf = Smf::parse(&data).map_err(|e| e.to_string())?;
+lazy_static::lazy_static! {
+    // Detected transpose per song hash, so reloading a song skips the detection
+    static ref TRANSPOSE_CACHE: std::sync::Mutex<std::collections::HashMap<String, i32>> =
+        std::sync::Mutex::new(std::collections::HashMap::new());
+}
 
-    let mut events = Vec::new();
-    let ticks_per_quarter = match smf.header.timing {
-        midly::Timing::Metrical(tpq) => tpq.as_int() as f64,
-        _ => 480.0, // Default
-    };
+// Recently parsed songs by hash, least recently used first. Bounded by count and
+// size so browsing a huge library doesn't keep every song resident.
+const PARSED_CACHE_MAX_ENTRIES: usize = 8;
+const PARSED_CACHE_MAX_BYTES: usize = 32 * 1024 * 1024;
 
-    let mut tempo_changes: Vec<(u64, f64)> = Vec::new();
+lazy_static::lazy_static! {
+    static ref PARSED_CACHE: std::sync::Mutex<Vec<(String, MidiData)>> =
+        std::sync::Mutex::new(Vec::new());
+}
 
-    // First pass: collect all tempo changes from all tracks
-    for track in &smf.tracks {
-        let mut track_time_ticks: u64 = 0;
-        for event in track {
-            track_time_ticks += event.delta.as_int() as u64;
-            if let TrackEventKind::Meta(midly::MetaMessage::Tempo(t)) = event.kind {
-                tempo_changes.push((track_time_ticks, t.as_int() as f64));
-            }
-        }
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedCacheStats {
+    pub entries: usize,
+    pub bytes: usize,
+    pub max_entries: usize,
+    pub max_bytes: usize,
+    pub transpose_entries: usize,
+}
+
+pub fn parsed_cache_stats() -> ParsedCacheStats {
+    let cache = PARSED_CACHE.lock().unwrap();
+    ParsedCacheStats {
+        entries: cache.len(),
+        bytes: cache.iter().map(|(_, m)| m.approx_bytes()).sum(),
+        max_entries: PARSED_CACHE_MAX_ENTRIES,
+        max_bytes: PARSED_CACHE_MAX_BYTES,
+        transpose_entries: TRANSPOSE_CACHE.lock().unwrap().len(),
     }
-    tempo_changes.sort_by_key(|(time, _)| *time);
+}
 
-    // Function to convert ticks to milliseconds with tempo changes
-    let ticks_to_ms = |ticks: u64| -> u64 {
-        let mut result_ms = 0.0;
-        let mut last_tick = 0u64;
-        let mut current_tempo = 500_000.0;
+pub fn clear_parsed_cache() {
+    PARSED_CACHE.lock().unwrap().clear();
+}
 
-        for &(change_tick, new_tempo) in &tempo_changes {
-            if change_tick >= ticks {
-                break;
-            }
-            // Add time up to this tempo change
-            let delta_ticks = change_tick - last_tick;
-            result_ms += delta_ticks as f64 / ticks_per_quarter * current_tempo / 1000.0;
-            last_tick = change_tick;
-            current_tempo = new_tempo;
-        }
+fn cached_parse(hash: &str) -> Option<MidiData> {
+    let mut cache = PARSED_CACHE.lock().unwrap();
+    let index = cache.iter().position(|(h, _)| h == hash)?;
+    let entry = cache.remove(index);
+    let midi_data = entry.1.clone();
+    cache.push(entry);
+    Some(midi_data)
+}
 
-        // Add remaining time
-        let delta_ticks = ticks - last_tick;
-        result_ms += delta_ticks as f64 / ticks_per_quarter * current_tempo / 1000.0;
-        result_ms as u64
-    };
+fn remember_parse(hash: &str, midi_data: &MidiData) {
+    if midi_data.approx_bytes() > PARSED_CACHE_MAX_BYTES {
+        return;
+    }
+    let mut cache = PARSED_CACHE.lock().unwrap();
+    cache.retain(|(h, _)| h != hash);
+    cache.push((hash.to_string(), midi_data.clone()));
+    let mut bytes: usize = cache.iter().map(|(_, m)| m.approx_bytes()).sum();
+    while cache.len() > PARSED_CACHE_MAX_ENTRIES || bytes > PARSED_CACHE_MAX_BYTES {
+        let (_, evicted) = cache.remove(0);
+        bytes -= evicted.approx_bytes();
+    }
+}
+
+pub fn load_midi(path: &str) -> Result<MidiData, AppError> {
+    load_midi_for(path, None)
+}
+
+/// Same as `load_midi`, but when the song's hash is known the parse comes from
+/// (and goes into) the LRU, and the detected transpose is reused
+pub fn load_midi_for(path: &str, hash: Option<&str>) -> Result<MidiData, AppError> {
+    if let Some(midi_data) = hash.and_then(cached_parse) {
+        return Ok(midi_data);
+    }
+    let midi_data = parse_midi_file(path, hash)?;
+    if let Some(hash) = hash {
+        remember_parse(hash, &midi_data);
+    }
+    Ok(midi_data)
+}
+
+fn parse_midi_file(path: &str, hash: Option<&str>) -> Result<MidiData, AppError> {
+    let data =
+        std::fs::read(crate::paths::long(path)).map_err(|e| AppError::io("read MIDI file", e))?;
+    midi_data_from_bytes(&data, hash)
+}
+
+/// Build the playable song from raw file bytes (what `load_midi` does after reading)
+pub fn midi_data_from_bytes(data: &[u8], hash: Option<&str>) -> Result<MidiData, AppError> {
+    let (smf, _) = parse_smf(data)?;
+
+    let mut events = Vec::new();
+    let mut end_tick = 0u64;
+
+    // First pass: collect all tempo changes from all tracks
+    let tempo_map = TempoMap::from_smf(&smf);
 
     // Second pass: process all tracks with proper timing
+    let offsets = track_offsets(&smf);
     for (track_idx, track) in smf.tracks.iter().enumerate() {
-        let mut track_time_ticks: u64 = 0;
+        let mut track_time_ticks: u64 = offsets[track_idx];
 
         for event in track {
             track_time_ticks += event.delta.as_int() as u64;
-            let time_ms = ticks_to_ms(track_time_ticks);
+            end_tick = end_tick.max(track_time_ticks);
+            let time_ms = tempo_map.ticks_to_ms(track_time_ticks) as u64;
 
             if let TrackEventKind::Midi { message, .. } = event.kind {
                 match message {
@@ -332,6 +1487,7 @@ pub fn load_midi(path: &str) -> Result<MidiData, String> {
                                 time_ms,
                                 event_type: EventType::NoteOn,
                                 note: key.as_int(),
+                                velocity: vel.as_int(),
                                 track_id: track_idx,
                             });
                         } else {
@@ -340,6 +1496,7 @@ pub fn load_midi(path: &str) -> Result<MidiData, String> {
                                 time_ms,
                                 event_type: EventType::NoteOff,
                                 note: key.as_int(),
+                                velocity: 0,
                                 track_id: track_idx,
                             });
                         }
@@ -349,6 +1506,7 @@ pub fn load_midi(path: &str) -> Result<MidiData, String> {
                             time_ms,
                             event_type: EventType::NoteOff,
                             note: key.as_int(),
+                            velocity: 0,
                             track_id: track_idx,
                         });
                     }
@@ -369,53 +1527,68 @@ pub fn load_midi(path: &str) -> Result<MidiData, String> {
     };
 
     // Detect best transpose (port of Python heuristic)
-    let transpose = detect_best_transpose(&events);
-    println!("Detected transpose: {} semitones", transpose);
+    let cached = hash.and_then(|h| TRANSPOSE_CACHE.lock().unwrap().get(h).copied());
+    let transpose = match cached {
+        Some(transpose) => transpose,
+        None => {
+            let transpose = detect_best_transpose(&events);
+            println!("Detected transpose: {} semitones", transpose);
+            if let Some(hash) = hash {
+                TRANSPOSE_CACHE
+                    .lock()
+                    .unwrap()
+                    .insert(hash.to_string(), transpose);
+            }
+            transpose
+        }
+    };
 
     Ok(MidiData {
         events,
         duration,
         transpose,
+        beats: beat_grid(&smf, &tempo_map, end_tick),
     })
 }
 
 fn detect_best_transpose(events: &[TimedEvent]) -> i32 {
+    use rayon::prelude::*;
     let instrument_notes = get_instrument_notes();
 
-    let mut best_transpose = 0;
-    let mut best_score = i32::MAX;
-
-    // Test transpose values from -12 to +12
-    for transpose in -12..=12 {
-        let mut score = 0;
-
-        for event in events {
-            if matches!(event.event_type, EventType::NoteOn) {
-                let transposed_note = (event.note as i32 + transpose) as i32;
-                let normalized = normalize_into_range(transposed_note);
-
-                // Find distance to nearest instrument note
-                let mut min_distance = i32::MAX;
-                for &inst_note in instrument_notes.iter() {
-                    let distance = (inst_note - normalized).abs();
-                    if distance < min_distance {
-                        min_distance = distance;
-                    }
-                }
-                score += min_distance;
-            }
-        }
-
-        if score < best_score {
-            best_score = score;
-            best_transpose = transpose;
+    // Score depends only on pitch, so count NoteOns per pitch once
+    let mut pitch_counts = [0i32; 128];
+    for event in events {
+        if matches!(event.event_type, EventType::NoteOn) {
+            pitch_counts[(event.note & 0x7F) as usize] += 1;
         }
     }
 
-    best_transpose
+    // Test transpose values from -12 to +12 in parallel; ties go to the lowest transpose
+    (-12..=12)
+        .into_par_iter()
+        .map(|transpose| {
+            let mut score = 0;
+            for (note, &count) in pitch_counts.iter().enumerate() {
+                if count == 0 {
+                    continue;
+                }
+                let normalized = normalize_into_range(note as i32 + transpose);
+
+                // Distance to nearest instrument note
+                let min_distance = instrument_notes
+                    .iter()
+                    .map(|&inst_note| (inst_note - normalized).abs())
+                    .min()
+                    .unwrap_or(0);
+                score += min_distance * count;
+            }
+            (score, transpose)
+        })
+        .min()
+        .map(|(_, transpose)| transpose)
+        .unwrap_or(0)
 }
 
-#[inline]
 fn get_instrument_notes() -> &'static [i32; 21] {
     &INSTRUMENT_NOTES
 }
@@ -573,6 +1746,139 @@ fn note_to_key_raw(note: i32) -> String {
     all_keys[key_idx as usize].to_string()
 }
 
+/// Row split points for Wide and 36-key modes (first note of the mid and high rows)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct OctaveSplits {
+    pub wide_low: i32,    // Wide: notes below go to the low row (default F#3 = 54)
+    pub wide_high: i32,   // Wide: notes from here go to the high row (default F#4 = 66)
+    pub keys36_low: i32,  // 36-key: default C4 = 60
+    pub keys36_high: i32, // 36-key: default C5 = 72
+    /// Center the rows on the loaded song's average pitch instead of the fixed points
+    #[serde(default)]
+    pub auto: bool,
+}
+
+impl Default for OctaveSplits {
+    fn default() -> Self {
+        OctaveSplits {
+            wide_low: 54,
+            wide_high: 66,
+            keys36_low: 60,
+            keys36_high: 72,
+            auto: false,
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref OCTAVE_SPLITS: std::sync::RwLock<OctaveSplits> =
+        std::sync::RwLock::new(OctaveSplits::default());
+}
+
+// Average pitch of the loaded song after its auto-transpose (used by auto splits)
+static SONG_CENTROID: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(66);
+
+pub fn set_octave_splits(splits: OctaveSplits) {
+    *OCTAVE_SPLITS.write().unwrap() = splits;
+}
+
+pub fn get_octave_splits() -> OctaveSplits {
+    *OCTAVE_SPLITS.read().unwrap()
+}
+
+/// Average NoteOn pitch including the song's transpose (66 = F#4 if there are no notes)
+pub fn pitch_centroid(midi_data: &MidiData) -> i32 {
+    let (sum, count) = midi_data
+        .events
+        .iter()
+        .filter(|e| matches!(e.event_type, EventType::NoteOn))
+        .fold((0i64, 0i64), |(sum, count), e| {
+            (sum + e.note as i64, count + 1)
+        });
+    if count == 0 {
+        66
+    } else {
+        (sum / count) as i32 + midi_data.transpose
+    }
+}
+
+// Whether loading a song applies its suggested octave shift
+static AUTO_OCTAVE_SHIFT: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+pub fn set_auto_octave_shift(enabled: bool) {
+    AUTO_OCTAVE_SHIFT.store(enabled, Ordering::SeqCst);
+}
+
+pub fn get_auto_octave_shift() -> bool {
+    AUTO_OCTAVE_SHIFT.load(Ordering::SeqCst)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct OctaveSuggestion {
+    pub shift: i8,
+    pub in_range: f32, // Share of notes inside the instrument range with the shift
+    pub unshifted_in_range: f32, // ... and without it
+}
+
+/// The octave shift (-2..=2) that puts the most notes inside the instrument range,
+/// ties going to the one that centers the song, then to the smaller shift
+pub fn suggest_octave_shift(midi_data: &MidiData) -> OctaveSuggestion {
+    let (lo, hi) = (INSTRUMENT_NOTES[0], INSTRUMENT_NOTES[20]);
+    let notes: Vec<i32> = midi_data
+        .events
+        .iter()
+        .filter(|e| matches!(e.event_type, EventType::NoteOn))
+        .map(|e| e.note as i32 + midi_data.transpose)
+        .collect();
+    let in_range = |shift: i32| {
+        notes
+            .iter()
+            .filter(|&&n| (lo..=hi).contains(&(n + shift * 12)))
+            .count()
+    };
+    let share = |count: usize| {
+        if notes.is_empty() {
+            1.0
+        } else {
+            count as f32 / notes.len() as f32
+        }
+    };
+
+    let centroid = pitch_centroid(midi_data);
+    let shift = (-2..=2)
+        .min_by_key(|&shift| {
+            let off_center = (2 * (centroid + shift * 12) - (lo + hi)).abs();
+            (std::cmp::Reverse(in_range(shift)), off_center, shift.abs())
+        })
+        .unwrap_or(0);
+    OctaveSuggestion {
+        shift: shift as i8,
+        in_range: share(in_range(shift)),
+        unshifted_in_range: share(in_range(0)),
+    }
+}
+
+pub fn set_song_centroid(centroid: i32) {
+    SONG_CENTROID.store(centroid, Ordering::SeqCst);
+}
+
+/// Effective (wide_low, wide_high, keys36_low, keys36_high)
+fn active_splits() -> (i32, i32, i32, i32) {
+    let splits = get_octave_splits();
+    if !splits.auto {
+        return (
+            splits.wide_low,
+            splits.wide_high,
+            splits.keys36_low,
+            splits.keys36_high,
+        );
+    }
+    // Mid row spans the octave around the centroid; 36-key rows stay on C boundaries
+    let c = SONG_CENTROID.load(Ordering::SeqCst);
+    let c36 = ((c - 6) as f64 / 12.0).round() as i32 * 12;
+    (c - 6, c + 6, c36, c36 + 12)
+}
+
 /// Wide mode - spread notes evenly across all 21 keys
 /// Uses high and low rows more often by mapping the song's note range proportionally
 fn note_to_key_wide(note: i32, transpose: i32) -> String {
@@ -587,10 +1893,11 @@ fn note_to_key_wide(note: i32, transpose: i32) -> String {
     let semitone = ((target % 12) + 12) % 12;
 
     // Determine octave based on actual note height
-    // This uses the full range: notes below 54 go low, 54-66 go mid, above 66 go high
-    let octave = if target < 54 {
+    // By default notes below 54 go low, 54-66 go mid, above 66 go high (see OctaveSplits)
+    let (low, high, _, _) = active_splits();
+    let octave = if target < low {
         0 // Low row - anything below F#3
-    } else if target < 66 {
+    } else if target < high {
         1 // Mid row - F#3 to F#4
     } else {
         2 // High row - anything above F#4
@@ -752,10 +2059,11 @@ fn semitone_to_key_36(semitone: i32, octave: usize) -> String {
 /// Calculate octave (0=low, 1=mid, 2=high) for 36-key mode
 fn get_octave_36(target: i32) -> usize {
     // C3=48, C4=60, C5=72
-    // Low octave: <60, Mid: 60-71, High: >=72
-    if target < 60 {
+    // Low octave: <60, Mid: 60-71, High: >=72 (by default, see OctaveSplits)
+    let (_, _, low, high) = active_splits();
+    if target < low {
         0
-    } else if target < 72 {
+    } else if target < high {
         1
     } else {
         2
@@ -861,255 +2169,116 @@ fn note_to_key_36_sharps(note: i32, transpose: i32) -> String {
     semitone_to_key_36(semitone, octave)
 }
 
-pub fn play_midi(
-    midi_data: MidiData,
-    is_playing: Arc<AtomicBool>,
-    is_paused: Arc<AtomicBool>,
-    loop_mode: Arc<AtomicBool>,
-    note_mode: Arc<AtomicU8>,
-    key_mode: Arc<AtomicU8>,
-    octave_shift: Arc<std::sync::atomic::AtomicI8>,
-    speed: Arc<std::sync::atomic::AtomicU16>,
-    current_position: Arc<std::sync::Mutex<f64>>,
-    seek_offset: Arc<std::sync::Mutex<f64>>,
-    band_filter: Arc<std::sync::Mutex<Option<BandFilter>>>,
-    window: Window,
-) {
-    // Log band mode if active at start
-    if let Some(ref filter) = *band_filter.lock().unwrap() {
-        match filter {
-            BandFilter::Split {
-                slot,
-                total_players,
-            } => {
-                println!(
-                    "[BAND] Split mode: playing note {} of every {} notes",
-                    slot + 1,
-                    total_players
-                );
-            }
-            BandFilter::Track { track_id } => {
-                println!("[BAND] Track mode: playing track {}", track_id);
+/// Map a MIDI note to the game key for the given key mode and note mode
+/// `transpose` includes the octave shift; `shift_semitones` is the octave shift alone (Raw modes)
+pub fn note_to_game_key(
+    note: i32,
+    key_mode: KeyMode,
+    note_mode: NoteMode,
+    transpose: i32,
+    shift_semitones: i32,
+) -> String {
+    match key_mode {
+        KeyMode::Keys36 => {
+            // 36-key mode - use note mode with modifier keys
+            match note_mode {
+                NoteMode::Closest => note_to_key_36_closest(note, transpose),
+                NoteMode::Quantize => note_to_key_36_quantize(note, transpose),
+                NoteMode::TransposeOnly => note_to_key_36_transpose(note, transpose),
+                NoteMode::Pentatonic => note_to_key_36_pentatonic(note, transpose),
+                NoteMode::Chromatic => note_to_key_36_chromatic(note, transpose),
+                NoteMode::Raw => note_to_key_36_raw(note + shift_semitones),
+                NoteMode::Python => note_to_key_python(note, transpose),
+                NoteMode::Wide => note_to_key_36_wide(note, transpose),
+                NoteMode::Sharps => note_to_key_36_sharps(note, transpose),
+                // Callers resolve Smart per song (SongSettings::resolve_note_mode)
+                NoteMode::Smart => note_to_key_python(note, transpose),
             }
         }
-    }
-
-    // Spawn a separate thread for progress updates
-    let is_playing_progress = Arc::clone(&is_playing);
-    let is_paused_progress = Arc::clone(&is_paused);
-    let current_position_progress = Arc::clone(&current_position);
-    let window_progress = window.clone();
-
-    std::thread::spawn(move || {
-        while is_playing_progress.load(Ordering::SeqCst) {
-            if !is_paused_progress.load(Ordering::SeqCst) {
-                let position = *current_position_progress.lock().unwrap();
-                let _ = window_progress.emit("playback-progress", position);
+        KeyMode::Keys21 => {
+            // 21-key mode - use note mode to determine mapping
+            match note_mode {
+                NoteMode::Closest => note_to_key(note, transpose),
+                NoteMode::Quantize => note_to_key_quantize(note, transpose),
+                NoteMode::TransposeOnly => note_to_key_transpose(note, transpose),
+                NoteMode::Pentatonic => note_to_key_pentatonic(note, transpose),
+                NoteMode::Chromatic => note_to_key_chromatic(note, transpose),
+                NoteMode::Raw => note_to_key_raw(note + shift_semitones),
+                NoteMode::Python => note_to_key_python(note, transpose),
+                NoteMode::Wide => note_to_key_wide(note, transpose),
+                NoteMode::Sharps => note_to_key(note, transpose), // Falls back to Closest in 21-key
+                NoteMode::Smart => note_to_key_python(note, transpose),
             }
-            std::thread::sleep(Duration::from_millis(100));
         }
-    });
-
-    loop {
-        // Get current seek offset (reset to 0 on loop)
-        let offset_ms = (*seek_offset.lock().unwrap() * 1000.0) as u64;
-
-        // Track which key is pressed for each MIDI note (note -> key that was pressed)
-        let _note_to_pressed_key: std::collections::HashMap<u8, String> =
-            std::collections::HashMap::new();
-        // Track reference count for each key (multiple notes might map to same key)
-        let key_active_count: std::collections::HashMap<String, i32> =
-            std::collections::HashMap::new();
-
-        // Helper to release all keys and reset modifier counts
-        let release_all_keys = |key_active_count: &std::collections::HashMap<String, i32>| {
-            for (key, count) in key_active_count {
-                if *count > 0 {
-                    crate::keyboard::key_up(key);
-                }
-            }
-            // Reset modifier reference counts when stopping
-            crate::keyboard::reset_modifier_counts();
-        };
-
-        // Track song position in milliseconds (not affected by speed changes)
-        let mut song_position_ms: u64 = offset_ms;
-        let mut last_event_time = Instant::now();
-
-        // Counter for split mode note filtering
-        let mut note_on_counter: usize = 0;
-
-        for event in &midi_data.events {
-            if event.time_ms < offset_ms {
-                continue;
-            }
-
-            if !is_playing.load(Ordering::SeqCst) {
-                release_all_keys(&key_active_count);
-                return;
-            }
-
-            // Calculate delta from last processed position to this event (in song time)
-            let delta_song_ms = event.time_ms.saturating_sub(song_position_ms);
-
-            // Wait for the delta time, adjusted by current speed
-            if delta_song_ms > 0 {
-                let mut remaining_song_ms = delta_song_ms as f64;
-
-                while remaining_song_ms > 0.0 {
-                    if !is_playing.load(Ordering::SeqCst) {
-                        release_all_keys(&key_active_count);
-                        return;
-                    }
-
-                    // Handle pause
-                    if is_paused.load(Ordering::SeqCst) {
-                        while is_paused.load(Ordering::SeqCst) && is_playing.load(Ordering::SeqCst)
-                        {
-                            std::thread::sleep(Duration::from_millis(50));
-                            if !is_playing.load(Ordering::SeqCst) {
-                                release_all_keys(&key_active_count);
-                                return;
-                            }
-                        }
-                        last_event_time = Instant::now();
-                        continue;
-                    }
-
-                    // Get current speed (stored as speed * 100)
-                    let current_speed = speed.load(Ordering::SeqCst) as f64 / 100.0;
-
-                    // Calculate real time to wait based on speed
-                    // sleep for a small chunk and update
-                    let sleep_ms = 2.0_f64.min(remaining_song_ms / current_speed);
-                    std::thread::sleep(Duration::from_micros((sleep_ms * 1000.0) as u64));
-
-                    let elapsed = last_event_time.elapsed();
-                    last_event_time = Instant::now();
-
-                    // Convert real elapsed time to song time
-                    let song_ms_passed = elapsed.as_secs_f64() * 1000.0 * current_speed;
-                    remaining_song_ms -= song_ms_passed;
+    }
+}
 
-                    // Update current position
-                    let new_pos = (event.time_ms as f64 - remaining_song_ms.max(0.0)) / 1000.0;
-                    *current_position.lock().unwrap() = new_pos;
-                }
-            }
+/// Reverse of the game key mapping - the MIDI note a (default) game key plays
+/// Low row starts at C3 (48), mid at C4 (60), high at C5 (72); Shift = +1, Ctrl = -1
+pub fn game_key_to_note(key: &str) -> Option<i32> {
+    const SCALE: [i32; 7] = [0, 2, 4, 5, 7, 9, 11];
 
-            // Update song position to this event's time
-            song_position_ms = event.time_ms;
-            last_event_time = Instant::now();
-
-            // Get key based on key mode and note calculation mode (read in realtime for live switching)
-            let current_key_mode = KeyMode::from(key_mode.load(Ordering::SeqCst));
-            let current_note_mode = NoteMode::from(note_mode.load(Ordering::SeqCst));
-            // Get octave shift in semitones (1 octave = 12 semitones)
-            let shift_semitones = octave_shift.load(Ordering::SeqCst) as i32 * 12;
-            let total_transpose = midi_data.transpose + shift_semitones;
-
-            // Select key mapping based on key mode and note mode
-            let key = match current_key_mode {
-                KeyMode::Keys36 => {
-                    // 36-key mode - use note mode with modifier keys
-                    match current_note_mode {
-                        NoteMode::Closest => {
-                            note_to_key_36_closest(event.note as i32, total_transpose)
-                        }
-                        NoteMode::Quantize => {
-                            note_to_key_36_quantize(event.note as i32, total_transpose)
-                        }
-                        NoteMode::TransposeOnly => {
-                            note_to_key_36_transpose(event.note as i32, total_transpose)
-                        }
-                        NoteMode::Pentatonic => {
-                            note_to_key_36_pentatonic(event.note as i32, total_transpose)
-                        }
-                        NoteMode::Chromatic => {
-                            note_to_key_36_chromatic(event.note as i32, total_transpose)
-                        }
-                        NoteMode::Raw => note_to_key_36_raw(event.note as i32 + shift_semitones),
-                        NoteMode::Python => note_to_key_python(event.note as i32, total_transpose),
-                        NoteMode::Wide => note_to_key_36_wide(event.note as i32, total_transpose),
-                        NoteMode::Sharps => {
-                            note_to_key_36_sharps(event.note as i32, total_transpose)
-                        }
-                    }
-                }
-                KeyMode::Keys21 => {
-                    // 21-key mode - use note mode to determine mapping
-                    match current_note_mode {
-                        NoteMode::Closest => note_to_key(event.note as i32, total_transpose),
-                        NoteMode::Quantize => {
-                            note_to_key_quantize(event.note as i32, total_transpose)
-                        }
-                        NoteMode::TransposeOnly => {
-                            note_to_key_transpose(event.note as i32, total_transpose)
-                        }
-                        NoteMode::Pentatonic => {
-                            note_to_key_pentatonic(event.note as i32, total_transpose)
-                        }
-                        NoteMode::Chromatic => {
-                            note_to_key_chromatic(event.note as i32, total_transpose)
-                        }
-                        NoteMode::Raw => note_to_key_raw(event.note as i32 + shift_semitones),
-                        NoteMode::Python => note_to_key_python(event.note as i32, total_transpose),
-                        NoteMode::Wide => note_to_key_wide(event.note as i32, total_transpose),
-                        NoteMode::Sharps => note_to_key(event.note as i32, total_transpose), // Falls back to Closest in 21-key
-                    }
-                }
-            };
-
-            match event.event_type {
-                EventType::NoteOn => {
-                    // Check band filter - read live for instant track switching
-                    let current_filter = band_filter.lock().unwrap().clone();
-                    let should_play = match &current_filter {
-                        Some(BandFilter::Split {
-                            slot,
-                            total_players,
-                        }) => {
-                            // In split mode, play every Nth note starting from slot
-                            let play = (note_on_counter % total_players) == *slot;
-                            note_on_counter += 1;
-                            play
-                        }
-                        Some(BandFilter::Track { track_id }) => {
-                            // Track mode: only play notes from the assigned track
-                            event.track_id == *track_id
-                        }
-                        None => true, // No filter, play all
-                    };
+    let (base, modifier) = if let Some(k) = key.strip_prefix("shift+") {
+        (k, 1)
+    } else if let Some(k) = key.strip_prefix("ctrl+") {
+        (k, -1)
+    } else {
+        (key, 0)
+    };
 
-                    if should_play {
-                        // Simple press-release for each note (game doesn't need hold)
-                        crate::keyboard::key_down(&key);
-                        crate::keyboard::key_up(&key);
+    let rows: [(&[&str; 7], i32); 3] = [(&LOW_KEYS, 48), (&MID_KEYS, 60), (&HIGH_KEYS, 72)];
+    rows.iter().find_map(|(row, root)| {
+        row.iter()
+            .position(|k| *k == base)
+            .map(|idx| root + SCALE[idx] + modifier)
+    })
+}
 
-                        // Emit note event for visualizer
-                        let _ = window.emit("note-event", &key);
-                    }
-                }
-                EventType::NoteOff => {
-                    // Ignore note off - we already released on note on
-                }
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(time_ms: u64, event_type: EventType) -> TimedEvent {
+        TimedEvent {
+            time_ms,
+            event_type,
+            note: 60,
+            velocity: 100,
+            track_id: 0,
         }
+    }
 
-        // Release all remaining keys
-        release_all_keys(&key_active_count);
-
-        if !loop_mode.load(Ordering::SeqCst) {
-            break;
-        }
+    #[test]
+    fn negative_offset_clamps_early_notes_to_start() {
+        let mut data = MidiData {
+            events: vec![
+                note(0, EventType::NoteOn),
+                note(100, EventType::NoteOff),
+                note(500, EventType::NoteOn),
+                note(600, EventType::NoteOff),
+            ],
+            duration: 0.6,
+            transpose: 0,
+            beats: vec![
+                Beat {
+                    time_ms: 0,
+                    downbeat: true,
+                },
+                Beat {
+                    time_ms: 500,
+                    downbeat: false,
+                },
+            ],
+        };
 
-        // Reset position to 0 for loop restart
-        *seek_offset.lock().unwrap() = 0.0;
-        *current_position.lock().unwrap() = 0.0;
+        data.shift_events(-200);
 
-        std::thread::sleep(Duration::from_millis(500));
+        let times: Vec<u64> = data.events.iter().map(|e| e.time_ms).collect();
+        assert_eq!(times, vec![0, 0, 300, 400]);
+        assert!(matches!(data.events[0].event_type, EventType::NoteOn));
+        assert!(matches!(data.events[1].event_type, EventType::NoteOff));
+        assert_eq!(data.beats.len(), 1);
+        assert_eq!(data.beats[0].time_ms, 300);
+        assert_eq!(data.duration, 0.4);
     }
-
-    is_playing.store(false, Ordering::SeqCst);
-    let _ = window.emit("playback-ended", ());
 }
@@ -0,0 +1,628 @@
+// MIDI file editing - writes new .mid files derived from existing ones
+// All operations work on absolute tick positions and re-encode deltas on save
+
+use midly::{
+    num::{u24, u28, u4, u7},
+    MetaMessage, MidiMessage, Smf, TrackEvent, TrackEventKind,
+};
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+use crate::midi::{game_key_to_note, note_to_game_key, parse_smf, KeyMode, NoteMode, TempoMap};
+
+/// Convert a track to (absolute tick, event) pairs, dropping End of Track
+fn to_absolute<'a>(track: &[TrackEvent<'a>]) -> Vec<(u64, TrackEventKind<'a>)> {
+    let mut tick: u64 = 0;
+    let mut events = Vec::with_capacity(track.len());
+    for event in track {
+        tick += event.delta.as_int() as u64;
+        if !matches!(event.kind, TrackEventKind::Meta(MetaMessage::EndOfTrack)) {
+            events.push((tick, event.kind));
+        }
+    }
+    events
+}
+
+/// Convert (absolute tick, event) pairs back to a delta-encoded track ending at `end_tick`
+fn to_delta<'a>(mut events: Vec<(u64, TrackEventKind<'a>)>, end_tick: u64) -> Vec<TrackEvent<'a>> {
+    // Stable sort keeps the original order of simultaneous events
+    events.sort_by_key(|(tick, _)| *tick);
+
+    let mut track = Vec::with_capacity(events.len() + 1);
+    let mut last_tick: u64 = 0;
+    for (tick, kind) in events {
+        track.push(TrackEvent {
+            delta: u28::new((tick - last_tick).min(u28::max_value().as_int() as u64) as u32),
+            kind,
+        });
+        last_tick = tick;
+    }
+    track.push(TrackEvent {
+        delta: u28::new(end_tick.saturating_sub(last_tick) as u32),
+        kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+    });
+    track
+}
+
+/// Ticks per beat; SMPTE files count ticks in frames, which has no beat to scale or snap to
+fn ticks_per_quarter(smf: &Smf) -> Result<u64, String> {
+    match smf.header.timing {
+        midly::Timing::Metrical(tpq) => Ok(tpq.as_int().max(1) as u64),
+        midly::Timing::Timecode(..) => Err("SMPTE-timed MIDI files are not supported".to_string()),
+    }
+}
+
+fn save_smf(smf: &Smf, dest: &Path) -> Result<(), String> {
+    smf.save(crate::paths::long(dest))
+        .map_err(|e| format!("Failed to write MIDI: {}", e))
+}
+
+/// Write the part of `src` between start_ms and end_ms to `dest`
+/// Tempo, time/key signature, program and controller state at the cut point are
+/// carried over to tick 0 so the section sounds the same as in the original.
+/// Notes that started before the cut are dropped; notes still held at the end are released.
+pub fn export_section(src: &str, dest: &Path, start_ms: f64, end_ms: f64) -> Result<(), String> {
+    let data = std::fs::read(crate::paths::long(src)).map_err(|e| e.to_string())?;
+    let (smf, _) = parse_smf(&data)?;
+
+    let tempo_map = TempoMap::from_smf(&smf);
+    let start_tick = tempo_map.ms_to_ticks(start_ms);
+    let end_tick = tempo_map.ms_to_ticks(end_ms);
+    if end_tick <= start_tick {
+        return Err("Section is empty".to_string());
+    }
+    let length = end_tick - start_tick;
+
+    let mut out = Smf::new(smf.header);
+    let mut note_count = 0;
+
+    for track in &smf.tracks {
+        let mut carried: Vec<TrackEventKind> = Vec::new();
+        let mut tempo = None;
+        let mut time_sig = None;
+        let mut key_sig = None;
+        let mut programs: BTreeMap<u8, TrackEventKind> = BTreeMap::new();
+        let mut controllers: BTreeMap<(u8, u8), TrackEventKind> = BTreeMap::new();
+        let mut bends: BTreeMap<u8, TrackEventKind> = BTreeMap::new();
+
+        let mut section: Vec<(u64, TrackEventKind)> = Vec::new();
+        let mut held: HashSet<(u8, u8)> = HashSet::new();
+
+        for (tick, kind) in to_absolute(track) {
+            if tick >= end_tick {
+                break;
+            }
+
+            if tick < start_tick {
+                // Remember state so it can be replayed at the start of the section
+                match kind {
+                    TrackEventKind::Meta(MetaMessage::Tempo(_)) => tempo = Some(kind),
+                    TrackEventKind::Meta(MetaMessage::TimeSignature(..)) => time_sig = Some(kind),
+                    TrackEventKind::Meta(MetaMessage::KeySignature(..)) => key_sig = Some(kind),
+                    TrackEventKind::Meta(MetaMessage::TrackName(_))
+                    | TrackEventKind::Meta(MetaMessage::InstrumentName(_)) => carried.push(kind),
+                    TrackEventKind::Midi { channel, message } => match message {
+                        MidiMessage::ProgramChange { .. } => {
+                            programs.insert(channel.as_int(), kind);
+                        }
+                        MidiMessage::Controller { controller, .. } => {
+                            controllers.insert((channel.as_int(), controller.as_int()), kind);
+                        }
+                        MidiMessage::PitchBend { .. } => {
+                            bends.insert(channel.as_int(), kind);
+                        }
+                        _ => {}
+                    },
+                    _ => {}
+                }
+                continue;
+            }
+
+            let rebased = tick - start_tick;
+            match kind {
+                TrackEventKind::Midi {
+                    channel,
+                    message: MidiMessage::NoteOn { key, vel },
+                } if vel.as_int() > 0 => {
+                    held.insert((channel.as_int(), key.as_int()));
+                    note_count += 1;
+                    section.push((rebased, kind));
+                }
+                TrackEventKind::Midi {
+                    channel,
+                    message: MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. },
+                } => {
+                    // Only release notes that were started inside the section
+                    if held.remove(&(channel.as_int(), key.as_int())) {
+                        section.push((rebased, kind));
+                    }
+                }
+                _ => section.push((rebased, kind)),
+            }
+        }
+
+        let mut events: Vec<(u64, TrackEventKind)> = Vec::new();
+        events.extend(carried.into_iter().map(|k| (0, k)));
+        events.extend(
+            [tempo, time_sig, key_sig]
+                .into_iter()
+                .flatten()
+                .map(|k| (0, k)),
+        );
+        events.extend(programs.into_values().map(|k| (0, k)));
+        events.extend(controllers.into_values().map(|k| (0, k)));
+        events.extend(bends.into_values().map(|k| (0, k)));
+        events.extend(section);
+
+        // Release anything still held at the cut
+        let mut still_held: Vec<_> = held.into_iter().collect();
+        still_held.sort();
+        for (channel, key) in still_held {
+            events.push((
+                length,
+                TrackEventKind::Midi {
+                    channel: channel.into(),
+                    message: MidiMessage::NoteOff {
+                        key: key.into(),
+                        vel: 0.into(),
+                    },
+                },
+            ));
+        }
+
+        out.tracks.push(to_delta(events, length));
+    }
+
+    if note_count == 0 {
+        return Err("No notes in the selected section".to_string());
+    }
+
+    save_smf(&out, dest)
+}
+
+/// Write a copy of `src` with every note replaced by the note its game key plays,
+/// so the file already sits inside the instrument range for the chosen modes
+pub fn bake_midi(
+    src: &str,
+    dest: &Path,
+    key_mode: KeyMode,
+    note_mode: NoteMode,
+    octave_shift: i8,
+) -> Result<(), String> {
+    let data = std::fs::read(crate::paths::long(src)).map_err(|e| e.to_string())?;
+    let (smf, _) = parse_smf(&data)?;
+
+    // Same auto-transpose the player would use
+    let shift_semitones = octave_shift as i32 * 12;
+    let transpose = crate::midi::load_midi(src)?.transpose + shift_semitones;
+
+    let bake_key = |key: u7| -> u7 {
+        let game_key = note_to_game_key(
+            key.as_int() as i32,
+            key_mode,
+            note_mode,
+            transpose,
+            shift_semitones,
+        );
+        match game_key_to_note(&game_key) {
+            Some(note) => (note.clamp(0, 127) as u8).into(),
+            None => key,
+        }
+    };
+
+    let mut out = Smf::new(smf.header);
+    for track in &smf.tracks {
+        let baked = track
+            .iter()
+            .map(|event| {
+                let kind = match event.kind {
+                    TrackEventKind::Midi { channel, message } => {
+                        let message = match message {
+                            MidiMessage::NoteOn { key, vel } => MidiMessage::NoteOn {
+                                key: bake_key(key),
+                                vel,
+                            },
+                            MidiMessage::NoteOff { key, vel } => MidiMessage::NoteOff {
+                                key: bake_key(key),
+                                vel,
+                            },
+                            MidiMessage::Aftertouch { key, vel } => MidiMessage::Aftertouch {
+                                key: bake_key(key),
+                                vel,
+                            },
+                            other => other,
+                        };
+                        TrackEventKind::Midi { channel, message }
+                    }
+                    other => other,
+                };
+                TrackEvent {
+                    delta: event.delta,
+                    kind,
+                }
+            })
+            .collect();
+        out.tracks.push(baked);
+    }
+
+    save_smf(&out, dest)
+}
+
+/// Meta events that affect timing for every track and must survive track removal
+fn is_global_meta(kind: &TrackEventKind) -> bool {
+    matches!(
+        kind,
+        TrackEventKind::Meta(
+            MetaMessage::Tempo(_)
+                | MetaMessage::TimeSignature(..)
+                | MetaMessage::KeySignature(..)
+                | MetaMessage::SmpteOffset(_)
+        )
+    )
+}
+
+fn check_track_ids(smf: &Smf, track_ids: &[usize]) -> Result<(), String> {
+    if let Some(id) = track_ids.iter().find(|id| **id >= smf.tracks.len()) {
+        return Err(format!("Track {} does not exist", id));
+    }
+    Ok(())
+}
+
+fn count_notes(smf: &Smf) -> usize {
+    smf.tracks
+        .iter()
+        .flatten()
+        .filter(|e| {
+            matches!(
+                e.kind,
+                TrackEventKind::Midi {
+                    message: MidiMessage::NoteOn { vel, .. },
+                    ..
+                } if vel.as_int() > 0
+            )
+        })
+        .count()
+}
+
+fn track_end(track: &[TrackEvent]) -> u64 {
+    track.iter().map(|e| e.delta.as_int() as u64).sum()
+}
+
+/// Write a copy of `src` without the given tracks (ids as returned by get_midi_tracks)
+/// Tempo and signature events in removed tracks are kept so timing doesn't change.
+pub fn strip_tracks(src: &str, dest: &Path, track_ids: &[usize]) -> Result<(), String> {
+    let data = std::fs::read(crate::paths::long(src)).map_err(|e| e.to_string())?;
+    let (smf, _) = parse_smf(&data)?;
+    check_track_ids(&smf, track_ids)?;
+
+    let mut out = Smf::new(smf.header);
+    for (idx, track) in smf.tracks.iter().enumerate() {
+        if !track_ids.contains(&idx) {
+            out.tracks.push(track.clone());
+            continue;
+        }
+
+        let end = track_end(track);
+        let kept: Vec<_> = to_absolute(track)
+            .into_iter()
+            .filter(|(_, kind)| is_global_meta(kind))
+            .collect();
+        if !kept.is_empty() {
+            out.tracks.push(to_delta(kept, end));
+        }
+    }
+
+    if count_notes(&out) == 0 {
+        return Err("No notes left after removing tracks".to_string());
+    }
+
+    save_smf(&out, dest)
+}
+
+/// Write a copy of `src` with the given tracks merged into one, at the position
+/// of the first selected track. The merged track keeps the first track's name.
+pub fn merge_tracks(src: &str, dest: &Path, track_ids: &[usize]) -> Result<(), String> {
+    let data = std::fs::read(crate::paths::long(src)).map_err(|e| e.to_string())?;
+    let (smf, _) = parse_smf(&data)?;
+    check_track_ids(&smf, track_ids)?;
+
+    let mut ids: Vec<usize> = track_ids.to_vec();
+    ids.sort_unstable();
+    ids.dedup();
+    if ids.len() < 2 {
+        return Err("Select at least two tracks to merge".to_string());
+    }
+
+    let mut merged: Vec<(u64, TrackEventKind)> = Vec::new();
+    let mut merged_end = 0;
+    let mut has_name = false;
+    for &idx in &ids {
+        let track = &smf.tracks[idx];
+        merged_end = merged_end.max(track_end(track));
+        for (tick, kind) in to_absolute(track) {
+            if matches!(kind, TrackEventKind::Meta(MetaMessage::TrackName(_))) {
+                if has_name {
+                    continue;
+                }
+                has_name = true;
+            }
+            merged.push((tick, kind));
+        }
+    }
+
+    let mut out = Smf::new(smf.header);
+    let mut merged = Some(to_delta(merged, merged_end));
+    for (idx, track) in smf.tracks.iter().enumerate() {
+        if idx == ids[0] {
+            if let Some(track) = merged.take() {
+                out.tracks.push(track);
+            }
+        } else if !ids.contains(&idx) {
+            out.tracks.push(track.clone());
+        }
+    }
+
+    save_smf(&out, dest)
+}
+
+/// Write a copy of `src` with every tempo scaled by `factor` (2.0 = twice as fast)
+/// Files without a tempo event get one at tick 0 (120 BPM scaled).
+pub fn change_tempo(src: &str, dest: &Path, factor: f64) -> Result<(), String> {
+    if !(factor.is_finite() && factor > 0.0) {
+        return Err("Invalid tempo factor".to_string());
+    }
+
+    let data = std::fs::read(crate::paths::long(src)).map_err(|e| e.to_string())?;
+    let (smf, _) = parse_smf(&data)?;
+    // Tempo events don't change the speed of an SMPTE file
+    ticks_per_quarter(&smf)?;
+
+    let scale = |tempo: u32| -> u24 {
+        let scaled = (tempo as f64 / factor).round() as u32;
+        scaled.clamp(1, u24::max_value().as_int()).into()
+    };
+
+    let mut out = Smf::new(smf.header);
+    let mut found_tempo = false;
+    for track in &smf.tracks {
+        let scaled = track
+            .iter()
+            .map(|event| match event.kind {
+                TrackEventKind::Meta(MetaMessage::Tempo(t)) => {
+                    found_tempo = true;
+                    TrackEvent {
+                        delta: event.delta,
+                        kind: TrackEventKind::Meta(MetaMessage::Tempo(scale(t.as_int()))),
+                    }
+                }
+                _ => *event,
+            })
+            .collect();
+        out.tracks.push(scaled);
+    }
+
+    if !found_tempo {
+        if let Some(first) = out.tracks.first_mut() {
+            first.insert(
+                0,
+                TrackEvent {
+                    delta: 0.into(),
+                    kind: TrackEventKind::Meta(MetaMessage::Tempo(scale(500_000))),
+                },
+            );
+        }
+    }
+
+    save_smf(&out, dest)
+}
+
+/// What cleanup_midi changed, for logging
+#[derive(Debug, Default)]
+pub struct CleanupStats {
+    pub zero_length_removed: usize,
+    pub duplicates_removed: usize,
+    pub empty_tracks_removed: usize,
+}
+
+struct Note {
+    channel: u4,
+    key: u7,
+    vel: u7,
+    on: u64,
+    off: u64,
+}
+
+/// Write a cleaned copy of `src`:
+/// - note starts/ends snapped to a grid of `grid_per_quarter` steps per beat
+/// - zero-length notes and same-pitch duplicates removed, overlaps trimmed
+/// - tracks without notes or timing events removed
+pub fn cleanup_midi(src: &str, dest: &Path, grid_per_quarter: u32) -> Result<CleanupStats, String> {
+    let data = std::fs::read(crate::paths::long(src)).map_err(|e| e.to_string())?;
+    let (smf, _) = parse_smf(&data)?;
+
+    let ticks_per_quarter = ticks_per_quarter(&smf)?;
+    let grid = (ticks_per_quarter / grid_per_quarter.max(1) as u64).max(1);
+    let snap = |tick: u64| (tick + grid / 2) / grid * grid;
+
+    let mut stats = CleanupStats::default();
+    let mut out = Smf::new(smf.header);
+
+    for track in &smf.tracks {
+        let end = track_end(track);
+        let mut others: Vec<(u64, TrackEventKind)> = Vec::new();
+        let mut notes: Vec<Note> = Vec::new();
+        // Open notes per (channel, key), oldest first
+        let mut open: std::collections::HashMap<(u8, u8), Vec<usize>> =
+            std::collections::HashMap::new();
+
+        for (tick, kind) in to_absolute(track) {
+            match kind {
+                TrackEventKind::Midi {
+                    channel,
+                    message: MidiMessage::NoteOn { key, vel },
+                } if vel.as_int() > 0 => {
+                    open.entry((channel.as_int(), key.as_int()))
+                        .or_default()
+                        .push(notes.len());
+                    notes.push(Note {
+                        channel,
+                        key,
+                        vel,
+                        on: tick,
+                        off: end,
+                    });
+                }
+                TrackEventKind::Midi {
+                    channel,
+                    message: MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. },
+                } => {
+                    if let Some(stack) = open.get_mut(&(channel.as_int(), key.as_int())) {
+                        if !stack.is_empty() {
+                            let idx = stack.remove(0);
+                            notes[idx].off = tick;
+                        }
+                    }
+                }
+                _ => others.push((tick, kind)),
+            }
+        }
+
+        let before = notes.len();
+        notes.retain(|n| n.off > n.on);
+        stats.zero_length_removed += before - notes.len();
+
+        for note in &mut notes {
+            note.on = snap(note.on);
+            note.off = snap(note.off).max(note.on + grid);
+        }
+
+        // Same pitch starting on the same step: keep the longest
+        notes.sort_by(|a, b| {
+            (a.channel.as_int(), a.key.as_int(), a.on)
+                .cmp(&(b.channel.as_int(), b.key.as_int(), b.on))
+                .then(b.off.cmp(&a.off))
+        });
+        let before = notes.len();
+        notes.dedup_by(|b, a| a.channel == b.channel && a.key == b.key && a.on == b.on);
+        stats.duplicates_removed += before - notes.len();
+
+        // Trim same-pitch overlaps so each note ends before the next one starts
+        for i in 1..notes.len() {
+            let (prev, next) = notes.split_at_mut(i);
+            let prev = &mut prev[i - 1];
+            let next = &next[0];
+            if prev.channel == next.channel && prev.key == next.key && prev.off > next.on {
+                prev.off = next.on;
+            }
+        }
+
+        let keep_track = !notes.is_empty() || others.iter().any(|(_, k)| is_global_meta(k));
+        if !keep_track {
+            stats.empty_tracks_removed += 1;
+            continue;
+        }
+
+        // Order at equal ticks: note offs, then other events, then note ons
+        let mut events: Vec<(u64, u8, TrackEventKind)> =
+            others.into_iter().map(|(t, k)| (t, 1, k)).collect();
+        for note in &notes {
+            events.push((
+                note.off,
+                0,
+                TrackEventKind::Midi {
+                    channel: note.channel,
+                    message: MidiMessage::NoteOff {
+                        key: note.key,
+                        vel: 0.into(),
+                    },
+                },
+            ));
+            events.push((
+                note.on,
+                2,
+                TrackEventKind::Midi {
+                    channel: note.channel,
+                    message: MidiMessage::NoteOn {
+                        key: note.key,
+                        vel: note.vel,
+                    },
+                },
+            ));
+        }
+        events.sort_by_key(|(tick, order, _)| (*tick, *order));
+
+        let last = events.last().map(|(t, _, _)| *t).unwrap_or(0);
+        out.tracks.push(to_delta(
+            events.into_iter().map(|(t, _, k)| (t, k)).collect(),
+            end.max(last),
+        ));
+    }
+
+    if count_notes(&out) == 0 {
+        return Err("No notes left after cleanup".to_string());
+    }
+
+    save_smf(&out, dest)?;
+    Ok(stats)
+}
+
+/// Cut times (ms) that split `src` into parts no longer than `max_ms`.
+/// Each cut goes at the best break in the second half of the allowed window:
+/// a moment where nothing is sounding, preferring the longest pause, otherwise
+/// the longest gap between note starts.
+pub fn find_split_points(src: &str, max_ms: f64) -> Result<Vec<f64>, String> {
+    let midi_data = crate::midi::load_midi(src)?;
+    let duration_ms = midi_data.duration * 1000.0;
+    if max_ms <= 0.0 {
+        return Err("Part length must be positive".to_string());
+    }
+    if duration_ms <= max_ms {
+        return Ok(Vec::new());
+    }
+
+    // Candidate breaks: (cut time, silent, gap length) between consecutive note starts
+    let mut candidates: Vec<(f64, bool, f64)> = Vec::new();
+    let mut active: i32 = 0;
+    let mut last_on: Option<u64> = None;
+    let mut silent_since: Option<u64> = None;
+    for event in &midi_data.events {
+        match event.event_type {
+            crate::midi::EventType::NoteOn => {
+                if let Some(prev) = last_on.filter(|&prev| event.time_ms > prev) {
+                    let gap = (event.time_ms - prev) as f64;
+                    match silent_since {
+                        Some(since) if active == 0 => {
+                            // Middle of the pause
+                            candidates.push(((since + event.time_ms) as f64 / 2.0, true, gap));
+                        }
+                        _ => candidates.push((event.time_ms as f64 - 1.0, false, gap)),
+                    }
+                }
+                last_on = Some(event.time_ms);
+                active += 1;
+                silent_since = None;
+            }
+            crate::midi::EventType::NoteOff => {
+                active = (active - 1).max(0);
+                if active == 0 {
+                    silent_since = Some(event.time_ms);
+                }
+            }
+        }
+    }
+
+    let mut cuts = Vec::new();
+    let mut part_start = 0.0;
+    while duration_ms - part_start > max_ms {
+        let window_end = part_start + max_ms;
+        let best = candidates
+            .iter()
+            .filter(|(t, _, _)| *t > part_start + max_ms / 2.0 && *t <= window_end)
+            .max_by(|a, b| (a.1, a.2).partial_cmp(&(b.1, b.2)).unwrap());
+        // No break in the window at all: hard cut at the limit
+        let cut = best.map(|(t, _, _)| *t).unwrap_or(window_end);
+        cuts.push(cut);
+        part_start = cut;
+    }
+    Ok(cuts)
+}
@@ -265,13 +265,7 @@ fn handle_midi_message(
         keyboard::key_down(&key);
 
         // Small delay then release (game uses tap, not hold)
-        std::thread::spawn({
-            let key = key.clone();
-            move || {
-                std::thread::sleep(std::time::Duration::from_millis(30));
-                keyboard::key_up(&key);
-            }
-        });
+        keyboard::schedule_release(&key, std::time::Duration::from_millis(30));
 
         // Emit event for frontend visualization
         let note_name = midi_note_to_name(note);
@@ -305,6 +299,7 @@ pub fn map_note_to_key(
             NoteMode::Python => note_to_key_python(note, transpose),
             NoteMode::Wide => note_to_key_36_wide(note, transpose),
             NoteMode::Sharps => note_to_key_36_sharps(note, transpose),
+            NoteMode::Smart => note_to_key_python(note, transpose), // No song to analyze
         },
         KeyMode::Keys21 => {
             match note_mode {
@@ -317,6 +312,7 @@ pub fn map_note_to_key(
                 NoteMode::Python => note_to_key_python(note, transpose),
                 NoteMode::Wide => note_to_key_wide(note, transpose),
                 NoteMode::Sharps => note_to_key(note, transpose), // Falls back to Closest in 21-key
+                NoteMode::Smart => note_to_key_python(note, transpose),
             }
         }
     }
@@ -0,0 +1,535 @@
+// Overlay window geometry. The process is per-monitor DPI aware, so bounds are kept in
+// logical pixels and converted with the scale of whichever monitor the window is on.
+// Physical sizes taken on one monitor and applied on another are what made the window
+// grow on every mini mode toggle on mixed-DPI setups.
+//
+// Z-order is here too: each layout (full, mini) can stay on top always, never, or only
+// while the game (or the overlay itself) has focus, so it doesn't cover other apps.
+// Optionally the overlay also lets clicks through while a song plays, so a stray click
+// during a performance lands in the game; hotkeys still pause and stop.
+//
+// Opacity and the layout preset (full, compact, bar only) live here as well, so hotkeys
+// can change them while the UI is hidden; "overlay-appearance" tells the UI to restyle.
+// On Windows the whole window is faded with a layered-window alpha; elsewhere the UI
+// fades its own content instead.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, LogicalPosition, LogicalSize, Manager, Window, WindowEvent};
+
+pub const MINI_SIZE: (f64, f64) = (64.0, 88.0); // Floating icon plus drag handle
+pub const FULL_MIN_SIZE: (f64, f64) = (960.0, 540.0);
+const FULL_DEFAULT_SIZE: (f64, f64) = (1180.0, 620.0);
+
+pub const MIN_OPACITY: u8 = 20; // Percent; any lower and it's hard to find again
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LayoutPreset {
+    Full,
+    Compact, // Transport, song list and visualizer only
+    BarOnly, // Just the transport bar
+}
+
+impl LayoutPreset {
+    /// Default and minimum window size
+    fn sizes(self) -> ((f64, f64), (f64, f64)) {
+        match self {
+            LayoutPreset::Full => (FULL_DEFAULT_SIZE, FULL_MIN_SIZE),
+            LayoutPreset::Compact => ((760.0, 420.0), (560.0, 320.0)),
+            LayoutPreset::BarOnly => ((720.0, 96.0), (420.0, 72.0)),
+        }
+    }
+
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))] // Global hotkeys only
+    pub fn next(self) -> LayoutPreset {
+        match self {
+            LayoutPreset::Full => LayoutPreset::Compact,
+            LayoutPreset::Compact => LayoutPreset::BarOnly,
+            LayoutPreset::BarOnly => LayoutPreset::Full,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Appearance {
+    pub opacity: u8, // Percent, MIN_OPACITY-100
+    pub layout: LayoutPreset,
+    // The window itself is faded, so the UI leaves its content opaque
+    #[serde(default, skip_deserializing)]
+    pub native_opacity: bool,
+}
+
+static APPEARANCE: Mutex<Appearance> = Mutex::new(Appearance {
+    opacity: 100,
+    layout: LayoutPreset::Full,
+    native_opacity: false,
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LogicalBounds {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+lazy_static::lazy_static! {
+    // Logical size of each window, put back when it lands on a monitor with another scale
+    static ref SIZES: Mutex<HashMap<String, (f64, f64)>> = Mutex::new(HashMap::new());
+}
+
+/// Where the window was before mini mode, also as fractions of the game window so it
+/// follows the game if that moved or resized in the meantime
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct MiniRestore {
+    absolute: LogicalBounds,
+    relative: Option<LogicalBounds>,
+}
+
+impl MiniRestore {
+    fn capture(window: LogicalBounds, game: Option<LogicalBounds>) -> Self {
+        let relative = game.map(|game| LogicalBounds {
+            x: (window.x - game.x) / game.width,
+            y: (window.y - game.y) / game.height,
+            width: window.width / game.width,
+            height: window.height / game.height,
+        });
+        MiniRestore {
+            absolute: window,
+            relative,
+        }
+    }
+
+    fn target(&self, game: Option<LogicalBounds>) -> LogicalBounds {
+        match (self.relative, game) {
+            (Some(rel), Some(game)) => LogicalBounds {
+                x: game.x + rel.x * game.width,
+                y: game.y + rel.y * game.height,
+                width: (rel.width * game.width).max(MINI_SIZE.0),
+                height: (rel.height * game.height).max(MINI_SIZE.1),
+            },
+            _ => self.absolute,
+        }
+    }
+}
+
+static MINI_RESTORE: Mutex<Option<MiniRestore>> = Mutex::new(None);
+static MINI_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TopmostMode {
+    Always,
+    WithGame, // Above the game, below other apps while they have focus
+    Never,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TopmostSettings {
+    pub main: TopmostMode,
+    pub mini: TopmostMode,
+}
+
+static TOPMOST: Mutex<TopmostSettings> = Mutex::new(TopmostSettings {
+    main: TopmostMode::Always,
+    mini: TopmostMode::Always,
+});
+static GAME_FOCUSED: AtomicBool = AtomicBool::new(false);
+static OVERLAY_FOCUSED: AtomicBool = AtomicBool::new(false);
+static TOPMOST_APPLIED: Mutex<Option<bool>> = Mutex::new(None);
+static CLICK_THROUGH_WHILE_PLAYING: AtomicBool = AtomicBool::new(false);
+static PLAYBACK_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Opt in to per-monitor DPI scaling before any window exists, so Windows reports real
+/// pixels and sends WM_DPICHANGED instead of bitmap-stretching the overlay
+#[cfg(target_os = "windows")]
+pub fn enable_dpi_awareness() {
+    use windows::Win32::UI::HiDpi::{
+        SetProcessDpiAwarenessContext, DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2,
+    };
+    // Fails if the manifest already set it, which is just as good
+    let _ = unsafe { SetProcessDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2) };
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn enable_dpi_awareness() {}
+
+pub fn bounds(window: &Window) -> Result<LogicalBounds, String> {
+    let scale = window
+        .scale_factor()
+        .map_err(|e| format!("Failed to read window scale: {}", e))?;
+    let position = window
+        .outer_position()
+        .map_err(|e| format!("Failed to read window position: {}", e))?
+        .to_logical::<f64>(scale);
+    let size = window
+        .inner_size()
+        .map_err(|e| format!("Failed to read window size: {}", e))?
+        .to_logical::<f64>(scale);
+    Ok(LogicalBounds {
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+    })
+}
+
+/// The game window in the overlay's logical pixels, if it's open
+fn game_bounds(window: &Window) -> Option<LogicalBounds> {
+    let (x, y, width, height) = crate::keyboard::get_game_window_rect()?;
+    if width <= 0 || height <= 0 {
+        return None;
+    }
+    let scale = window.scale_factor().ok()?;
+    Some(LogicalBounds {
+        x: x as f64 / scale,
+        y: y as f64 / scale,
+        width: width as f64 / scale,
+        height: height as f64 / scale,
+    })
+}
+
+/// Move first, then size: the size is then converted with the scale of the monitor the
+/// window ends up on rather than the one it left
+pub fn apply(window: &Window, bounds: LogicalBounds) -> Result<(), String> {
+    SIZES
+        .lock()
+        .unwrap()
+        .insert(window.label().to_string(), (bounds.width, bounds.height));
+    window
+        .set_position(LogicalPosition::new(bounds.x, bounds.y))
+        .map_err(|e| format!("Failed to move window: {}", e))?;
+    window
+        .set_size(LogicalSize::new(bounds.width, bounds.height))
+        .map_err(|e| format!("Failed to resize window: {}", e))?;
+    Ok(())
+}
+
+fn resize(window: &Window, (width, height): (f64, f64)) -> Result<(), String> {
+    SIZES
+        .lock()
+        .unwrap()
+        .insert(window.label().to_string(), (width, height));
+    window
+        .set_size(LogicalSize::new(width, height))
+        .map_err(|e| format!("Failed to resize window: {}", e))
+}
+
+/// Flip the mini flag and hand back the bounds to restore when leaving mini mode.
+/// Bounds are only taken on the way in; a second "on" would remember the mini icon.
+fn toggle_mini(
+    enabled: bool,
+    capture: impl FnOnce() -> Result<MiniRestore, String>,
+) -> Result<Option<MiniRestore>, String> {
+    let was_mini = MINI_ACTIVE.load(Ordering::SeqCst);
+    if enabled && !was_mini {
+        *MINI_RESTORE.lock().unwrap() = Some(capture()?);
+    }
+    MINI_ACTIVE.store(enabled, Ordering::SeqCst);
+    if enabled {
+        Ok(None)
+    } else {
+        Ok(MINI_RESTORE.lock().unwrap().take())
+    }
+}
+
+pub fn set_mini_mode(window: &Window, enabled: bool) -> Result<(), String> {
+    let restore = toggle_mini(enabled, || {
+        Ok(MiniRestore::capture(bounds(window)?, game_bounds(window)))
+    })?;
+    update_topmost(window.app_handle());
+    let (default_size, min_size) = get_appearance().layout.sizes();
+    let (min_width, min_height) = if enabled { MINI_SIZE } else { min_size };
+    window
+        .set_min_size(Some(LogicalSize::new(min_width, min_height)))
+        .map_err(|e| format!("Failed to set minimum size: {}", e))?;
+
+    if enabled {
+        return resize(window, MINI_SIZE);
+    }
+    match restore {
+        Some(restore) => apply(window, restore.target(game_bounds(window))),
+        None => resize(window, default_size),
+    }
+}
+
+pub fn is_mini_mode() -> bool {
+    MINI_ACTIVE.load(Ordering::SeqCst)
+}
+
+pub fn set_topmost_settings(settings: TopmostSettings) {
+    *TOPMOST.lock().unwrap() = settings;
+}
+
+pub fn get_topmost_settings() -> TopmostSettings {
+    *TOPMOST.lock().unwrap()
+}
+
+/// Raise or drop the main window per the current layout's mode; called by the game
+/// window watcher when focus moves, and whenever the settings or layout change
+pub fn update_topmost(app: &AppHandle) {
+    let settings = get_topmost_settings();
+    let mode = if is_mini_mode() {
+        settings.mini
+    } else {
+        settings.main
+    };
+    let on_top = match mode {
+        TopmostMode::Always => true,
+        TopmostMode::Never => false,
+        TopmostMode::WithGame => {
+            GAME_FOCUSED.load(Ordering::SeqCst) || OVERLAY_FOCUSED.load(Ordering::SeqCst)
+        }
+    };
+
+    let mut applied = TOPMOST_APPLIED.lock().unwrap();
+    if *applied == Some(on_top) {
+        return;
+    }
+    if let Some(window) = app.get_webview_window("main") {
+        match window.set_always_on_top(on_top) {
+            Ok(()) => *applied = Some(on_top),
+            Err(e) => crate::app_error!("[OVERLAY] Failed to change z-order: {}", e),
+        }
+    }
+}
+
+pub fn on_game_focus(app: &AppHandle, focused: bool) {
+    GAME_FOCUSED.store(focused, Ordering::SeqCst);
+    update_topmost(app);
+}
+
+pub fn set_click_through_while_playing(app: &AppHandle, enabled: bool) {
+    CLICK_THROUGH_WHILE_PLAYING.store(enabled, Ordering::SeqCst);
+    update_click_through(app);
+}
+
+pub fn get_click_through_while_playing() -> bool {
+    CLICK_THROUGH_WHILE_PLAYING.load(Ordering::SeqCst)
+}
+
+fn update_click_through(app: &AppHandle) {
+    let ignore = get_click_through_while_playing() && PLAYBACK_ACTIVE.load(Ordering::SeqCst);
+    if let Some(window) = app.get_webview_window("main") {
+        match window.set_ignore_cursor_events(ignore) {
+            Ok(()) => {
+                // Toggling click-through rewrites the layered style, which resets the alpha
+                set_native_opacity(&window, get_appearance().opacity);
+                let _ = app.emit("click-through", ignore);
+            }
+            Err(e) => crate::app_error!("[OVERLAY] Failed to change click-through: {}", e),
+        }
+    }
+}
+
+/// Follow the playback engine: click-through while a song plays, interactive otherwise
+pub fn start_click_through(app: AppHandle) {
+    crate::player::set_active_listener(move |active| {
+        PLAYBACK_ACTIVE.store(active, Ordering::SeqCst);
+        if get_click_through_while_playing() {
+            update_click_through(&app);
+        }
+    });
+}
+
+/// Fade the whole window (webview included) with a layered-window alpha.
+/// Returns whether it worked; the UI falls back to CSS opacity otherwise.
+#[cfg(target_os = "windows")]
+fn set_native_opacity(window: &tauri::WebviewWindow, opacity: u8) -> bool {
+    use windows::Win32::Foundation::{COLORREF, HWND};
+    use windows::Win32::UI::WindowsAndMessaging::{
+        GetWindowLongPtrW, SetLayeredWindowAttributes, SetWindowLongPtrW, GWL_EXSTYLE, LWA_ALPHA,
+        WS_EX_LAYERED,
+    };
+
+    let hwnd = match window.hwnd() {
+        Ok(hwnd) => HWND(hwnd.0),
+        Err(e) => {
+            crate::app_error!("[OVERLAY] Failed to get window handle: {}", e);
+            return false;
+        }
+    };
+    let alpha = (opacity.min(100) as u32 * 255 / 100) as u8;
+    unsafe {
+        let style = GetWindowLongPtrW(hwnd, GWL_EXSTYLE);
+        let layered = WS_EX_LAYERED.0 as isize;
+        if style & layered == 0 {
+            if alpha == 255 {
+                return true; // Nothing to fade
+            }
+            SetWindowLongPtrW(hwnd, GWL_EXSTYLE, style | layered);
+        }
+        match SetLayeredWindowAttributes(hwnd, COLORREF(0), alpha, LWA_ALPHA) {
+            Ok(()) => true,
+            Err(e) => {
+                crate::app_error!("[OVERLAY] Failed to set window opacity: {}", e);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn set_native_opacity(_window: &tauri::WebviewWindow, _opacity: u8) -> bool {
+    false
+}
+
+pub fn get_appearance() -> Appearance {
+    *APPEARANCE.lock().unwrap()
+}
+
+/// Apply new opacity and layout; a new layout resizes the window unless it's in mini mode
+pub fn set_appearance(app: &AppHandle, appearance: Appearance) -> Appearance {
+    let opacity = appearance.opacity.clamp(MIN_OPACITY, 100);
+    let native_opacity = app
+        .get_webview_window("main")
+        .is_some_and(|window| set_native_opacity(&window, opacity));
+    let appearance = Appearance {
+        opacity,
+        native_opacity,
+        ..appearance
+    };
+    let previous = std::mem::replace(&mut *APPEARANCE.lock().unwrap(), appearance);
+
+    if previous.layout != appearance.layout && !is_mini_mode() {
+        if let Some(window) = app.get_webview_window("main") {
+            let (size, (min_width, min_height)) = appearance.layout.sizes();
+            let _ = window.set_min_size(Some(LogicalSize::new(min_width, min_height)));
+            SIZES.lock().unwrap().insert("main".to_string(), size);
+            if let Err(e) = window.set_size(LogicalSize::new(size.0, size.1)) {
+                crate::app_error!(
+                    "[OVERLAY] Failed to resize for {:?}: {}",
+                    appearance.layout,
+                    e
+                );
+            }
+        }
+    }
+    let _ = app.emit("overlay-appearance", appearance);
+    appearance
+}
+
+/// Opacity up or down by `step` percent (hotkeys)
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+pub fn step_opacity(app: &AppHandle, step: i16) -> Appearance {
+    let mut appearance = get_appearance();
+    appearance.opacity = (appearance.opacity as i16 + step).clamp(0, 100) as u8;
+    set_appearance(app, appearance)
+}
+
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))] // Global hotkeys only
+pub fn cycle_layout(app: &AppHandle) -> Appearance {
+    let mut appearance = get_appearance();
+    appearance.layout = appearance.layout.next();
+    set_appearance(app, appearance)
+}
+
+/// Keep track of each window's logical size and hold it across DPI changes
+pub fn on_window_event(window: &Window, event: &WindowEvent) {
+    match event {
+        WindowEvent::Focused(focused) if window.label() == "main" => {
+            OVERLAY_FOCUSED.store(*focused, Ordering::SeqCst);
+            update_topmost(window.app_handle());
+        }
+        WindowEvent::Resized(size) => {
+            let Ok(scale) = window.scale_factor() else {
+                return;
+            };
+            if size.width == 0 || size.height == 0 {
+                return; // Minimized
+            }
+            let logical = size.to_logical::<f64>(scale);
+            SIZES
+                .lock()
+                .unwrap()
+                .insert(window.label().to_string(), (logical.width, logical.height));
+        }
+        WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+            let size = SIZES.lock().unwrap().get(window.label()).copied();
+            if let Some((width, height)) = size {
+                println!(
+                    "[DPI] {} moved to scale {}, keeping {}x{}",
+                    window.label(),
+                    scale_factor,
+                    width,
+                    height
+                );
+                let _ = window.set_size(LogicalSize::new(width, height));
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mini_mode_twice_restores_the_full_window() {
+        let full = MiniRestore {
+            absolute: LogicalBounds {
+                x: 100.0,
+                y: 50.0,
+                width: 1180.0,
+                height: 620.0,
+            },
+            relative: None,
+        };
+        let mini = MiniRestore {
+            absolute: LogicalBounds {
+                x: 100.0,
+                y: 50.0,
+                width: MINI_SIZE.0,
+                height: MINI_SIZE.1,
+            },
+            relative: None,
+        };
+
+        assert_eq!(toggle_mini(true, || Ok(full)), Ok(None));
+        assert_eq!(toggle_mini(true, || Ok(mini)), Ok(None));
+        assert!(is_mini_mode());
+
+        assert_eq!(toggle_mini(false, || Ok(mini)), Ok(Some(full)));
+        assert!(!is_mini_mode());
+    }
+
+    #[test]
+    fn restore_follows_the_game_window() {
+        let game = LogicalBounds {
+            x: 0.0,
+            y: 0.0,
+            width: 1920.0,
+            height: 1080.0,
+        };
+        let window = LogicalBounds {
+            x: 960.0,
+            y: 270.0,
+            width: 480.0,
+            height: 270.0,
+        };
+        let restore = MiniRestore::capture(window, Some(game));
+
+        let moved = LogicalBounds {
+            x: 100.0,
+            y: 100.0,
+            width: 960.0,
+            height: 540.0,
+        };
+        let target = restore.target(Some(moved));
+        assert_eq!(
+            target,
+            LogicalBounds {
+                x: 580.0,
+                y: 235.0,
+                width: 240.0,
+                height: 135.0,
+            }
+        );
+        // Game gone: back to where the window was
+        assert_eq!(restore.target(None), window);
+    }
+}
@@ -0,0 +1,144 @@
+// Album folders on network shares (\\NAS\midi) and paths past the old 260 character
+// limit. File operations go through the extended-length form (\\?\C:\..., \\?\UNC\...)
+// while the paths handed to the UI, caches and playlists stay in the plain form, so
+// existing keys keep matching. Scans retry a few times when a share briefly drops out.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const RETRIES: u32 = 3;
+const RETRY_DELAY_MS: u64 = 200; // Doubled after each attempt
+
+#[cfg(target_os = "windows")]
+const VERBATIM: &str = r"\\?\";
+#[cfg(target_os = "windows")]
+const VERBATIM_UNC: &str = r"\\?\UNC\";
+
+/// The form to hand to the file system. Verbatim paths skip Windows' own
+/// normalization, so separators and `.`/`..` are resolved here first.
+#[cfg(target_os = "windows")]
+pub fn long(path: impl AsRef<Path>) -> PathBuf {
+    let raw = path.as_ref().to_string_lossy().replace('/', "\\");
+    if raw.starts_with(VERBATIM) || raw.starts_with(r"\\.\") {
+        return PathBuf::from(raw);
+    }
+    let (prefix, rest) = if let Some(share) = raw.strip_prefix(r"\\") {
+        (VERBATIM_UNC, share)
+    } else if raw.as_bytes().get(1) == Some(&b':') && raw.as_bytes().get(2) == Some(&b'\\') {
+        (VERBATIM, raw.as_str())
+    } else {
+        // Relative paths can't be made verbatim
+        return PathBuf::from(raw);
+    };
+
+    let mut parts: Vec<&str> = Vec::new();
+    for part in rest.split('\\') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                // Never climb above the drive or the \\server\share root
+                let root = if prefix == VERBATIM_UNC { 2 } else { 1 };
+                if parts.len() > root {
+                    parts.pop();
+                }
+            }
+            _ => parts.push(part),
+        }
+    }
+    PathBuf::from(format!("{}{}", prefix, parts.join("\\")))
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn long(path: impl AsRef<Path>) -> PathBuf {
+    path.as_ref().to_path_buf()
+}
+
+/// The plain form of a path, as shown to players and used as a key
+#[cfg(target_os = "windows")]
+pub fn short(path: &Path) -> String {
+    let raw = path.to_string_lossy();
+    if let Some(share) = raw.strip_prefix(VERBATIM_UNC) {
+        format!(r"\\{}", share)
+    } else if let Some(local) = raw.strip_prefix(VERBATIM) {
+        local.to_string()
+    } else {
+        raw.to_string()
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn short(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+/// Whether `path` is inside `dir`, whichever form either is written in
+pub fn is_inside(path: &Path, dir: &Path) -> bool {
+    let path = long(path);
+    let dir = long(dir);
+    if cfg!(target_os = "windows") {
+        // Drive letters and share names aren't case sensitive
+        let path = path.to_string_lossy().to_lowercase();
+        let dir = dir.to_string_lossy().to_lowercase();
+        let dir = dir.trim_end_matches('\\');
+        path.strip_prefix(dir)
+            .is_some_and(|rest| rest.starts_with('\\'))
+    } else {
+        path.starts_with(&dir)
+    }
+}
+
+/// Errors a flaky share or a busy file server can clear up on their own
+fn is_transient(e: &io::Error) -> bool {
+    if matches!(
+        e.kind(),
+        io::ErrorKind::TimedOut
+            | io::ErrorKind::Interrupted
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+    ) {
+        return true;
+    }
+    // ERROR_SHARING_VIOLATION, ERROR_BAD_NETPATH, ERROR_NETWORK_BUSY,
+    // ERROR_UNEXP_NET_ERR, ERROR_NETNAME_DELETED, ERROR_SEM_TIMEOUT
+    cfg!(target_os = "windows") && matches!(e.raw_os_error(), Some(32 | 53 | 54 | 59 | 64 | 121))
+}
+
+/// Run `op` again with backoff while it fails with a transient error
+pub fn retry<T>(what: &str, mut op: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    let mut attempt = 0;
+    loop {
+        match op() {
+            Err(e) if attempt < RETRIES && is_transient(&e) => {
+                attempt += 1;
+                let delay = RETRY_DELAY_MS << (attempt - 1);
+                println!(
+                    "[PATHS] Failed to {} ({}), retrying in {} ms",
+                    what, e, delay
+                );
+                std::thread::sleep(Duration::from_millis(delay));
+            }
+            result => return result,
+        }
+    }
+}
+
+/// The .mid files directly in `dir`, in plain form
+pub fn list_midi(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let dir = long(dir);
+    retry("read album folder", || {
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(&dir)? {
+            let path = match entry {
+                Ok(entry) => entry.path(),
+                Err(e) if is_transient(&e) => return Err(e),
+                Err(_) => continue,
+            };
+            if path.extension().and_then(|s| s.to_str()) == Some("mid") {
+                files.push(PathBuf::from(short(&path)));
+            }
+        }
+        Ok(files)
+    })
+}
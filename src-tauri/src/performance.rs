@@ -0,0 +1,121 @@
+// Performance packages: one song plus everything needed to play it the same way, for band
+// leaders handing out parts. A zip with the MIDI file and a metadata.json holding the
+// song's settings (track transpose, muted tracks, chord reduction, ...), its note and tags,
+// and the exporter's note mode, key mode, octave shift and speed.
+
+use crate::error::AppError;
+use crate::midi::{KeyMode, NoteMode};
+use crate::song_meta::SongMeta;
+use serde::{Deserialize, Serialize};
+
+const EXPORT_TYPE: &str = "performance";
+const MANIFEST: &str = "metadata.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlaySetup {
+    pub note_mode: NoteMode,
+    pub key_mode: KeyMode,
+    pub octave_shift: i8,
+    pub speed: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PerformancePackage {
+    pub export_type: String,
+    pub name: String,
+    pub filename: String,
+    #[serde(default)]
+    pub song: SongMeta, // Rating left out; it's the exporter's opinion
+    #[serde(default)]
+    pub setup: Option<PlaySetup>,
+    #[serde(default)]
+    pub exported_at: String,
+    #[serde(default)]
+    pub version: String,
+}
+
+pub fn write(
+    path: &str,
+    midi_path: &str,
+    mut song: SongMeta,
+    setup: Option<PlaySetup>,
+) -> Result<PerformancePackage, AppError> {
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    let source = std::path::Path::new(midi_path);
+    let midi_data =
+        std::fs::read(crate::paths::long(source)).map_err(|e| AppError::io("read MIDI file", e))?;
+    let filename = source
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("song.mid")
+        .to_string();
+    song.rating = None;
+    let package = PerformancePackage {
+        export_type: EXPORT_TYPE.to_string(),
+        name: filename.trim_end_matches(".mid").to_string(),
+        filename,
+        song,
+        setup,
+        exported_at: crate::chrono_now(),
+        version: "1.0".to_string(),
+    };
+    let manifest = serde_json::to_string_pretty(&package)
+        .map_err(|e| format!("Failed to serialize package: {}", e))?;
+
+    let file = std::fs::File::create(path).map_err(|e| AppError::io("create package", e))?;
+    let mut zip = zip::ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    zip.start_file(&package.filename, options)
+        .map_err(|e| format!("Failed to add file to package: {}", e))?;
+    zip.write_all(&midi_data)
+        .map_err(|e| format!("Failed to write file data: {}", e))?;
+    zip.start_file(MANIFEST, options)
+        .map_err(|e| format!("Failed to add metadata to package: {}", e))?;
+    zip.write_all(manifest.as_bytes())
+        .map_err(|e| format!("Failed to write metadata: {}", e))?;
+    zip.finish()
+        .map_err(|e| format!("Failed to finalize package: {}", e))?;
+    Ok(package)
+}
+
+/// The package's manifest and MIDI file, checked like any other shared file
+pub fn read(path: &str) -> Result<(PerformancePackage, Vec<u8>), AppError> {
+    let mut archive = crate::open_zip_checked(path)?;
+    let mut total = 0u64;
+
+    let manifest = archive
+        .by_name(MANIFEST)
+        .map_err(|_| AppError::InvalidInput("Not a performance package".to_string()))?;
+    let manifest = crate::read_entry_limited(manifest, crate::ZIP_MAX_METADATA_BYTES, &mut total)?;
+    let mut package: PerformancePackage = serde_json::from_slice(&manifest)
+        .map_err(|e| AppError::InvalidInput(format!("Invalid performance package: {}", e)))?;
+    if package.export_type != EXPORT_TYPE {
+        return Err(AppError::InvalidInput(format!(
+            "Not a performance package: {}",
+            package.export_type
+        )));
+    }
+    let safe_name = crate::safe_entry_name(Some(std::path::Path::new(&package.filename)))
+        .filter(|name| name.to_lowercase().ends_with(".mid"))
+        .ok_or_else(|| {
+            AppError::InvalidInput(format!(
+                "Invalid file name in package: {}",
+                package.filename
+            ))
+        })?;
+
+    let entry = archive
+        .by_name(&package.filename)
+        .map_err(|_| AppError::InvalidInput("Package has no MIDI file".to_string()))?;
+    let data = crate::read_entry_limited(entry, crate::ZIP_MAX_FILE_BYTES, &mut total)?;
+    if !crate::check_midi_bytes(&data)? {
+        return Err(AppError::InvalidMidi(
+            "Package contains an invalid MIDI file".to_string(),
+        ));
+    }
+    // The entry name can't be trusted for saving; the flattened one can
+    package.filename = safe_name;
+    Ok((package, data))
+}
@@ -0,0 +1,1087 @@
+// Playback engine. One long-lived thread owns the song being played and everything
+// the playback loop reads (modes, speed, band filter, per-song settings); the rest of
+// the app drives it with PlayerCommands over a channel instead of sharing Arcs with it.
+// Position and play/pause state are published as PlayerSnapshot on a watch channel,
+// so getters never wait on the player.
+
+use crate::midi::{
+    dropped_notes, get_max_polyphony, get_velocity_emphasis, note_to_game_key, BandFilter,
+    EventType, KeyMode, MidiData, NoteMode, SongSettings, VelocityEmphasis,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+use tauri::{Emitter, Window};
+use tokio::sync::watch;
+
+// Longest the engine sleeps between timing checks while a song plays
+const TICK: Duration = Duration::from_millis(2);
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+const LOOP_GAP: Duration = Duration::from_millis(500);
+const GUARD_INTERVAL: Duration = Duration::from_millis(500);
+// How long a caller waits for the engine to confirm a command
+const ACK_TIMEOUT: Duration = Duration::from_secs(1);
+pub const MAX_NOTE_EVENT_BATCH_MS: u64 = 250;
+
+// Played keys are sent to the webview in batches ("note-events", one array per flush)
+// to keep IPC down in dense songs. 0 sends each key as its own "note-event" right away.
+static NOTE_EVENT_BATCH_MS: AtomicU64 = AtomicU64::new(30);
+
+pub fn set_note_event_batch_ms(ms: u64) {
+    NOTE_EVENT_BATCH_MS.store(ms.min(MAX_NOTE_EVENT_BATCH_MS), Ordering::SeqCst);
+}
+
+pub fn get_note_event_batch_ms() -> u64 {
+    NOTE_EVENT_BATCH_MS.load(Ordering::SeqCst)
+}
+
+// Silent rehearsal: with no game window at Play, the song still runs (progress,
+// visualizer, events) but no keys are sent. Notes go out as "rehearsal-notes" instead,
+// with pitch and velocity, for the preview synth.
+static SILENT_REHEARSAL: AtomicBool = AtomicBool::new(true);
+
+pub fn set_silent_rehearsal(enabled: bool) {
+    SILENT_REHEARSAL.store(enabled, Ordering::SeqCst);
+}
+
+pub fn get_silent_rehearsal() -> bool {
+    SILENT_REHEARSAL.load(Ordering::SeqCst)
+}
+
+// Game window guard: refuse to start without a game window, and pause while it's gone
+// (or, in SendInput mode, not focused) instead of sending keys nowhere. "playback-blocked"
+// says why; "playback-unblocked" fires when the guard resumes the song.
+static REQUIRE_GAME_WINDOW: AtomicBool = AtomicBool::new(false);
+
+pub fn set_require_game_window(enabled: bool) {
+    REQUIRE_GAME_WINDOW.store(enabled, Ordering::SeqCst);
+}
+
+pub fn get_require_game_window() -> bool {
+    REQUIRE_GAME_WINDOW.load(Ordering::SeqCst)
+}
+
+// Told whenever a song starts or stops actually playing (paused counts as not playing),
+// from the engine thread
+type ActiveListener = Box<dyn Fn(bool) + Send + Sync>;
+
+static ACTIVE_LISTENER: std::sync::RwLock<Option<ActiveListener>> = std::sync::RwLock::new(None);
+
+pub fn set_active_listener(listener: impl Fn(bool) + Send + Sync + 'static) {
+    *ACTIVE_LISTENER.write().unwrap() = Some(Box::new(listener));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockReason {
+    GameNotFound,
+    GameNotFocused, // SendInput mode only; PostMessage reaches a background window
+}
+
+impl BlockReason {
+    pub fn message(self) -> &'static str {
+        match self {
+            BlockReason::GameNotFound => "Playback blocked: game window not found",
+            BlockReason::GameNotFocused => "Playback paused: game window is not focused",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlaybackBlocked {
+    pub reason: BlockReason,
+    pub message: String,
+}
+
+impl From<BlockReason> for PlaybackBlocked {
+    fn from(reason: BlockReason) -> Self {
+        PlaybackBlocked {
+            reason,
+            message: reason.message().to_string(),
+        }
+    }
+}
+
+/// What keeps keys from reaching the game right now, when the guard is on
+fn game_window_block() -> Option<BlockReason> {
+    if !get_require_game_window() {
+        return None;
+    }
+    if !crate::keyboard::refresh_game_window() {
+        return Some(BlockReason::GameNotFound);
+    }
+    if crate::keyboard::get_send_input_mode() && !crate::keyboard::is_wwm_focused().unwrap_or(false)
+    {
+        return Some(BlockReason::GameNotFocused);
+    }
+    None
+}
+
+pub enum PlayerCommand {
+    Play {
+        midi_data: Box<MidiData>,
+        start_at: f64, // seconds
+        path: Option<String>,
+        window: Option<Window>, // None plays headless: keys only, no events (tests)
+    },
+    TogglePause,
+    Seek(f64),
+    SetNoteMode(NoteMode),
+    SetKeyMode(KeyMode),
+    SetOctaveShift(i8),
+    SetSpeed(f64),
+    SetLoop {
+        enabled: bool,
+        count: u16,
+    },
+    SetBandFilter(Option<BandFilter>),
+    SetSongSettings(SongSettings),
+    SetTrackMutes(std::collections::BTreeSet<usize>),
+    Stop,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlayerSnapshot {
+    pub session: u64, // Bumped by every Play
+    pub is_playing: bool,
+    pub is_paused: bool,
+    pub position: f64, // seconds into the song
+    pub loops_completed: u16,
+    pub rehearsal: bool,              // Playing without sending keys
+    pub blocked: Option<BlockReason>, // Paused by the game window guard
+}
+
+type Envelope = (PlayerCommand, Option<mpsc::Sender<()>>);
+
+#[derive(Debug, Clone, Serialize)]
+struct PlaybackStopped {
+    position: f64, // seconds reached
+}
+
+/// A note played during silent rehearsal
+#[derive(Debug, Clone, Serialize)]
+struct RehearsalNote {
+    key: String,
+    note: u8, // After transpose and octave shift
+    velocity: u8,
+    time_ms: u64, // Song time
+}
+
+/// Handle to the engine thread
+pub struct Player {
+    commands: mpsc::Sender<Envelope>,
+    snapshots: watch::Receiver<PlayerSnapshot>,
+    engine: std::thread::JoinHandle<()>,
+}
+
+impl Player {
+    pub fn spawn() -> Player {
+        let (commands, receiver) = mpsc::channel();
+        let (publisher, snapshots) = watch::channel(PlayerSnapshot::default());
+        let mut engine = Engine {
+            commands: receiver,
+            snapshot: publisher,
+            options: Options::default(),
+            session: None,
+        };
+        let engine = std::thread::Builder::new()
+            .name("playback".to_string())
+            .spawn(move || engine.run())
+            .expect("Failed to start playback engine");
+        Player {
+            commands,
+            snapshots,
+            engine,
+        }
+    }
+
+    /// Stop playback and wait until the engine confirms every key is up ("playback-stopped"
+    /// is emitted by then). If it doesn't answer in time, release keys from here.
+    pub fn stop(&mut self) -> Result<(), String> {
+        let result = self.call(PlayerCommand::Stop);
+        if let Err(ref e) = result {
+            crate::app_error!("[PLAYER] Stop not confirmed: {}", e);
+            crate::keyboard::flush_releases();
+            crate::keyboard::reset_modifier_counts();
+            // The engine only exits if its thread died; start a fresh one
+            if self.engine.is_finished() {
+                let dead = std::mem::replace(self, Player::spawn());
+                let _ = dead.engine.join();
+            }
+        }
+        result
+    }
+
+    /// Queue a command without waiting for it
+    pub fn send(&self, command: PlayerCommand) {
+        let _ = self.commands.send((command, None));
+    }
+
+    /// Send a command and wait until the engine has applied it, so a snapshot read
+    /// afterwards reflects it (and for Stop, every key is released)
+    pub fn call(&self, command: PlayerCommand) -> Result<(), String> {
+        let (ack, done) = mpsc::channel();
+        self.commands
+            .send((command, Some(ack)))
+            .map_err(|_| "Playback engine is not running".to_string())?;
+        done.recv_timeout(ACK_TIMEOUT)
+            .map_err(|_| "Playback engine did not respond".to_string())
+    }
+
+    pub fn snapshot(&self) -> PlayerSnapshot {
+        self.snapshots.borrow().clone()
+    }
+}
+
+/// Settings the playback loop follows live; they outlive any one song
+struct Options {
+    note_mode: NoteMode,
+    key_mode: KeyMode,
+    octave_shift: i8,
+    speed: f64,
+    loop_mode: bool,
+    loop_count: u16, // Total plays before stopping (0 = forever)
+    band_filter: Option<BandFilter>,
+    song_settings: SongSettings,
+    track_mutes: std::collections::BTreeSet<usize>, // Live, not saved with the song
+    // song_settings with band parts and live mutes applied, for the thinning key
+    thinning_settings: SongSettings,
+}
+
+impl Options {
+    /// Rebuild thinning_settings after the song settings, band filter or mutes change
+    fn refresh_thinning_settings(&mut self) {
+        let mut settings = self.song_settings.clone();
+        if self.band_filter.is_some() {
+            settings.muted_tracks.clear(); // Band parts pick their own tracks
+        }
+        settings
+            .muted_tracks
+            .extend(self.track_mutes.iter().copied());
+        self.thinning_settings = settings;
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            note_mode: NoteMode::Python,
+            key_mode: KeyMode::Keys21,
+            octave_shift: 0,
+            speed: 1.0,
+            loop_mode: false,
+            loop_count: 0,
+            band_filter: None,
+            song_settings: SongSettings::default(),
+            track_mutes: std::collections::BTreeSet::new(),
+            thinning_settings: SongSettings::default(),
+        }
+    }
+}
+
+type ThinningKey = (usize, Option<usize>, SongSettings, VelocityEmphasis);
+
+/// The song being played
+struct Session {
+    id: u64,
+    midi_data: MidiData,
+    path: Option<String>,
+    window: Option<Window>,
+    next: usize,      // Index of the next event to play
+    position_ms: f64, // Song time; negative during the count-in
+    last_tick: Instant,
+    paused: bool,
+    count_in: Vec<f64>, // Song times of the remaining count-in clicks
+    count_in_total: u32,
+    metronome_started: bool,
+    loop_restart: Option<Instant>, // Waiting out the gap before the next loop
+    loops_completed: u16,
+    // Counted in every mode so a live switch to Split stays in step with other players
+    note_on_counter: usize,
+    // Notes thinned out (polyphony cap, octave dedupe, out of range, ghost notes),
+    // rebuilt when an option changes
+    thinning_key: ThinningKey,
+    skipped_notes: std::collections::HashSet<usize>,
+    last_progress: Instant,
+    played_keys: Vec<String>, // Waiting for the next note-events flush
+    last_flush: Instant,
+    rehearsal: bool,
+    rehearsal_notes: Vec<RehearsalNote>, // Flushed with played_keys
+    blocked: Option<BlockReason>,
+    last_guard_check: Instant,
+}
+
+impl Session {
+    fn emit<S: Serialize + Clone>(&self, event: &str, payload: S) {
+        if let Some(window) = &self.window {
+            let _ = window.emit(event, payload);
+        }
+    }
+
+    fn flush_note_events(&mut self) {
+        self.last_flush = Instant::now();
+        if !self.played_keys.is_empty() {
+            let keys = std::mem::take(&mut self.played_keys);
+            self.emit("note-events", keys);
+        }
+        if !self.rehearsal_notes.is_empty() {
+            let notes = std::mem::take(&mut self.rehearsal_notes);
+            self.emit("rehearsal-notes", notes);
+        }
+    }
+}
+
+impl Session {
+    /// Jump to `position_ms`, cancelling any count-in or loop gap
+    fn reposition(&mut self, position_ms: f64) {
+        let position_ms = position_ms.max(0.0);
+        self.position_ms = position_ms;
+        self.next = self
+            .midi_data
+            .events
+            .partition_point(|e| (e.time_ms as f64) < position_ms);
+        self.note_on_counter = self.midi_data.events[..self.next]
+            .iter()
+            .filter(|e| matches!(e.event_type, EventType::NoteOn))
+            .count();
+        self.count_in.clear();
+        self.loop_restart = None;
+        self.last_tick = Instant::now();
+    }
+}
+
+struct Engine {
+    commands: mpsc::Receiver<Envelope>,
+    snapshot: watch::Sender<PlayerSnapshot>,
+    options: Options,
+    session: Option<Session>,
+}
+
+impl Engine {
+    fn run(&mut self) {
+        loop {
+            // A panic is recorded by the crash hook; drop the song so the UI doesn't
+            // think we're still playing, and keep the engine alive for the next one
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| self.serve()));
+            match result {
+                Ok(()) => return, // Every Player handle is gone
+                Err(_) => {
+                    if let Some(session) = self.session.take() {
+                        session.emit("playback-error", "Playback thread crashed");
+                    }
+                    crate::keyboard::reset_modifier_counts();
+                    self.publish();
+                }
+            }
+        }
+    }
+
+    fn serve(&mut self) {
+        loop {
+            let envelope = match self.wait_time() {
+                None => match self.commands.recv() {
+                    Ok(envelope) => Some(envelope),
+                    Err(_) => return,
+                },
+                Some(wait) => match self.commands.recv_timeout(wait) {
+                    Ok(envelope) => Some(envelope),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                },
+            };
+            if let Some((command, ack)) = envelope {
+                self.handle(command);
+                if let Some(ack) = ack {
+                    let _ = ack.send(());
+                }
+            }
+            self.step();
+        }
+    }
+
+    /// How long to wait for a command before the song needs attention again
+    fn wait_time(&self) -> Option<Duration> {
+        let session = self.session.as_ref()?;
+        if session.paused {
+            // Keep checking whether the guard can resume
+            return session.blocked.map(|_| GUARD_INTERVAL);
+        }
+        if let Some(restart) = session.loop_restart {
+            return Some(restart.saturating_duration_since(Instant::now()));
+        }
+        let due_ms = session
+            .count_in
+            .first()
+            .copied()
+            .or_else(|| {
+                session
+                    .midi_data
+                    .events
+                    .get(session.next)
+                    .map(|e| e.time_ms as f64)
+            })
+            .unwrap_or(session.position_ms);
+        let wait_ms = (due_ms - session.position_ms).max(0.0) / self.options.speed;
+        Some(TICK.min(Duration::from_secs_f64(wait_ms / 1000.0)))
+    }
+
+    fn publish(&self) {
+        let snapshot = match &self.session {
+            Some(session) => PlayerSnapshot {
+                session: session.id,
+                is_playing: true,
+                is_paused: session.paused,
+                position: session.position_ms.max(0.0) / 1000.0,
+                loops_completed: session.loops_completed,
+                rehearsal: session.rehearsal,
+                blocked: session.blocked,
+            },
+            None => PlayerSnapshot {
+                session: self.snapshot.borrow().session,
+                loops_completed: self.snapshot.borrow().loops_completed,
+                ..PlayerSnapshot::default()
+            },
+        };
+        let active = snapshot.is_playing && !snapshot.is_paused;
+        let mut was_active = active;
+        self.snapshot.send_if_modified(|current| {
+            was_active = current.is_playing && !current.is_paused;
+            let changed = *current != snapshot;
+            *current = snapshot;
+            changed
+        });
+        if was_active != active {
+            if let Some(listener) = ACTIVE_LISTENER.read().unwrap().as_ref() {
+                listener(active);
+            }
+        }
+    }
+
+    fn handle(&mut self, command: PlayerCommand) {
+        match command {
+            PlayerCommand::Play {
+                midi_data,
+                start_at,
+                path,
+                window,
+            } => self.play(*midi_data, start_at, path, window),
+            PlayerCommand::TogglePause => {
+                if let Some(session) = self.session.as_mut() {
+                    session.paused = !session.paused;
+                    session.last_tick = Instant::now();
+                    // A manual resume overrides the guard until its next check
+                    session.blocked = None;
+                }
+            }
+            PlayerCommand::Seek(position) => {
+                if let Some(session) = self.session.as_mut() {
+                    session.reposition(position * 1000.0);
+                }
+            }
+            PlayerCommand::SetNoteMode(mode) => self.options.note_mode = mode,
+            PlayerCommand::SetKeyMode(mode) => self.options.key_mode = mode,
+            PlayerCommand::SetOctaveShift(shift) => self.options.octave_shift = shift,
+            PlayerCommand::SetSpeed(speed) => self.options.speed = speed.max(0.01),
+            PlayerCommand::SetLoop { enabled, count } => {
+                self.options.loop_mode = enabled;
+                self.options.loop_count = count;
+            }
+            PlayerCommand::SetBandFilter(filter) => {
+                self.options.band_filter = filter;
+                self.options.refresh_thinning_settings();
+            }
+            PlayerCommand::SetSongSettings(settings) => {
+                self.options.song_settings = settings;
+                self.options.refresh_thinning_settings();
+            }
+            PlayerCommand::SetTrackMutes(tracks) => {
+                self.options.track_mutes = tracks;
+                self.options.refresh_thinning_settings();
+            }
+            PlayerCommand::Stop => {
+                if let Some(mut session) = self.session.take() {
+                    session.flush_note_events();
+                    // Notes are tapped, but scheduled releases may still be pending
+                    crate::keyboard::flush_releases();
+                    crate::keyboard::reset_modifier_counts();
+                    session.emit(
+                        "playback-stopped",
+                        PlaybackStopped {
+                            position: session.position_ms.max(0.0) / 1000.0,
+                        },
+                    );
+                }
+            }
+        }
+        self.publish();
+    }
+
+    fn play(
+        &mut self,
+        midi_data: MidiData,
+        start_at: f64,
+        path: Option<String>,
+        window: Option<Window>,
+    ) {
+        log_band_filter(&self.options.band_filter);
+
+        // Count in only when starting from the top, not on seeks or resumes
+        let metronome = crate::metronome::get_settings();
+        let count_in_total = if start_at == 0.0 && metronome.enabled {
+            metronome.count_in as u32
+        } else {
+            0
+        };
+        let beat_ms = crate::metronome::count_in_beat_ms(&midi_data.beats);
+        let count_in: Vec<f64> = (0..count_in_total)
+            .map(|i| -((count_in_total - i) as f64) * beat_ms)
+            .collect();
+
+        // Headless sessions (tests) always go through the key sink
+        let rehearsal =
+            window.is_some() && get_silent_rehearsal() && !crate::keyboard::refresh_game_window();
+        if rehearsal {
+            println!("[PLAYER] No game window found, playing as a silent rehearsal");
+        }
+
+        let now = Instant::now();
+        let mut session = Session {
+            id: self.snapshot.borrow().session + 1,
+            midi_data,
+            path,
+            window,
+            next: 0,
+            position_ms: 0.0,
+            last_tick: now,
+            paused: false,
+            count_in: Vec::new(),
+            count_in_total,
+            metronome_started: false,
+            loop_restart: None,
+            loops_completed: 0,
+            note_on_counter: 0,
+            thinning_key: (
+                0,
+                None,
+                SongSettings::default(),
+                VelocityEmphasis::default(),
+            ),
+            skipped_notes: std::collections::HashSet::new(),
+            last_progress: now,
+            played_keys: Vec::new(),
+            last_flush: now,
+            rehearsal,
+            rehearsal_notes: Vec::new(),
+            blocked: None,
+            last_guard_check: now,
+        };
+        session.emit("silent-rehearsal", rehearsal);
+        session.reposition(start_at * 1000.0);
+        if let Some(&first) = count_in.first() {
+            session.position_ms = first;
+            session.count_in = count_in;
+        }
+        self.session = Some(session);
+    }
+
+    /// Advance the song clock and play whatever is due
+    fn step(&mut self) {
+        let Some(session) = self.session.as_mut() else {
+            return;
+        };
+        let now = Instant::now();
+        // Headless and rehearsal sessions don't need the game
+        if session.window.is_some()
+            && !session.rehearsal
+            && now.duration_since(session.last_guard_check) >= GUARD_INTERVAL
+        {
+            session.last_guard_check = now;
+            guard_game_window(session);
+        }
+        if session.paused {
+            session.last_tick = now;
+            return;
+        }
+
+        if let Some(restart) = session.loop_restart {
+            if now < restart {
+                return;
+            }
+            session.reposition(0.0);
+        }
+
+        session.position_ms +=
+            now.duration_since(session.last_tick).as_secs_f64() * 1000.0 * self.options.speed;
+        session.last_tick = now;
+
+        while session
+            .count_in
+            .first()
+            .is_some_and(|&t| t <= session.position_ms)
+        {
+            session.count_in.remove(0);
+            let beat = session.count_in_total - session.count_in.len() as u32;
+            if let Some(window) = &session.window {
+                crate::metronome::count_in_tick(window, beat);
+            }
+        }
+        if session.position_ms < 0.0 {
+            return;
+        }
+        if !session.metronome_started {
+            session.metronome_started = true;
+            if let Some(window) = &session.window {
+                crate::metronome::spawn(
+                    session.midi_data.beats.clone(),
+                    self.snapshot.subscribe(),
+                    session.id,
+                    window.clone(),
+                );
+            }
+        }
+
+        while let Some(event) = session.midi_data.events.get(session.next) {
+            if event.time_ms as f64 > session.position_ms {
+                break;
+            }
+            let event_idx = session.next;
+            session.next += 1;
+            if matches!(event.event_type, EventType::NoteOn) {
+                play_note(session, &self.options, event_idx);
+            }
+            // NoteOff is ignored - notes are released as they're pressed
+        }
+
+        let batch_ms = get_note_event_batch_ms();
+        if batch_ms == 0 || now.duration_since(session.last_flush).as_millis() as u64 >= batch_ms {
+            session.flush_note_events();
+        }
+
+        if now.duration_since(session.last_progress) >= PROGRESS_INTERVAL {
+            session.last_progress = now;
+            session.emit("playback-progress", session.position_ms / 1000.0);
+        }
+
+        if session.next >= session.midi_data.events.len() {
+            self.finish_play();
+        } else {
+            self.publish();
+        }
+    }
+
+    /// The song reached its end: loop again or finish
+    fn finish_play(&mut self) {
+        let Some(session) = self.session.as_mut() else {
+            return;
+        };
+        if session.loop_restart.is_some() {
+            return;
+        }
+        crate::keyboard::reset_modifier_counts();
+        session.flush_note_events();
+        session.loops_completed += 1;
+        let completed = session.loops_completed;
+        let max_loops = self.options.loop_count;
+        if self.options.loop_mode && (max_loops == 0 || completed < max_loops) {
+            session.loop_restart = Some(Instant::now() + LOOP_GAP);
+            session.position_ms = 0.0;
+            self.publish();
+            return;
+        }
+        if max_loops > 0 {
+            println!("[LOOP] Finished {} of {} plays", completed, max_loops);
+        }
+
+        let session = self.session.take().unwrap();
+        self.publish();
+        // Finished on its own: let the backend pick the next song if enabled. That
+        // starts playback through AppState, so it runs off the engine thread.
+        std::thread::spawn(move || {
+            if let Some(ref path) = session.path {
+                crate::history::record_stop(path, 0.0, true);
+            }
+            let Some(window) = &session.window else {
+                return;
+            };
+            if !crate::auto_advance(window) {
+                // Nothing left to resume once the set has finished
+                crate::clear_session();
+                let _ = window.emit("playback-ended", ());
+            }
+        });
+    }
+}
+
+/// Pause while the game can't take keys, and resume once it can again
+fn guard_game_window(session: &mut Session) {
+    let block = game_window_block();
+    match (block, session.blocked) {
+        (Some(reason), None) if !session.paused => {
+            println!("[PLAYER] {}", reason.message());
+            session.paused = true;
+            session.blocked = Some(reason);
+            crate::keyboard::flush_releases();
+            crate::keyboard::reset_modifier_counts();
+            session.emit("playback-blocked", PlaybackBlocked::from(reason));
+        }
+        (Some(reason), Some(previous)) if reason != previous => {
+            session.blocked = Some(reason);
+            session.emit("playback-blocked", PlaybackBlocked::from(reason));
+        }
+        (None, Some(_)) => {
+            println!("[PLAYER] Game window is back, resuming");
+            session.blocked = None;
+            session.paused = false;
+            session.last_tick = Instant::now();
+            session.emit("playback-unblocked", ());
+        }
+        _ => {}
+    }
+}
+
+fn play_note(session: &mut Session, options: &Options, event_idx: usize) {
+    let midi_data = &session.midi_data;
+    let event = &midi_data.events[event_idx];
+
+    let note_mode = options
+        .song_settings
+        .resolve_note_mode(options.note_mode, options.key_mode);
+    // Octave shift in semitones (1 octave = 12 semitones), plus this track's offset
+    let shift_semitones =
+        options.octave_shift as i32 * 12 + options.song_settings.track_offset(event.track_id);
+    let total_transpose = midi_data.transpose + shift_semitones;
+
+    let note_index = session.note_on_counter;
+    session.note_on_counter += 1;
+    let should_play = match &options.band_filter {
+        // In split mode, play every Nth note starting from slot
+        Some(BandFilter::Split {
+            slot,
+            total_players,
+        }) => (note_index % total_players) == *slot,
+        // Track mode: only play notes from the assigned track
+        Some(BandFilter::Track { track_id }) => event.track_id == *track_id,
+        Some(BandFilter::Tracks { track_ids }) => track_ids.contains(&event.track_id),
+        Some(BandFilter::Range { low, high }) => event.note >= *low && event.note <= *high,
+        None => true, // No filter, play all
+    };
+
+    let track_only = match &options.band_filter {
+        Some(BandFilter::Track { track_id }) => Some(*track_id),
+        _ => None,
+    };
+    let max_polyphony = get_max_polyphony() as usize;
+    let emphasis = get_velocity_emphasis();
+    let (key_polyphony, key_track, key_settings, key_emphasis) = &session.thinning_key;
+    if (*key_polyphony, *key_track, *key_emphasis) != (max_polyphony, track_only, emphasis)
+        || *key_settings != options.thinning_settings
+    {
+        session.skipped_notes = dropped_notes(
+            &midi_data.events,
+            track_only,
+            max_polyphony,
+            midi_data.transpose,
+            &options.thinning_settings,
+            emphasis,
+        );
+        session.thinning_key = (
+            max_polyphony,
+            track_only,
+            options.thinning_settings.clone(),
+            emphasis,
+        );
+    }
+
+    if should_play && !session.skipped_notes.contains(&event_idx) {
+        let key = note_to_game_key(
+            event.note as i32,
+            options.key_mode,
+            note_mode,
+            total_transpose,
+            shift_semitones,
+        );
+        if session.rehearsal {
+            session.rehearsal_notes.push(RehearsalNote {
+                key: key.clone(),
+                note: (event.note as i32 + total_transpose).clamp(0, 127) as u8,
+                velocity: event.velocity,
+                time_ms: event.time_ms,
+            });
+        } else {
+            // Simple press-release for each note (held only if a key duration is set)
+            crate::keyboard::tap_key(&key);
+        }
+
+        // Visualizer feed
+        if get_note_event_batch_ms() == 0 {
+            session.emit("note-event", &key);
+        } else {
+            session.played_keys.push(key);
+        }
+    }
+}
+
+fn log_band_filter(filter: &Option<BandFilter>) {
+    match filter {
+        Some(BandFilter::Split {
+            slot,
+            total_players,
+        }) => println!(
+            "[BAND] Split mode: playing note {} of every {} notes",
+            slot + 1,
+            total_players
+        ),
+        Some(BandFilter::Track { track_id }) => {
+            println!("[BAND] Track mode: playing track {}", track_id)
+        }
+        Some(BandFilter::Tracks { track_ids }) => {
+            println!("[BAND] Tracks mode: playing tracks {:?}", track_ids)
+        }
+        Some(BandFilter::Range { low, high }) => {
+            println!("[BAND] Range mode: playing notes {}-{}", low, high)
+        }
+        None => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keyboard::{set_key_sink, RecordingKeySink};
+    use midly::num::{u15, u24, u28, u4, u7};
+    use midly::{
+        Format, Header, MetaMessage, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind,
+    };
+    use std::sync::{Arc, Mutex};
+
+    // The key sink is global, so engine tests take turns
+    static SINK_LOCK: Mutex<()> = Mutex::new(());
+    const NOTE_SPACING_MS: u32 = 100;
+
+    const C_MAJOR: [u8; 8] = [60, 62, 64, 65, 67, 69, 71, 72];
+    const CHROMATIC: [u8; 12] = [60, 61, 62, 63, 64, 65, 66, 67, 68, 69, 70, 71];
+
+    /// Write a one-track file with `notes` every NOTE_SPACING_MS and load it like a song.
+    /// 120 BPM at 500 ticks per beat, so one tick is one millisecond.
+    fn fixture(name: &str, notes: &[u8]) -> MidiData {
+        let mut timeline: Vec<(u32, bool, u8)> = Vec::new();
+        for (i, &note) in notes.iter().enumerate() {
+            let start = i as u32 * NOTE_SPACING_MS;
+            timeline.push((start, true, note));
+            timeline.push((start + NOTE_SPACING_MS / 2, false, note));
+        }
+        timeline.sort_by_key(|&(time, on, _)| (time, on));
+
+        let mut track = vec![TrackEvent {
+            delta: u28::new(0),
+            kind: TrackEventKind::Meta(MetaMessage::Tempo(u24::new(500_000))),
+        }];
+        let mut last = 0;
+        for (time, on, note) in timeline {
+            let message = if on {
+                MidiMessage::NoteOn {
+                    key: u7::new(note),
+                    vel: u7::new(100),
+                }
+            } else {
+                MidiMessage::NoteOff {
+                    key: u7::new(note),
+                    vel: u7::new(0),
+                }
+            };
+            track.push(TrackEvent {
+                delta: u28::new(time - last),
+                kind: TrackEventKind::Midi {
+                    channel: u4::new(0),
+                    message,
+                },
+            });
+            last = time;
+        }
+        track.push(TrackEvent {
+            delta: u28::new(0),
+            kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+        });
+
+        let mut smf = Smf::new(Header::new(
+            Format::SingleTrack,
+            Timing::Metrical(u15::new(500)),
+        ));
+        smf.tracks.push(track);
+        let path =
+            std::env::temp_dir().join(format!("wwm-fixture-{}-{}.mid", name, std::process::id()));
+        smf.save(&path).expect("write fixture");
+        let midi_data = crate::midi::load_midi(path.to_str().unwrap()).expect("load fixture");
+        let _ = std::fs::remove_file(&path);
+        midi_data
+    }
+
+    /// Play a song to the end through the engine, recording every key it sends
+    fn run(midi_data: MidiData, note_mode: NoteMode, key_mode: KeyMode) -> Arc<RecordingKeySink> {
+        let _turn = SINK_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let sink = Arc::new(RecordingKeySink::new());
+        let previous = set_key_sink(sink.clone());
+
+        let player = Player::spawn();
+        player.send(PlayerCommand::SetNoteMode(note_mode));
+        player.send(PlayerCommand::SetKeyMode(key_mode));
+        player
+            .call(PlayerCommand::Play {
+                midi_data: Box::new(midi_data),
+                start_at: 0.0,
+                path: None,
+                window: None,
+            })
+            .expect("engine accepts play");
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while player.snapshot().is_playing {
+            assert!(Instant::now() < deadline, "song never finished");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        set_key_sink(previous);
+        sink
+    }
+
+    /// Pressed keys with ms since the first press
+    fn play(midi_data: MidiData, note_mode: NoteMode, key_mode: KeyMode) -> Vec<(String, f64)> {
+        let presses = run(midi_data, note_mode, key_mode).presses();
+        let first = presses.first().map(|(_, at)| *at).unwrap_or_default();
+        presses
+            .into_iter()
+            .map(|(key, at)| (key, (at - first).as_secs_f64() * 1000.0))
+            .collect()
+    }
+
+    fn assert_plays(notes: &[u8], note_mode: NoteMode, key_mode: KeyMode, expected: &[&str]) {
+        let name = format!("{:?}-{:?}", note_mode, key_mode).to_lowercase();
+        let presses = play(fixture(&name, notes), note_mode, key_mode);
+        let keys: Vec<&str> = presses.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(keys, expected, "{:?} / {:?}", note_mode, key_mode);
+        // Wall-clock timing depends on how busy the machine is; only the order is checked
+        for pair in presses.windows(2) {
+            assert!(
+                pair[1].1 >= pair[0].1,
+                "{:?}: {} pressed before {}",
+                note_mode,
+                pair[1].0,
+                pair[0].0
+            );
+        }
+    }
+
+    // Both fixtures auto-transpose by -12 (ties go to the lowest shift), so they land
+    // on the low row. Smart has no per-song choice here and falls back to Python.
+    #[test]
+    fn keys21_plays_each_note_mode() {
+        let cases: [(NoteMode, [&str; 8]); 10] = [
+            (NoteMode::Closest, ["z", "x", "c", "v", "b", "n", "m", "a"]),
+            (NoteMode::Quantize, ["z", "x", "c", "v", "b", "n", "m", "a"]),
+            (
+                NoteMode::TransposeOnly,
+                ["z", "s", "d", "d", "g", "h", "j", "a"],
+            ),
+            (
+                NoteMode::Pentatonic,
+                ["z", "x", "c", "c", "b", "n", "n", "a"],
+            ),
+            (
+                NoteMode::Chromatic,
+                ["z", "x", "c", "v", "b", "n", "m", "a"],
+            ),
+            (NoteMode::Raw, ["t", "u", "x", "c", "b", "m", "s", "d"]),
+            (NoteMode::Python, ["z", "x", "c", "v", "b", "n", "m", "a"]),
+            (NoteMode::Wide, ["z", "x", "c", "v", "g", "h", "j", "a"]),
+            (NoteMode::Sharps, ["z", "x", "c", "v", "b", "n", "m", "a"]),
+            (NoteMode::Smart, ["z", "x", "c", "v", "b", "n", "m", "a"]),
+        ];
+        for (mode, expected) in cases {
+            assert_plays(&C_MAJOR, mode, KeyMode::Keys21, &expected);
+        }
+    }
+
+    #[test]
+    fn keys36_plays_each_note_mode() {
+        let cases: [(NoteMode, [&str; 12]); 10] = [
+            (
+                NoteMode::Closest,
+                [
+                    "z", "shift+z", "x", "ctrl+c", "c", "v", "shift+v", "b", "shift+b", "n",
+                    "ctrl+m", "m",
+                ],
+            ),
+            (
+                NoteMode::Quantize,
+                ["z", "z", "x", "x", "c", "v", "v", "b", "b", "n", "n", "m"],
+            ),
+            (
+                NoteMode::TransposeOnly,
+                [
+                    "z", "shift+z", "x", "ctrl+c", "c", "v", "shift+v", "b", "shift+b", "n",
+                    "ctrl+m", "m",
+                ],
+            ),
+            (
+                NoteMode::Pentatonic,
+                ["z", "z", "x", "x", "c", "c", "c", "b", "b", "n", "n", "n"],
+            ),
+            (
+                NoteMode::Chromatic,
+                [
+                    "z", "shift+z", "x", "ctrl+c", "c", "v", "shift+v", "b", "shift+b", "n",
+                    "ctrl+m", "m",
+                ],
+            ),
+            (
+                NoteMode::Raw,
+                [
+                    "q", "shift+q", "w", "ctrl+e", "e", "r", "shift+r", "t", "shift+t", "y",
+                    "ctrl+u", "u",
+                ],
+            ),
+            (
+                NoteMode::Python,
+                ["z", "z", "x", "x", "c", "v", "v", "b", "b", "n", "n", "m"],
+            ),
+            (
+                NoteMode::Wide,
+                [
+                    "z", "shift+z", "x", "ctrl+c", "c", "v", "shift+f", "g", "shift+g", "h",
+                    "ctrl+j", "j",
+                ],
+            ),
+            (
+                NoteMode::Sharps,
+                [
+                    "shift+z", "x", "ctrl+c", "c", "v", "shift+v", "b", "shift+b", "n", "ctrl+m",
+                    "m", "a",
+                ],
+            ),
+            (
+                NoteMode::Smart,
+                ["z", "z", "x", "x", "c", "v", "v", "b", "b", "n", "n", "m"],
+            ),
+        ];
+        for (mode, expected) in cases {
+            assert_plays(&CHROMATIC, mode, KeyMode::Keys36, &expected);
+        }
+    }
+
+    #[test]
+    fn every_press_is_released() {
+        let sink = run(
+            fixture("release", &C_MAJOR),
+            NoteMode::Python,
+            KeyMode::Keys36,
+        );
+        let mut held: Vec<String> = Vec::new();
+        for sent in sink.sent() {
+            if sent.down {
+                held.push(sent.key);
+            } else if let Some(i) = held.iter().position(|k| *k == sent.key) {
+                held.remove(i);
+            }
+        }
+        assert!(held.is_empty(), "keys left down: {:?}", held);
+    }
+}
@@ -0,0 +1,207 @@
+// Saved playlists, stored in playlists.json next to the exe.
+// Every edit is a locked read-modify-write in the backend so two windows editing
+// at once can't overwrite each other; changes are broadcast as "playlists-changed"
+
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlaylistTrack {
+    pub hash: String,
+    pub name: String,
+    // Resolved from the hash when listing; never written to disk
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    // Library fields the UI shows (duration, bpm, ...), kept as sent
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Playlist {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub tracks: Vec<PlaylistTrack>,
+    #[serde(default, rename = "createdAt")]
+    pub created_at: String,
+    // Any other fields the UI keeps on a playlist
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+lazy_static::lazy_static! {
+    static ref PLAYLISTS_LOCK: Mutex<()> = Mutex::new(());
+}
+
+fn load() -> Vec<Playlist> {
+    crate::get_data_path("playlists.json")
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save(playlists: &[Playlist]) -> Result<(), String> {
+    let mut stripped = playlists.to_vec();
+    for track in stripped.iter_mut().flat_map(|p| p.tracks.iter_mut()) {
+        track.path = None;
+    }
+    let content = serde_json::to_string_pretty(&stripped)
+        .map_err(|e| format!("Failed to serialize playlists: {}", e))?;
+    crate::data_watch::write("playlists.json", content)
+        .map_err(|e| format!("Failed to write playlists: {}", e))
+}
+
+/// One playlist as stored (track paths not resolved)
+pub fn get(id: &str) -> Option<Playlist> {
+    load().into_iter().find(|p| p.id == id)
+}
+
+/// All playlists with track paths resolved against the current library
+pub fn list() -> Vec<Playlist> {
+    let mut playlists = load();
+    let hashes = playlists
+        .iter()
+        .flat_map(|p| p.tracks.iter().map(|t| t.hash.clone()))
+        .collect();
+    let paths = crate::resolve_paths_by_hash(&hashes);
+    for track in playlists.iter_mut().flat_map(|p| p.tracks.iter_mut()) {
+        track.path = paths.get(&track.hash).cloned();
+    }
+    playlists
+}
+
+pub fn notify_changed(app: &AppHandle) {
+    let _ = app.emit("playlists-changed", list());
+}
+
+fn modify<T>(
+    app: &AppHandle,
+    apply: impl FnOnce(&mut Vec<Playlist>) -> Result<T, String>,
+) -> Result<T, String> {
+    let result = {
+        let _guard = PLAYLISTS_LOCK.lock().unwrap();
+        let mut playlists = load();
+        let result = apply(&mut playlists)?;
+        save(&playlists)?;
+        result
+    };
+    notify_changed(app);
+    Ok(result)
+}
+
+fn find<'a>(playlists: &'a mut [Playlist], id: &str) -> Result<&'a mut Playlist, String> {
+    playlists
+        .iter_mut()
+        .find(|p| p.id == id)
+        .ok_or_else(|| "Playlist not found".to_string())
+}
+
+// Millisecond timestamp ids, matching what the UI generated before
+fn new_id(playlists: &[Playlist]) -> String {
+    let mut id = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    while playlists.iter().any(|p| p.id == id.to_string()) {
+        id += 1;
+    }
+    id.to_string()
+}
+
+pub fn create(app: &AppHandle, name: &str) -> Result<Playlist, String> {
+    modify(app, |playlists| {
+        let playlist = Playlist {
+            id: new_id(playlists),
+            name: name.to_string(),
+            tracks: Vec::new(),
+            created_at: crate::chrono_now(),
+            extra: serde_json::Map::new(),
+        };
+        playlists.push(playlist.clone());
+        Ok(playlist)
+    })
+}
+
+pub fn rename(app: &AppHandle, id: &str, name: &str) -> Result<(), String> {
+    modify(app, |playlists| {
+        find(playlists, id)?.name = name.to_string();
+        Ok(())
+    })
+}
+
+pub fn delete(app: &AppHandle, id: &str) -> Result<(), String> {
+    modify(app, |playlists| {
+        let before = playlists.len();
+        playlists.retain(|p| p.id != id);
+        if playlists.len() == before {
+            return Err("Playlist not found".to_string());
+        }
+        Ok(())
+    })?;
+    // Stop auto-advancing through a playlist that no longer exists
+    if crate::queue::get_active_playlist().is_some_and(|a| a.id == id) {
+        crate::queue::set_active_playlist(None);
+    }
+    Ok(())
+}
+
+pub fn duplicate(app: &AppHandle, id: &str, name: Option<&str>) -> Result<Playlist, String> {
+    modify(app, |playlists| {
+        let source = find(playlists, id)?.clone();
+        let copy = Playlist {
+            id: new_id(playlists),
+            name: name
+                .map(String::from)
+                .unwrap_or_else(|| format!("{} (copy)", source.name)),
+            tracks: source.tracks,
+            created_at: crate::chrono_now(),
+            extra: source.extra,
+        };
+        playlists.push(copy.clone());
+        Ok(copy)
+    })
+}
+
+/// Append tracks, skipping ones already in the playlist. Returns how many were added.
+pub fn add_tracks(app: &AppHandle, id: &str, tracks: Vec<PlaylistTrack>) -> Result<usize, String> {
+    modify(app, |playlists| {
+        let playlist = find(playlists, id)?;
+        let before = playlist.tracks.len();
+        for track in tracks {
+            if !playlist.tracks.iter().any(|t| t.hash == track.hash) {
+                playlist.tracks.push(track);
+            }
+        }
+        Ok(playlist.tracks.len() - before)
+    })
+}
+
+pub fn remove_track(app: &AppHandle, id: &str, hash: &str) -> Result<(), String> {
+    modify(app, |playlists| {
+        find(playlists, id)?.tracks.retain(|t| t.hash != hash);
+        Ok(())
+    })
+}
+
+pub fn move_track(app: &AppHandle, id: &str, from: usize, to: usize) -> Result<(), String> {
+    modify(app, |playlists| {
+        let tracks = &mut find(playlists, id)?.tracks;
+        if from >= tracks.len() || to >= tracks.len() {
+            return Err("Track index out of range".to_string());
+        }
+        let track = tracks.remove(from);
+        tracks.insert(to, track);
+        Ok(())
+    })
+}
+
+/// Reorder playlists to match `ids`; playlists not listed keep their relative order at the end
+pub fn reorder(app: &AppHandle, ids: &[String]) -> Result<(), String> {
+    modify(app, |playlists| {
+        playlists.sort_by_key(|p| ids.iter().position(|id| *id == p.id).unwrap_or(usize::MAX));
+        Ok(())
+    })
+}
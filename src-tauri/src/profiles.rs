@@ -0,0 +1,139 @@
+// Game profiles: input settings that suit how the game is being run. A cloud streaming
+// client (GeForce Now) needs SendInput, keys held a little longer so the stream picks
+// them up, a modifier delay and the game focused; the local game needs none of that.
+// The profile follows the detected game window unless automatic switching is off.
+// The player's own settings are kept aside while the cloud profile is active and put
+// back when the local game shows up again.
+
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GameProfile {
+    Local,
+    Cloud,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputSettings {
+    pub send_input: bool,
+    pub key_duration_ms: u64,
+    pub modifier_delay_ms: u64,
+    pub require_game_window: bool, // Also pauses while unfocused in SendInput mode
+}
+
+impl InputSettings {
+    pub const CLOUD_RECOMMENDED: InputSettings = InputSettings {
+        send_input: true,
+        key_duration_ms: 30,
+        modifier_delay_ms: 20,
+        require_game_window: true,
+    };
+
+    fn current() -> Self {
+        InputSettings {
+            send_input: crate::keyboard::get_send_input_mode(),
+            key_duration_ms: crate::keyboard::get_key_duration(),
+            modifier_delay_ms: crate::keyboard::get_modifier_delay(),
+            require_game_window: crate::player::get_require_game_window(),
+        }
+    }
+
+    fn apply(&self) {
+        crate::keyboard::set_send_input_mode(self.send_input);
+        crate::keyboard::set_key_duration(self.key_duration_ms);
+        crate::keyboard::set_modifier_delay(self.modifier_delay_ms);
+        crate::player::set_require_game_window(self.require_game_window);
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileStatus {
+    pub auto: bool,
+    pub active: GameProfile,
+    pub cloud: InputSettings,
+    pub settings: InputSettings, // In effect right now
+}
+
+struct Profiles {
+    auto: bool,
+    active: GameProfile,
+    cloud: InputSettings,
+    local: Option<InputSettings>, // The player's settings, while the cloud profile is active
+}
+
+static PROFILES: Mutex<Profiles> = Mutex::new(Profiles {
+    auto: true,
+    active: GameProfile::Local,
+    cloud: InputSettings::CLOUD_RECOMMENDED,
+    local: None,
+});
+
+pub fn status() -> ProfileStatus {
+    let profiles = PROFILES.lock().unwrap();
+    ProfileStatus {
+        auto: profiles.auto,
+        active: profiles.active,
+        cloud: profiles.cloud.clone(),
+        settings: InputSettings::current(),
+    }
+}
+
+/// Saved choices, applied at startup before any window is seen
+pub fn load(auto: bool, cloud: Option<InputSettings>) {
+    let mut profiles = PROFILES.lock().unwrap();
+    profiles.auto = auto;
+    if let Some(cloud) = cloud {
+        profiles.cloud = cloud;
+    }
+}
+
+fn switch(profiles: &mut Profiles, to: GameProfile) {
+    match to {
+        GameProfile::Cloud => {
+            profiles.local = Some(InputSettings::current());
+            profiles.cloud.apply();
+        }
+        GameProfile::Local => {
+            if let Some(local) = profiles.local.take() {
+                local.apply();
+            }
+        }
+    }
+    profiles.active = to;
+    println!("[PROFILE] Switched to the {:?} profile", to);
+}
+
+/// Turning automatic switching off puts the player's own settings back
+pub fn set_auto(enabled: bool) {
+    let mut profiles = PROFILES.lock().unwrap();
+    profiles.auto = enabled;
+    if !enabled && profiles.active == GameProfile::Cloud {
+        switch(&mut profiles, GameProfile::Local);
+    }
+}
+
+/// Override the cloud bundle; takes effect right away if it's active
+pub fn set_cloud_settings(settings: InputSettings) {
+    let mut profiles = PROFILES.lock().unwrap();
+    if profiles.active == GameProfile::Cloud {
+        settings.apply();
+    }
+    profiles.cloud = settings;
+}
+
+/// The game window was (re)detected; returns the new profile if it switched
+pub fn on_game_window(cloud: bool) -> Option<GameProfile> {
+    let mut profiles = PROFILES.lock().unwrap();
+    let wanted = if cloud {
+        GameProfile::Cloud
+    } else {
+        GameProfile::Local
+    };
+    if !profiles.auto || profiles.active == wanted {
+        return None;
+    }
+    switch(&mut profiles, wanted);
+    Some(wanted)
+}
@@ -0,0 +1,147 @@
+// Backend playback queue
+// Shared by the UI and integrations (chat song requests) so songs can be
+// enqueued without the webview being in the loop
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueEntry {
+    pub path: String,
+    pub name: String,
+    #[serde(default)]
+    pub hash: String,
+    #[serde(default)]
+    pub requested_by: Option<String>, // Chat user for song requests, None for local adds
+    #[serde(default)]
+    pub source: String, // "local", "twitch", "webhook"
+}
+
+/// Playlist the backend advances through when a song ends
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivePlaylist {
+    pub id: String,
+    pub index: usize, // Track currently playing
+}
+
+// When enabled, the playback thread starts the next song itself instead of
+// leaving it to the webview's playback-ended handler
+static AUTO_ADVANCE: AtomicBool = AtomicBool::new(false);
+
+// Silence between auto-advanced songs, and whether to skip the next song's lead-in
+static SONG_GAP_MS: AtomicU64 = AtomicU64::new(0);
+static SONG_PICKUP: AtomicBool = AtomicBool::new(false);
+pub const MAX_SONG_GAP_MS: u64 = 10_000;
+
+// Bumped whenever playback is stopped or replaced, so a pending advance
+// waiting out the gap knows to give up
+static ADVANCE_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+lazy_static::lazy_static! {
+    static ref QUEUE: Mutex<Vec<QueueEntry>> = Mutex::new(Vec::new());
+    static ref ACTIVE_PLAYLIST: Mutex<Option<ActivePlaylist>> = Mutex::new(None);
+}
+
+pub fn set_auto_advance(enabled: bool) {
+    AUTO_ADVANCE.store(enabled, Ordering::SeqCst);
+}
+
+pub fn get_auto_advance() -> bool {
+    AUTO_ADVANCE.load(Ordering::SeqCst)
+}
+
+pub fn set_song_gap_ms(gap_ms: u64) {
+    SONG_GAP_MS.store(gap_ms.min(MAX_SONG_GAP_MS), Ordering::SeqCst);
+}
+
+pub fn get_song_gap_ms() -> u64 {
+    SONG_GAP_MS.load(Ordering::SeqCst)
+}
+
+pub fn set_song_pickup(enabled: bool) {
+    SONG_PICKUP.store(enabled, Ordering::SeqCst);
+}
+
+pub fn get_song_pickup() -> bool {
+    SONG_PICKUP.load(Ordering::SeqCst)
+}
+
+pub fn cancel_pending_advance() {
+    ADVANCE_GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+pub fn advance_generation() -> u64 {
+    ADVANCE_GENERATION.load(Ordering::SeqCst)
+}
+
+pub fn set_active_playlist(playlist: Option<ActivePlaylist>) {
+    *ACTIVE_PLAYLIST.lock().unwrap() = playlist;
+}
+
+pub fn get_active_playlist() -> Option<ActivePlaylist> {
+    ACTIVE_PLAYLIST.lock().unwrap().clone()
+}
+
+fn emit_changed(app: &AppHandle) {
+    let _ = app.emit("queue-changed", get_queue());
+}
+
+pub fn get_queue() -> Vec<QueueEntry> {
+    QUEUE.lock().unwrap().clone()
+}
+
+/// Append an entry and return its position in the queue
+pub fn enqueue(entry: QueueEntry, app: &AppHandle) -> usize {
+    let position = {
+        let mut queue = QUEUE.lock().unwrap();
+        queue.push(entry);
+        queue.len() - 1
+    };
+    emit_changed(app);
+    position
+}
+
+pub fn remove(index: usize, app: &AppHandle) -> Result<QueueEntry, String> {
+    let removed = {
+        let mut queue = QUEUE.lock().unwrap();
+        if index >= queue.len() {
+            return Err("Queue index out of range".to_string());
+        }
+        queue.remove(index)
+    };
+    emit_changed(app);
+    Ok(removed)
+}
+
+pub fn clear(app: &AppHandle) {
+    QUEUE.lock().unwrap().clear();
+    emit_changed(app);
+}
+
+/// Take the next entry off the front of the queue
+pub fn pop_next(app: &AppHandle) -> Option<QueueEntry> {
+    let next = {
+        let mut queue = QUEUE.lock().unwrap();
+        if queue.is_empty() {
+            None
+        } else {
+            Some(queue.remove(0))
+        }
+    };
+    if next.is_some() {
+        emit_changed(app);
+    }
+    next
+}
+
+/// Number of entries currently queued by a given requester
+pub fn count_requested_by(user: &str) -> usize {
+    QUEUE
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|e| e.requested_by.as_deref() == Some(user))
+        .count()
+}
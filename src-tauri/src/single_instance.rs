@@ -0,0 +1,106 @@
+// One running copy at a time. Two copies install two keyboard hooks and fight over the
+// global hotkeys, so the first one listens on a fixed localhost port and a second one
+// hands its command line (files to open) over that port and exits. If the port belongs
+// to something else the handshake fails and the app starts normally.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::path::Path;
+use std::time::Duration;
+
+const PORT: u16 = 47_219;
+const HELLO: &str = "wwm-midi-player/1";
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// What a second copy sent: its arguments, without the exe path
+pub type Handoff = Vec<String>;
+
+pub enum Instance {
+    First(TcpListener),
+    HandedOff, // Another copy is running and has the arguments; exit
+    Unguarded, // Couldn't tell; run without single-instance protection
+}
+
+/// Become the running instance, or hand `args` to the one already running
+pub fn acquire(args: &[String]) -> Instance {
+    match TcpListener::bind((Ipv4Addr::LOCALHOST, PORT)) {
+        Ok(listener) => Instance::First(listener),
+        Err(_) => match hand_off(args) {
+            Ok(()) => Instance::HandedOff,
+            Err(e) => {
+                crate::app_error!("[INSTANCE] Port {} is taken but not by us: {}", PORT, e);
+                Instance::Unguarded
+            }
+        },
+    }
+}
+
+/// Relative paths resolved against this copy's working directory; the running copy
+/// has its own and would look for them in the wrong folder
+fn absolute_args(args: &[String]) -> Vec<String> {
+    let Ok(cwd) = std::env::current_dir() else {
+        return args.to_vec();
+    };
+    args.iter()
+        .map(|arg| {
+            let path = Path::new(arg);
+            let joined = cwd.join(path);
+            if path.is_relative() && joined.exists() {
+                joined.to_string_lossy().to_string()
+            } else {
+                arg.clone()
+            }
+        })
+        .collect()
+}
+
+fn hand_off(args: &[String]) -> std::io::Result<()> {
+    let args = absolute_args(args);
+    let mut stream = TcpStream::connect_timeout(&(Ipv4Addr::LOCALHOST, PORT).into(), TIMEOUT)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    let message = serde_json::to_string(&args).map_err(std::io::Error::other)?;
+    writeln!(stream, "{}\n{}", HELLO, message)?;
+
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    if reply.trim() != HELLO {
+        return Err(std::io::Error::other("unexpected reply"));
+    }
+    println!(
+        "[INSTANCE] Already running, handed over {} arguments",
+        args.len()
+    );
+    Ok(())
+}
+
+fn read_handoff(stream: TcpStream) -> std::io::Result<Handoff> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut hello = String::new();
+    reader.read_line(&mut hello)?;
+    if hello.trim() != HELLO {
+        return Err(std::io::Error::other("not a hand-off"));
+    }
+    let mut message = String::new();
+    reader.read_line(&mut message)?;
+    let args = serde_json::from_str(&message).map_err(std::io::Error::other)?;
+    writeln!(&stream, "{}", HELLO)?;
+    Ok(args)
+}
+
+/// Accept hand-offs from later copies for as long as the app runs
+pub fn listen(listener: TcpListener, on_handoff: impl Fn(Handoff) + Send + 'static) {
+    let spawned = std::thread::Builder::new()
+        .name("single-instance".to_string())
+        .spawn(move || {
+            for stream in listener.incoming().flatten() {
+                match read_handoff(stream) {
+                    Ok(args) => on_handoff(args),
+                    Err(e) => println!("[INSTANCE] Ignored connection: {}", e),
+                }
+            }
+        });
+    if let Err(e) = spawned {
+        crate::app_error!("[INSTANCE] Failed to start instance listener: {}", e);
+    }
+}
@@ -0,0 +1,102 @@
+// User annotations and playback settings per song (tags, rating, note, options),
+// keyed by content hash so they survive renames. Stored in song_meta.json next to the exe
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+pub const MAX_RATING: u8 = 5;
+pub const MAX_NOTE_CHARS: usize = 2000;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SongMeta {
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub rating: Option<u8>, // 1-5 stars, None = unrated
+    #[serde(default)]
+    pub note: String, // Free text ("use Wide mode, +1 octave, skip intro")
+    #[serde(default)]
+    pub settings: crate::midi::SongSettings,
+}
+
+lazy_static::lazy_static! {
+    // Serializes read-modify-write of song_meta.json
+    static ref META_LOCK: Mutex<()> = Mutex::new(());
+}
+
+pub fn load_all() -> HashMap<String, SongMeta> {
+    crate::get_data_path("song_meta.json")
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_all(all: &HashMap<String, SongMeta>) -> Result<(), String> {
+    let path = crate::get_data_path("song_meta.json")?;
+    let content = serde_json::to_string_pretty(all)
+        .map_err(|e| format!("Failed to serialize song metadata: {}", e))?;
+    std::fs::write(&path, content).map_err(|e| format!("Failed to write song metadata: {}", e))
+}
+
+pub fn get(hash: &str) -> SongMeta {
+    load_all().remove(hash).unwrap_or_default()
+}
+
+fn update(hash: &str, apply: impl FnOnce(&mut SongMeta)) -> Result<SongMeta, String> {
+    let _guard = META_LOCK.lock().unwrap();
+    let mut all = load_all();
+    let meta = all.entry(hash.to_string()).or_default();
+    apply(meta);
+    let updated = meta.clone();
+    // Drop entries that no longer carry anything
+    if updated.tags.is_empty()
+        && updated.rating.is_none()
+        && updated.note.is_empty()
+        && updated.settings == crate::midi::SongSettings::default()
+    {
+        all.remove(hash);
+    }
+    save_all(&all)?;
+    Ok(updated)
+}
+
+pub fn set_rating(hash: &str, rating: Option<u8>) -> Result<SongMeta, String> {
+    let rating = rating.filter(|r| *r > 0).map(|r| r.min(MAX_RATING));
+    update(hash, |meta| meta.rating = rating)
+}
+
+/// Replace the song's note; trimmed and cut at MAX_NOTE_CHARS, empty clears it
+pub fn set_note(hash: &str, note: &str) -> Result<SongMeta, String> {
+    let note: String = note.trim().chars().take(MAX_NOTE_CHARS).collect();
+    update(hash, |meta| meta.note = note)
+}
+
+/// Take settings, note and tags from a shared package; the local rating is kept and
+/// tags are added to the song's own
+pub fn import(hash: &str, incoming: SongMeta) -> Result<SongMeta, String> {
+    let mut tags = get(hash).tags;
+    tags.extend(incoming.tags);
+    set_tags(hash, tags)?;
+    update(hash, |meta| {
+        meta.settings = incoming.settings;
+        meta.note = incoming.note.trim().chars().take(MAX_NOTE_CHARS).collect();
+    })
+}
+
+/// Replace the song's tags (trimmed, empty and duplicate tags removed)
+pub fn set_tags(hash: &str, tags: Vec<String>) -> Result<SongMeta, String> {
+    let mut cleaned: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim().to_string();
+        if !tag.is_empty() && !cleaned.iter().any(|t| t.eq_ignore_ascii_case(&tag)) {
+            cleaned.push(tag);
+        }
+    }
+    update(hash, |meta| meta.tags = cleaned)
+}
+
+pub fn set_settings(hash: &str, settings: crate::midi::SongSettings) -> Result<SongMeta, String> {
+    update(hash, |meta| meta.settings = settings)
+}
@@ -0,0 +1,428 @@
+// Chat song requests (opt-in)
+// Listens to Twitch chat (anonymous IRC) and/or a local webhook endpoint,
+// matches "!sr <name>" against the album and enqueues the best match
+
+use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+use tokio::sync::broadcast;
+
+use crate::queue::{self, QueueEntry};
+
+const TWITCH_IRC_ADDR: &str = "irc.chat.twitch.tv:6667";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SongRequestConfig {
+    pub enabled: bool,
+    pub twitch_channel: String,     // Empty = Twitch disabled
+    pub webhook_port: Option<u16>,  // None = webhook disabled
+    pub command: String,            // Default: "!sr"
+    pub user_cooldown_secs: u64,    // Minimum time between requests per user
+    pub max_queued_per_user: usize, // 0 = unlimited
+}
+
+impl Default for SongRequestConfig {
+    fn default() -> Self {
+        SongRequestConfig {
+            enabled: false,
+            twitch_channel: String::new(),
+            webhook_port: None,
+            command: "!sr".to_string(),
+            user_cooldown_secs: 60,
+            max_queued_per_user: 2,
+        }
+    }
+}
+
+/// Result of a single chat request, emitted to the UI as "song-request"
+#[derive(Debug, Clone, Serialize)]
+pub struct SongRequestEvent {
+    pub user: String,
+    pub query: String,
+    pub source: String,
+    pub accepted: bool,
+    pub song: Option<String>,
+    pub reason: Option<String>,
+    #[serde(skip)]
+    pub rejection: Option<Rejection>, // Why it was turned down, for the webhook status code
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    RateLimited, // Cooldown or per-user queue limit
+    NotFound,    // Nothing in the library matched
+    Failed,      // The library search itself failed
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SongRequestStatus {
+    pub running: bool,
+    pub twitch_connected: bool,
+    pub webhook_running: bool,
+}
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+static TWITCH_CONNECTED: AtomicBool = AtomicBool::new(false);
+static WEBHOOK_RUNNING: AtomicBool = AtomicBool::new(false);
+// Bumped on every start and stop, so a Twitch thread still waiting out a read or the
+// back-off after stop() gives up instead of running alongside the next one
+static TWITCH_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+lazy_static::lazy_static! {
+    static ref LAST_REQUEST: Mutex<HashMap<String, Instant>> = Mutex::new(HashMap::new());
+    static ref WEBHOOK_SHUTDOWN: Mutex<Option<broadcast::Sender<()>>> = Mutex::new(None);
+}
+
+pub fn load_config() -> SongRequestConfig {
+    let config = crate::load_config();
+    config
+        .get("song_requests")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_config(cfg: &SongRequestConfig) {
+    let mut config = crate::load_config();
+    config["song_requests"] = serde_json::to_value(cfg).unwrap_or_default();
+    crate::save_config(&config);
+}
+
+pub fn get_status() -> SongRequestStatus {
+    SongRequestStatus {
+        running: RUNNING.load(Ordering::SeqCst),
+        twitch_connected: TWITCH_CONNECTED.load(Ordering::SeqCst),
+        webhook_running: WEBHOOK_RUNNING.load(Ordering::SeqCst),
+    }
+}
+
+/// Extract the query from a chat line if it starts with the request command
+fn parse_command<'a>(message: &'a str, command: &str) -> Option<&'a str> {
+    let message = message.trim();
+    let rest = message.strip_prefix(command)?;
+    // Require a separator so "!srfoo" doesn't count as "!sr foo"
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let query = rest.trim();
+    if query.is_empty() {
+        None
+    } else {
+        Some(query)
+    }
+}
+
+/// Check per-user rate limits, then search the library and enqueue the best match
+fn handle_request(
+    cfg: &SongRequestConfig,
+    user: &str,
+    query: &str,
+    source: &str,
+    app: &AppHandle,
+) -> SongRequestEvent {
+    let user_key = user.to_lowercase();
+
+    let reject = |rejection: Rejection, reason: String| SongRequestEvent {
+        user: user.to_string(),
+        query: query.to_string(),
+        source: source.to_string(),
+        accepted: false,
+        song: None,
+        reason: Some(reason),
+        rejection: Some(rejection),
+    };
+
+    // Check and reserve the user's slot in one go, so two requests arriving together
+    // can't both get past the cooldown while the first is still searching
+    let previous = {
+        let mut last = LAST_REQUEST.lock().unwrap();
+        if let Some(t) = last.get(&user_key) {
+            let cooldown = Duration::from_secs(cfg.user_cooldown_secs);
+            if t.elapsed() < cooldown {
+                let wait = (cooldown - t.elapsed()).as_secs();
+                return reject(
+                    Rejection::RateLimited,
+                    format!("Cooldown active ({}s left)", wait),
+                );
+            }
+        }
+
+        if cfg.max_queued_per_user > 0
+            && queue::count_requested_by(&user_key) >= cfg.max_queued_per_user
+        {
+            return reject(
+                Rejection::RateLimited,
+                "Too many songs already queued".to_string(),
+            );
+        }
+        last.insert(user_key.clone(), Instant::now())
+    };
+    // A request that doesn't go through doesn't count toward the cooldown
+    let release = || {
+        let mut last = LAST_REQUEST.lock().unwrap();
+        match previous {
+            Some(t) => last.insert(user_key.clone(), t),
+            None => last.remove(&user_key),
+        };
+    };
+
+    let best = match crate::search_album(query, 1) {
+        Ok(results) => results.into_iter().next(),
+        Err(e) => {
+            release();
+            return reject(Rejection::Failed, e);
+        }
+    };
+
+    match best {
+        Some(file) => {
+            queue::enqueue(
+                QueueEntry {
+                    path: file.path,
+                    name: file.name.clone(),
+                    hash: file.hash,
+                    requested_by: Some(user_key),
+                    source: source.to_string(),
+                },
+                app,
+            );
+            crate::app_log!("[SR] {} requested '{}' -> {}", user, query, file.name);
+            SongRequestEvent {
+                user: user.to_string(),
+                query: query.to_string(),
+                source: source.to_string(),
+                accepted: true,
+                song: Some(file.name),
+                reason: None,
+                rejection: None,
+            }
+        }
+        None => {
+            release();
+            reject(Rejection::NotFound, "No matching song".to_string())
+        }
+    }
+}
+
+fn process_chat_line(
+    user: &str,
+    message: &str,
+    source: &str,
+    app: &AppHandle,
+) -> Option<SongRequestEvent> {
+    let cfg = load_config();
+    let query = parse_command(message, &cfg.command)?;
+    let event = handle_request(&cfg, user, query, source, app);
+    let _ = app.emit("song-request", &event);
+    Some(event)
+}
+
+// ============ Twitch IRC ============
+
+/// Parse a Twitch PRIVMSG line into (user, message)
+/// Format: ":user!user@user.tmi.twitch.tv PRIVMSG #channel :message"
+fn parse_privmsg(line: &str) -> Option<(&str, &str)> {
+    let rest = line.strip_prefix(':')?;
+    let (prefix, rest) = rest.split_once(' ')?;
+    let rest = rest.strip_prefix("PRIVMSG ")?;
+    let (_, message) = rest.split_once(" :")?;
+    let user = prefix.split('!').next()?;
+    Some((user, message))
+}
+
+/// Log in anonymously and handle chat lines until the connection drops or we stop
+fn read_chat(mut stream: TcpStream, channel: &str, app: &AppHandle, generation: u64) {
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(1)));
+    // Anonymous read-only login (justinfan nicks need no OAuth token)
+    let login = format!(
+        "PASS SCHMOOPIIE\r\nNICK justinfan{}\r\nJOIN #{}\r\n",
+        std::process::id() % 100_000,
+        channel
+    );
+    if let Err(e) = stream.write_all(login.as_bytes()) {
+        crate::app_error!("[SR] Twitch login failed: {}", e);
+        return;
+    }
+    TWITCH_CONNECTED.store(true, Ordering::SeqCst);
+    emit_status(app);
+    crate::app_log!("[SR] Connected to Twitch chat #{}", channel);
+
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    while twitch_current(generation) {
+        line.clear();
+        match reader.read_line(&mut line) {
+            Ok(0) => break, // Connection closed
+            Ok(_) => {
+                let msg = line.trim_end();
+                if let Some(payload) = msg.strip_prefix("PING") {
+                    let _ = reader
+                        .get_mut()
+                        .write_all(format!("PONG{}\r\n", payload).as_bytes());
+                } else if let Some((user, message)) = parse_privmsg(msg) {
+                    process_chat_line(user, message, "twitch", app);
+                }
+            }
+            Err(ref e)
+                if e.kind() == std::io::ErrorKind::WouldBlock
+                    || e.kind() == std::io::ErrorKind::TimedOut => {}
+            Err(_) => break,
+        }
+    }
+
+    // After a quick stop and start the next thread owns the flag
+    if !RUNNING.load(Ordering::SeqCst) || twitch_current(generation) {
+        TWITCH_CONNECTED.store(false, Ordering::SeqCst);
+        emit_status(app);
+    }
+}
+
+fn twitch_current(generation: u64) -> bool {
+    RUNNING.load(Ordering::SeqCst) && TWITCH_GENERATION.load(Ordering::SeqCst) == generation
+}
+
+fn run_twitch(channel: String, app: AppHandle, generation: u64) {
+    let channel = channel.trim_start_matches('#').to_lowercase();
+
+    while twitch_current(generation) {
+        match TcpStream::connect(TWITCH_IRC_ADDR) {
+            Ok(stream) => read_chat(stream, &channel, &app, generation),
+            Err(e) => {
+                crate::app_error!("[SR] Twitch connect failed: {}", e);
+            }
+        }
+
+        // Back off before reconnecting
+        for _ in 0..10 {
+            if !twitch_current(generation) {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(500));
+        }
+    }
+}
+
+fn emit_status(app: &AppHandle) {
+    let _ = app.emit("song-request-status", get_status());
+}
+
+// ============ Webhook ============
+
+#[derive(Debug, Deserialize)]
+struct WebhookRequest {
+    user: String,
+    message: String, // Raw chat line, e.g. "!sr moonlight"
+}
+
+async fn webhook_handler(
+    State(app): State<AppHandle>,
+    Json(req): Json<WebhookRequest>,
+) -> (StatusCode, Json<Option<SongRequestEvent>>) {
+    let event = tokio::task::spawn_blocking(move || {
+        process_chat_line(&req.user, &req.message, "webhook", &app)
+    })
+    .await
+    .ok()
+    .flatten();
+
+    match event {
+        Some(e) if e.accepted => (StatusCode::OK, Json(Some(e))),
+        Some(e) => {
+            let status = match e.rejection {
+                Some(Rejection::RateLimited) => StatusCode::TOO_MANY_REQUESTS,
+                Some(Rejection::NotFound) => StatusCode::NOT_FOUND,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            (status, Json(Some(e)))
+        }
+        None => (StatusCode::BAD_REQUEST, Json(None)),
+    }
+}
+
+async fn run_webhook(port: u16, app: AppHandle) -> Result<(), String> {
+    let (tx, mut rx) = broadcast::channel::<()>(1);
+    *WEBHOOK_SHUTDOWN.lock().unwrap() = Some(tx);
+
+    let router = Router::new()
+        .route("/request", post(webhook_handler))
+        .with_state(app.clone());
+
+    // Local only - chat bots forward to us on the same machine
+    let addr = format!("127.0.0.1:{}", port);
+    let listener = tokio::net::TcpListener::bind(&addr)
+        .await
+        .map_err(|e| format!("Failed to bind: {}", e))?;
+
+    WEBHOOK_RUNNING.store(true, Ordering::SeqCst);
+    emit_status(&app);
+    crate::app_log!("[SR] Webhook listening on {}", addr);
+
+    let result = axum::serve(listener, router)
+        .with_graceful_shutdown(async move {
+            let _ = rx.recv().await;
+        })
+        .await
+        .map_err(|e| format!("Webhook error: {}", e));
+
+    WEBHOOK_RUNNING.store(false, Ordering::SeqCst);
+    emit_status(&app);
+    result
+}
+
+// ============ Lifecycle ============
+
+pub fn start(app: AppHandle) -> Result<(), String> {
+    let cfg = load_config();
+    if !cfg.enabled {
+        return Err("Song requests are disabled".to_string());
+    }
+    if cfg.twitch_channel.trim().is_empty() && cfg.webhook_port.is_none() {
+        return Err("No Twitch channel or webhook port configured".to_string());
+    }
+    if RUNNING.swap(true, Ordering::SeqCst) {
+        return Err("Song requests already running".to_string());
+    }
+    let generation = TWITCH_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+
+    if !cfg.twitch_channel.trim().is_empty() {
+        let channel = cfg.twitch_channel.clone();
+        let app_twitch = app.clone();
+        std::thread::spawn(move || run_twitch(channel, app_twitch, generation));
+    }
+
+    if let Some(port) = cfg.webhook_port {
+        let app_webhook = app.clone();
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = run_webhook(port, app_webhook).await {
+                crate::app_error!("[SR] {}", e);
+            }
+        });
+    }
+
+    emit_status(&app);
+    Ok(())
+}
+
+pub fn stop(app: &AppHandle) {
+    RUNNING.store(false, Ordering::SeqCst);
+    TWITCH_GENERATION.fetch_add(1, Ordering::SeqCst);
+    if let Some(tx) = WEBHOOK_SHUTDOWN.lock().unwrap().take() {
+        let _ = tx.send(());
+    }
+    LAST_REQUEST.lock().unwrap().clear();
+    emit_status(app);
+}
+
+/// Start automatically on launch if the user opted in
+pub fn autostart(app: AppHandle) {
+    if load_config().enabled {
+        if let Err(e) = start(app) {
+            crate::app_error!("[SR] Autostart failed: {}", e);
+        }
+    }
+}
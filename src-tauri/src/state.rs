@@ -1,11 +1,12 @@
 use serde::{Deserialize, Serialize};
-use std::sync::atomic::{AtomicBool, AtomicI8, AtomicU16, AtomicU8, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicI8, AtomicU8, Ordering};
 use std::sync::Arc;
-use std::time::Instant;
-use tauri::Window;
+use tauri::{Emitter, Window};
 
-use crate::midi::{BandFilter, EventType, KeyMode, NoteMode};
+use crate::error::AppError;
+use crate::midi::{BandFilter, EventType, KeyMode, NoteMode, SongSettings};
 use crate::midi_input::MidiInputState;
+use crate::player::{BlockReason, PlaybackBlocked, Player, PlayerCommand};
 
 /// Note event for visualizer (simplified for frontend)
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,7 +17,7 @@ pub struct VisualizerNote {
     pub key_index: u8,    // Key index (0-20 for 21 keys)
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PlaybackState {
     pub is_playing: bool,
     pub is_paused: bool,
@@ -24,28 +25,62 @@ pub struct PlaybackState {
     pub total_duration: f64,
     pub current_file: Option<String>,
     pub loop_mode: bool,
+    pub loop_count: u16,      // Total plays before stopping (0 = loop forever)
+    pub loops_completed: u16, // Finished plays of the current song
     pub note_mode: NoteMode,
     pub key_mode: KeyMode,
     pub octave_shift: i8,
     pub speed: f64,
+    #[serde(default)]
+    pub rehearsal: bool, // Silent rehearsal: no game window, so no keys are sent
+    #[serde(default)]
+    pub blocked: Option<crate::player::BlockReason>, // Paused by the game window guard
+}
+
+/// A parsed song, ready for `AppState::install_midi`
+pub struct PreparedMidi {
+    pub path: String,
+    pub hash: Option<String>,
+    pub midi_data: crate::midi::MidiData,
+    pub octave_suggestion: crate::midi::OctaveSuggestion,
+}
+
+/// Parse a song without touching the app state, so it can run off the state lock
+pub fn prepare_midi(path: &str) -> Result<PreparedMidi, String> {
+    let hash = crate::compute_file_hash(std::path::Path::new(path));
+    let midi_data = crate::midi::load_midi_for(path, hash.as_deref())?;
+    let octave_suggestion = crate::midi::suggest_octave_shift(&midi_data);
+    Ok(PreparedMidi {
+        path: path.to_string(),
+        hash,
+        midi_data,
+        octave_suggestion,
+    })
 }
 
 pub struct AppState {
-    is_playing: Arc<AtomicBool>,
-    is_paused: Arc<AtomicBool>,
-    loop_mode: Arc<AtomicBool>,
+    // Playback runs on the engine thread (see player.rs); this side keeps what the
+    // UI reads and forwards every change as a command
+    player: Player,
+    loop_mode: bool,
+    loop_count: u16,
+    // Shared with live MIDI input
     note_mode: Arc<AtomicU8>,
     key_mode: Arc<AtomicU8>,
     octave_shift: Arc<AtomicI8>,
-    speed: Arc<AtomicU16>, // Stored as speed * 100 (e.g., 100 = 1.0x, 50 = 0.5x)
-    current_position: Arc<std::sync::Mutex<f64>>,
-    total_duration: Arc<std::sync::Mutex<f64>>,
-    current_file: Arc<std::sync::Mutex<Option<String>>>,
-    playback_start: Arc<std::sync::Mutex<Option<Instant>>>,
-    midi_data: Arc<std::sync::Mutex<Option<crate::midi::MidiData>>>,
-    seek_offset: Arc<std::sync::Mutex<f64>>,
+    speed: f64,
+    total_duration: f64,
+    current_file: Option<String>,
+    midi_data: Option<crate::midi::MidiData>,
+    seek_offset: f64,      // Where the next start_playback begins
+    stopped_position: f64, // Position reported while nothing plays
     // Band mode filter
-    band_filter: Arc<std::sync::Mutex<Option<BandFilter>>>,
+    band_filter: Option<BandFilter>,
+    // Per-song options of the loaded file
+    song_settings: SongSettings,
+    current_hash: Option<String>,
+    // Tracks muted for now (auditioning), on top of the song's saved ones
+    track_mutes: std::collections::BTreeSet<usize>,
     // Live MIDI input state
     pub midi_input_state: Arc<std::sync::Mutex<MidiInputState>>,
     pub is_live_mode_active: Arc<AtomicBool>,
@@ -55,20 +90,22 @@ pub struct AppState {
 impl AppState {
     pub fn new() -> Self {
         AppState {
-            is_playing: Arc::new(AtomicBool::new(false)),
-            is_paused: Arc::new(AtomicBool::new(false)),
-            loop_mode: Arc::new(AtomicBool::new(false)),
+            player: Player::spawn(),
+            loop_mode: false,
+            loop_count: 0,
             note_mode: Arc::new(AtomicU8::new(NoteMode::Python as u8)),
             key_mode: Arc::new(AtomicU8::new(KeyMode::Keys21 as u8)),
             octave_shift: Arc::new(AtomicI8::new(0)),
-            speed: Arc::new(AtomicU16::new(100)), // Default 1.0x speed
-            current_position: Arc::new(std::sync::Mutex::new(0.0)),
-            total_duration: Arc::new(std::sync::Mutex::new(0.0)),
-            current_file: Arc::new(std::sync::Mutex::new(None)),
-            playback_start: Arc::new(std::sync::Mutex::new(None)),
-            midi_data: Arc::new(std::sync::Mutex::new(None)),
-            seek_offset: Arc::new(std::sync::Mutex::new(0.0)),
-            band_filter: Arc::new(std::sync::Mutex::new(None)),
+            speed: 1.0, // Default 1.0x speed
+            total_duration: 0.0,
+            current_file: None,
+            midi_data: None,
+            seek_offset: 0.0,
+            stopped_position: 0.0,
+            band_filter: None,
+            song_settings: SongSettings::default(),
+            current_hash: None,
+            track_mutes: std::collections::BTreeSet::new(),
             // Live MIDI input
             midi_input_state: Arc::new(std::sync::Mutex::new(MidiInputState::new())),
             is_live_mode_active: Arc::new(AtomicBool::new(false)),
@@ -123,79 +160,188 @@ impl AppState {
         } else {
             None
         };
-        *self.band_filter.lock().unwrap() = filter;
+        self.band_filter = filter.clone();
+        self.player.send(PlayerCommand::SetBandFilter(filter));
+    }
+
+    /// Shift the loaded song's events for band latency compensation (see band.rs)
+    pub fn apply_band_offset(&mut self, offset_ms: i64) {
+        if let Some(midi_data) = self.midi_data.as_mut() {
+            midi_data.shift_events(offset_ms);
+            self.total_duration = midi_data.duration;
+        }
     }
 
     #[allow(dead_code)]
     pub fn clear_band_filter(&mut self) {
-        *self.band_filter.lock().unwrap() = None;
+        self.band_filter = None;
+        self.player.send(PlayerCommand::SetBandFilter(None));
     }
 
     pub fn load_midi(&mut self, path: &str) -> Result<(), String> {
-        let midi_data = crate::midi::load_midi(path)?;
+        self.install_midi(prepare_midi(path)?);
+        Ok(())
+    }
 
-        *self.total_duration.lock().unwrap() = midi_data.duration;
-        *self.current_file.lock().unwrap() = Some(path.to_string());
-        *self.midi_data.lock().unwrap() = Some(midi_data);
+    /// Make a song parsed with `prepare_midi` the loaded one
+    pub fn install_midi(&mut self, prepared: PreparedMidi) {
+        let PreparedMidi {
+            path,
+            hash,
+            midi_data,
+            octave_suggestion,
+        } = prepared;
+
+        crate::midi::set_song_centroid(crate::midi::pitch_centroid(&midi_data));
+        self.total_duration = midi_data.duration;
+        self.current_file = Some(path);
+        self.song_settings = hash
+            .as_deref()
+            .map(|h| crate::song_meta::get(h).settings)
+            .unwrap_or_default();
+        self.current_hash = hash;
+        self.midi_data = Some(midi_data);
+        if self.get_note_mode() == NoteMode::Smart {
+            self.ensure_smart_choice();
+        }
+        if crate::midi::get_auto_octave_shift() {
+            self.set_octave_shift(octave_suggestion.shift);
+        }
+        self.player
+            .send(PlayerCommand::SetSongSettings(self.song_settings.clone()));
+        // Track numbers mean something else in the next song
+        if !self.track_mutes.is_empty() {
+            self.set_track_mutes(Vec::new());
+        }
         // Reset seek offset and position for new song
-        *self.seek_offset.lock().unwrap() = 0.0;
-        *self.current_position.lock().unwrap() = 0.0;
+        self.seek_offset = 0.0;
+        self.stopped_position = 0.0;
+    }
 
-        Ok(())
+    pub fn start_playback(&mut self, window: Window) -> Result<(), AppError> {
+        let midi_data = self
+            .midi_data
+            .clone()
+            .ok_or_else(AppError::no_song_loaded)?;
+        if crate::player::get_require_game_window() && !crate::keyboard::refresh_game_window() {
+            let blocked = PlaybackBlocked::from(BlockReason::GameNotFound);
+            let _ = window.emit("playback-blocked", &blocked);
+            return Err(AppError::GameWindowNotFound(blocked.message));
+        }
+        if let Some(ref path) = self.current_file {
+            crate::history::record_start(path, midi_data.duration);
+        }
+        Ok(self.player.call(PlayerCommand::Play {
+            midi_data: Box::new(midi_data),
+            start_at: self.seek_offset,
+            path: self.current_file.clone(),
+            window: Some(window),
+        })?)
     }
 
-    pub fn start_playback(&mut self, window: Window) -> Result<(), String> {
-        if let Some(midi_data) = self.midi_data.lock().unwrap().clone() {
-            self.is_playing.store(true, Ordering::SeqCst);
-            self.is_paused.store(false, Ordering::SeqCst);
-            let offset = *self.seek_offset.lock().unwrap();
-            *self.playback_start.lock().unwrap() = Some(Instant::now());
-            *self.current_position.lock().unwrap() = offset;
-
-            // Clone Arc references for the thread
-            let is_playing = Arc::clone(&self.is_playing);
-            let is_paused = Arc::clone(&self.is_paused);
-            let loop_mode = Arc::clone(&self.loop_mode);
-            let note_mode = Arc::clone(&self.note_mode);
-            let key_mode = Arc::clone(&self.key_mode);
-            let octave_shift = Arc::clone(&self.octave_shift);
-            let speed = Arc::clone(&self.speed);
-            let current_position = Arc::clone(&self.current_position);
-            let seek_offset = Arc::clone(&self.seek_offset);
-            // Pass Arc reference for live track switching
-            let band_filter = Arc::clone(&self.band_filter);
-
-            std::thread::spawn(move || {
-                crate::midi::play_midi(
-                    midi_data,
-                    is_playing,
-                    is_paused,
-                    loop_mode,
-                    note_mode,
-                    key_mode,
-                    octave_shift,
-                    speed,
-                    current_position,
-                    seek_offset,
-                    band_filter,
-                    window,
-                );
-            });
+    pub fn get_band_filter(&self) -> Option<BandFilter> {
+        self.band_filter.clone()
+    }
+
+    /// Apply new per-song settings right away if they belong to the loaded song
+    pub fn update_song_settings_live(&mut self, hash: &str, settings: &SongSettings) {
+        if self.current_hash.as_deref() == Some(hash) {
+            self.song_settings = settings.clone();
+            self.player
+                .send(PlayerCommand::SetSongSettings(settings.clone()));
+        }
+    }
 
-            Ok(())
+    /// Shift one track of the loaded song by some semitones (0 clears it); saved with the song
+    pub fn set_track_transpose(&mut self, track_id: usize, semitones: i32) -> Result<(), String> {
+        let semitones = semitones.clamp(
+            -crate::midi::MAX_TRACK_TRANSPOSE,
+            crate::midi::MAX_TRACK_TRANSPOSE,
+        );
+        let mut settings = self.song_settings.clone();
+        if semitones == 0 {
+            settings.track_transpose.remove(&track_id);
         } else {
-            Err("No MIDI file loaded".to_string())
+            settings.track_transpose.insert(track_id, semitones);
         }
+        if let Some(hash) = self.current_hash.as_deref() {
+            crate::song_meta::set_settings(hash, settings.clone())?;
+        }
+        self.song_settings = settings.clone();
+        self.player.send(PlayerCommand::SetSongSettings(settings));
+        Ok(())
     }
 
-    /// Update band filter live during playback
-    pub fn update_band_filter_live(&self, track_id: Option<usize>) {
-        let filter = track_id.map(|id| BandFilter::Track { track_id: id });
-        *self.band_filter.lock().unwrap() = filter;
+    /// Tracks the loaded song never plays in solo playback; saved with the song
+    pub fn set_muted_tracks(&mut self, track_ids: Vec<usize>) -> Result<(), String> {
+        let mut settings = self.song_settings.clone();
+        settings.muted_tracks = track_ids.into_iter().collect();
+        if let Some(hash) = self.current_hash.as_deref() {
+            crate::song_meta::set_settings(hash, settings.clone())?;
+        }
+        self.song_settings = settings.clone();
+        self.player.send(PlayerCommand::SetSongSettings(settings));
+        Ok(())
+    }
+
+    /// Mute tracks for now, applied from the next note; not saved with the song
+    pub fn set_track_mutes(&mut self, track_ids: Vec<usize>) {
+        self.track_mutes = track_ids.into_iter().collect();
+        self.player
+            .send(PlayerCommand::SetTrackMutes(self.track_mutes.clone()));
+    }
+
+    pub fn get_track_mutes(&self) -> Vec<usize> {
+        self.track_mutes.iter().copied().collect()
+    }
+
+    /// Update band filter live during playback (any mode, or None to play everything).
+    /// The player applies it from the next note.
+    pub fn update_band_filter_live(&mut self, filter: Option<BandFilter>) -> Result<(), String> {
+        if let Some(BandFilter::Split {
+            slot,
+            total_players,
+        }) = &filter
+        {
+            if *total_players == 0 || slot >= total_players {
+                return Err("Split slot must be below the number of players".to_string());
+            }
+        }
+        self.band_filter = filter.clone();
+        self.player.send(PlayerCommand::SetBandFilter(filter));
+        Ok(())
     }
 
     pub fn set_note_mode(&mut self, mode: NoteMode) {
         self.note_mode.store(mode as u8, Ordering::SeqCst);
+        self.player.send(PlayerCommand::SetNoteMode(mode));
+        if mode == NoteMode::Smart {
+            self.ensure_smart_choice();
+        }
+    }
+
+    /// Analyze the loaded song for Smart mode unless its choice is already saved
+    fn ensure_smart_choice(&mut self) {
+        if self.song_settings.smart_mode.is_some() {
+            return;
+        }
+        let choice = match self.midi_data.as_ref() {
+            Some(midi_data) => crate::midi::pick_smart_modes(midi_data),
+            None => return,
+        };
+        crate::app_log!(
+            "[SMART] Picked {:?} (21-key) / {:?} (36-key)",
+            choice.keys21,
+            choice.keys36
+        );
+        self.song_settings.smart_mode = Some(choice);
+        if let Some(hash) = self.current_hash.as_deref() {
+            if let Err(e) = crate::song_meta::set_settings(hash, self.song_settings.clone()) {
+                crate::app_error!("[SMART] Failed to save choice: {}", e);
+            }
+        }
+        self.player
+            .send(PlayerCommand::SetSongSettings(self.song_settings.clone()));
     }
 
     pub fn get_note_mode(&self) -> NoteMode {
@@ -204,6 +350,8 @@ impl AppState {
 
     pub fn set_key_mode(&mut self, mode: KeyMode) {
         self.key_mode.store(mode as u8, Ordering::SeqCst);
+        crate::keyboard::set_binding_key_mode(mode);
+        self.player.send(PlayerCommand::SetKeyMode(mode));
     }
 
     pub fn get_key_mode(&self) -> KeyMode {
@@ -214,6 +362,7 @@ impl AppState {
         // Clamp to -2 to +2 octaves
         let clamped = shift.clamp(-2, 2);
         self.octave_shift.store(clamped, Ordering::SeqCst);
+        self.player.send(PlayerCommand::SetOctaveShift(clamped));
     }
 
     pub fn get_octave_shift(&self) -> i8 {
@@ -221,91 +370,144 @@ impl AppState {
     }
 
     pub fn set_speed(&mut self, speed: f64) {
-        // Clamp to 0.25x - 2.0x range, store as integer (speed * 100)
-        let clamped = (speed.clamp(0.25, 2.0) * 100.0) as u16;
-        self.speed.store(clamped, Ordering::SeqCst);
+        // Clamp to 0.25x - 2.0x range, in 0.01 steps
+        self.speed = (speed.clamp(0.25, 2.0) * 100.0).trunc() / 100.0;
+        self.player.send(PlayerCommand::SetSpeed(self.speed));
     }
 
     pub fn get_speed(&self) -> f64 {
-        self.speed.load(Ordering::SeqCst) as f64 / 100.0
+        self.speed
     }
 
     pub fn toggle_pause(&mut self) {
-        if self.is_playing.load(Ordering::SeqCst) {
-            let paused = !self.is_paused.load(Ordering::SeqCst);
-            self.is_paused.store(paused, Ordering::SeqCst);
+        if self.player.snapshot().is_playing {
+            let _ = self.player.call(PlayerCommand::TogglePause);
         }
     }
 
-    pub fn stop_playback(&mut self) {
-        self.is_playing.store(false, Ordering::SeqCst);
-        self.is_paused.store(false, Ordering::SeqCst);
-        *self.current_position.lock().unwrap() = 0.0;
-        *self.playback_start.lock().unwrap() = None;
+    /// Returns once the player has stopped and released every key
+    pub fn stop_playback(&mut self) -> Result<(), String> {
+        crate::queue::cancel_pending_advance();
+        let snapshot = self.player.snapshot();
+        if snapshot.is_playing {
+            if let Some(ref path) = self.current_file {
+                crate::history::record_stop(path, snapshot.position, false);
+            }
+        }
+        self.stopped_position = 0.0;
+        let result = self.player.stop();
+        if result.is_err() {
+            self.sync_player(); // In case the engine was restarted
+        }
+        result
+    }
 
-        // Wait for the playback thread to detect the stop flag and clean up
-        std::thread::sleep(std::time::Duration::from_millis(100));
+    /// Send every option the engine follows
+    fn sync_player(&self) {
+        self.player
+            .send(PlayerCommand::SetNoteMode(self.get_note_mode()));
+        self.player
+            .send(PlayerCommand::SetKeyMode(self.get_key_mode()));
+        self.player
+            .send(PlayerCommand::SetOctaveShift(self.get_octave_shift()));
+        self.player.send(PlayerCommand::SetSpeed(self.speed));
+        self.send_loop();
+        self.player
+            .send(PlayerCommand::SetBandFilter(self.band_filter.clone()));
+        self.player
+            .send(PlayerCommand::SetSongSettings(self.song_settings.clone()));
     }
 
     pub fn set_loop_mode(&mut self, enabled: bool) {
-        self.loop_mode.store(enabled, Ordering::SeqCst);
+        self.loop_mode = enabled;
+        self.send_loop();
     }
 
-    pub fn seek(&mut self, position: f64, window: Window) -> Result<(), String> {
-        let was_paused = self.is_paused.load(Ordering::SeqCst);
-
-        if self.is_playing.load(Ordering::SeqCst) {
-            // Store the seek position
-            *self.seek_offset.lock().unwrap() = position;
+    /// Play the song `count` times in total, then stop (0 = loop forever).
+    /// Enables loop mode when count > 0.
+    pub fn set_loop_count(&mut self, count: u16) {
+        self.loop_count = count;
+        if count > 0 {
+            self.loop_mode = true;
+        }
+        self.send_loop();
+    }
 
-            // Restart playback from the new position
-            self.stop_playback();
-            self.start_playback(window)?;
+    fn send_loop(&self) {
+        self.player.send(PlayerCommand::SetLoop {
+            enabled: self.loop_mode,
+            count: self.loop_count,
+        });
+    }
 
-            // Restore paused state if it was paused before seeking
-            if was_paused {
-                self.is_paused.store(true, Ordering::SeqCst);
-            }
+    pub fn seek(&mut self, position: f64) -> Result<(), String> {
+        self.seek_offset = position;
+        if self.player.snapshot().is_playing {
+            // The player jumps in place, keeping its paused state
+            self.player.call(PlayerCommand::Seek(position))?;
         } else {
             // Just set the position if not playing
-            *self.current_position.lock().unwrap() = position;
-            *self.seek_offset.lock().unwrap() = position;
+            self.stopped_position = position;
         }
         Ok(())
     }
 
     pub fn get_playback_state(&self) -> PlaybackState {
-        let mut position = *self.current_position.lock().unwrap();
-
-        // Update position based on playback time if playing
-        if self.is_playing.load(Ordering::SeqCst) && !self.is_paused.load(Ordering::SeqCst) {
-            if let Some(start_time) = *self.playback_start.lock().unwrap() {
-                position = start_time.elapsed().as_secs_f64();
-            }
-        }
-
+        let snapshot = self.player.snapshot();
         PlaybackState {
-            is_playing: self.is_playing.load(Ordering::SeqCst),
-            is_paused: self.is_paused.load(Ordering::SeqCst),
-            current_position: position,
-            total_duration: *self.total_duration.lock().unwrap(),
-            current_file: self.current_file.lock().unwrap().clone(),
-            loop_mode: self.loop_mode.load(Ordering::SeqCst),
+            is_playing: snapshot.is_playing,
+            is_paused: snapshot.is_paused,
+            current_position: self.current_position(),
+            total_duration: self.total_duration,
+            current_file: self.current_file.clone(),
+            loop_mode: self.loop_mode,
+            loop_count: self.loop_count,
+            loops_completed: snapshot.loops_completed,
             note_mode: self.get_note_mode(),
             key_mode: self.get_key_mode(),
             octave_shift: self.get_octave_shift(),
             speed: self.get_speed(),
+            rehearsal: snapshot.rehearsal,
+            blocked: snapshot.blocked,
+        }
+    }
+
+    /// Song position in seconds as tracked by the player (includes seeks and speed)
+    pub fn current_position(&self) -> f64 {
+        let snapshot = self.player.snapshot();
+        if snapshot.is_playing {
+            snapshot.position
+        } else {
+            self.stopped_position
         }
     }
 
+    /// Time of the first note in seconds (end of the lead-in silence)
+    pub fn first_note_time(&self) -> f64 {
+        self.midi_data
+            .as_ref()
+            .and_then(|data| {
+                data.events
+                    .iter()
+                    .find(|e| matches!(e.event_type, EventType::NoteOn))
+            })
+            .map(|e| e.time_ms as f64 / 1000.0)
+            .unwrap_or(0.0)
+    }
+
+    /// Event count and rough heap size of the loaded song
+    pub fn loaded_song_size(&self) -> (usize, usize) {
+        self.midi_data
+            .as_ref()
+            .map(|m| (m.events.len(), m.approx_bytes()))
+            .unwrap_or((0, 0))
+    }
+
     /// Get note events for visualizer - only shows actual key presses (21 keys)
     pub fn get_visualizer_notes(&self) -> Vec<VisualizerNote> {
-        let midi_data = self.midi_data.lock().unwrap();
-        if midi_data.is_none() {
+        let Some(midi) = self.midi_data.as_ref() else {
             return Vec::new();
-        }
-
-        let midi = midi_data.as_ref().unwrap();
+        };
         let transpose = midi.transpose;
         let mut notes: Vec<VisualizerNote> = Vec::new();
 
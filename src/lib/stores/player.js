@@ -1,10 +1,8 @@
 import { writable, derived } from 'svelte/store';
 import { invoke } from '../tauri/core-proxy.js';
 import { listen } from '@tauri-apps/api/event';
-import { getCurrentWindow, LogicalSize } from '@tauri-apps/api/window';
 import { calculateProgress } from '../utils/playerStats.js';
 import { logUiAction } from '../utils/uiActionLogger.js';
-import { rememberWindowBoundsRelativeToGame, restoreWindowBounds } from './windowState.js';
 
 // Player state
 export const isPlaying = writable(false);
@@ -83,27 +81,12 @@ export const lastLiveNote = writable(null); // { midiNote, key, noteName, veloci
 // Toggle mini mode with window resize
 export async function toggleMiniMode() {
   const currentMiniMode = get(miniMode);
-  const appWindow = getCurrentWindow();
 
-  if (!currentMiniMode) {
-    // Entering mini mode - save current state and resize
-    try {
-      await rememberWindowBoundsRelativeToGame();
-
-      // Set to mini size (64x88 for the floating icon + drag handle)
-      await appWindow.setMinSize(new LogicalSize(64, 88));
-      await appWindow.setSize(new LogicalSize(64, 88));
-    } catch (error) {
-      console.error('Failed to resize window for mini mode:', error);
-    }
-  } else {
-    // Exiting mini mode - restore previous state
-    try {
-      await appWindow.setMinSize(new LogicalSize(960, 540));
-      await restoreWindowBounds({ width: 1180, height: 620 });
-    } catch (error) {
-      console.error('Failed to restore window from mini mode:', error);
-    }
+  try {
+    // The backend keeps the bounds in logical pixels so they survive monitors with different scaling
+    await invoke('set_mini_mode', { enabled: !currentMiniMode });
+  } catch (error) {
+    console.error('Failed to resize window for mini mode:', error);
   }
 
   miniMode.update(v => !v);